@@ -0,0 +1,323 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use crate::class::access_flags::*;
+use crate::class::member::{Field, Method};
+use crate::class::registry::LoaderId;
+use crate::class::runtime_constant_pool::{Constant, RuntimeConstantPool};
+use crate::class::simple_loader::attribute_info::{find_attribute, BootstrapMethod, BootstrapMethodsAttribute, NestHostAttribute, SourceFileAttribute};
+use crate::class::simple_loader::class_reader::ClassFile;
+use crate::class::vtable::{ITable, MethodSlot};
+use crate::env::basic_env_elements::{LocalVars, ThreadId};
+use crate::env::object::ObjRef;
+
+// where a class is in its initialization (JVMS 5.5)
+#[derive(Clone, PartialEq, Debug)]
+pub enum InitState {
+    Uninitialized,
+    BeingInitialized(ThreadId),
+    Initialized,
+    // <clinit> failed, the class can't be used any more
+    Erroneous,
+}
+
+/*
+The run-time representation of a class or interface, built from a parsed
+ClassFile. Names are binary names with '/', e.g. "java/lang/String".
+ */
+pub struct Class {
+    // of the class file, the rules for some instructions changed over the versions
+    pub major_version: u16,
+    pub access_flags: u16,
+    pub name: String,
+    // None only for java/lang/Object
+    pub super_class_name: Option<String>,
+    pub interface_names: Vec<String>,
+    // filled in when the class is defined, before it is shared
+    pub loader: LoaderId,
+    pub super_class: Option<Rc<Class>>,
+    pub interfaces: Vec<Rc<Class>>,
+    // of an array class whose elements are references, None for all other classes
+    pub component_class: Option<Rc<Class>>,
+    pub constant_pool: RuntimeConstantPool,
+    pub fields: Vec<Field>,
+    pub methods: Vec<Rc<Method>>,
+    pub source_file: Option<String>,
+    // the class whose private members this one may access, itself if it has no NestHost
+    pub nest_host_name: String,
+    // the BootstrapMethods attribute, what invokedynamic call sites are linked with
+    pub bootstrap_methods: Vec<BootstrapMethod>,
+    // slots an instance needs, the fields of the super classes included
+    pub instance_slot_count: usize,
+    pub static_slot_count: usize,
+    pub static_vars: RefCell<LocalVars>,
+    // built when the class is linked, empty for interfaces
+    pub vtable: RefCell<Vec<MethodSlot>>,
+    pub itables: RefCell<Vec<ITable>>,
+    pub linked: Cell<bool>,
+    pub init_state: RefCell<InitState>,
+    // the java.lang.Class object, made when it is first asked for
+    pub mirror: Cell<Option<ObjRef>>,
+}
+
+impl Class {
+    pub fn new(file: &ClassFile) -> Class {
+        let cp = &file.constant_pool;
+        let name = cp.get_class_name(file.this_class as usize).unwrap_or("").to_string();
+        let super_class_name = match file.super_class {
+            0 => None,
+            index => cp.get_class_name(index as usize).map(|s| s.to_string()),
+        };
+        let interface_names = file.interfaces.iter()
+            .filter_map(|index| cp.get_class_name(*index as usize))
+            .map(|s| s.to_string())
+            .collect();
+        let source_file = find_attribute::<SourceFileAttribute>(&file.attributes)
+            .and_then(|a| cp.get_utf8_str(a.source_file_index as usize))
+            .map(|s| s.to_string());
+        let nest_host_name = find_attribute::<NestHostAttribute>(&file.attributes)
+            .and_then(|a| cp.get_class_name(a.host_class_index as usize))
+            .unwrap_or(&name)
+            .to_string();
+        let bootstrap_methods = find_attribute::<BootstrapMethodsAttribute>(&file.attributes)
+            .map_or(vec![], |a| a.bootstrap_methods.clone());
+
+        let mut fields: Vec<Field> = file.fields.iter().map(|info| Field::new(info, cp)).collect();
+        let mut instance_slot_count = 0;
+        let mut static_slot_count = 0;
+        for field in fields.iter_mut() {
+            if field.is_static() {
+                field.slot_id = static_slot_count;
+                static_slot_count += field.slot_size();
+            } else {
+                field.slot_id = instance_slot_count;
+                instance_slot_count += field.slot_size();
+            }
+        }
+
+        return Class {
+            major_version: file.major_version,
+            access_flags: file.access_flags,
+            name,
+            super_class_name,
+            interface_names,
+            loader: LoaderId(0),
+            super_class: None,
+            interfaces: vec![],
+            component_class: None,
+            constant_pool: RuntimeConstantPool::new(cp),
+            fields,
+            methods: file.methods.iter().map(|info| Rc::new(Method::new(info, cp))).collect(),
+            source_file,
+            nest_host_name,
+            bootstrap_methods,
+            instance_slot_count,
+            static_slot_count,
+            static_vars: RefCell::new(LocalVars::new(static_slot_count)),
+            vtable: RefCell::new(vec![]),
+            itables: RefCell::new(vec![]),
+            linked: Cell::new(false),
+            init_state: RefCell::new(InitState::Uninitialized),
+            mirror: Cell::new(None),
+        }
+    }
+
+    /*
+    A class the VM makes up itself on behalf of `host`, e.g. for a lambda.
+    It has no class file and no fields until add_instance_fields, lives in
+    the loader and nest of its host and isn't registered with any loader, so
+    it can't be found by name.
+     */
+    pub fn synthesized(host: &Class, name: String, super_class: Rc<Class>, interfaces: Vec<Rc<Class>>,
+                       constant_pool: RuntimeConstantPool, methods: Vec<Method>) -> Class {
+        let mut class = Class {
+            major_version: host.major_version,
+            access_flags: ACC_FINAL | ACC_SUPER | ACC_SYNTHETIC,
+            name,
+            super_class_name: Some(super_class.name.clone()),
+            interface_names: interfaces.iter().map(|i| i.name.clone()).collect(),
+            loader: host.loader,
+            super_class: None,
+            interfaces,
+            component_class: None,
+            constant_pool,
+            fields: vec![],
+            methods: methods.into_iter().map(Rc::new).collect(),
+            source_file: None,
+            nest_host_name: host.nest_host_name.clone(),
+            bootstrap_methods: vec![],
+            instance_slot_count: 0,
+            static_slot_count: 0,
+            static_vars: RefCell::new(LocalVars::new(0)),
+            vtable: RefCell::new(vec![]),
+            itables: RefCell::new(vec![]),
+            linked: Cell::new(false),
+            init_state: RefCell::new(InitState::Uninitialized),
+            mirror: Cell::new(None),
+        };
+        class.set_super_class(super_class);
+        return class;
+    }
+
+    /*
+    An array class such as "[I" or "[[Ljava/lang/String;", which has no class
+    file either: it extends Object, implements Cloneable and Serializable and
+    belongs to the loader of its element type, the bootstrap loader for
+    primitive ones (JVMS 5.3.3). It is as accessible as its element type.
+     */
+    pub fn array(name: &str, component: Option<Rc<Class>>, object: Rc<Class>, interfaces: Vec<Rc<Class>>) -> Class {
+        let constant_pool = RuntimeConstantPool::from_constants(vec![Constant::Empty]);
+        let mut class = Class::synthesized(&object, name.to_string(), object.clone(), interfaces, constant_pool, vec![]);
+        class.access_flags = ACC_FINAL | ACC_ABSTRACT;
+        if component.as_ref().is_none_or(|c| c.is_public()) {
+            class.access_flags |= ACC_PUBLIC;
+        }
+        class.loader = component.as_ref().map_or(LoaderId::BOOTSTRAP, |c| c.loader);
+        class.nest_host_name = name.to_string();
+        class.component_class = component;
+        return class;
+    }
+
+    // gives a synthesized class instance fields, laid out after those it inherits
+    pub fn add_instance_fields(&mut self, fields: Vec<Field>) {
+        for mut field in fields {
+            field.slot_id = self.instance_slot_count;
+            self.instance_slot_count += field.slot_size();
+            self.fields.push(field);
+        }
+    }
+
+    /*
+    Instance fields are laid out after those of the super class, which is
+    only known once it has been loaded, so the slots are shifted then.
+     */
+    pub fn set_super_class(&mut self, super_class: Rc<Class>) {
+        let base = super_class.instance_slot_count;
+        for field in self.fields.iter_mut().filter(|f| !f.is_static()) {
+            field.slot_id += base;
+        }
+        self.instance_slot_count += base;
+        self.super_class = Some(super_class);
+    }
+
+    // whether this is `other` or one of its subclasses, by name and loader
+    pub fn is_subclass_of(&self, other: &Class) -> bool {
+        if self.name == other.name && self.loader == other.loader {
+            return true;
+        }
+        return match &self.super_class {
+            Some(super_class) => super_class.is_subclass_of(other),
+            None => false,
+        };
+    }
+
+    pub fn is_array(&self) -> bool {
+        return self.name.starts_with('[');
+    }
+
+    // the type of the elements of an array class as a descriptor, e.g. "I" for "[I"
+    pub fn component_type(&self) -> Option<&str> {
+        return self.name.strip_prefix('[');
+    }
+
+    // the name of the class of arrays of this one, e.g. "[Ljava/lang/String;" for "java/lang/String"
+    pub fn array_class_name(&self) -> String {
+        return match self.is_array() {
+            true => format!("[{}", self.name),
+            false => format!("[L{};", self.name),
+        };
+    }
+
+    pub fn is_public(&self) -> bool {
+        return self.access_flags & ACC_PUBLIC != 0;
+    }
+
+    pub fn is_final(&self) -> bool {
+        return self.access_flags & ACC_FINAL != 0;
+    }
+
+    pub fn is_interface(&self) -> bool {
+        return self.access_flags & ACC_INTERFACE != 0;
+    }
+
+    pub fn is_abstract(&self) -> bool {
+        return self.access_flags & ACC_ABSTRACT != 0;
+    }
+
+    // the package part of the name, "" for the unnamed package
+    pub fn package_name(&self) -> &str {
+        return match self.name.rfind('/') {
+            Some(n) => &self.name[..n],
+            None => "",
+        };
+    }
+
+    // the same runtime package: the same package, defined by the same loader (JVMS 5.3)
+    pub fn is_same_runtime_package(&self, other: &Class) -> bool {
+        return self.loader == other.loader && self.package_name() == other.package_name();
+    }
+
+    pub fn is_nestmate_of(&self, other: &Class) -> bool {
+        return self.loader == other.loader && self.nest_host_name == other.nest_host_name;
+    }
+
+    // whether this is `other` or implements it, directly or through a super type
+    pub fn is_implementing(&self, other: &Class) -> bool {
+        if self.name == other.name && self.loader == other.loader {
+            return true;
+        }
+        if self.interfaces.iter().any(|i| i.is_implementing(other)) {
+            return true;
+        }
+        return match &self.super_class {
+            Some(super_class) => super_class.is_implementing(other),
+            None => false,
+        };
+    }
+
+    /*
+    Whether a value of this class can be used where one of `other` is
+    expected, what checkcast, instanceof and aastore test (JVMS 6.5
+    checkcast): a class is assignable to its super classes and the
+    interfaces it implements, an interface to Object and its super
+    interfaces, and an array to Object, Cloneable and Serializable and to
+    the arrays of the same primitive type or of the types its element type
+    is assignable to.
+     */
+    pub fn is_assignable_to(&self, other: &Class) -> bool {
+        if !self.is_array() {
+            return match other.is_interface() {
+                true => self.is_implementing(other),
+                false => self.is_subclass_of(other),
+            };
+        }
+        if !other.is_array() {
+            return match other.name.as_str() {
+                "java/lang/Object" | "java/lang/Cloneable" | "java/io/Serializable" => other.loader == LoaderId::BOOTSTRAP,
+                _ => false,
+            };
+        }
+        return match (&self.component_class, &other.component_class) {
+            (Some(component), Some(other_component)) => component.is_assignable_to(other_component),
+            (None, None) => self.name == other.name,
+            _ => false,
+        };
+    }
+
+    // a field declared by this class itself
+    pub fn get_field(&self, name: &str, descriptor: &str) -> Option<&Field> {
+        return self.fields.iter().find(|f| f.name == name && f.descriptor == descriptor);
+    }
+
+    // a method declared by this class itself
+    pub fn get_method(&self, name: &str, descriptor: &str) -> Option<Rc<Method>> {
+        return self.methods.iter().find(|m| m.name == name && m.descriptor == descriptor).cloned();
+    }
+
+    pub fn get_main_method(&self) -> Option<Rc<Method>> {
+        return self.get_method("main", "([Ljava/lang/String;)V").filter(|m| m.is_static());
+    }
+
+    pub fn get_clinit_method(&self) -> Option<Rc<Method>> {
+        return self.get_method("<clinit>", "()V").filter(|m| m.is_static());
+    }
+}
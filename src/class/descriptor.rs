@@ -0,0 +1,72 @@
+/*
+Field and method descriptors (JVMS 4.3), e.g. "[Ljava/lang/String;" or
+"(IJLjava/lang/Object;)V".
+ */
+
+// the length of the field type at the start of d, None if there's none
+fn field_type_len(d: &str) -> Option<usize> {
+    let bytes = d.as_bytes();
+    let mut n = 0;
+    while n < bytes.len() && bytes[n] == b'[' {
+        n += 1;
+    }
+    // at most 255 array dimensions
+    if n > 255 {
+        return None;
+    }
+    match bytes.get(n)? {
+        b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z' => return Some(n + 1),
+        b'L' => {
+            let end = d[n..].find(';')? + n;
+            if end == n + 1 {
+                return None;
+            }
+            return Some(end + 1);
+        }
+        _ => return None,
+    }
+}
+
+pub fn is_field_descriptor(d: &str) -> bool {
+    return field_type_len(d) == Some(d.len());
+}
+
+// how many local variable or operand stack slots a value of this type takes
+pub fn slot_size(field_type: &str) -> usize {
+    return match field_type {
+        "J" | "D" => 2,
+        "V" => 0,
+        _ => 1,
+    };
+}
+
+pub struct MethodDescriptor {
+    pub parameter_types: Vec<String>,
+    pub return_type: String,
+}
+
+impl MethodDescriptor {
+    pub fn parse(d: &str) -> Option<MethodDescriptor> {
+        let mut rest = d.strip_prefix('(')?;
+        let mut parameter_types = vec![];
+        while !rest.starts_with(')') {
+            let len = field_type_len(rest)?;
+            parameter_types.push(rest[..len].to_string());
+            rest = &rest[len..];
+        }
+        let return_type = &rest[1..];
+        if return_type != "V" && !is_field_descriptor(return_type) {
+            return None;
+        }
+
+        return Some(MethodDescriptor {
+            parameter_types,
+            return_type: return_type.to_string(),
+        })
+    }
+
+    // the slots the arguments take, not counting `this`
+    pub fn arg_slot_count(&self) -> usize {
+        return self.parameter_types.iter().map(|t| slot_size(t)).sum();
+    }
+}
@@ -0,0 +1,101 @@
+use std::rc::Rc;
+use crate::class::class::{Class, InitState};
+use crate::class::linker::link_class;
+use crate::class::member::Method;
+use crate::env::basic_env_elements::ThreadId;
+use crate::env::env::Environment;
+use crate::env::error::JavaError;
+
+// runs a <clinit> method, normally on the interpreter
+pub type ClinitRunner<'a> = dyn FnMut(&mut Environment, &Rc<Class>, &Rc<Method>) -> Result<(), JavaError> + 'a;
+
+#[derive(PartialEq, Debug)]
+pub enum InitStatus {
+    Initialized,
+    // the calling thread is running the initializer already, e.g. the class
+    // is used from its own <clinit>; it may be used as it is
+    InProgress,
+    // another thread is running the initializer, the caller has to wait for it
+    Blocked(ThreadId),
+}
+
+/*
+Initializes a class before its first active use (JVMS 5.5): `new`,
+getstatic, putstatic, invokestatic or reflection. The class is linked,
+then its super class and the super interfaces that declare default
+methods are initialized, and finally its own <clinit> runs. A class whose
+initializer failed is erroneous and every later use fails with
+NoClassDefFoundError.
+ */
+pub fn initialize_class(env: &mut Environment, class: &Rc<Class>, thread: ThreadId, run_clinit: &mut ClinitRunner) -> Result<InitStatus, JavaError> {
+    let state = class.init_state.borrow().clone();
+    match state {
+        InitState::Initialized => return Ok(InitStatus::Initialized),
+        InitState::BeingInitialized(owner) if owner == thread => return Ok(InitStatus::InProgress),
+        InitState::BeingInitialized(owner) => return Ok(InitStatus::Blocked(owner)),
+        InitState::Erroneous => {
+            return Err(JavaError::new("java/lang/NoClassDefFoundError",
+                format!("Could not initialize class {}", class.name.replace('/', "."))));
+        }
+        InitState::Uninitialized => {}
+    }
+
+    link_class(env, class)?;
+    *class.init_state.borrow_mut() = InitState::BeingInitialized(thread);
+
+    let result = initialize_supertypes(env, class, thread, run_clinit).and_then(|_| {
+        return match class.get_clinit_method() {
+            Some(clinit) => run_clinit(env, class, &clinit).map_err(|e| wrap_in_initializer_error(env, e)),
+            None => Ok(()),
+        };
+    });
+
+    *class.init_state.borrow_mut() = match result {
+        Ok(_) => InitState::Initialized,
+        Err(_) => InitState::Erroneous,
+    };
+    result?;
+    return Ok(InitStatus::Initialized);
+}
+
+fn initialize_supertypes(env: &mut Environment, class: &Rc<Class>, thread: ThreadId, run_clinit: &mut ClinitRunner) -> Result<(), JavaError> {
+    if class.is_interface() {
+        return Ok(());
+    }
+    if let Some(super_class) = &class.super_class {
+        initialize_class(env, super_class, thread, run_clinit)?;
+    }
+    let mut interfaces: Vec<Rc<Class>> = vec![];
+    superinterfaces(class, &mut interfaces);
+    for interface in &interfaces {
+        if declares_default_methods(interface) {
+            initialize_class(env, interface, thread, run_clinit)?;
+        }
+    }
+    return Ok(());
+}
+
+/*
+The superinterfaces of `class`, direct and indirect, in the order JVMS 5.5
+initializes them: each direct one in turn, its own superinterfaces before it.
+ */
+fn superinterfaces(class: &Class, out: &mut Vec<Rc<Class>>) {
+    for interface in &class.interfaces {
+        superinterfaces(interface, out);
+        if !out.iter().any(|i| Rc::ptr_eq(i, interface)) {
+            out.push(interface.clone());
+        }
+    }
+}
+
+fn declares_default_methods(interface: &Class) -> bool {
+    return interface.methods.iter().any(|m| !m.is_abstract() && !m.is_static());
+}
+
+// errors pass through as they are, other throwables become an ExceptionInInitializerError
+fn wrap_in_initializer_error(env: &mut Environment, e: JavaError) -> JavaError {
+    if e.is_error(env) {
+        return e;
+    }
+    return JavaError::new("java/lang/ExceptionInInitializerError", e.to_string());
+}
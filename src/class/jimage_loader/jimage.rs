@@ -0,0 +1,290 @@
+use std::fs::File;
+use std::io::{Error, ErrorKind, Read, Result, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use crate::class::simple_loader::inflate::inflate;
+
+/*
+jimage header {
+    u4 magic;                       // 0xCAFEDADA in the byte order of the image
+    u4 version;                     // major << 16 | minor
+    u4 flags;
+    u4 resource_count;
+    u4 table_length;
+    u4 locations_size;
+    u4 strings_size;
+}
+followed by
+    s4 redirect[table_length];
+    u4 offsets[table_length];
+    u1 locations[locations_size];
+    u1 strings[strings_size];
+and then the resources themselves.
+ */
+const MAGIC: u32 = 0xCAFEDADA;
+const MAJOR_VERSION: u32 = 1;
+const HEADER_SIZE: usize = 7 * 4;
+const HASH_MULTIPLIER: u32 = 0x01000193;
+
+const ATTRIBUTE_END: u8 = 0;
+const ATTRIBUTE_MODULE: u8 = 1;
+const ATTRIBUTE_PARENT: u8 = 2;
+const ATTRIBUTE_BASE: u8 = 3;
+const ATTRIBUTE_EXTENSION: u8 = 4;
+const ATTRIBUTE_OFFSET: u8 = 5;
+const ATTRIBUTE_COMPRESSED: u8 = 6;
+const ATTRIBUTE_UNCOMPRESSED: u8 = 7;
+const ATTRIBUTE_COUNT: usize = 8;
+
+/*
+compressed resource header {
+    u4 magic;                       // 0xCAFEFAFA
+    u8 size;
+    u8 uncompressed_size;
+    u4 decompressor_name_offset;
+    u4 decompressor_config_offset;
+    u1 is_terminal;
+}
+ */
+const COMPRESSED_MAGIC: u32 = 0xCAFEFAFA;
+const COMPRESSED_HEADER_SIZE: usize = 29;
+
+fn invalid(msg: String) -> Error {
+    return Error::new(ErrorKind::InvalidData, msg);
+}
+
+// jimage files are written in the byte order of the platform that built them
+fn u4_at(data: &[u8], at: usize, big_endian: bool) -> u32 {
+    let bytes = [data[at], data[at + 1], data[at + 2], data[at + 3]];
+    return if big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) };
+}
+
+// the hash used to place names in the redirect table
+fn hash_code(name: &str, seed: u32) -> u32 {
+    let mut h = seed;
+    for b in name.bytes() {
+        h = h.wrapping_mul(HASH_MULTIPLIER) ^ b as u32;
+    }
+    return h & 0x7FFFFFFF;
+}
+
+// the attributes of one resource, indexed by ATTRIBUTE_*
+pub struct ImageLocation {
+    attributes: [u64; ATTRIBUTE_COUNT],
+}
+
+impl ImageLocation {
+    fn get(&self, kind: u8) -> u64 {
+        return self.attributes[kind as usize];
+    }
+}
+
+/*
+A JDK modules image (lib/modules), as shipped by JDK 9 and later in
+place of rt.jar. The index is kept in memory, resources are read from
+the file on demand.
+ */
+pub struct JImage {
+    path: PathBuf,
+    big_endian: bool,
+    redirect: Vec<i32>,
+    offsets: Vec<u32>,
+    locations: Vec<u8>,
+    strings: Vec<u8>,
+    // where the resources start, the OFFSET attribute is relative to it
+    index_size: u64,
+}
+
+impl JImage {
+    pub fn open(path: &Path) -> Result<JImage> {
+        let mut file = File::open(path)?;
+        let len = file.metadata()?.len();
+        if len < HEADER_SIZE as u64 {
+            return Err(invalid(format!("{}: too short for a jimage", path.display())));
+        }
+        let mut header = [0u8; HEADER_SIZE];
+        file.read_exact(&mut header)?;
+
+        let big_endian = match u32::from_le_bytes([header[0], header[1], header[2], header[3]]) {
+            MAGIC => false,
+            m if m.swap_bytes() == MAGIC => true,
+            m => return Err(invalid(format!("{}: bad jimage magic {:#010X}", path.display(), m))),
+        };
+        let read_u4 = |data: &[u8], at: usize| u4_at(data, at, big_endian);
+
+        let version = read_u4(&header, 4);
+        if version >> 16 != MAJOR_VERSION {
+            return Err(invalid(format!("{}: unsupported jimage version {}.{}", path.display(), version >> 16, version & 0xFFFF)));
+        }
+        let table_length = read_u4(&header, 16) as usize;
+        let locations_size = read_u4(&header, 20) as usize;
+        let strings_size = read_u4(&header, 24) as usize;
+
+        // the sizes are checked against the file before anything is allocated for them
+        let index_size = (HEADER_SIZE + table_length * 8 + locations_size + strings_size) as u64;
+        if index_size > len {
+            return Err(invalid(format!("{}: image index past the end of the file", path.display())));
+        }
+        let mut tables = vec![0u8; table_length * 8];
+        file.read_exact(&mut tables)?;
+        let redirect = (0..table_length).map(|i| read_u4(&tables, i * 4) as i32).collect();
+        let offsets = (0..table_length).map(|i| read_u4(&tables, (table_length + i) * 4)).collect();
+
+        let mut locations = vec![0u8; locations_size];
+        file.read_exact(&mut locations)?;
+        let mut strings = vec![0u8; strings_size];
+        file.read_exact(&mut strings)?;
+
+        return Ok(JImage {
+            path: path.to_path_buf(),
+            big_endian,
+            redirect,
+            offsets,
+            locations,
+            strings,
+            index_size,
+        })
+    }
+
+    fn read_u4(&self, data: &[u8], at: usize) -> u32 {
+        return u4_at(data, at, self.big_endian);
+    }
+
+    // a NUL-terminated string of the strings table
+    fn get_string(&self, offset: u64) -> Option<&str> {
+        let start = offset as usize;
+        let len = self.strings.get(start..)?.iter().position(|b| *b == 0)?;
+        return std::str::from_utf8(&self.strings[start..start + len]).ok();
+    }
+
+    fn decode_location(&self, offset: usize) -> Option<ImageLocation> {
+        let mut attributes = [0u64; ATTRIBUTE_COUNT];
+        let mut at = offset;
+        loop {
+            let byte = *self.locations.get(at)?;
+            let kind = byte >> 3;
+            if kind == ATTRIBUTE_END {
+                break;
+            }
+            if kind as usize >= ATTRIBUTE_COUNT {
+                return None;
+            }
+            // the value is stored big-endian in 1 to 8 bytes
+            let n = (byte & 7) as usize + 1;
+            let mut value = 0u64;
+            for b in self.locations.get(at + 1..at + 1 + n)? {
+                value = (value << 8) | *b as u64;
+            }
+            attributes[kind as usize] = value;
+            at += 1 + n;
+        }
+        return Some(ImageLocation {
+            attributes,
+        })
+    }
+
+    // the full name of a location, "/module/parent/base.extension"
+    fn location_name(&self, location: &ImageLocation) -> Option<String> {
+        let mut name = String::new();
+        let module = self.get_string(location.get(ATTRIBUTE_MODULE))?;
+        if !module.is_empty() {
+            name.push('/');
+            name.push_str(module);
+            name.push('/');
+        }
+        let parent = self.get_string(location.get(ATTRIBUTE_PARENT))?;
+        if !parent.is_empty() {
+            name.push_str(parent);
+            name.push('/');
+        }
+        name.push_str(self.get_string(location.get(ATTRIBUTE_BASE))?);
+        let extension = self.get_string(location.get(ATTRIBUTE_EXTENSION))?;
+        if !extension.is_empty() {
+            name.push('.');
+            name.push_str(extension);
+        }
+        return Some(name);
+    }
+
+    pub fn find_location(&self, name: &str) -> Option<ImageLocation> {
+        let length = self.redirect.len();
+        if length == 0 {
+            return None;
+        }
+
+        // a negative redirect is the index itself, a positive one a new hash seed
+        let mut index = hash_code(name, HASH_MULTIPLIER) as usize % length;
+        let value = self.redirect[index];
+        if value < 0 {
+            index = (-1 - value) as usize;
+        } else if value > 0 {
+            index = hash_code(name, value as u32) as usize % length;
+        } else {
+            return None;
+        }
+
+        let location = self.decode_location(*self.offsets.get(index)? as usize)?;
+        // the table is a perfect hash over the names it holds, so any other name collides
+        if self.location_name(&location)? != name {
+            return None;
+        }
+        return Some(location);
+    }
+
+    pub fn read_resource(&self, location: &ImageLocation) -> Result<Vec<u8>> {
+        let compressed_size = location.get(ATTRIBUTE_COMPRESSED) as usize;
+        let uncompressed_size = location.get(ATTRIBUTE_UNCOMPRESSED) as usize;
+        let size = if compressed_size != 0 { compressed_size } else { uncompressed_size };
+
+        let mut file = File::open(&self.path)?;
+        let start = file.seek(SeekFrom::Start(self.index_size + location.get(ATTRIBUTE_OFFSET)))?;
+        if start + size as u64 > file.metadata()?.len() {
+            return Err(invalid(format!("{}: resource past the end of the file", self.path.display())));
+        }
+        let mut data = vec![0u8; size];
+        file.read_exact(&mut data)?;
+
+        if compressed_size == 0 {
+            return Ok(data);
+        }
+        return self.decompress(data, uncompressed_size);
+    }
+
+    // resources can be compressed several times over, each layer with its own header
+    fn decompress(&self, mut data: Vec<u8>, uncompressed_size: usize) -> Result<Vec<u8>> {
+        while data.len() >= COMPRESSED_HEADER_SIZE && self.read_u4(&data, 0) == COMPRESSED_MAGIC {
+            let name_offset = self.read_u4(&data, 20) as u64;
+            let decompressor = self.get_string(name_offset).unwrap_or("");
+            let body = &data[COMPRESSED_HEADER_SIZE..];
+            data = match decompressor {
+                // a zlib stream: skip its two byte header, the rest is raw deflate
+                "zip" if body.len() > 2 => inflate(&body[2..], uncompressed_size)?,
+                _ => {
+                    return Err(invalid(format!("{}: unsupported decompressor \"{}\"", self.path.display(), decompressor)));
+                }
+            };
+        }
+        if data.len() != uncompressed_size {
+            return Err(invalid(format!("{}: resource decompressed to the wrong size", self.path.display())));
+        }
+        return Ok(data);
+    }
+
+    /*
+    The module that holds a package, e.g. "java/lang" is in "java.base".
+    The "/packages/java.lang" resource is a sequence of (is_empty, module
+    name offset) u4 pairs; the first non-empty module wins.
+     */
+    pub fn package_to_module(&self, package: &str) -> Result<Option<String>> {
+        let location = match self.find_location(&format!("/packages/{}", package.replace('/', "."))) {
+            Some(location) => location,
+            None => return Ok(None),
+        };
+        let content = self.read_resource(&location)?;
+        for pair in content.chunks_exact(8) {
+            if self.read_u4(pair, 0) == 0 {
+                return Ok(self.get_string(self.read_u4(pair, 4) as u64).map(|s| s.to_string()));
+            }
+        }
+        return Ok(None);
+    }
+}
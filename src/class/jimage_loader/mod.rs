@@ -0,0 +1,90 @@
+pub mod jimage;
+
+pub mod jimage_loader {
+    use crate::class::*;
+    use std::cell::OnceCell;
+    use std::io;
+    use std::path::{Path, PathBuf};
+    use crate::class::jimage_loader::jimage::JImage;
+    use crate::class::simple_loader::class_reader::{ClassReader, ClassFile};
+    use crate::class::simple_loader::class_format_error::{ClassFormatError, FormatErrorReason};
+
+    /*
+    Loads the boot classes of a JDK 9+ installation straight out of its
+    modules image, e.g. "java/lang/String" from "/java.base/java/lang/String.class".
+     */
+    pub struct JImageClassLoader {
+        image_path: PathBuf,
+        // the image index, read on first use, or why the image can't be read
+        image: OnceCell<Result<JImage, (io::ErrorKind, String)>>,
+    }
+
+    impl JImageClassLoader {
+        pub fn new(image_path: PathBuf) -> JImageClassLoader {
+            return JImageClassLoader {
+                image_path,
+                image: OnceCell::new(),
+            }
+        }
+
+        // the image of the JDK installed at java_home
+        pub fn from_java_home(java_home: &Path) -> JImageClassLoader {
+            return JImageClassLoader::new(java_home.join("lib").join("modules"));
+        }
+
+        /*
+        The bytes of a class, or None if the image doesn't have it. As for
+        class path entries, a missing image has no classes while one that
+        can't be read is an error.
+         */
+        pub fn read_class_bytes(&self, class_name: &str) -> io::Result<Option<Vec<u8>>> {
+            let image = self.image.get_or_init(|| {
+                JImage::open(&self.image_path).map_err(|e| (e.kind(), e.to_string()))
+            });
+            let image = match image {
+                Ok(image) => image,
+                Err((io::ErrorKind::NotFound, _)) => return Ok(None),
+                Err((kind, message)) => return Err(io::Error::new(*kind, message.clone())),
+            };
+
+            // classes of the unnamed package can't live in a module
+            let package = match class_name.rsplit_once('/') {
+                Some((package, _)) => package,
+                None => return Ok(None),
+            };
+            let module = match image.package_to_module(package)? {
+                Some(module) => module,
+                None => return Ok(None),
+            };
+            return match image.find_location(&format!("/{}/{}.class", module, class_name)) {
+                Some(location) => image.read_resource(&location).map(Some),
+                None => Ok(None),
+            };
+        }
+
+        fn load_class_file(&self, path: String) -> Result<Option<Class>, ClassFormatError> {
+            let data = match self.read_class_bytes(&path) {
+                Ok(Some(data)) => data,
+                Ok(None) => return Ok(None),
+                Err(e) => {
+                    return Err(ClassFormatError {
+                        offset: 0,
+                        structure: "modules image",
+                        reason: FormatErrorReason::Unreadable(e.to_string()),
+                    });
+                }
+            };
+
+            let mut reader = ClassReader::new(data);
+            let file = ClassFile::new(&mut reader)?;
+
+            return Ok(Some(Class::new(&file)));
+        }
+    }
+
+    impl ClassLoader for JImageClassLoader {
+        fn load_class(&self, class_name: &str) -> Result<Option<Class>, ClassFormatError> {
+            return self.load_class_file(class_name.to_string());
+        }
+    }
+}
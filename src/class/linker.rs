@@ -0,0 +1,385 @@
+use std::rc::Rc;
+use crate::class::Class;
+use crate::class::access_flags::*;
+use crate::class::descriptor::MethodDescriptor;
+use crate::class::runtime_constant_pool::*;
+use crate::class::verifier::verify_code;
+use crate::class::vtable::build_tables;
+use crate::env::env::Environment;
+use crate::env::error::JavaError;
+use crate::env::string::intern;
+
+/*
+Linking (JVMS 5.4): a loaded class is verified and prepared before it is
+initialized, its super types first. Symbolic references from its constant
+pool are resolved later, one at a time, when an instruction first uses them.
+ */
+pub fn link_class(env: &mut Environment, class: &Rc<Class>) -> Result<(), JavaError> {
+    if class.linked.get() {
+        return Ok(());
+    }
+    if let Some(super_class) = &class.super_class {
+        link_class(env, super_class)?;
+    }
+    for interface in &class.interfaces {
+        link_class(env, interface)?;
+    }
+
+    verify(class)?;
+    prepare(env, class)?;
+    build_tables(class);
+    class.linked.set(true);
+    return Ok(());
+}
+
+fn verify_error(class: &Class, message: String) -> JavaError {
+    return JavaError::new("java/lang/VerifyError", format!("{} in class {}", message, class.name));
+}
+
+/*
+Structural checks only: what the interpreter relies on to stay within
+the code, locals and operand stack of a method. Type checking of the
+byte code itself isn't done.
+ */
+fn verify(class: &Class) -> Result<(), JavaError> {
+    if let Some(super_class) = &class.super_class {
+        if super_class.is_final() {
+            return Err(verify_error(class, format!("Cannot inherit from final class {}", super_class.name)));
+        }
+    }
+
+    for method in &class.methods {
+        let at = format!("method {}{}", method.name, method.descriptor);
+        if method.is_abstract() || method.is_native() {
+            if !method.code.is_empty() {
+                return Err(verify_error(class, format!("Code attribute in native or abstract {}", at)));
+            }
+            continue;
+        }
+
+        if method.code.is_empty() {
+            return Err(verify_error(class, format!("Missing Code attribute in {}", at)));
+        }
+        if method.max_locals < method.arg_slot_count {
+            return Err(verify_error(class, format!("Arguments can't fit into locals in {}", at)));
+        }
+        let code_len = method.code.len();
+        for entry in &method.exception_table {
+            let (start, end, handler) = (entry.start_pc as usize, entry.end_pc as usize, entry.handler_pc as usize);
+            if start >= end || end > code_len || handler >= code_len {
+                return Err(verify_error(class, format!("Illegal exception table range in {}", at)));
+            }
+        }
+
+        if let Err(message) = verify_code(class, method) {
+            return Err(verify_error(class, format!("{} in {}", message, at)));
+        }
+    }
+    return Ok(());
+}
+
+/*
+Static fields start out zeroed, which is what LocalVars::new gives, except
+for the constant ones whose ConstantValue attribute is applied here. A
+String constant is the interned String of its value, the one ldc pushes.
+ */
+fn prepare(env: &mut Environment, class: &Class) -> Result<(), JavaError> {
+    for field in class.fields.iter().filter(|f| f.is_static() && f.is_final()) {
+        let index = match field.const_value_index {
+            Some(index) => index as usize,
+            None => continue,
+        };
+        let string = match class.constant_pool.get(index) {
+            Some(Constant::String(value)) => Some(intern(env, value)?),
+            _ => None,
+        };
+        let mut vars = class.static_vars.borrow_mut();
+        match class.constant_pool.get(index) {
+            Some(Constant::Integer(v)) => vars.set_i32(field.slot_id, *v)?,
+            Some(Constant::Float(v)) => vars.set_f32(field.slot_id, *v)?,
+            Some(Constant::Long(v)) => vars.set_i64(field.slot_id, *v)?,
+            Some(Constant::Double(v)) => vars.set_f64(field.slot_id, *v)?,
+            Some(Constant::String(_)) => vars.set_ref(field.slot_id, string)?,
+            _ => {}
+        }
+    }
+    return Ok(());
+}
+
+fn is_accessible(class: &Class, from: &Class) -> bool {
+    if let Some(component) = &class.component_class {
+        return is_accessible(component, from);
+    }
+    return class.is_public() || class.is_same_runtime_package(from);
+}
+
+// the access rules for a member of `owner` with `access_flags` (JVMS 5.4.4)
+fn is_member_accessible(owner: &Class, access_flags: u16, from: &Class) -> bool {
+    if access_flags & ACC_PUBLIC != 0 {
+        return true;
+    }
+    if access_flags & ACC_PRIVATE != 0 {
+        return from.is_nestmate_of(owner);
+    }
+    if access_flags & ACC_PROTECTED != 0 && from.is_subclass_of(owner) {
+        return true;
+    }
+    return from.is_same_runtime_package(owner);
+}
+
+fn load_referenced(env: &mut Environment, from: &Class, class_name: &str) -> Result<Rc<Class>, JavaError> {
+    let class = match env.load_class_from(from.loader, class_name)? {
+        Some(class) => class,
+        None => return Err(JavaError::new("java/lang/NoClassDefFoundError", class_name.to_string())),
+    };
+    if !is_accessible(&class, from) {
+        return Err(JavaError::new("java/lang/IllegalAccessError",
+            format!("failed to access class {} from class {}", class.name, from.name)));
+    }
+    return Ok(class);
+}
+
+fn check_index(from: &Class, index: u16) -> Result<&Constant, JavaError> {
+    return from.constant_pool.get(index as usize).ok_or_else(|| {
+        JavaError::new("java/lang/VerifyError", format!("Illegal constant pool index {} in class {}", index, from.name))
+    });
+}
+
+fn wrong_kind(from: &Class, index: u16, wanted: &str) -> JavaError {
+    return JavaError::new("java/lang/VerifyError",
+        format!("Illegal type at constant pool entry {} in class {}, {} expected", index, from.name, wanted));
+}
+
+// resolves the CONSTANT_Class at `index` of `from`'s constant pool (JVMS 5.4.3.1)
+pub fn resolve_class(env: &mut Environment, from: &Rc<Class>, index: u16) -> Result<Rc<Class>, JavaError> {
+    let sym = match check_index(from, index)? {
+        Constant::Class(sym) => sym,
+        _ => return Err(wrong_kind(from, index, "class")),
+    };
+    if let Some(class) = sym.resolved.get() {
+        return Ok(class.clone());
+    }
+
+    let class = load_referenced(env, from, &sym.class_name)?;
+    let _ = sym.resolved.set(class.clone());
+    return Ok(class);
+}
+
+// looks for a field in a class, its super interfaces, then its super class (JVMS 5.4.3.2)
+fn find_field(class: &Rc<Class>, name: &str, descriptor: &str) -> Option<ResolvedField> {
+    if let Some(index) = class.fields.iter().position(|f| f.name == name && f.descriptor == descriptor) {
+        return Some(ResolvedField {
+            class: class.clone(),
+            index,
+        });
+    }
+    for interface in &class.interfaces {
+        if let Some(field) = find_field(interface, name, descriptor) {
+            return Some(field);
+        }
+    }
+    return find_field(class.super_class.as_ref()?, name, descriptor);
+}
+
+pub fn resolve_field(env: &mut Environment, from: &Rc<Class>, index: u16) -> Result<ResolvedField, JavaError> {
+    let sym = match check_index(from, index)? {
+        Constant::FieldRef(sym) => sym,
+        _ => return Err(wrong_kind(from, index, "field")),
+    };
+    if let Some(field) = sym.resolved.get() {
+        return Ok(field.clone());
+    }
+
+    // a synthesized class, which its loader can't find by name, may refer to its own fields
+    let class = match sym.class_name == from.name {
+        true => from.clone(),
+        false => load_referenced(env, from, &sym.class_name)?,
+    };
+    let resolved = match find_field(&class, &sym.name, &sym.descriptor) {
+        Some(resolved) => resolved,
+        None => return Err(JavaError::new("java/lang/NoSuchFieldError", sym.name.clone())),
+    };
+    if !is_member_accessible(&resolved.class, resolved.field().access_flags, from) {
+        return Err(JavaError::new("java/lang/IllegalAccessError",
+            format!("tried to access field {}.{} from class {}", resolved.class.name, sym.name, from.name)));
+    }
+    let _ = sym.resolved.set(resolved.clone());
+    return Ok(resolved);
+}
+
+fn find_in_superclasses(class: &Rc<Class>, name: &str, descriptor: &str) -> Option<ResolvedMethod> {
+    if let Some(method) = class.get_method(name, descriptor) {
+        return Some(ResolvedMethod {
+            class: class.clone(),
+            method,
+        });
+    }
+    return find_in_superclasses(class.super_class.as_ref()?, name, descriptor);
+}
+
+// a non-private, non-static method of the super interfaces, preferring a non-abstract one
+fn find_in_superinterfaces(class: &Rc<Class>, name: &str, descriptor: &str) -> Option<ResolvedMethod> {
+    let mut found: Option<ResolvedMethod> = None;
+    let mut interfaces: Vec<Rc<Class>> = class.interfaces.clone();
+    let mut next = class.super_class.clone();
+    while let Some(c) = next {
+        interfaces.extend(c.interfaces.iter().cloned());
+        next = c.super_class.clone();
+    }
+
+    while let Some(interface) = interfaces.pop() {
+        if let Some(method) = interface.get_method(name, descriptor) {
+            if !method.is_private() && !method.is_static() {
+                if !method.is_abstract() {
+                    return Some(ResolvedMethod { class: interface, method });
+                }
+                if found.is_none() {
+                    found = Some(ResolvedMethod { class: interface.clone(), method });
+                }
+            }
+        }
+        interfaces.extend(interface.interfaces.iter().cloned());
+    }
+    return found;
+}
+
+fn no_such_method(class: &Class, name: &str, descriptor: &str) -> JavaError {
+    return JavaError::new("java/lang/NoSuchMethodError", format!("{}.{}{}", class.name, name, descriptor));
+}
+
+fn check_method_access(resolved: &ResolvedMethod, from: &Class) -> Result<(), JavaError> {
+    if !is_member_accessible(&resolved.class, resolved.method.access_flags, from) {
+        return Err(JavaError::new("java/lang/IllegalAccessError",
+            format!("tried to access method {}.{}{} from class {}",
+                resolved.class.name, resolved.method.name, resolved.method.descriptor, from.name)));
+    }
+    return Ok(());
+}
+
+// resolves a CONSTANT_Methodref, which must name a class (JVMS 5.4.3.3)
+pub fn resolve_method(env: &mut Environment, from: &Rc<Class>, index: u16) -> Result<ResolvedMethod, JavaError> {
+    let sym = match check_index(from, index)? {
+        Constant::MethodRef(sym) => sym,
+        _ => return Err(wrong_kind(from, index, "method")),
+    };
+    if let Some(method) = sym.resolved.get() {
+        return Ok(method.clone());
+    }
+
+    let class = load_referenced(env, from, &sym.class_name)?;
+    if class.is_interface() {
+        return Err(JavaError::new("java/lang/IncompatibleClassChangeError",
+            format!("Found interface {}, but class was expected", class.name)));
+    }
+    let resolved = find_in_superclasses(&class, &sym.name, &sym.descriptor)
+        .or_else(|| find_in_superinterfaces(&class, &sym.name, &sym.descriptor))
+        .ok_or_else(|| no_such_method(&class, &sym.name, &sym.descriptor))?;
+    check_method_access(&resolved, from)?;
+    let _ = sym.resolved.set(resolved.clone());
+    return Ok(resolved);
+}
+
+// resolves a CONSTANT_InterfaceMethodref, which must name an interface (JVMS 5.4.3.4)
+pub fn resolve_interface_method(env: &mut Environment, from: &Rc<Class>, index: u16) -> Result<ResolvedMethod, JavaError> {
+    let sym = match check_index(from, index)? {
+        Constant::InterfaceMethodRef(sym) => sym,
+        _ => return Err(wrong_kind(from, index, "interface method")),
+    };
+    if let Some(method) = sym.resolved.get() {
+        return Ok(method.clone());
+    }
+
+    let class = load_referenced(env, from, &sym.class_name)?;
+    if !class.is_interface() {
+        return Err(JavaError::new("java/lang/IncompatibleClassChangeError",
+            format!("Found class {}, but interface was expected", class.name)));
+    }
+
+    let mut resolved = class.get_method(&sym.name, &sym.descriptor).map(|method| ResolvedMethod {
+        class: class.clone(),
+        method,
+    });
+    // interfaces inherit the public instance methods of java/lang/Object
+    if resolved.is_none() {
+        if let Some(object) = &class.super_class {
+            resolved = object.get_method(&sym.name, &sym.descriptor)
+                .filter(|m| m.access_flags & ACC_PUBLIC != 0 && !m.is_static())
+                .map(|method| ResolvedMethod { class: object.clone(), method });
+        }
+    }
+    let resolved = resolved
+        .or_else(|| find_in_superinterfaces(&class, &sym.name, &sym.descriptor))
+        .ok_or_else(|| no_such_method(&class, &sym.name, &sym.descriptor))?;
+    check_method_access(&resolved, from)?;
+    let _ = sym.resolved.set(resolved.clone());
+    return Ok(resolved);
+}
+
+fn member_kind_error(handle: &MethodHandleRef, message: String) -> JavaError {
+    return JavaError::new("java/lang/IncompatibleClassChangeError",
+        format!("{} for method handle of kind {}", message, handle.reference_kind));
+}
+
+/*
+Resolves a CONSTANT_MethodHandle (JVMS 5.4.3.5) by resolving the field or
+method it refers to, which has to fit the reference kind: static or not,
+and only REF_newInvokeSpecial may name a constructor.
+ */
+pub fn resolve_method_handle(env: &mut Environment, from: &Rc<Class>, index: u16) -> Result<ResolvedMethodHandle, JavaError> {
+    let handle = match check_index(from, index)? {
+        Constant::MethodHandle(handle) => handle,
+        _ => return Err(wrong_kind(from, index, "method handle")),
+    };
+    if let Some(resolved) = handle.resolved.get() {
+        return Ok(resolved.clone());
+    }
+
+    let kind = handle.reference_kind;
+    let resolved = match kind {
+        REF_GET_FIELD..=REF_PUT_STATIC => {
+            let field = resolve_field(env, from, handle.reference_index)?;
+            let wants_static = kind == REF_GET_STATIC || kind == REF_PUT_STATIC;
+            if field.field().is_static() != wants_static {
+                return Err(member_kind_error(handle, format!("Unexpected field {}.{}", field.class.name, field.field().name)));
+            }
+            ResolvedMethodHandle::Field(kind, field)
+        }
+        REF_INVOKE_VIRTUAL..=REF_INVOKE_INTERFACE => {
+            let method = match (kind, from.constant_pool.get(handle.reference_index as usize)) {
+                (REF_INVOKE_INTERFACE, _) | (REF_INVOKE_STATIC, Some(Constant::InterfaceMethodRef(_)))
+                    | (REF_INVOKE_SPECIAL, Some(Constant::InterfaceMethodRef(_))) => {
+                    resolve_interface_method(env, from, handle.reference_index)?
+                }
+                _ => resolve_method(env, from, handle.reference_index)?,
+            };
+            let is_init = method.method.name == "<init>";
+            if is_init != (kind == REF_NEW_INVOKE_SPECIAL) || method.method.name == "<clinit>" {
+                return Err(member_kind_error(handle, format!("Unexpected method {}.{}", method.class.name, method.method.name)));
+            }
+            if method.method.is_static() != (kind == REF_INVOKE_STATIC) {
+                return Err(member_kind_error(handle, format!("Unexpected method {}.{}{}",
+                    method.class.name, method.method.name, method.method.descriptor)));
+            }
+            ResolvedMethodHandle::Method(kind, method)
+        }
+        _ => return Err(wrong_kind(from, index, "method handle")),
+    };
+    let _ = handle.resolved.set(resolved.clone());
+    return Ok(resolved);
+}
+
+// resolves a CONSTANT_MethodType by loading the classes its descriptor names (JVMS 5.4.3.5)
+pub fn resolve_method_type(env: &mut Environment, from: &Rc<Class>, index: u16) -> Result<String, JavaError> {
+    let descriptor = match check_index(from, index)? {
+        Constant::MethodType(descriptor) => descriptor.clone(),
+        _ => return Err(wrong_kind(from, index, "method type")),
+    };
+    let parsed = MethodDescriptor::parse(&descriptor)
+        .ok_or_else(|| JavaError::new("java/lang/ClassFormatError", format!("Illegal method type {}", descriptor)))?;
+    for field_type in parsed.parameter_types.iter().chain(std::iter::once(&parsed.return_type)) {
+        if let Some(class_name) = field_type.trim_start_matches('[').strip_prefix('L') {
+            load_referenced(env, from, class_name.trim_end_matches(';'))?;
+        }
+    }
+    return Ok(descriptor);
+}
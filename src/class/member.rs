@@ -0,0 +1,144 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use crate::class::access_flags::*;
+use crate::class::descriptor::{slot_size, MethodDescriptor};
+use crate::class::simple_loader::attribute_info::*;
+use crate::class::simple_loader::class_reader::MemberInfo;
+use crate::class::simple_loader::constant_pool::ConstantPool;
+use crate::env::call_site::CallSite;
+
+pub struct Field {
+    pub access_flags: u16,
+    pub name: String,
+    pub descriptor: String,
+    // index into the static vars of the class, or into the fields of an instance
+    pub slot_id: usize,
+    // the ConstantValue attribute, used to prepare static final fields
+    pub const_value_index: Option<u16>,
+}
+
+impl Field {
+    pub fn new(info: &MemberInfo, cp: &ConstantPool) -> Field {
+        return Field {
+            access_flags: info.access_flags,
+            name: info.get_name(cp).to_string(),
+            descriptor: info.get_descriptor(cp).to_string(),
+            slot_id: 0,
+            const_value_index: find_attribute::<ConstantValueAttribute>(&info.attributes).map(|a| a.constant_value_index),
+        }
+    }
+
+    pub fn is_static(&self) -> bool {
+        return self.access_flags & ACC_STATIC != 0;
+    }
+
+    pub fn is_final(&self) -> bool {
+        return self.access_flags & ACC_FINAL != 0;
+    }
+
+    // longs and doubles take two slots
+    pub fn slot_size(&self) -> usize {
+        return slot_size(&self.descriptor);
+    }
+}
+
+pub struct Method {
+    pub access_flags: u16,
+    pub name: String,
+    pub descriptor: String,
+    pub max_stack: usize,
+    pub max_locals: usize,
+    // empty for abstract and native methods
+    pub code: Vec<u8>,
+    pub exception_table: Vec<ExceptionTableEntry>,
+    pub line_numbers: Vec<LineNumberTableEntry>,
+    // the slots the arguments take, including `this` for instance methods
+    pub arg_slot_count: usize,
+    // the slots the return value takes, 0 for void
+    pub return_slot_count: usize,
+    // the index into the vtable of the declaring class once it is linked, None for
+    // methods that aren't selected virtually
+    pub vtable_index: Cell<Option<usize>>,
+    // the call sites its invokedynamic instructions linked, by their pc
+    pub call_sites: RefCell<HashMap<usize, Rc<CallSite>>>,
+}
+
+impl Method {
+    // a method without code yet
+    fn declared(access_flags: u16, name: String, descriptor: String) -> Method {
+        let parsed = MethodDescriptor::parse(&descriptor);
+        let mut arg_slot_count = parsed.as_ref().map_or(0, |d| d.arg_slot_count());
+        let return_slot_count = parsed.as_ref().map_or(0, |d| slot_size(&d.return_type));
+        if access_flags & ACC_STATIC == 0 {
+            arg_slot_count += 1;
+        }
+
+        return Method {
+            access_flags,
+            name,
+            descriptor,
+            max_stack: 0,
+            max_locals: 0,
+            code: vec![],
+            exception_table: vec![],
+            line_numbers: vec![],
+            arg_slot_count,
+            return_slot_count,
+            vtable_index: Cell::new(None),
+            call_sites: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn new(info: &MemberInfo, cp: &ConstantPool) -> Method {
+        let mut method = Method::declared(info.access_flags, info.get_name(cp).to_string(), info.get_descriptor(cp).to_string());
+        if let Some(code) = find_attribute::<CodeAttribute>(&info.attributes) {
+            method.max_stack = code.max_stack as usize;
+            method.max_locals = code.max_locals as usize;
+            method.code = code.code.clone();
+            method.exception_table = code.exception_table.clone();
+            if let Some(lines) = find_attribute::<LineNumberTableAttribute>(&code.attributes) {
+                method.line_numbers = lines.line_number_entries.clone();
+                // javac writes them in code order, but nothing says it has to
+                method.line_numbers.sort_by_key(|e| e.start_pc);
+            }
+        }
+        return method;
+    }
+
+    // a method the VM generates the code of itself, e.g. for a lambda class
+    pub fn synthesized(access_flags: u16, name: &str, descriptor: &str, max_stack: usize, code: Vec<u8>) -> Method {
+        let mut method = Method::declared(access_flags, name.to_string(), descriptor.to_string());
+        method.max_stack = max_stack;
+        method.max_locals = method.arg_slot_count;
+        method.code = code;
+        return method;
+    }
+
+    pub fn is_static(&self) -> bool {
+        return self.access_flags & ACC_STATIC != 0;
+    }
+
+    pub fn is_private(&self) -> bool {
+        return self.access_flags & ACC_PRIVATE != 0;
+    }
+
+    pub fn is_abstract(&self) -> bool {
+        return self.access_flags & ACC_ABSTRACT != 0;
+    }
+
+    pub fn is_native(&self) -> bool {
+        return self.access_flags & ACC_NATIVE != 0;
+    }
+
+    pub fn is_synchronized(&self) -> bool {
+        return self.access_flags & ACC_SYNCHRONIZED != 0;
+    }
+
+    // the source line of the instruction at pc, None without a LineNumberTable
+    pub fn get_line_number(&self, pc: usize) -> Option<u16> {
+        // the entries are sorted by start_pc, the last one starting at or before pc has the line
+        let n = self.line_numbers.partition_point(|e| e.start_pc as usize <= pc);
+        return self.line_numbers.get(n.checked_sub(1)?).map(|e| e.line_number);
+    }
+}
@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+use crate::class::*;
+use crate::class::simple_loader::class_reader::{ClassReader, ClassFile};
+use crate::class::simple_loader::class_format_error::ClassFormatError;
+
+/*
+Serves class files handed over as bytes, e.g. classes generated at run
+time or read by a plugin host from somewhere other than the file system.
+ */
+pub struct MemoryClassLoader {
+    classes: HashMap<String, Vec<u8>>,
+}
+
+impl MemoryClassLoader {
+    pub fn new() -> MemoryClassLoader {
+        return MemoryClassLoader {
+            classes: HashMap::new(),
+        }
+    }
+
+    pub fn add_class(&mut self, class_name: &str, data: Vec<u8>) {
+        self.classes.insert(class_name.to_string(), data);
+    }
+}
+
+impl Default for MemoryClassLoader {
+    fn default() -> MemoryClassLoader {
+        return MemoryClassLoader::new();
+    }
+}
+
+impl ClassLoader for MemoryClassLoader {
+    fn load_class(&self, class_name: &str) -> Result<Option<Class>, ClassFormatError> {
+        let data = match self.classes.get(class_name) {
+            Some(data) => data.clone(),
+            None => return Ok(None),
+        };
+
+        let mut reader = ClassReader::new(data);
+        let file = ClassFile::new(&mut reader)?;
+
+        return Ok(Some(Class::new(&file)));
+    }
+}
@@ -1,9 +1,39 @@
 pub mod simple_loader;
+pub mod jimage_loader;
+pub mod access_flags;
+pub mod descriptor;
+pub mod runtime_constant_pool;
+pub mod member;
+pub mod class;
+pub mod registry;
+pub mod memory_loader;
+pub mod linker;
+pub mod verifier;
+pub mod initializer;
+pub mod vtable;
 
-pub struct Class {
+pub use crate::class::class::Class;
+use crate::class::simple_loader::class_format_error::ClassFormatError;
 
+pub trait ClassLoader {
+    // Ok(None) means the class isn't available from this loader
+    fn load_class(&self, class_name: &str) -> Result<Option<Class>, ClassFormatError>;
 }
 
-pub trait ClassLoader {
-    fn load_class(&self, class_name: &str) -> Option<&Class>;
-}
\ No newline at end of file
+impl<T: ClassLoader + ?Sized> ClassLoader for Box<T> {
+    fn load_class(&self, class_name: &str) -> Result<Option<Class>, ClassFormatError> {
+        return (**self).load_class(class_name);
+    }
+}
+
+// a class path: the first entry that has the class wins
+impl<T: ClassLoader> ClassLoader for Vec<T> {
+    fn load_class(&self, class_name: &str) -> Result<Option<Class>, ClassFormatError> {
+        for loader in self {
+            if let Some(class) = loader.load_class(class_name)? {
+                return Ok(Some(class));
+            }
+        }
+        return Ok(None);
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use crate::class::Class;
+
+// names a class loader of the Environment, by its position in the loader list
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct LoaderId(pub usize);
+
+impl LoaderId {
+    // the loaders every Environment starts with, each the parent of the next
+    pub const BOOTSTRAP: LoaderId = LoaderId(0);
+    pub const PLATFORM: LoaderId = LoaderId(1);
+    pub const APP: LoaderId = LoaderId(2);
+}
+
+/*
+Every class loaded so far. A class is defined once, by the loader that
+read its class file (the defining loader), but other loaders may have
+been asked for it and delegated (initiating loaders, JVMS 5.3). Both are
+recorded: the pair (defining loader, name) identifies the class, while
+lookups go through the loader that was asked.
+ */
+pub struct ClassRegistry {
+    defined: HashMap<(LoaderId, String), Rc<Class>>,
+    initiated: HashMap<(LoaderId, String), Rc<Class>>,
+}
+
+impl ClassRegistry {
+    pub fn new() -> ClassRegistry {
+        return ClassRegistry {
+            defined: HashMap::new(),
+            initiated: HashMap::new(),
+        }
+    }
+
+    // the class `loader` has been recorded as an initiating loader of, if any
+    pub fn find_loaded(&self, loader: LoaderId, name: &str) -> Option<Rc<Class>> {
+        return self.initiated.get(&(loader, name.to_string())).cloned();
+    }
+
+    // the class defined by `loader` itself, if any
+    pub fn find_defined(&self, loader: LoaderId, name: &str) -> Option<Rc<Class>> {
+        return self.defined.get(&(loader, name.to_string())).cloned();
+    }
+
+    /*
+    Registers a class with its defining loader, which counts as an
+    initiating loader too. Returns the class already registered under the
+    same key instead if there is one, so each class exists only once.
+     */
+    pub fn define(&mut self, class: Class) -> Rc<Class> {
+        let key = (class.loader, class.name.clone());
+        if let Some(existing) = self.defined.get(&key) {
+            return existing.clone();
+        }
+        let class = Rc::new(class);
+        self.defined.insert(key.clone(), class.clone());
+        self.initiated.insert(key, class.clone());
+        return class;
+    }
+
+    /*
+    Makes up and registers the array class `name` with elements of the class
+    `component`, None if they are primitive, unless it exists already. Array
+    classes aren't read from class files, so this is how they are defined.
+     */
+    pub fn define_array(&mut self, name: &str, component: Option<Rc<Class>>, object: Rc<Class>, interfaces: Vec<Rc<Class>>) -> Rc<Class> {
+        let loader = component.as_ref().map_or(LoaderId::BOOTSTRAP, |c| c.loader);
+        if let Some(existing) = self.find_defined(loader, name) {
+            return existing;
+        }
+        return self.define(Class::array(name, component, object, interfaces));
+    }
+
+    pub fn record_initiating(&mut self, loader: LoaderId, class: &Rc<Class>) {
+        self.initiated.insert((loader, class.name.clone()), class.clone());
+    }
+
+    // the loaders that were asked for `name` and which class each of them got,
+    // to check loader constraints against
+    pub fn initiating_loaders(&self, name: &str) -> Vec<(LoaderId, Rc<Class>)> {
+        let mut loaders: Vec<(LoaderId, Rc<Class>)> = self.initiated.iter()
+            .filter(|((_, n), _)| n == name)
+            .map(|((loader, _), class)| (*loader, class.clone()))
+            .collect();
+        loaders.sort_by_key(|(loader, _)| loader.0);
+        return loaders;
+    }
+
+    // every class defined so far
+    pub fn classes(&self) -> impl Iterator<Item = &Rc<Class>> {
+        return self.defined.values();
+    }
+
+    pub fn len(&self) -> usize {
+        return self.defined.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.defined.is_empty();
+    }
+}
+
+impl Default for ClassRegistry {
+    fn default() -> ClassRegistry {
+        return ClassRegistry::new();
+    }
+}
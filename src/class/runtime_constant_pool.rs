@@ -0,0 +1,210 @@
+use std::cell::OnceCell;
+use std::rc::Rc;
+use crate::class::Class;
+use crate::class::member::{Field, Method};
+use crate::class::simple_loader::constant_pool::*;
+
+/*
+Symbolic references are resolved on first use (JVMS 5.4.3) and the
+result is cached in the reference, so executing the same instruction
+again costs no more than a lookup. A failed resolution isn't cached and
+fails again the next time, with the same error.
+ */
+
+// a symbolic reference to a class, e.g. the target of `new` or `checkcast`
+pub struct ClassSymRef {
+    pub class_name: String,
+    pub resolved: OnceCell<Rc<Class>>,
+}
+
+// a symbolic reference to a field or method of some class
+pub struct MemberSymRef<T> {
+    pub class_name: String,
+    pub name: String,
+    pub descriptor: String,
+    pub resolved: OnceCell<T>,
+}
+
+// a field and the class that declares it, which may be a super type of the referenced one
+#[derive(Clone)]
+pub struct ResolvedField {
+    pub class: Rc<Class>,
+    pub index: usize,
+}
+
+impl ResolvedField {
+    pub fn field(&self) -> &Field {
+        return &self.class.fields[self.index];
+    }
+}
+
+#[derive(Clone)]
+pub struct ResolvedMethod {
+    pub class: Rc<Class>,
+    pub method: Rc<Method>,
+}
+
+// the reference kinds of a CONSTANT_MethodHandle (JVMS 5.4.3.5)
+pub const REF_GET_FIELD: u8 = 1;
+pub const REF_GET_STATIC: u8 = 2;
+pub const REF_PUT_FIELD: u8 = 3;
+pub const REF_PUT_STATIC: u8 = 4;
+pub const REF_INVOKE_VIRTUAL: u8 = 5;
+pub const REF_INVOKE_STATIC: u8 = 6;
+pub const REF_INVOKE_SPECIAL: u8 = 7;
+pub const REF_NEW_INVOKE_SPECIAL: u8 = 8;
+pub const REF_INVOKE_INTERFACE: u8 = 9;
+
+// the field or method a method handle refers to, with its reference kind
+#[derive(Clone)]
+pub enum ResolvedMethodHandle {
+    Field(u8, ResolvedField),
+    Method(u8, ResolvedMethod),
+}
+
+pub struct MethodHandleRef {
+    pub reference_kind: u8,
+    pub reference_index: u16,
+    pub resolved: OnceCell<ResolvedMethodHandle>,
+}
+
+pub struct InvokeDynamicRef {
+    pub bootstrap_method_attr_index: u16,
+    pub name: String,
+    pub descriptor: String,
+}
+
+/*
+An entry of the run-time constant pool (JVMS 5.1). Indices into the
+class file's pool are replaced by the names they point at, so the
+entries can be used without going back to the ClassFile.
+ */
+pub enum Constant {
+    // index #0 and the slot after a long or double
+    Empty,
+    Integer(i32),
+    Float(f32),
+    Long(i64),
+    Double(f64),
+    String(String),
+    Utf8(String),
+    Class(ClassSymRef),
+    FieldRef(MemberSymRef<ResolvedField>),
+    MethodRef(MemberSymRef<ResolvedMethod>),
+    InterfaceMethodRef(MemberSymRef<ResolvedMethod>),
+    NameAndType(String, String),
+    MethodHandle(MethodHandleRef),
+    MethodType(String),
+    InvokeDynamic(InvokeDynamicRef),
+}
+
+pub struct RuntimeConstantPool {
+    constants: Vec<Constant>,
+}
+
+impl RuntimeConstantPool {
+    // the class file's pool has been validated, so every index resolves
+    pub fn new(cp: &ConstantPool) -> RuntimeConstantPool {
+        let mut constants = vec![];
+        for n in 0..cp.len() {
+            constants.push(RuntimeConstantPool::convert(cp, n));
+        }
+
+        return RuntimeConstantPool {
+            constants,
+        }
+    }
+
+    // the pool of a class the VM makes up itself, index #0 is Empty as usual
+    pub fn from_constants(constants: Vec<Constant>) -> RuntimeConstantPool {
+        return RuntimeConstantPool {
+            constants,
+        }
+    }
+
+    fn member_ref<T>(cp: &ConstantPool, class_index: u16, name_and_type_index: u16) -> MemberSymRef<T> {
+        let (name, descriptor) = cp.get_name_and_type(name_and_type_index as usize).unwrap_or(("", ""));
+        return MemberSymRef {
+            class_name: cp.get_class_name(class_index as usize).unwrap_or("").to_string(),
+            name: name.to_string(),
+            descriptor: descriptor.to_string(),
+            resolved: OnceCell::new(),
+        }
+    }
+
+    fn convert(cp: &ConstantPool, n: usize) -> Constant {
+        let any = match cp.get_any(n) {
+            Some(any) => any,
+            None => return Constant::Empty,
+        };
+        let utf8 = |index: u16| cp.get_utf8_str(index as usize).unwrap_or("").to_string();
+
+        if let Some(c) = any.downcast_ref::<ConstantIntegerInfo>() {
+            return Constant::Integer(c.val);
+        }
+        if let Some(c) = any.downcast_ref::<ConstantFloatInfo>() {
+            return Constant::Float(c.val);
+        }
+        if let Some(c) = any.downcast_ref::<ConstantLongInfo>() {
+            return Constant::Long(c.val);
+        }
+        if let Some(c) = any.downcast_ref::<ConstantDoubleInfo>() {
+            return Constant::Double(c.val);
+        }
+        if let Some(c) = any.downcast_ref::<ConstantStringInfo>() {
+            return Constant::String(utf8(c.string_index));
+        }
+        if let Some(c) = any.downcast_ref::<ConstantUTF8Info>() {
+            return Constant::Utf8(c.get_str().clone());
+        }
+        if let Some(c) = any.downcast_ref::<ConstantClassInfo>() {
+            return Constant::Class(ClassSymRef {
+                class_name: utf8(c.name_index),
+                resolved: OnceCell::new(),
+            });
+        }
+        if let Some(c) = any.downcast_ref::<ConstantFieldRefInfo>() {
+            return Constant::FieldRef(RuntimeConstantPool::member_ref(cp, c.class_index, c.name_and_type_index));
+        }
+        if let Some(c) = any.downcast_ref::<ConstantMethodRefInfo>() {
+            return Constant::MethodRef(RuntimeConstantPool::member_ref(cp, c.class_index, c.name_and_type_index));
+        }
+        if let Some(c) = any.downcast_ref::<ConstantInterfaceMethodRefInfo>() {
+            return Constant::InterfaceMethodRef(RuntimeConstantPool::member_ref(cp, c.class_index, c.name_and_type_index));
+        }
+        if let Some(c) = any.downcast_ref::<ConstantNameAndTypeInfo>() {
+            return Constant::NameAndType(utf8(c.name_index), utf8(c.descriptor_index));
+        }
+        if let Some(c) = any.downcast_ref::<ConstantMethodHandleInfo>() {
+            return Constant::MethodHandle(MethodHandleRef {
+                reference_kind: c.reference_kind,
+                reference_index: c.reference_index,
+                resolved: OnceCell::new(),
+            });
+        }
+        if let Some(c) = any.downcast_ref::<ConstantMethodTypeInfo>() {
+            return Constant::MethodType(utf8(c.descriptor_index));
+        }
+        if let Some(c) = any.downcast_ref::<ConstantInvokeDynamicInfo>() {
+            let (name, descriptor) = cp.get_name_and_type(c.name_and_type_index as usize).unwrap_or(("", ""));
+            return Constant::InvokeDynamic(InvokeDynamicRef {
+                bootstrap_method_attr_index: c.bootstrap_method_attr_index,
+                name: name.to_string(),
+                descriptor: descriptor.to_string(),
+            });
+        }
+        return Constant::Empty;
+    }
+
+    pub fn get(&self, n: usize) -> Option<&Constant> {
+        return self.constants.get(n);
+    }
+
+    pub fn len(&self) -> usize {
+        return self.constants.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.constants.len() <= 1;
+    }
+}
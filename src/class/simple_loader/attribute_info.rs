@@ -1,12 +1,20 @@
 use crate::class::simple_loader::class_reader::ClassReader;
-use crate::class::simple_loader::constant_pool::{ConstantPool, ConstantInfo};
-use crate::class::*;
-use std::fs::read;
+use crate::class::simple_loader::constant_pool::*;
+use crate::class::simple_loader::class_format_error::{ClassFormatError, FormatErrorReason};
+use std::any::Any;
 
 pub trait AttributeInfo {
-    fn read_data(&mut self, data: Vec<u8>, constant_pool: &ConstantPool);
+    // the reader covers exactly attribute_length bytes of the attribute body
+    fn read_data(&mut self, reader: &mut ClassReader, constant_pool: &ConstantPool) -> Result<(), ClassFormatError>;
 
-    fn get_name(&self) -> &str;
+    fn get_name(&self) -> &'static str;
+
+    fn as_any(&self) -> &dyn Any;
+}
+
+// the first attribute of type T, e.g. the CodeAttribute of a method
+pub fn find_attribute<T: 'static>(attributes: &[Box<dyn AttributeInfo>]) -> Option<&T> {
+    return attributes.iter().find_map(|a| a.as_any().downcast_ref::<T>());
 }
 
 pub struct UnknownAttributeInfo {
@@ -14,32 +22,44 @@ pub struct UnknownAttributeInfo {
 }
 
 impl AttributeInfo for UnknownAttributeInfo {
-    fn read_data(&mut self, data: Vec<u8>, constant_pool: &ConstantPool) {
-        self.data = data;
+    fn read_data(&mut self, reader: &mut ClassReader, _constant_pool: &ConstantPool) -> Result<(), ClassFormatError> {
+        self.data = reader.read_data(reader.remaining())?;
+        return Ok(());
     }
 
-    fn get_name(&self) -> &str {
+    fn get_name(&self) -> &'static str {
         return "UnknownAttribute";
     }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
 }
 
-pub fn read_attribute_info_vec(reader: &mut ClassReader, cp: &ConstantPool) -> Vec<Box<dyn AttributeInfo>> {
-    let n = reader.read_u16();
+pub fn read_attribute_info_vec(reader: &mut ClassReader, cp: &ConstantPool) -> Result<Vec<Box<dyn AttributeInfo>>, ClassFormatError> {
+    let n = reader.read_u16()?;
     let mut vec : Vec<Box<dyn AttributeInfo>> = vec![];
 
-    for i in 0..n {
-        vec.push(read_attribute_info(reader, cp));
+    for _ in 0..n {
+        let outer = reader.begin("attribute_info");
+        vec.push(read_attribute_info(reader, cp)?);
+        reader.end(outer);
     }
 
-    return vec;
+    return Ok(vec);
 }
 
-pub fn read_attribute_info(reader: &mut ClassReader, cp: &ConstantPool) -> Box<dyn AttributeInfo> {
-    let name_index = reader.read_u16() as usize;
-    let name = cp.get_utf8(name_index).unwrap().get_str().as_str();
+pub fn read_attribute_info(reader: &mut ClassReader, cp: &ConstantPool) -> Result<Box<dyn AttributeInfo>, ClassFormatError> {
+    let name_offset = reader.offset();
+    let name_index = reader.read_u16()?;
+    let name = match cp.get_utf8(name_index as usize) {
+        Some(utf8) => utf8.get_str().as_str(),
+        None => return Err(reader.error_at(name_offset, FormatErrorReason::BadConstantIndex(name_index))),
+    };
 
-    let data_len = reader.read_u32() as usize;
-    let data = reader.read_data(data_len);
+    let data_len = reader.read_u32()? as usize;
+    let data_offset = reader.offset();
+    let data = reader.read_data(data_len)?;
 
     let mut info : Box<dyn AttributeInfo>;
     match name {
@@ -67,15 +87,23 @@ pub fn read_attribute_info(reader: &mut ClassReader, cp: &ConstantPool) -> Box<d
 
         "SourceFile" => info = Box::new(SourceFileAttribute{ source_file_index: 0 }),
 
-        _ => {
-            // todo
-            print!("Unknown attribute: {}\n", name);
-            info = Box::new(UnknownAttributeInfo{ data: vec![] })
-        }
+        "NestHost" => info = Box::new(NestHostAttribute{ host_class_index: 0 }),
+
+        "BootstrapMethods" => info = Box::new(BootstrapMethodsAttribute{ bootstrap_methods: vec![] }),
+
+        // attributes the VM doesn't use are skipped silently, as JVMS 4.7.1 asks
+        _ => info = Box::new(UnknownAttributeInfo{ data: vec![] }),
+    }
+    let mut data_reader = ClassReader::with_base(data, data_offset, info.get_name());
+    info.read_data(&mut data_reader, cp)?;
+    if data_reader.remaining() != 0 {
+        return Err(data_reader.error(FormatErrorReason::LengthMismatch {
+            declared: data_len,
+            consumed: data_len - data_reader.remaining(),
+        }));
     }
-    info.read_data(data, cp);
 
-    return info;
+    return Ok(info);
 }
 
 /*
@@ -99,11 +127,12 @@ Code_attribute {
 }
 */
 
+#[derive(Clone, Copy)]
 pub struct ExceptionTableEntry {
-    start_pc: u16,
-    end_pc: u16,
-    handler_pc: u16,
-    catch_type: u16,
+    pub start_pc: u16,
+    pub end_pc: u16,
+    pub handler_pc: u16,
+    pub catch_type: u16,
 }
 
 pub struct CodeAttribute {
@@ -115,38 +144,42 @@ pub struct CodeAttribute {
 }
 
 impl CodeAttribute {
-    fn read_exception_table(reader: &mut ClassReader) -> Vec<ExceptionTableEntry> {
-        let n = reader.read_u16();
+    fn read_exception_table(reader: &mut ClassReader) -> Result<Vec<ExceptionTableEntry>, ClassFormatError> {
+        let n = reader.read_u16()?;
         let mut vec: Vec<ExceptionTableEntry> = vec![];
 
-        for i in 0..n {
+        for _ in 0..n {
             vec.push(ExceptionTableEntry{
-                start_pc: reader.read_u16(),
-                end_pc: reader.read_u16(),
-                handler_pc: reader.read_u16(),
-                catch_type: reader.read_u16(),
+                start_pc: reader.read_u16()?,
+                end_pc: reader.read_u16()?,
+                handler_pc: reader.read_u16()?,
+                catch_type: reader.read_u16()?,
             })
         }
 
-        return vec;
+        return Ok(vec);
     }
 }
 
 impl AttributeInfo for CodeAttribute {
-    fn read_data(&mut self, data: Vec<u8>, constant_pool: &ConstantPool) {
-        let mut reader = ClassReader::new(data);
-
-        self.max_stack = reader.read_u16();
-        self.max_locals = reader.read_u16();
-        let code_len = reader.read_u32() as usize;
-        self.code = reader.read_data(code_len);
-        self.exception_table = CodeAttribute::read_exception_table(&mut reader);
-        self.attributes = read_attribute_info_vec(&mut reader, constant_pool);
+    fn read_data(&mut self, reader: &mut ClassReader, constant_pool: &ConstantPool) -> Result<(), ClassFormatError> {
+        self.max_stack = reader.read_u16()?;
+        self.max_locals = reader.read_u16()?;
+        let code_len = reader.read_u32()? as usize;
+        self.code = reader.read_data(code_len)?;
+        self.exception_table = CodeAttribute::read_exception_table(reader)?;
+        self.attributes = read_attribute_info_vec(reader, constant_pool)?;
+
+        return Ok(());
     }
 
-    fn get_name(&self) -> &str {
+    fn get_name(&self) -> &'static str {
         return "Code";
     }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
 }
 
 /*
@@ -157,18 +190,32 @@ ConstantValue_attribute {
 }
 */
 pub struct ConstantValueAttribute {
-    constant_value_index: u16
+    pub constant_value_index: u16
 }
 
 impl AttributeInfo for ConstantValueAttribute {
-    fn read_data(&mut self, data: Vec<u8>, constant_pool: &ConstantPool) {
-        let mut reader = ClassReader::new(data);
-        self.constant_value_index = reader.read_u16();
+    fn read_data(&mut self, reader: &mut ClassReader, constant_pool: &ConstantPool) -> Result<(), ClassFormatError> {
+        let offset = reader.offset();
+        self.constant_value_index = reader.read_u16()?;
+        let loadable = constant_pool.check::<ConstantIntegerInfo>(self.constant_value_index)
+            .and(constant_pool.check::<ConstantFloatInfo>(self.constant_value_index))
+            .and(constant_pool.check::<ConstantLongInfo>(self.constant_value_index))
+            .and(constant_pool.check::<ConstantDoubleInfo>(self.constant_value_index))
+            .and(constant_pool.check::<ConstantStringInfo>(self.constant_value_index));
+        if let Some(index) = loadable {
+            return Err(reader.error_at(offset, FormatErrorReason::BadConstantIndex(index)));
+        }
+
+        return Ok(());
     }
 
-    fn get_name(&self) -> &str {
+    fn get_name(&self) -> &'static str {
         return "ConstantValue";
     }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
 }
 
 /*
@@ -184,37 +231,42 @@ InnerClasses_attribute {
 }
 */
 pub struct InnerClassesEntry {
-    inner_class_info_index: u16,
-    outer_class_info_index: u16,
-    inner_name_index: u16,
-    inner_class_access_flags: u16,
+    pub inner_class_info_index: u16,
+    pub outer_class_info_index: u16,
+    pub inner_name_index: u16,
+    pub inner_class_access_flags: u16,
 }
 
 pub struct InnerClassesAttribute {
-    classes: Vec<InnerClassesEntry>
+    pub classes: Vec<InnerClassesEntry>
 }
 
 impl AttributeInfo for InnerClassesAttribute {
-    fn read_data(&mut self, data: Vec<u8>, constant_pool: &ConstantPool) {
-        let mut reader = ClassReader::new(data);
-        let n = reader.read_u16();
+    fn read_data(&mut self, reader: &mut ClassReader, _constant_pool: &ConstantPool) -> Result<(), ClassFormatError> {
+        let n = reader.read_u16()?;
         let mut vec: Vec<InnerClassesEntry> = vec![];
 
-        for i in 0..n {
+        for _ in 0..n {
             vec.push(InnerClassesEntry{
-                inner_class_info_index: reader.read_u16(),
-                outer_class_info_index: reader.read_u16(),
-                inner_name_index: reader.read_u16(),
-                inner_class_access_flags: reader.read_u16()
+                inner_class_info_index: reader.read_u16()?,
+                outer_class_info_index: reader.read_u16()?,
+                inner_name_index: reader.read_u16()?,
+                inner_class_access_flags: reader.read_u16()?
             });
         }
 
         self.classes = vec;
+
+        return Ok(());
     }
 
-    fn get_name(&self) -> &str {
+    fn get_name(&self) -> &'static str {
         return "InnerClasses"
     }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
 }
 
 /*
@@ -226,18 +278,23 @@ Exceptions_attribute {
 }
 */
 pub struct ExceptionsAttribute {
-    exception_index_table: Vec<u16>
+    pub exception_index_table: Vec<u16>
 }
 
 impl AttributeInfo for ExceptionsAttribute {
-    fn read_data(&mut self, data: Vec<u8>, constant_pool: &ConstantPool) {
-        let mut reader = ClassReader::new(data);
-        self.exception_index_table = reader.read_u16s();
+    fn read_data(&mut self, reader: &mut ClassReader, _constant_pool: &ConstantPool) -> Result<(), ClassFormatError> {
+        self.exception_index_table = reader.read_u16s()?;
+
+        return Ok(());
     }
 
-    fn get_name(&self) -> &str {
+    fn get_name(&self) -> &'static str {
         return "Exceptions";
     }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
 }
 
 /*
@@ -248,18 +305,23 @@ Signature_attribute {
 }
 */
 pub struct SignatureAttribute {
-    signature_index: u16
+    pub signature_index: u16
 }
 
 impl AttributeInfo for SignatureAttribute {
-    fn read_data(&mut self, data: Vec<u8>, constant_pool: &ConstantPool) {
-        let mut reader = ClassReader::new(data);
-        self.signature_index = reader.read_u16();
+    fn read_data(&mut self, reader: &mut ClassReader, _constant_pool: &ConstantPool) -> Result<(), ClassFormatError> {
+        self.signature_index = reader.read_u16()?;
+
+        return Ok(());
     }
 
-    fn get_name(&self) -> &str {
+    fn get_name(&self) -> &'static str {
         return "Signature";
     }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
 }
 
 /* StackMapTable_attribute {
@@ -275,13 +337,18 @@ pub struct StackMapTableAttribute {
 }
 
 impl AttributeInfo for StackMapTableAttribute {
-    fn read_data(&mut self, data: Vec<u8>, constant_pool: &ConstantPool) {
-        self.data = data;
+    fn read_data(&mut self, reader: &mut ClassReader, _constant_pool: &ConstantPool) -> Result<(), ClassFormatError> {
+        self.data = reader.read_data(reader.remaining())?;
+        return Ok(());
     }
 
-    fn get_name(&self) -> &str {
+    fn get_name(&self) -> &'static str {
         return "StackMapTable";
     }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
 }
 
 /*
@@ -294,34 +361,40 @@ LineNumberTable_attribute {
     } line_number_table[line_number_table_length];
 }
 */
+#[derive(Clone, Copy)]
 pub struct LineNumberTableEntry {
-    start_pc: u16,
-    line_number: u16
+    pub start_pc: u16,
+    pub line_number: u16
 }
 
 pub struct LineNumberTableAttribute {
-    line_number_entries: Vec<LineNumberTableEntry>
+    pub line_number_entries: Vec<LineNumberTableEntry>
 }
 
 impl AttributeInfo for LineNumberTableAttribute {
-    fn read_data(&mut self, data: Vec<u8>, constant_pool: &ConstantPool) {
-        let mut reader = ClassReader::new(data);
-        let n = reader.read_u16() as usize;
+    fn read_data(&mut self, reader: &mut ClassReader, _constant_pool: &ConstantPool) -> Result<(), ClassFormatError> {
+        let n = reader.read_u16()? as usize;
         let mut vec: Vec<LineNumberTableEntry> = vec![];
 
-        for i in 0..n {
+        for _ in 0..n {
             vec.push(LineNumberTableEntry {
-                start_pc: reader.read_u16(),
-                line_number: reader.read_u16()
+                start_pc: reader.read_u16()?,
+                line_number: reader.read_u16()?
             })
         }
 
         self.line_number_entries = vec;
+
+        return Ok(());
     }
 
-    fn get_name(&self) -> &str {
+    fn get_name(&self) -> &'static str {
         return "LineNumberTable";
     }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
 }
 
 /*
@@ -338,39 +411,44 @@ LocalVariableTable_attribute {
 }
 */
 pub struct LocalVariableTableEntry {
-    start_pc: u16,
-    length: u16,
-    name_index: u16,
-    descriptor_index: u16,
-    index: u16
+    pub start_pc: u16,
+    pub length: u16,
+    pub name_index: u16,
+    pub descriptor_index: u16,
+    pub index: u16
 }
 
 pub struct LocalVariableTableAttribute {
-    local_var_table_entries: Vec<LocalVariableTableEntry>
+    pub local_var_table_entries: Vec<LocalVariableTableEntry>
 }
 
 impl AttributeInfo for LocalVariableTableAttribute {
-    fn read_data(&mut self, data: Vec<u8>, constant_pool: &ConstantPool) {
-        let mut reader = ClassReader::new(data);
-        let n = reader.read_u16() as usize;
+    fn read_data(&mut self, reader: &mut ClassReader, _constant_pool: &ConstantPool) -> Result<(), ClassFormatError> {
+        let n = reader.read_u16()? as usize;
         let mut vec: Vec<LocalVariableTableEntry> = vec![];
 
-        for i in 0..n {
+        for _ in 0..n {
             vec.push(LocalVariableTableEntry {
-                start_pc: reader.read_u16(),
-                length: reader.read_u16(),
-                name_index: reader.read_u16(),
-                descriptor_index: reader.read_u16(),
-                index: reader.read_u16()
+                start_pc: reader.read_u16()?,
+                length: reader.read_u16()?,
+                name_index: reader.read_u16()?,
+                descriptor_index: reader.read_u16()?,
+                index: reader.read_u16()?
             });
         }
 
         self.local_var_table_entries = vec;
+
+        return Ok(());
     }
 
-    fn get_name(&self) -> &str {
+    fn get_name(&self) -> &'static str {
         return "LocalVariableTable";
     }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
 }
 
 /*
@@ -381,17 +459,126 @@ SourceFile_attribute {
 }
 */
 pub struct SourceFileAttribute {
-    source_file_index: u16
+    pub source_file_index: u16
 }
 
 impl AttributeInfo for SourceFileAttribute {
-    fn read_data(&mut self, data: Vec<u8>, constant_pool: &ConstantPool) {
-        let mut reader = ClassReader::new(data);
+    fn read_data(&mut self, reader: &mut ClassReader, constant_pool: &ConstantPool) -> Result<(), ClassFormatError> {
+        let offset = reader.offset();
+        self.source_file_index = reader.read_u16()?;
+        if let Some(index) = constant_pool.check::<ConstantUTF8Info>(self.source_file_index) {
+            return Err(reader.error_at(offset, FormatErrorReason::BadConstantIndex(index)));
+        }
 
-        self.source_file_index = reader.read_u16();
+        return Ok(());
     }
 
-    fn get_name(&self) -> &str {
+    fn get_name(&self) -> &'static str {
         return "SourceFile"
     }
-}
\ No newline at end of file
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/*
+NestHost_attribute {
+    u2 attribute_name_index;
+    u4 attribute_length;
+    u2 host_class_index;
+}
+*/
+pub struct NestHostAttribute {
+    pub host_class_index: u16
+}
+
+impl AttributeInfo for NestHostAttribute {
+    fn read_data(&mut self, reader: &mut ClassReader, constant_pool: &ConstantPool) -> Result<(), ClassFormatError> {
+        let offset = reader.offset();
+        self.host_class_index = reader.read_u16()?;
+        if let Some(index) = constant_pool.check::<ConstantClassInfo>(self.host_class_index) {
+            return Err(reader.error_at(offset, FormatErrorReason::BadConstantIndex(index)));
+        }
+
+        return Ok(());
+    }
+
+    fn get_name(&self) -> &'static str {
+        return "NestHost"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/*
+BootstrapMethods_attribute {
+    u2 attribute_name_index;
+    u4 attribute_length;
+    u2 num_bootstrap_methods;
+    {   u2 bootstrap_method_ref;
+        u2 num_bootstrap_arguments;
+        u2 bootstrap_arguments[num_bootstrap_arguments];
+    } bootstrap_methods[num_bootstrap_methods];
+}
+*/
+#[derive(Clone)]
+pub struct BootstrapMethod {
+    pub bootstrap_method_ref: u16,
+    pub bootstrap_arguments: Vec<u16>,
+}
+
+pub struct BootstrapMethodsAttribute {
+    pub bootstrap_methods: Vec<BootstrapMethod>
+}
+
+impl AttributeInfo for BootstrapMethodsAttribute {
+    fn read_data(&mut self, reader: &mut ClassReader, constant_pool: &ConstantPool) -> Result<(), ClassFormatError> {
+        let n = reader.read_u16()?;
+        let mut vec: Vec<BootstrapMethod> = vec![];
+
+        for _ in 0..n {
+            let offset = reader.offset();
+            let bootstrap_method_ref = reader.read_u16()?;
+            if let Some(index) = constant_pool.check::<ConstantMethodHandleInfo>(bootstrap_method_ref) {
+                return Err(reader.error_at(offset, FormatErrorReason::BadConstantIndex(index)));
+            }
+
+            let offset = reader.offset();
+            let bootstrap_arguments = reader.read_u16s()?;
+            // the static arguments are loadable constants (JVMS 4.4)
+            for argument in &bootstrap_arguments {
+                let loadable = constant_pool.check::<ConstantIntegerInfo>(*argument)
+                    .and(constant_pool.check::<ConstantFloatInfo>(*argument))
+                    .and(constant_pool.check::<ConstantLongInfo>(*argument))
+                    .and(constant_pool.check::<ConstantDoubleInfo>(*argument))
+                    .and(constant_pool.check::<ConstantStringInfo>(*argument))
+                    .and(constant_pool.check::<ConstantClassInfo>(*argument))
+                    .and(constant_pool.check::<ConstantMethodHandleInfo>(*argument))
+                    .and(constant_pool.check::<ConstantMethodTypeInfo>(*argument));
+                if let Some(index) = loadable {
+                    return Err(reader.error_at(offset, FormatErrorReason::BadConstantIndex(index)));
+                }
+            }
+
+            vec.push(BootstrapMethod {
+                bootstrap_method_ref,
+                bootstrap_arguments,
+            });
+        }
+
+        self.bootstrap_methods = vec;
+
+        return Ok(());
+    }
+
+    fn get_name(&self) -> &'static str {
+        return "BootstrapMethods"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
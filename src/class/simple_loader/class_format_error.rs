@@ -0,0 +1,63 @@
+use std::fmt;
+
+/*
+Raised while decoding a class file. The offset is absolute within the
+class file, even for bytes parsed out of nested attributes, so that a
+corrupt class can be inspected with a hex dump.
+ */
+#[derive(Debug)]
+pub struct ClassFormatError {
+    pub offset: usize,
+    pub structure: &'static str,
+    pub reason: FormatErrorReason,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FormatErrorReason {
+    UnexpectedEnd { wanted: usize, remaining: usize },
+    BadMagic(u32),
+    UnknownConstantTag(u8),
+    MalformedUtf8(&'static str),
+    BadConstantIndex(u16),
+    BadDescriptor(String),
+    LengthMismatch { declared: usize, consumed: usize },
+    ExtraBytes(usize),
+    // the class file couldn't be read, e.g. from a corrupt archive
+    Unreadable(String),
+}
+
+impl fmt::Display for FormatErrorReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatErrorReason::UnexpectedEnd { wanted, remaining } =>
+                write!(f, "truncated data, {} bytes wanted but {} remaining", wanted, remaining),
+            FormatErrorReason::BadMagic(magic) =>
+                write!(f, "incompatible magic value {:#010X}", magic),
+            FormatErrorReason::UnknownConstantTag(tag) =>
+                write!(f, "unknown constant tag {}", tag),
+            FormatErrorReason::MalformedUtf8(detail) =>
+                write!(f, "malformed modified UTF-8, {}", detail),
+            FormatErrorReason::BadConstantIndex(index) =>
+                write!(f, "invalid constant pool index #{}", index),
+            FormatErrorReason::BadDescriptor(descriptor) =>
+                write!(f, "invalid descriptor \"{}\"", descriptor),
+            FormatErrorReason::LengthMismatch { declared, consumed } =>
+                write!(f, "declared length {} but {} bytes were consumed", declared, consumed),
+            FormatErrorReason::ExtraBytes(n) =>
+                write!(f, "{} extra bytes at the end of the class file", n),
+            FormatErrorReason::Unreadable(message) =>
+                write!(f, "{}", message),
+        }
+    }
+}
+
+impl fmt::Display for ClassFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let FormatErrorReason::Unreadable(_) = self.reason {
+            return write!(f, "{}: {}", self.structure, self.reason);
+        }
+        return write!(f, "{} at offset {}: {}", self.structure, self.offset, self.reason);
+    }
+}
+
+impl std::error::Error for ClassFormatError {}
@@ -1,50 +1,76 @@
-use crate::class::*;
-use crate::class::simple_loader::constant_pool::ConstantPool;
+use crate::class::simple_loader::constant_pool::{ConstantPool, ConstantClassInfo};
+use crate::class::descriptor::{is_field_descriptor, MethodDescriptor};
 use crate::class::simple_loader::attribute_info::{AttributeInfo, read_attribute_info_vec};
+use crate::class::simple_loader::class_format_error::{ClassFormatError, FormatErrorReason};
 
 pub struct ClassFile {
     // magic
-    pub(crate) minor_version: u16,
-    pub(crate) major_version: u16,
-    pub(crate) constant_pool: ConstantPool,
-    pub(crate) access_flags: u16,
-    pub(crate) this_class: u16,
-    pub(crate) super_class: u16,
-    pub(crate) interfaces: Vec<u16>,
-    pub(crate) fields: Vec<MemberInfo>,
-    pub(crate) methods: Vec<MemberInfo>,
-    pub(crate) attributes: Vec<Box<dyn AttributeInfo>>
+    pub minor_version: u16,
+    pub major_version: u16,
+    pub constant_pool: ConstantPool,
+    pub access_flags: u16,
+    pub this_class: u16,
+    pub super_class: u16,
+    pub interfaces: Vec<u16>,
+    pub fields: Vec<MemberInfo>,
+    pub methods: Vec<MemberInfo>,
+    pub attributes: Vec<Box<dyn AttributeInfo>>
 }
 
 impl ClassFile {
-    pub fn new(reader: &mut ClassReader) -> ClassFile {
-        let magic_number = reader.read_u32();
+    pub fn new(reader: &mut ClassReader) -> Result<ClassFile, ClassFormatError> {
+        let magic_number = reader.read_u32()?;
         if magic_number != 0xCAFEBABE {
-            panic!("The magic number doesn't conform to the regulation.");
+            return Err(ClassFormatError {
+                offset: 0,
+                structure: "ClassFile",
+                reason: FormatErrorReason::BadMagic(magic_number),
+            });
         }
-        let minor_version = reader.read_u16();
-        let major_version = reader.read_u16();
-        let constant_pool = ConstantPool::new(reader);
-        let access_flags = reader.read_u16();
-        let this_class = reader.read_u16();
-        let super_class = reader.read_u16();
-        let interfaces = reader.read_u16s();
-        let fields = MemberInfo::read_members(reader, &constant_pool);
-        let methods = MemberInfo::read_members(reader, &constant_pool);
-        let attributes = read_attribute_info_vec(reader, &constant_pool);
-
-        return ClassFile {
-            minor_version: minor_version,
-            major_version: major_version,
-            constant_pool: constant_pool,
-            access_flags: access_flags,
-            this_class: this_class,
-            super_class: super_class,
-            interfaces: interfaces,
-            fields: fields,
-            methods: methods,
-            attributes: attributes
+        let minor_version = reader.read_u16()?;
+        let major_version = reader.read_u16()?;
+        let constant_pool = ConstantPool::new(reader)?;
+        let access_flags = reader.read_u16()?;
+        let this_class = ClassFile::read_class_index(reader, &constant_pool, false)?;
+        // only java/lang/Object has no super class
+        let super_class = ClassFile::read_class_index(reader, &constant_pool, true)?;
+        let n = reader.read_u16()?;
+        let mut interfaces: Vec<u16> = vec![];
+        for _ in 0..n {
+            interfaces.push(ClassFile::read_class_index(reader, &constant_pool, false)?);
         }
+        let fields = MemberInfo::read_members(reader, &constant_pool, "field_info")?;
+        let methods = MemberInfo::read_members(reader, &constant_pool, "method_info")?;
+        let attributes = read_attribute_info_vec(reader, &constant_pool)?;
+
+        if reader.remaining() != 0 {
+            return Err(reader.error(FormatErrorReason::ExtraBytes(reader.remaining())));
+        }
+
+        return Ok(ClassFile {
+            minor_version,
+            major_version,
+            constant_pool,
+            access_flags,
+            this_class,
+            super_class,
+            interfaces,
+            fields,
+            methods,
+            attributes
+        });
+    }
+
+    fn read_class_index(reader: &mut ClassReader, cp: &ConstantPool, zero_allowed: bool) -> Result<u16, ClassFormatError> {
+        let offset = reader.offset();
+        let index = reader.read_u16()?;
+        if index == 0 && zero_allowed {
+            return Ok(0);
+        }
+        if cp.check::<ConstantClassInfo>(index).is_some() {
+            return Err(reader.error_at(offset, FormatErrorReason::BadConstantIndex(index)));
+        }
+        return Ok(index);
     }
 }
 
@@ -65,119 +91,179 @@ method_info {
 }
 */
 
+// name and descriptor are checked to be valid Utf8 entries while parsing
 pub struct MemberInfo {
-    access_flags: u16,
-    name_index: u16,
-    descriptor_index: u16,
-    attributes: Vec<Box<dyn AttributeInfo>>,
+    pub access_flags: u16,
+    pub name_index: u16,
+    pub descriptor_index: u16,
+    pub attributes: Vec<Box<dyn AttributeInfo>>,
 }
 
 impl MemberInfo {
-    pub fn new(reader: &mut ClassReader, cp: &ConstantPool) -> MemberInfo {
-        return MemberInfo {
-            access_flags: reader.read_u16(),
-            name_index: reader.read_u16(),
-            descriptor_index: reader.read_u16(),
-            attributes: read_attribute_info_vec(reader, cp),
+    pub fn new(reader: &mut ClassReader, cp: &ConstantPool, is_method: bool) -> Result<MemberInfo, ClassFormatError> {
+        let access_flags = reader.read_u16()?;
+        let name_offset = reader.offset();
+        let name_index = reader.read_u16()?;
+        if cp.get_utf8(name_index as usize).is_none() {
+            return Err(reader.error_at(name_offset, FormatErrorReason::BadConstantIndex(name_index)));
+        }
+
+        let descriptor_offset = reader.offset();
+        let descriptor_index = reader.read_u16()?;
+        let descriptor = match cp.get_utf8(descriptor_index as usize) {
+            Some(utf8) => utf8.get_str(),
+            None => return Err(reader.error_at(descriptor_offset, FormatErrorReason::BadConstantIndex(descriptor_index))),
+        };
+        let valid = if is_method { MethodDescriptor::parse(descriptor).is_some() } else { is_field_descriptor(descriptor) };
+        if !valid {
+            return Err(reader.error_at(descriptor_offset, FormatErrorReason::BadDescriptor(descriptor.clone())));
         }
+
+        return Ok(MemberInfo {
+            access_flags,
+            name_index,
+            descriptor_index,
+            attributes: read_attribute_info_vec(reader, cp)?,
+        });
     }
 
-    pub fn read_members(reader: &mut ClassReader, cp: &ConstantPool) -> Vec<MemberInfo> {
-        let n = reader.read_u16();
+    pub fn read_members(reader: &mut ClassReader, cp: &ConstantPool, structure: &'static str) -> Result<Vec<MemberInfo>, ClassFormatError> {
+        let n = reader.read_u16()?;
         let mut vec: Vec<MemberInfo> = vec![];
 
-        for i in 0..n {
-            vec.push(MemberInfo::new(reader, cp));
+        for _ in 0..n {
+            let outer = reader.begin(structure);
+            vec.push(MemberInfo::new(reader, cp, structure == "method_info")?);
+            reader.end(outer);
         }
 
-        return vec;
+        return Ok(vec);
+    }
+
+    pub fn get_name<'a>(&self, cp: &'a ConstantPool) -> &'a str {
+        return cp.get_utf8(self.name_index as usize).map_or("", |utf8| utf8.get_str().as_str());
+    }
+
+    pub fn get_descriptor<'a>(&self, cp: &'a ConstantPool) -> &'a str {
+        return cp.get_utf8(self.descriptor_index as usize).map_or("", |utf8| utf8.get_str().as_str());
     }
 }
 
 pub struct ClassReader {
     data: Vec<u8>,
     point: usize,
+    // absolute offset of data[0] within the class file
+    base: usize,
+    structure: &'static str,
 }
 
 impl ClassReader {
     pub fn new(data: Vec<u8>) -> ClassReader {
+        return ClassReader::with_base(data, 0, "ClassFile");
+    }
+
+    // a reader over a nested part of the class file, e.g. the body of an attribute
+    pub fn with_base(data: Vec<u8>, base: usize, structure: &'static str) -> ClassReader {
         return ClassReader {
             data,
             point: 0,
+            base,
+            structure,
         }
     }
 
-    pub fn read_u64(&mut self) -> u64 {
-        let data = self.read_reversed_data(8);
+    // marks the start of a nested structure and returns the enclosing one,
+    // which should be handed back to `end` once the structure is read.
+    pub fn begin(&mut self, structure: &'static str) -> &'static str {
+        let outer = self.structure;
+        self.structure = structure;
+        return outer;
+    }
+
+    pub fn end(&mut self, outer: &'static str) {
+        self.structure = outer;
+    }
 
-        // let ptr :*const u8 = data.as_ptr();
-        // let ptr :*const u64 = ptr as *const u64;
-        // let s = unsafe{ *ptr};
-        // return s;
-        let d : [u8; 8] = [*data.get(0).unwrap(), *data.get(1).unwrap(),
-            *data.get(2).unwrap(), *data.get(3).unwrap(),
-            *data.get(4).unwrap(), *data.get(5).unwrap(),
-            *data.get(6).unwrap(), *data.get(7).unwrap()];
+    pub fn offset(&self) -> usize {
+        return self.base + self.point;
+    }
 
-        return u64::from_le_bytes(d);
+    pub fn remaining(&self) -> usize {
+        return self.data.len() - self.point;
     }
 
-    pub fn read_u16(&mut self) -> u16 {
-        let data = self.read_reversed_data(2);
+    pub fn error(&self, reason: FormatErrorReason) -> ClassFormatError {
+        return self.error_at(self.offset(), reason);
+    }
 
-        // let ptr :*const u8 = data.as_ptr();
-        // let ptr :*const u16 = ptr as *const u16;
-        // let s = unsafe{ *ptr};
-        // return s;
-        let d : [u8; 2] = [*data.get(0).unwrap(), *data.get(1).unwrap()];
+    pub fn error_at(&self, offset: usize, reason: FormatErrorReason) -> ClassFormatError {
+        return ClassFormatError {
+            offset,
+            structure: self.structure,
+            reason,
+        }
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, ClassFormatError> {
+        let data = self.read_reversed_data(8)?;
+        let d : [u8; 8] = [data[0], data[1], data[2], data[3],
+            data[4], data[5], data[6], data[7]];
 
-        return u16::from_le_bytes(d);
+        return Ok(u64::from_le_bytes(d));
     }
 
-    pub fn read_u32(&mut self) -> u32 {
-        // let ptr :*const u8 = data.as_ptr();
-        // let ptr :*const u32 = ptr as *const u32;
-        // let s = unsafe{ *ptr};
-        // return u32::from_be_bytes(data);
-        let data = self.read_reversed_data(4);
-        let d : [u8; 4] = [*data.get(0).unwrap(), *data.get(1).unwrap(),
-            *data.get(2).unwrap(), *data.get(3).unwrap()];
+    pub fn read_u16(&mut self) -> Result<u16, ClassFormatError> {
+        let data = self.read_reversed_data(2)?;
+        let d : [u8; 2] = [data[0], data[1]];
 
-        return u32::from_le_bytes(d);
+        return Ok(u16::from_le_bytes(d));
     }
 
-    pub fn read_u16s(&mut self) -> Vec<u16> {
-        let n = self.read_u16();
+    pub fn read_u32(&mut self) -> Result<u32, ClassFormatError> {
+        let data = self.read_reversed_data(4)?;
+        let d : [u8; 4] = [data[0], data[1], data[2], data[3]];
+
+        return Ok(u32::from_le_bytes(d));
+    }
+
+    pub fn read_u16s(&mut self) -> Result<Vec<u16>, ClassFormatError> {
+        let n = self.read_u16()?;
         let mut s : Vec<u16> = vec!();
-        for i in 0..n {
-            s.push(self.read_u16());
+        for _ in 0..n {
+            s.push(self.read_u16()?);
         }
-        return s;
+        return Ok(s);
     }
 
-    pub fn read_u8(&mut self) -> u8 {
+    pub fn read_u8(&mut self) -> Result<u8, ClassFormatError> {
+        self.ensure(1)?;
         let d = self.data[self.point];
         self.point += 1;
-        return d;
+        return Ok(d);
     }
 
-    pub fn read_reversed_data(&mut self, size: usize) -> Vec<u8> {
-        let data = &self.data[self.point .. self.point + size];
-        self.point += size;
-
-        let mut rev: Vec<u8> = vec!();
-        for i in 0..data.len() {
-            rev.push(data[i]);
-        }
+    pub fn read_reversed_data(&mut self, size: usize) -> Result<Vec<u8>, ClassFormatError> {
+        let mut rev = self.read_data(size)?;
         rev.reverse();
 
-        return rev;
+        return Ok(rev);
     }
 
-    pub fn read_data(&mut self, size: usize) -> Vec<u8> {
+    pub fn read_data(&mut self, size: usize) -> Result<Vec<u8>, ClassFormatError> {
+        self.ensure(size)?;
         let src_data = &self.data[self.point .. self.point + size];
         self.point += size;
 
-        return src_data.to_vec();
+        return Ok(src_data.to_vec());
     }
-}
\ No newline at end of file
+
+    fn ensure(&self, size: usize) -> Result<(), ClassFormatError> {
+        if self.remaining() < size {
+            return Err(self.error(FormatErrorReason::UnexpectedEnd {
+                wanted: size,
+                remaining: self.remaining(),
+            }));
+        }
+        return Ok(());
+    }
+}
@@ -1,19 +1,26 @@
 use std::char::{decode_utf16, REPLACEMENT_CHARACTER};
 use crate::class::simple_loader::class_reader::ClassReader;
+use crate::class::simple_loader::class_format_error::{ClassFormatError, FormatErrorReason};
 
 pub struct ConstantUTF8Info {
     pub(crate) str: String,
 }
 
 impl ConstantInfo for ConstantUTF8Info {
-    fn read_info(&mut self, reader: &mut ClassReader) -> bool {
-        let size = reader.read_u16();
+    fn read_info(&mut self, reader: &mut ClassReader) -> Result<bool, ClassFormatError> {
+        let size = reader.read_u16()?;
+        let start = reader.offset();
         // don't reverse the data
-        let data = reader.read_data(size as usize);
+        let data = reader.read_data(size as usize)?;
 
-        self.str = ConstantUTF8Info::decode_mutf8(data);
+        match ConstantUTF8Info::decode_mutf8(data) {
+            Ok(str) => self.str = str,
+            Err((at, detail)) => {
+                return Err(reader.error_at(start + at, FormatErrorReason::MalformedUtf8(detail)));
+            }
+        }
 
-        return false;
+        return Ok(false);
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -26,7 +33,8 @@ impl ConstantUTF8Info {
         return &self.str;
     }
 
-    fn decode_mutf8(data: Vec<u8>) -> String {
+    // on failure, returns the index of the offending byte and what is wrong with it
+    fn decode_mutf8(data: Vec<u8>) -> Result<String, (usize, &'static str)> {
         /*
         the data vector shouldn't be reversed,
         as the following code is paraphrased from Java.
@@ -53,7 +61,7 @@ impl ConstantUTF8Info {
         while count < utflen {
             c = data[count] as u16;
             match c >> 4 {
-                0|1|2|3|4|5|6|7 => {
+                0..=7 => {
                     count += 1;
                     charvec[charvec_count] = c;
                     charvec_count += 1;
@@ -61,38 +69,38 @@ impl ConstantUTF8Info {
                 12|13 => {
                     count += 2;
                     if count > utflen {
-                        panic!("partial character at end");
+                        return Err((count - 2, "partial character at end"));
                     }
                     char2 = data[count - 1] as u16;
                     if char2 & 0xC0 != 0x80 {
-                        panic!("malformed input")
+                        return Err((count - 1, "malformed input"));
                     }
-                    charvec[charvec_count] = c&0x1F<<6 | char2&0x3F;
+                    charvec[charvec_count] = (c&0x1F)<<6 | char2&0x3F;
                     charvec_count += 1;
                 }
                 14 => {
                     count += 3;
                     if count > utflen {
-                        panic!("malformed input");
+                        return Err((count - 3, "partial character at end"));
                     }
                     char2 = data[count - 2] as u16;
                     char3 = data[count - 1] as u16;
                     if char2&0xC0 != 0x80 || char3&0xC0 != 0x80 {
-                        panic!("malformed input")
+                        return Err((count - 3, "malformed input"));
                     }
-                    charvec[charvec_count] = c&0x0F<<12 | char2&0x3F<<6 | char3&0x3F<<0;
+                    charvec[charvec_count] = (c&0x0F)<<12 | (char2&0x3F)<<6 | char3&0x3F;
                     charvec_count += 1;
                 }
                 _ => {
-                    panic!("malformed input")
+                    return Err((count, "malformed input"));
                 }
             }
         }
 
-        let (left, right) = charvec.split_at(charvec_count);
-        return decode_utf16(left.iter().cloned()).
+        let (left, _) = charvec.split_at(charvec_count);
+        return Ok(decode_utf16(left.iter().cloned()).
             map(|r| r.unwrap_or(REPLACEMENT_CHARACTER)).
-            collect::<String>();
+            collect::<String>());
     }
 }
 
@@ -101,10 +109,10 @@ pub struct ConstantClassInfo {
 }
 
 impl ConstantInfo for ConstantClassInfo {
-    fn read_info(&mut self, reader: &mut ClassReader) -> bool {
-        self.name_index = reader.read_u16();
+    fn read_info(&mut self, reader: &mut ClassReader) -> Result<bool, ClassFormatError> {
+        self.name_index = reader.read_u16()?;
 
-        return false;
+        return Ok(false);
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -117,28 +125,10 @@ pub struct ConstantStringInfo {
 }
 
 impl ConstantInfo for ConstantStringInfo {
-    fn read_info(&mut self, reader: &mut ClassReader) -> bool {
-        self.string_index = reader.read_u16();
+    fn read_info(&mut self, reader: &mut ClassReader) -> Result<bool, ClassFormatError> {
+        self.string_index = reader.read_u16()?;
 
-        return false;
-    }
-
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
-}
-
-pub struct ConstantMemberRefInfo {
-    class_index: u16,
-    name_and_type_index: u16,
-}
-
-impl ConstantInfo for ConstantMemberRefInfo {
-    fn read_info(&mut self, reader: &mut ClassReader) -> bool {
-        self.class_index = reader.read_u16();
-        self.name_and_type_index = reader.read_u16();
-
-        return false;
+        return Ok(false);
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -151,10 +141,10 @@ pub struct ConstantIntegerInfo {
 }
 
 impl ConstantInfo for ConstantIntegerInfo {
-    fn read_info(&mut self, reader: &mut ClassReader) -> bool {
-        self.val = reader.read_u32() as i32;
+    fn read_info(&mut self, reader: &mut ClassReader) -> Result<bool, ClassFormatError> {
+        self.val = reader.read_u32()? as i32;
 
-        return false;
+        return Ok(false);
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -167,10 +157,10 @@ pub struct ConstantLongInfo {
 }
 
 impl ConstantInfo for ConstantLongInfo {
-    fn read_info(&mut self, reader: &mut ClassReader) -> bool {
-        self.val = reader.read_u64() as i64;
+    fn read_info(&mut self, reader: &mut ClassReader) -> Result<bool, ClassFormatError> {
+        self.val = reader.read_u64()? as i64;
 
-        return true;
+        return Ok(true);
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -183,15 +173,13 @@ pub struct ConstantDoubleInfo {
 }
 
 impl ConstantInfo for ConstantDoubleInfo {
-    fn read_info(&mut self, reader: &mut ClassReader) -> bool {
-        let d = reader.read_data(8);
+    fn read_info(&mut self, reader: &mut ClassReader) -> Result<bool, ClassFormatError> {
+        let d = reader.read_data(8)?;
         let mut data:[u8; 8] = [0; 8];
-        for i in 0..8 {
-            data[i] = d[i];
-        }
+        data.copy_from_slice(&d);
         self.val = f64::from_be_bytes(data);
 
-        return true;
+        return Ok(true);
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -204,15 +192,13 @@ pub struct ConstantFloatInfo {
 }
 
 impl ConstantInfo for ConstantFloatInfo {
-    fn read_info(&mut self, reader: &mut ClassReader) -> bool {
-        let d = reader.read_data(4);
+    fn read_info(&mut self, reader: &mut ClassReader) -> Result<bool, ClassFormatError> {
+        let d = reader.read_data(4)?;
         let mut data:[u8; 4] = [0; 4];
-        for i in 0..4 {
-            data[i] = d[i];
-        }
+        data.copy_from_slice(&d);
         self.val = f32::from_be_bytes(data);
 
-        return false;
+        return Ok(false);
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -226,11 +212,11 @@ pub struct ConstantFieldRefInfo {
 }
 
 impl ConstantInfo for ConstantFieldRefInfo {
-    fn read_info(&mut self, reader: &mut ClassReader) -> bool {
-        self.class_index = reader.read_u16();
-        self.name_and_type_index = reader.read_u16();
+    fn read_info(&mut self, reader: &mut ClassReader) -> Result<bool, ClassFormatError> {
+        self.class_index = reader.read_u16()?;
+        self.name_and_type_index = reader.read_u16()?;
 
-        return false;
+        return Ok(false);
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -244,11 +230,11 @@ pub struct ConstantMethodRefInfo {
 }
 
 impl ConstantInfo for ConstantMethodRefInfo {
-    fn read_info(&mut self, reader: &mut ClassReader) -> bool {
-        self.class_index = reader.read_u16();
-        self.name_and_type_index = reader.read_u16();
+    fn read_info(&mut self, reader: &mut ClassReader) -> Result<bool, ClassFormatError> {
+        self.class_index = reader.read_u16()?;
+        self.name_and_type_index = reader.read_u16()?;
 
-        return false;
+        return Ok(false);
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -262,11 +248,11 @@ pub struct ConstantInterfaceMethodRefInfo {
 }
 
 impl ConstantInfo for ConstantInterfaceMethodRefInfo {
-    fn read_info(&mut self, reader: &mut ClassReader) -> bool {
-        self.class_index = reader.read_u16();
-        self.name_and_type_index = reader.read_u16();
+    fn read_info(&mut self, reader: &mut ClassReader) -> Result<bool, ClassFormatError> {
+        self.class_index = reader.read_u16()?;
+        self.name_and_type_index = reader.read_u16()?;
 
-        return false;
+        return Ok(false);
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -280,11 +266,11 @@ pub struct ConstantNameAndTypeInfo {
 }
 
 impl ConstantInfo for ConstantNameAndTypeInfo {
-    fn read_info(&mut self, reader: &mut ClassReader) -> bool {
-        self.name_index = reader.read_u16();
-        self.descriptor_index = reader.read_u16();
+    fn read_info(&mut self, reader: &mut ClassReader) -> Result<bool, ClassFormatError> {
+        self.name_index = reader.read_u16()?;
+        self.descriptor_index = reader.read_u16()?;
 
-        return false;
+        return Ok(false);
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -298,11 +284,11 @@ pub struct ConstantMethodHandleInfo {
 }
 
 impl ConstantInfo for ConstantMethodHandleInfo {
-    fn read_info(&mut self, reader: &mut ClassReader) -> bool {
-        self.reference_kind = reader.read_u8();
-        self.reference_index = reader.read_u16();
+    fn read_info(&mut self, reader: &mut ClassReader) -> Result<bool, ClassFormatError> {
+        self.reference_kind = reader.read_u8()?;
+        self.reference_index = reader.read_u16()?;
 
-        return false;
+        return Ok(false);
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -315,10 +301,10 @@ pub struct ConstantMethodTypeInfo {
 }
 
 impl ConstantInfo for ConstantMethodTypeInfo {
-    fn read_info(&mut self, reader: &mut ClassReader) -> bool {
-        self.descriptor_index = reader.read_u16();
+    fn read_info(&mut self, reader: &mut ClassReader) -> Result<bool, ClassFormatError> {
+        self.descriptor_index = reader.read_u16()?;
 
-        return false;
+        return Ok(false);
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -332,11 +318,11 @@ pub struct ConstantInvokeDynamicInfo {
 }
 
 impl ConstantInfo for ConstantInvokeDynamicInfo {
-    fn read_info(&mut self, reader: &mut ClassReader) -> bool {
-        self.bootstrap_method_attr_index = reader.read_u16();
-        self.name_and_type_index = reader.read_u16();
+    fn read_info(&mut self, reader: &mut ClassReader) -> Result<bool, ClassFormatError> {
+        self.bootstrap_method_attr_index = reader.read_u16()?;
+        self.name_and_type_index = reader.read_u16()?;
 
-        return false;
+        return Ok(false);
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -347,47 +333,52 @@ impl ConstantInfo for ConstantInvokeDynamicInfo {
 pub struct ConstantPool (Vec<Option<Box<dyn ConstantInfo>>>);
 
 pub trait ConstantInfo {
-    fn read_info(&mut self, reader: &mut ClassReader) -> bool;
+    fn read_info(&mut self, reader: &mut ClassReader) -> Result<bool, ClassFormatError>;
 
     fn as_any(&self) -> &dyn Any;
 }
 
 use std::any::{Any};
 
-fn read_constant_info(reader: &mut ClassReader) -> Box<dyn ConstantInfo> {
-    let index = reader.read_u8();
+fn read_constant_info(reader: &mut ClassReader) -> Result<Box<dyn ConstantInfo>, ClassFormatError> {
+    let offset = reader.offset();
+    let index = reader.read_u8()?;
 
     match index {
-        1 => return Box::new(ConstantUTF8Info{ str: "".to_string() }),
-        3 => return Box::new(ConstantIntegerInfo{ val: 0 }),
-        4 => return Box::new(ConstantFloatInfo{val: 0f32}),
-        5 => return Box::new(ConstantLongInfo{val: 0}),
-        6 => return Box::new(ConstantDoubleInfo{val: 0f64}),
-        7 => return Box::new(ConstantClassInfo{ name_index: 0 }),
-        8 => return Box::new(ConstantStringInfo{ string_index: 0 }),
-        9 => return Box::new(ConstantFieldRefInfo { class_index: 0, name_and_type_index: 0 }),
-        10 => return Box::new(ConstantMethodRefInfo{ class_index: 0, name_and_type_index: 0 }),
-        11 => return Box::new(ConstantInterfaceMethodRefInfo{ class_index: 0, name_and_type_index: 0 }),
-        12 => return Box::new(ConstantNameAndTypeInfo{ name_index: 0, descriptor_index: 0 }),
-        15 => return Box::new(ConstantMethodHandleInfo{ reference_kind: 0, reference_index: 0 }),
-        16 => return Box::new(ConstantMethodTypeInfo{ descriptor_index: 0 }),
-        18 => return Box::new(ConstantInvokeDynamicInfo{ bootstrap_method_attr_index: 0, name_and_type_index: 0 }),
+        1 => return Ok(Box::new(ConstantUTF8Info{ str: "".to_string() })),
+        3 => return Ok(Box::new(ConstantIntegerInfo{ val: 0 })),
+        4 => return Ok(Box::new(ConstantFloatInfo{val: 0f32})),
+        5 => return Ok(Box::new(ConstantLongInfo{val: 0})),
+        6 => return Ok(Box::new(ConstantDoubleInfo{val: 0f64})),
+        7 => return Ok(Box::new(ConstantClassInfo{ name_index: 0 })),
+        8 => return Ok(Box::new(ConstantStringInfo{ string_index: 0 })),
+        9 => return Ok(Box::new(ConstantFieldRefInfo { class_index: 0, name_and_type_index: 0 })),
+        10 => return Ok(Box::new(ConstantMethodRefInfo{ class_index: 0, name_and_type_index: 0 })),
+        11 => return Ok(Box::new(ConstantInterfaceMethodRefInfo{ class_index: 0, name_and_type_index: 0 })),
+        12 => return Ok(Box::new(ConstantNameAndTypeInfo{ name_index: 0, descriptor_index: 0 })),
+        15 => return Ok(Box::new(ConstantMethodHandleInfo{ reference_kind: 0, reference_index: 0 })),
+        16 => return Ok(Box::new(ConstantMethodTypeInfo{ descriptor_index: 0 })),
+        18 => return Ok(Box::new(ConstantInvokeDynamicInfo{ bootstrap_method_attr_index: 0, name_and_type_index: 0 })),
         _ => {
-            panic!("Unknown constant info id: {}", index);
+            return Err(reader.error_at(offset, FormatErrorReason::UnknownConstantTag(index)));
         }
     }
 }
 
 impl ConstantPool {
-    pub fn new(reader: &mut ClassReader) -> ConstantPool {
-        let cp_count = reader.read_u16();
+    pub fn new(reader: &mut ClassReader) -> Result<ConstantPool, ClassFormatError> {
+        let cp_count = reader.read_u16()?;
         let mut info: Vec<Option<Box<dyn ConstantInfo>>> = vec![];
+        let outer = reader.begin("constant_pool");
 
         let mut i = 1;
+        let mut offsets: Vec<usize> = vec![0];
         info.push(None); // placeholder for index #0
+        offsets.push(0);
         while i < cp_count {
-            let mut cp = read_constant_info(reader);
-            let plus = cp.read_info(reader);
+            offsets.push(reader.offset());
+            let mut cp = read_constant_info(reader)?;
+            let plus = cp.read_info(reader)?;
 
             info.push(Some(cp));
             i += 1;
@@ -395,10 +386,62 @@ impl ConstantPool {
             if plus {
                 i += 1;
                 info.push(None);
+                offsets.push(0);
+            }
+        }
+
+        let pool = ConstantPool(info);
+        for (n, offset) in offsets.iter().enumerate() {
+            if let Some(bad) = pool.check_references(n) {
+                return Err(reader.error_at(*offset, FormatErrorReason::BadConstantIndex(bad)));
             }
         }
 
-        return ConstantPool(info);
+        reader.end(outer);
+        return Ok(pool);
+    }
+
+    // Some(index) if the index isn't an entry of type T
+    pub fn check<T: 'static>(&self, index: u16) -> Option<u16> {
+        return match self.get_any(index as usize) {
+            Some(any) if any.is::<T>() => None,
+            _ => Some(index),
+        };
+    }
+
+    // every index held by entry n must point at an entry of the right kind
+    fn check_references(&self, n: usize) -> Option<u16> {
+        let any = self.get_any(n)?;
+        if let Some(c) = any.downcast_ref::<ConstantClassInfo>() {
+            return self.check::<ConstantUTF8Info>(c.name_index);
+        }
+        if let Some(c) = any.downcast_ref::<ConstantStringInfo>() {
+            return self.check::<ConstantUTF8Info>(c.string_index);
+        }
+        if let Some(c) = any.downcast_ref::<ConstantFieldRefInfo>() {
+            return self.check::<ConstantClassInfo>(c.class_index).or(self.check::<ConstantNameAndTypeInfo>(c.name_and_type_index));
+        }
+        if let Some(c) = any.downcast_ref::<ConstantMethodRefInfo>() {
+            return self.check::<ConstantClassInfo>(c.class_index).or(self.check::<ConstantNameAndTypeInfo>(c.name_and_type_index));
+        }
+        if let Some(c) = any.downcast_ref::<ConstantInterfaceMethodRefInfo>() {
+            return self.check::<ConstantClassInfo>(c.class_index).or(self.check::<ConstantNameAndTypeInfo>(c.name_and_type_index));
+        }
+        if let Some(c) = any.downcast_ref::<ConstantNameAndTypeInfo>() {
+            return self.check::<ConstantUTF8Info>(c.name_index).or(self.check::<ConstantUTF8Info>(c.descriptor_index));
+        }
+        if let Some(c) = any.downcast_ref::<ConstantMethodHandleInfo>() {
+            return self.check::<ConstantFieldRefInfo>(c.reference_index)
+                .and(self.check::<ConstantMethodRefInfo>(c.reference_index))
+                .and(self.check::<ConstantInterfaceMethodRefInfo>(c.reference_index));
+        }
+        if let Some(c) = any.downcast_ref::<ConstantMethodTypeInfo>() {
+            return self.check::<ConstantUTF8Info>(c.descriptor_index);
+        }
+        if let Some(c) = any.downcast_ref::<ConstantInvokeDynamicInfo>() {
+            return self.check::<ConstantNameAndTypeInfo>(c.name_and_type_index);
+        }
+        return None;
     }
 
     pub fn get(&self, n: usize) -> Option<&Option<Box<dyn ConstantInfo>>> {
@@ -407,10 +450,35 @@ impl ConstantPool {
     }
 
     pub fn get_utf8(&self, n: usize) -> Option<&ConstantUTF8Info> {
-        self.get_any(n).downcast_ref::<ConstantUTF8Info>()
+        self.get_any(n)?.downcast_ref::<ConstantUTF8Info>()
+    }
+
+    pub fn get_utf8_str(&self, n: usize) -> Option<&str> {
+        return self.get_utf8(n).map(|utf8| utf8.get_str().as_str());
+    }
+
+    // the name of a CONSTANT_Class entry, e.g. "java/lang/Object"
+    pub fn get_class_name(&self, n: usize) -> Option<&str> {
+        let class = self.get_any(n)?.downcast_ref::<ConstantClassInfo>()?;
+        return self.get_utf8_str(class.name_index as usize);
+    }
+
+    // the name and descriptor of a CONSTANT_NameAndType entry
+    pub fn get_name_and_type(&self, n: usize) -> Option<(&str, &str)> {
+        let nt = self.get_any(n)?.downcast_ref::<ConstantNameAndTypeInfo>()?;
+        return Some((self.get_utf8_str(nt.name_index as usize)?, self.get_utf8_str(nt.descriptor_index as usize)?));
     }
 
-    pub fn get_any(&self, n: usize) -> &dyn Any {
-        self.get(n).unwrap().as_ref().unwrap().as_any()
+    pub fn len(&self) -> usize {
+        return self.0.len();
     }
-}
\ No newline at end of file
+
+    pub fn is_empty(&self) -> bool {
+        return self.0.len() <= 1;
+    }
+
+    pub fn get_any(&self, n: usize) -> Option<&dyn Any> {
+        let info = self.get(n)?.as_ref()?;
+        return Some(info.as_any());
+    }
+}
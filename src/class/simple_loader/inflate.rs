@@ -0,0 +1,276 @@
+/*
+A decoder for raw DEFLATE streams (RFC 1951), the compression used by
+jar and zip entries. It follows the canonical-Huffman approach of zlib's
+puff.c: every code table is described by the number of codes of each
+length plus the symbols ordered by code.
+ */
+
+use std::io::{Error, ErrorKind, Result};
+
+const MAX_BITS: usize = 15;
+
+// base lengths and extra bits of the length codes 257..285
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+    35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+    3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+// base distances and extra bits of the distance codes 0..29
+const DIST_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+    257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DIST_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6,
+    7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+// the most a deflate stream can expand, a length 258 match in every 2 bits or so
+const MAX_RATIO: usize = 1032;
+
+// the order in which code length code lengths are stored in a dynamic block
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+fn invalid(msg: &str) -> Error {
+    return Error::new(ErrorKind::InvalidData, format!("invalid deflate stream: {}", msg));
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    point: usize,
+    bit_buf: u32,
+    bit_count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> BitReader<'a> {
+        return BitReader {
+            data,
+            point: 0,
+            bit_buf: 0,
+            bit_count: 0,
+        }
+    }
+
+    // deflate packs bits starting with the least significant one
+    fn bits(&mut self, need: u32) -> Result<u32> {
+        while self.bit_count < need {
+            if self.point >= self.data.len() {
+                return Err(invalid("unexpected end of data"));
+            }
+            self.bit_buf |= (self.data[self.point] as u32) << self.bit_count;
+            self.point += 1;
+            self.bit_count += 8;
+        }
+        let val = self.bit_buf & ((1u32 << need) - 1);
+        self.bit_buf >>= need;
+        self.bit_count -= need;
+        return Ok(val);
+    }
+
+    fn align_to_byte(&mut self) {
+        self.bit_buf = 0;
+        self.bit_count = 0;
+    }
+
+    fn read_u16_le(&mut self) -> Result<u16> {
+        if self.point + 2 > self.data.len() {
+            return Err(invalid("unexpected end of data"));
+        }
+        let v = u16::from_le_bytes([self.data[self.point], self.data[self.point + 1]]);
+        self.point += 2;
+        return Ok(v);
+    }
+}
+
+struct Huffman {
+    // number of codes of each length
+    count: [u16; MAX_BITS + 1],
+    // symbols ordered by their code
+    symbol: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Huffman> {
+        let mut count = [0u16; MAX_BITS + 1];
+        for len in lengths {
+            count[*len as usize] += 1;
+        }
+
+        // reject over-subscribed sets, incomplete ones are allowed as zlib does
+        let mut left: i32 = 1;
+        for n in &count[1..] {
+            left <<= 1;
+            left -= *n as i32;
+            if left < 0 {
+                return Err(invalid("over-subscribed code lengths"));
+            }
+        }
+
+        let mut offs = [0u16; MAX_BITS + 1];
+        for len in 1..MAX_BITS {
+            offs[len + 1] = offs[len] + count[len];
+        }
+        let mut symbol = vec![0u16; lengths.len()];
+        for (sym, len) in lengths.iter().enumerate() {
+            if *len != 0 {
+                symbol[offs[*len as usize] as usize] = sym as u16;
+                offs[*len as usize] += 1;
+            }
+        }
+
+        return Ok(Huffman {
+            count,
+            symbol,
+        })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+        for len in 1..=MAX_BITS {
+            code |= reader.bits(1)? as i32;
+            let count = self.count[len] as i32;
+            if code - count < first {
+                return Ok(self.symbol[(index + (code - first)) as usize]);
+            }
+            index += count;
+            first += count;
+            first <<= 1;
+            code <<= 1;
+        }
+        return Err(invalid("ran out of codes"));
+    }
+}
+
+fn inflate_stored(reader: &mut BitReader, out: &mut Vec<u8>) -> Result<()> {
+    reader.align_to_byte();
+    let len = reader.read_u16_le()?;
+    let nlen = reader.read_u16_le()?;
+    if len != !nlen {
+        return Err(invalid("stored block length doesn't match its complement"));
+    }
+    let end = reader.point + len as usize;
+    if end > reader.data.len() {
+        return Err(invalid("unexpected end of data"));
+    }
+    out.extend_from_slice(&reader.data[reader.point..end]);
+    reader.point = end;
+    return Ok(());
+}
+
+fn inflate_codes(reader: &mut BitReader, out: &mut Vec<u8>, lencode: &Huffman, distcode: &Huffman) -> Result<()> {
+    loop {
+        let sym = lencode.decode(reader)? as usize;
+        if sym < 256 {
+            out.push(sym as u8);
+        } else if sym == 256 {
+            return Ok(());
+        } else {
+            let sym = sym - 257;
+            if sym >= LENGTH_BASE.len() {
+                return Err(invalid("bad length symbol"));
+            }
+            let len = LENGTH_BASE[sym] as usize + reader.bits(LENGTH_EXTRA[sym] as u32)? as usize;
+
+            let dsym = distcode.decode(reader)? as usize;
+            if dsym >= DIST_BASE.len() {
+                return Err(invalid("bad distance symbol"));
+            }
+            let dist = DIST_BASE[dsym] as usize + reader.bits(DIST_EXTRA[dsym] as u32)? as usize;
+            if dist > out.len() {
+                return Err(invalid("distance too far back"));
+            }
+
+            // the copy may overlap the bytes it produces, so go one byte at a time
+            let start = out.len() - dist;
+            for i in 0..len {
+                let b = out[start + i];
+                out.push(b);
+            }
+        }
+    }
+}
+
+fn fixed_tables() -> Result<(Huffman, Huffman)> {
+    let mut lengths = [0u8; 288];
+    for (sym, len) in lengths.iter_mut().enumerate() {
+        *len = match sym {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            _ => 8,
+        };
+    }
+    return Ok((Huffman::new(&lengths)?, Huffman::new(&[5u8; 30])?));
+}
+
+fn dynamic_tables(reader: &mut BitReader) -> Result<(Huffman, Huffman)> {
+    let nlen = reader.bits(5)? as usize + 257;
+    let ndist = reader.bits(5)? as usize + 1;
+    let ncode = reader.bits(4)? as usize + 4;
+    if nlen > 286 || ndist > 30 {
+        return Err(invalid("bad counts"));
+    }
+
+    let mut lengths = [0u8; 19];
+    for index in CODE_LENGTH_ORDER.iter().take(ncode) {
+        lengths[*index] = reader.bits(3)? as u8;
+    }
+    let lencode = Huffman::new(&lengths)?;
+
+    let mut lengths = vec![0u8; nlen + ndist];
+    let mut index = 0;
+    while index < nlen + ndist {
+        let sym = lencode.decode(reader)?;
+        if sym < 16 {
+            lengths[index] = sym as u8;
+            index += 1;
+            continue;
+        }
+
+        let (len, repeat) = match sym {
+            16 => {
+                if index == 0 {
+                    return Err(invalid("repeat with no previous length"));
+                }
+                (lengths[index - 1], 3 + reader.bits(2)?)
+            }
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        if index + repeat as usize > nlen + ndist {
+            return Err(invalid("too many lengths"));
+        }
+        for _ in 0..repeat {
+            lengths[index] = len;
+            index += 1;
+        }
+    }
+
+    if lengths[256] == 0 {
+        return Err(invalid("no end-of-block code"));
+    }
+
+    return Ok((Huffman::new(&lengths[..nlen])?, Huffman::new(&lengths[nlen..])?));
+}
+
+// `size_hint` comes from an archive header, so it is only trusted as far as `data` can expand
+pub fn inflate(data: &[u8], size_hint: usize) -> Result<Vec<u8>> {
+    let mut reader = BitReader::new(data);
+    let mut out: Vec<u8> = Vec::with_capacity(size_hint.min(data.len().saturating_mul(MAX_RATIO)));
+
+    loop {
+        let last = reader.bits(1)?;
+        match reader.bits(2)? {
+            0 => inflate_stored(&mut reader, &mut out)?,
+            1 => {
+                let (lencode, distcode) = fixed_tables()?;
+                inflate_codes(&mut reader, &mut out, &lencode, &distcode)?;
+            }
+            2 => {
+                let (lencode, distcode) = dynamic_tables(&mut reader)?;
+                inflate_codes(&mut reader, &mut out, &lencode, &distcode)?;
+            }
+            _ => return Err(invalid("bad block type")),
+        }
+        if last == 1 {
+            return Ok(out);
+        }
+    }
+}
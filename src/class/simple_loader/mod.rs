@@ -1,51 +1,144 @@
 pub mod constant_pool;
-mod class_reader;
+pub mod class_reader;
 pub mod attribute_info;
+pub mod class_format_error;
+pub(crate) mod inflate;
+mod zip;
 
 pub mod simple_loader {
     use crate::class::*;
-    use std::fs::File;
+    use std::cell::OnceCell;
+    use std::env;
+    use std::ffi::OsStr;
     use std::fs;
+    use std::io;
+    use std::path::{Path, PathBuf};
     use crate::class::simple_loader::class_reader::{ClassReader, ClassFile};
+    use crate::class::simple_loader::class_format_error::{ClassFormatError, FormatErrorReason};
+    use crate::class::simple_loader::zip::ZipArchive;
 
     pub enum ClassPathType {
-        // Zip,
+        // a .jar or .zip archive
+        Zip,
         Folder,
     }
 
     pub struct SimpleClassLoader {
-        class_path: String,
+        class_path: PathBuf,
         class_path_type: ClassPathType,
+        // the central directory, read on first use, or why the archive can't be read
+        archive: OnceCell<Result<ZipArchive, (io::ErrorKind, String)>>,
     }
 
     impl SimpleClassLoader {
-        pub fn new(class_path: String, class_path_type: ClassPathType) -> SimpleClassLoader {
+        pub fn new(class_path: PathBuf, class_path_type: ClassPathType) -> SimpleClassLoader {
             return SimpleClassLoader {
                 class_path,
                 class_path_type,
+                archive: OnceCell::new(),
             }
         }
 
-        fn load_class_file(&self, path: String) -> Option<&Class> {
-            let file_path = format!("{}{}.class", self.class_path, path.replace("/", "\\"));
-            let result = fs::read(file_path);
-            if result.is_err() {
-                let opt : Option<&Class> = None;
-                return opt;
+        // Parses a -cp style class path, whose entries are separated by ':'
+        // (';' on Windows), into loaders in search order.
+        pub fn parse_class_path(class_path: &str) -> Vec<SimpleClassLoader> {
+            let mut loaders = vec![];
+            for entry in env::split_paths(class_path) {
+                // an empty entry is the current directory, as in java
+                let entry = if entry.as_os_str().is_empty() { PathBuf::from(".") } else { entry };
+                loaders.append(&mut SimpleClassLoader::from_entry(&entry));
             }
+            return loaders;
+        }
+
+        // Creates the loaders for one class path entry: a folder, a .jar/.zip
+        // archive, or a wildcard "dir/*" standing for every jar in dir.
+        pub fn from_entry(entry: &Path) -> Vec<SimpleClassLoader> {
+            if entry.file_name() == Some(OsStr::new("*")) {
+                let mut loaders = vec![];
+                let dir = match entry.parent() {
+                    Some(dir) if !dir.as_os_str().is_empty() => dir,
+                    _ => Path::new("."),
+                };
+                if let Ok(read_dir) = fs::read_dir(dir) {
+                    let mut paths: Vec<_> = read_dir.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+                    paths.sort();
+                    for path in paths {
+                        if path.is_file() && SimpleClassLoader::is_archive(&path) {
+                            loaders.push(SimpleClassLoader::new(path, ClassPathType::Zip));
+                        }
+                    }
+                }
+                return loaders;
+            }
+
+            if SimpleClassLoader::is_archive(entry) {
+                return vec![SimpleClassLoader::new(entry.to_path_buf(), ClassPathType::Zip)];
+            }
+            return vec![SimpleClassLoader::new(entry.to_path_buf(), ClassPathType::Folder)];
+        }
+
+        fn is_archive(path: &Path) -> bool {
+            return match path.extension().and_then(|e| e.to_str()) {
+                Some(ext) => ext.eq_ignore_ascii_case("jar") || ext.eq_ignore_ascii_case("zip"),
+                None => false,
+            };
+        }
+
+        /*
+        The raw bytes of a class file, e.g. "java/lang/Object", Ok(None) if
+        the entry doesn't have it. A folder or archive that doesn't exist has
+        no classes, as in java, while one that can't be read is an error.
+         */
+        pub fn read_class_bytes(&self, class_name: &str) -> io::Result<Option<Vec<u8>>> {
+            match self.class_path_type {
+                ClassPathType::Folder => {
+                    // binary names always use '/', whatever the platform's separator is
+                    let mut file_path = self.class_path.clone();
+                    file_path.extend(class_name.split('/'));
+                    file_path.as_mut_os_string().push(".class");
+                    return match fs::read(&file_path) {
+                        Ok(data) => Ok(Some(data)),
+                        Err(e) if matches!(e.kind(), io::ErrorKind::NotFound | io::ErrorKind::NotADirectory) => Ok(None),
+                        Err(e) => Err(io::Error::new(e.kind(), format!("{}: {}", file_path.display(), e))),
+                    };
+                }
+                ClassPathType::Zip => {
+                    let archive = self.archive.get_or_init(|| {
+                        ZipArchive::open(&self.class_path).map_err(|e| (e.kind(), e.to_string()))
+                    });
+                    return match archive {
+                        Ok(archive) => archive.read(&format!("{}.class", class_name)),
+                        Err((io::ErrorKind::NotFound, _)) => Ok(None),
+                        Err((kind, message)) => Err(io::Error::new(*kind, message.clone())),
+                    };
+                }
+            }
+        }
+
+        fn load_class_file(&self, path: String) -> Result<Option<Class>, ClassFormatError> {
+            let data = match self.read_class_bytes(&path) {
+                Ok(Some(data)) => data,
+                Ok(None) => return Ok(None),
+                Err(e) => {
+                    return Err(ClassFormatError {
+                        offset: 0,
+                        structure: "class path entry",
+                        reason: FormatErrorReason::Unreadable(e.to_string()),
+                    });
+                }
+            };
 
-            let data = result.unwrap();
             let mut reader = ClassReader::new(data);
-            let mut file = ClassFile::new(&mut reader);
+            let file = ClassFile::new(&mut reader)?;
 
-            let opt : Option<&Class> = None;
-            return opt;
+            return Ok(Some(Class::new(&file)));
         }
     }
 
     impl ClassLoader for SimpleClassLoader {
-        fn load_class(&self, class_name: &str) -> Option<&Class> {
+        fn load_class(&self, class_name: &str) -> Result<Option<Class>, ClassFormatError> {
             return self.load_class_file(class_name.to_string());
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Error, ErrorKind, Read, Result, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use crate::class::simple_loader::inflate::inflate;
+
+/*
+end of central directory record {
+    u4 signature;                   // 0x06054b50
+    u2 number_of_this_disk;
+    u2 disk_with_central_directory;
+    u2 entries_on_this_disk;
+    u2 total_entries;
+    u4 central_directory_size;
+    u4 central_directory_offset;
+    u2 comment_length;
+    u1 comment[comment_length];
+}
+ */
+const EOCD_SIGNATURE: u32 = 0x06054b50;
+const EOCD_SIZE: usize = 22;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x02014b50;
+const CENTRAL_HEADER_SIZE: usize = 46;
+const LOCAL_HEADER_SIGNATURE: u32 = 0x04034b50;
+const LOCAL_HEADER_SIZE: usize = 30;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+
+fn invalid(msg: String) -> Error {
+    return Error::new(ErrorKind::InvalidData, msg);
+}
+
+fn u16_at(data: &[u8], at: usize) -> u16 {
+    return u16::from_le_bytes([data[at], data[at + 1]]);
+}
+
+fn u32_at(data: &[u8], at: usize) -> u32 {
+    return u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]);
+}
+
+pub struct ZipEntry {
+    method: u16,
+    flags: u16,
+    crc32: u32,
+    compressed_size: u32,
+    size: u32,
+    local_header_offset: u32,
+}
+
+/*
+The central directory of a zip or jar archive. Only the directory is kept
+in memory, entries are read from the file on demand.
+ */
+pub struct ZipArchive {
+    path: PathBuf,
+    entries: HashMap<String, ZipEntry>,
+}
+
+impl ZipArchive {
+    pub fn open(path: &Path) -> Result<ZipArchive> {
+        let mut file = File::open(path)?;
+        let len = file.seek(SeekFrom::End(0))?;
+        if len < EOCD_SIZE as u64 {
+            return Err(invalid(format!("{}: too short for a zip archive", path.display())));
+        }
+
+        // the record sits at the very end, followed by a comment of at most 65535 bytes
+        let tail_len = std::cmp::min(len, (EOCD_SIZE + 0xFFFF) as u64) as usize;
+        let mut tail = vec![0u8; tail_len];
+        file.seek(SeekFrom::Start(len - tail_len as u64))?;
+        file.read_exact(&mut tail)?;
+
+        let eocd = match (0..=tail_len.saturating_sub(EOCD_SIZE)).rev().find(|at| u32_at(&tail, *at) == EOCD_SIGNATURE) {
+            Some(at) => at,
+            None => return Err(invalid(format!("{}: end of central directory not found", path.display()))),
+        };
+        let total_entries = u16_at(&tail, eocd + 10) as usize;
+        let directory_size = u32_at(&tail, eocd + 12) as usize;
+        let directory_offset = u32_at(&tail, eocd + 16) as u64;
+        if total_entries == 0xFFFF || directory_offset == 0xFFFFFFFF {
+            return Err(invalid(format!("{}: zip64 archives are not supported", path.display())));
+        }
+
+        // sizes and offsets are checked against the file before anything is allocated for them
+        if directory_offset + directory_size as u64 > len {
+            return Err(invalid(format!("{}: central directory past the end of the file", path.display())));
+        }
+        let mut directory = vec![0u8; directory_size];
+        file.seek(SeekFrom::Start(directory_offset))?;
+        file.read_exact(&mut directory)?;
+
+        let mut entries = HashMap::new();
+        let mut at = 0;
+        for _ in 0..total_entries {
+            if at + CENTRAL_HEADER_SIZE > directory.len() || u32_at(&directory, at) != CENTRAL_HEADER_SIGNATURE {
+                return Err(invalid(format!("{}: bad central directory header at {}", path.display(), directory_offset as usize + at)));
+            }
+            let name_len = u16_at(&directory, at + 28) as usize;
+            let extra_len = u16_at(&directory, at + 30) as usize;
+            let comment_len = u16_at(&directory, at + 32) as usize;
+            let name_end = at + CENTRAL_HEADER_SIZE + name_len;
+            if name_end > directory.len() {
+                return Err(invalid(format!("{}: truncated central directory", path.display())));
+            }
+
+            let name = String::from_utf8_lossy(&directory[at + CENTRAL_HEADER_SIZE..name_end]).to_string();
+            entries.insert(name, ZipEntry {
+                flags: u16_at(&directory, at + 8),
+                method: u16_at(&directory, at + 10),
+                crc32: u32_at(&directory, at + 16),
+                compressed_size: u32_at(&directory, at + 20),
+                size: u32_at(&directory, at + 24),
+                local_header_offset: u32_at(&directory, at + 42),
+            });
+
+            at = name_end + extra_len + comment_len;
+        }
+
+        return Ok(ZipArchive {
+            path: path.to_path_buf(),
+            entries,
+        })
+    }
+
+    // Ok(None) if there is no such entry
+    pub fn read(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        let entry = match self.entries.get(name) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        if entry.flags & 1 != 0 {
+            return Err(invalid(format!("{}!{}: encrypted entries are not supported", self.path.display(), name)));
+        }
+
+        let mut file = File::open(&self.path)?;
+        let mut header = [0u8; LOCAL_HEADER_SIZE];
+        file.seek(SeekFrom::Start(entry.local_header_offset as u64))?;
+        file.read_exact(&mut header)?;
+        if u32_at(&header, 0) != LOCAL_HEADER_SIGNATURE {
+            return Err(invalid(format!("{}!{}: bad local header", self.path.display(), name)));
+        }
+        // the local name and extra field may differ in length from the central ones
+        let skip = u16_at(&header, 26) as i64 + u16_at(&header, 28) as i64;
+        let start = file.seek(SeekFrom::Current(skip))?;
+        if start + entry.compressed_size as u64 > file.metadata()?.len() {
+            return Err(invalid(format!("{}!{}: entry past the end of the file", self.path.display(), name)));
+        }
+
+        let mut raw = vec![0u8; entry.compressed_size as usize];
+        file.read_exact(&mut raw)?;
+
+        let data = match entry.method {
+            METHOD_STORED => raw,
+            METHOD_DEFLATED => inflate(&raw, entry.size as usize)?,
+            method => {
+                return Err(invalid(format!("{}!{}: unsupported compression method {}", self.path.display(), name, method)));
+            }
+        };
+
+        if data.len() != entry.size as usize || crc32(&data) != entry.crc32 {
+            return Err(invalid(format!("{}!{}: corrupt entry", self.path.display(), name)));
+        }
+
+        return Ok(Some(data));
+    }
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+    for b in data {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB88320 & mask);
+        }
+    }
+    return !crc;
+}
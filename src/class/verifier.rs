@@ -0,0 +1,281 @@
+use crate::class::Class;
+use crate::class::descriptor::{MethodDescriptor, slot_size};
+use crate::class::member::Method;
+use crate::class::runtime_constant_pool::Constant;
+use crate::env::byte_code_reader::ByteCodeReader;
+use crate::env::insts::factory::{fetch_instruction, mnemonic};
+
+/*
+What one instruction does to the frame, as far as the checks below are
+concerned: the stack slots it pops and then pushes, the local variables
+it touches and where execution can go next.
+ */
+struct Effect {
+    pops: usize,
+    pushes: usize,
+    // the first local variable and the number of slots from it
+    locals: Option<(usize, usize)>,
+    // absolute pcs it may branch to, unchecked
+    targets: Vec<i64>,
+    // whether it can go on with the next instruction
+    falls_through: bool,
+}
+
+impl Effect {
+    fn stack(pops: usize, pushes: usize) -> Effect {
+        return Effect { pops, pushes, locals: None, targets: vec![], falls_through: true };
+    }
+
+    fn local(pops: usize, pushes: usize, index: usize, size: usize) -> Effect {
+        return Effect { locals: Some((index, size)), ..Effect::stack(pops, pushes) };
+    }
+
+    fn branch(pops: usize, pushes: usize, targets: Vec<i64>, falls_through: bool) -> Effect {
+        return Effect { targets, falls_through, ..Effect::stack(pops, pushes) };
+    }
+
+    fn end(pops: usize) -> Effect {
+        return Effect { falls_through: false, ..Effect::stack(pops, 0) };
+    }
+}
+
+// the slot size of a load or store's value: int, long, float, double, reference
+const KIND_SIZES: [usize; 5] = [1, 2, 1, 2, 1];
+
+/*
+Checks that `method` stays within its frame whatever path it takes: every
+local variable it uses is below max_locals, the operand stack neither
+underflows nor grows past max_stack, and every branch and exception
+handler lands on the start of an instruction. The stack depth at each
+instruction is worked out by following the branches, and has to be the
+same on every path to it, as JVMS 4.10.2.2 requires. Types aren't checked.
+
+jsr and ret are followed the way the old inference verifier does: the
+subroutine starts with the return address pushed, and the jsr goes on
+with the stack as it was, taking the subroutine to leave it that way.
+ */
+pub fn verify_code(class: &Class, method: &Method) -> Result<(), String> {
+    let code = &method.code;
+    let mut starts = vec![false; code.len()];
+    let mut reader = ByteCodeReader::new(code);
+    while reader.pc < reader.len() {
+        let pc = reader.pc;
+        starts[pc] = true;
+        if let Err(e) = fetch_instruction(&mut reader) {
+            return Err(format!("{} at pc {}", e.message, pc));
+        }
+    }
+    let is_start = |pc: i64| pc >= 0 && (pc as usize) < code.len() && starts[pc as usize];
+
+    let mut depths: Vec<Option<usize>> = vec![None; code.len()];
+    let mut pending: Vec<(usize, usize)> = vec![(0, 0)];
+    for entry in &method.exception_table {
+        let (start, end, handler) = (entry.start_pc as i64, entry.end_pc as i64, entry.handler_pc as i64);
+        if !is_start(start) || !(is_start(end) || end as usize == code.len()) || !is_start(handler) {
+            return Err("Illegal exception table range".to_string());
+        }
+        // a handler starts with the exception alone on the stack
+        if method.max_stack < 1 {
+            return Err(format!("Operand stack overflow at pc {}", handler));
+        }
+        pending.push((handler as usize, 1));
+    }
+
+    while let Some((pc, depth)) = pending.pop() {
+        match depths[pc] {
+            Some(d) if d == depth => continue,
+            Some(d) => return Err(format!("Inconsistent stack height {} != {} at pc {}", d, depth, pc)),
+            None => depths[pc] = Some(depth),
+        }
+
+        let effect = effect_of(class, code, pc)?;
+        if let Some((index, size)) = effect.locals {
+            if index + size > method.max_locals {
+                return Err(format!("Illegal local variable number {} at pc {}", index, pc));
+            }
+        }
+        if effect.pops > depth {
+            return Err(format!("Unable to pop operand off an empty stack at pc {}", pc));
+        }
+        let after = depth - effect.pops + effect.pushes;
+        if after > method.max_stack {
+            return Err(format!("Operand stack overflow at pc {}", pc));
+        }
+
+        let jsr = code[pc] == 0xa8 || code[pc] == 0xc9;
+        for target in effect.targets {
+            if !is_start(target) {
+                return Err(format!("Illegal target of jump or branch at pc {}", pc));
+            }
+            pending.push((target as usize, after));
+        }
+        if effect.falls_through {
+            let mut next = ByteCodeReader::new(code);
+            next.pc = pc;
+            fetch_instruction(&mut next).map_err(|e| e.message)?;
+            if next.pc >= code.len() {
+                return Err("Falling off the end of the code".to_string());
+            }
+            // the subroutine's ret comes back with the return address gone
+            pending.push((next.pc, if jsr { depth } else { after }));
+        }
+    }
+    return Ok(());
+}
+
+// the slots taken by the arguments and return value of the method a constant refers to
+fn invoked(class: &Class, index: u16, pc: usize) -> Result<(usize, usize), String> {
+    let descriptor = match class.constant_pool.get(index as usize) {
+        Some(Constant::MethodRef(m)) | Some(Constant::InterfaceMethodRef(m)) => &m.descriptor,
+        Some(Constant::InvokeDynamic(d)) => &d.descriptor,
+        _ => return Err(format!("Illegal constant pool index {} at pc {}", index, pc)),
+    };
+    let parsed = MethodDescriptor::parse(descriptor).ok_or_else(|| format!("Illegal method descriptor {} at pc {}", descriptor, pc))?;
+    return Ok((parsed.arg_slot_count(), slot_size(&parsed.return_type)));
+}
+
+// the slots a value of the field a constant refers to takes
+fn field_size(class: &Class, index: u16, pc: usize) -> Result<usize, String> {
+    return match class.constant_pool.get(index as usize) {
+        Some(Constant::FieldRef(f)) => Ok(slot_size(&f.descriptor)),
+        _ => Err(format!("Illegal constant pool index {} at pc {}", index, pc)),
+    };
+}
+
+// the effect of the instruction at `pc`, which is known to decode
+fn effect_of(class: &Class, code: &[u8], pc: usize) -> Result<Effect, String> {
+    let mut reader = ByteCodeReader::new(code);
+    reader.pc = pc + 1;
+    let opcode = code[pc];
+    let at = |offset: i32| pc as i64 + offset as i64;
+
+    let effect = match opcode {
+        0x00 => Effect::stack(0, 0),
+        0x01..=0x08 | 0x0b..=0x0d | 0x10..=0x13 => Effect::stack(0, 1),
+        0x09 | 0x0a | 0x0e | 0x0f | 0x14 => Effect::stack(0, 2),
+        // iload, lload, fload, dload, aload
+        0x15..=0x19 => {
+            let size = KIND_SIZES[(opcode - 0x15) as usize];
+            Effect::local(0, size, reader.read_u8() as usize, size)
+        }
+        // <kind>load_<n>
+        0x1a..=0x2d => {
+            let size = KIND_SIZES[((opcode - 0x1a) / 4) as usize];
+            Effect::local(0, size, ((opcode - 0x1a) % 4) as usize, size)
+        }
+        0x2f | 0x31 => Effect::stack(2, 2),
+        0x2e..=0x35 => Effect::stack(2, 1),
+        // istore, lstore, fstore, dstore, astore
+        0x36..=0x3a => {
+            let size = KIND_SIZES[(opcode - 0x36) as usize];
+            Effect::local(size, 0, reader.read_u8() as usize, size)
+        }
+        // <kind>store_<n>
+        0x3b..=0x4e => {
+            let size = KIND_SIZES[((opcode - 0x3b) / 4) as usize];
+            Effect::local(size, 0, ((opcode - 0x3b) % 4) as usize, size)
+        }
+        0x50 | 0x52 => Effect::stack(4, 0),
+        0x4f..=0x56 => Effect::stack(3, 0),
+        0x57 => Effect::stack(1, 0),
+        0x58 => Effect::stack(2, 0),
+        0x59 => Effect::stack(1, 2),
+        0x5a => Effect::stack(2, 3),
+        0x5b => Effect::stack(3, 4),
+        0x5c => Effect::stack(2, 4),
+        0x5d => Effect::stack(3, 5),
+        0x5e => Effect::stack(4, 6),
+        0x5f => Effect::stack(2, 2),
+        // add, sub, mul, div and rem come as int, long, float, double
+        0x60..=0x73 => {
+            let size = KIND_SIZES[((opcode - 0x60) % 4) as usize];
+            Effect::stack(size * 2, size)
+        }
+        0x74..=0x77 => {
+            let size = KIND_SIZES[((opcode - 0x74) % 4) as usize];
+            Effect::stack(size, size)
+        }
+        // the shift distance is an int for both ints and longs
+        0x78 | 0x7a | 0x7c => Effect::stack(2, 1),
+        0x79 | 0x7b | 0x7d => Effect::stack(3, 2),
+        0x7e | 0x80 | 0x82 => Effect::stack(2, 1),
+        0x7f | 0x81 | 0x83 => Effect::stack(4, 2),
+        0x84 => Effect::local(0, 0, reader.read_u8() as usize, 1),
+        0x85 | 0x87 | 0x8c | 0x8d => Effect::stack(1, 2),
+        0x86 | 0x8b | 0x91..=0x93 => Effect::stack(1, 1),
+        0x88 | 0x89 | 0x8e | 0x90 => Effect::stack(2, 1),
+        0x8a | 0x8f => Effect::stack(2, 2),
+        0x94 | 0x97 | 0x98 => Effect::stack(4, 1),
+        0x95 | 0x96 => Effect::stack(2, 1),
+        0x99..=0x9e | 0xc6 | 0xc7 => Effect::branch(1, 0, vec![at(reader.read_i16() as i32)], true),
+        0x9f..=0xa6 => Effect::branch(2, 0, vec![at(reader.read_i16() as i32)], true),
+        0xa7 => Effect::branch(0, 0, vec![at(reader.read_i16() as i32)], false),
+        0xc8 => Effect::branch(0, 0, vec![at(reader.read_i32())], false),
+        0xa8 => Effect::branch(0, 1, vec![at(reader.read_i16() as i32)], true),
+        0xc9 => Effect::branch(0, 1, vec![at(reader.read_i32())], true),
+        0xa9 => Effect { falls_through: false, ..Effect::local(0, 0, reader.read_u8() as usize, 1) },
+        0xaa => {
+            reader.skip_padding();
+            let mut targets = vec![at(reader.read_i32())];
+            let low = reader.read_i32() as i64;
+            let high = reader.read_i32() as i64;
+            for _ in low..=high {
+                targets.push(at(reader.read_i32()));
+            }
+            Effect::branch(1, 0, targets, false)
+        }
+        0xab => {
+            reader.skip_padding();
+            let mut targets = vec![at(reader.read_i32())];
+            let npairs = reader.read_i32();
+            for _ in 0..npairs {
+                reader.read_i32();
+                targets.push(at(reader.read_i32()));
+            }
+            Effect::branch(1, 0, targets, false)
+        }
+        0xac | 0xae | 0xb0 => Effect::end(1),
+        0xad | 0xaf => Effect::end(2),
+        0xb1 => Effect::end(0),
+        0xb2 => Effect::stack(0, field_size(class, reader.read_u16(), pc)?),
+        0xb3 => Effect::stack(field_size(class, reader.read_u16(), pc)?, 0),
+        0xb4 => Effect::stack(1, field_size(class, reader.read_u16(), pc)?),
+        0xb5 => Effect::stack(1 + field_size(class, reader.read_u16(), pc)?, 0),
+        // invokevirtual, invokespecial and invokeinterface take the receiver too
+        0xb6 | 0xb7 | 0xb9 => {
+            let (args, result) = invoked(class, reader.read_u16(), pc)?;
+            Effect::stack(1 + args, result)
+        }
+        0xb8 | 0xba => {
+            let (args, result) = invoked(class, reader.read_u16(), pc)?;
+            Effect::stack(args, result)
+        }
+        0xbb => Effect::stack(0, 1),
+        0xbc | 0xbd | 0xbe | 0xc0 | 0xc1 => Effect::stack(1, 1),
+        0xbf => Effect::end(1),
+        0xc2 | 0xc3 => Effect::stack(1, 0),
+        0xc4 => {
+            let opcode = reader.read_u8();
+            let index = reader.read_u16() as usize;
+            match opcode {
+                0x15..=0x19 => {
+                    let size = KIND_SIZES[(opcode - 0x15) as usize];
+                    Effect::local(0, size, index, size)
+                }
+                0x36..=0x3a => {
+                    let size = KIND_SIZES[(opcode - 0x36) as usize];
+                    Effect::local(size, 0, index, size)
+                }
+                0x84 => Effect::local(0, 0, index, 1),
+                0xa9 => Effect { falls_through: false, ..Effect::local(0, 0, index, 1) },
+                _ => return Err(format!("Bad instruction wide {} at pc {}", mnemonic(opcode).unwrap_or("?"), pc)),
+            }
+        }
+        0xc5 => {
+            reader.read_u16();
+            Effect::stack(reader.read_u8() as usize, 1)
+        }
+        _ => return Err(format!("Bad instruction {} at pc {}", mnemonic(opcode).unwrap_or("?"), pc)),
+    };
+    return Ok(effect);
+}
@@ -0,0 +1,200 @@
+use std::rc::{Rc, Weak};
+use crate::class::Class;
+use crate::class::access_flags::*;
+use crate::class::member::Method;
+use crate::class::runtime_constant_pool::ResolvedMethod;
+use crate::env::error::JavaError;
+
+/*
+Method selection (JVMS 5.4.6) goes through tables built when a class is
+linked. The vtable lists the instance methods a class declares or inherits
+from its super classes, the inherited ones first and each override at the
+index of the method it overrides. An itable maps each method of an
+implemented interface to the one an instance of the class runs for it: a
+class method if there is one, else the maximally-specific default method.
+Default methods are never in the vtable, a subclass may implement a more
+specific interface than the class that first picked one up.
+ */
+
+// an entry of a vtable or itable; the class is weak as it's often the one owning the table
+#[derive(Clone)]
+pub struct MethodSlot {
+    pub class: Weak<Class>,
+    pub method: Rc<Method>,
+}
+
+#[derive(Clone)]
+pub enum Selected {
+    Method(MethodSlot),
+    // only abstract declarations, AbstractMethodError when invoked
+    Abstract,
+    // more than one maximally-specific default method, IncompatibleClassChangeError when invoked
+    Conflict,
+}
+
+pub struct ITable {
+    pub interface: Rc<Class>,
+    // parallel to the methods of the interface
+    pub methods: Vec<Selected>,
+}
+
+// the methods selected by invokevirtual and invokeinterface
+fn is_virtual(method: &Method) -> bool {
+    return !method.is_static() && !method.is_private() && method.name != "<init>" && method.name != "<clinit>";
+}
+
+fn same_signature(a: &Method, b: &Method) -> bool {
+    return a.name == b.name && a.descriptor == b.descriptor;
+}
+
+// whether a method of `class` overrides the one in `slot` (JVMS 5.4.5)
+fn can_override(class: &Class, slot: &MethodSlot) -> bool {
+    if slot.method.access_flags & (ACC_PUBLIC | ACC_PROTECTED) != 0 {
+        return true;
+    }
+    return match slot.class.upgrade() {
+        Some(owner) => owner.is_same_runtime_package(class),
+        None => false,
+    };
+}
+
+// the super interfaces of a class, direct or not, each one once
+fn all_interfaces(class: &Class) -> Vec<Rc<Class>> {
+    let mut found: Vec<Rc<Class>> = vec![];
+    let mut pending: Vec<Rc<Class>> = class.interfaces.clone();
+    let mut next = class.super_class.clone();
+    while let Some(c) = next {
+        pending.extend(c.interfaces.iter().cloned());
+        next = c.super_class.clone();
+    }
+    while let Some(interface) = pending.pop() {
+        if found.iter().any(|i| Rc::ptr_eq(i, &interface)) {
+            continue;
+        }
+        pending.extend(interface.interfaces.iter().cloned());
+        found.push(interface);
+    }
+    return found;
+}
+
+// the one non-abstract method among the maximally-specific superinterface methods
+fn maximally_specific(interfaces: &[Rc<Class>], name: &str, descriptor: &str) -> Selected {
+    let candidates: Vec<(&Rc<Class>, Rc<Method>)> = interfaces.iter()
+        .filter_map(|i| i.get_method(name, descriptor).filter(|m| is_virtual(m)).map(|m| (i, m)))
+        .collect();
+    // a candidate is less specific than the ones declared by its subinterfaces
+    let defaults: Vec<&(&Rc<Class>, Rc<Method>)> = candidates.iter()
+        .filter(|(i, _)| !candidates.iter().any(|(other, _)| !Rc::ptr_eq(other, i) && other.is_implementing(i)))
+        .filter(|(_, m)| !m.is_abstract())
+        .collect();
+    return match defaults.as_slice() {
+        [(interface, method)] => Selected::Method(MethodSlot {
+            class: Rc::downgrade(interface),
+            method: method.clone(),
+        }),
+        [] => Selected::Abstract,
+        _ => Selected::Conflict,
+    };
+}
+
+// fills in the vtable and itables of a linked class, its super class has them already
+pub fn build_tables(class: &Rc<Class>) {
+    if class.is_interface() {
+        return;
+    }
+    let mut vtable: Vec<MethodSlot> = match &class.super_class {
+        Some(super_class) => super_class.vtable.borrow().clone(),
+        None => vec![],
+    };
+    for method in class.methods.iter().filter(|m| is_virtual(m)) {
+        let slot = MethodSlot {
+            class: Rc::downgrade(class),
+            method: method.clone(),
+        };
+        let index = match vtable.iter().position(|s| same_signature(&s.method, method) && can_override(class, s)) {
+            Some(n) => {
+                vtable[n] = slot;
+                n
+            }
+            None => {
+                vtable.push(slot);
+                vtable.len() - 1
+            }
+        };
+        method.vtable_index.set(Some(index));
+    }
+
+    let interfaces = all_interfaces(class);
+    let itables = interfaces.iter().map(|interface| ITable {
+        interface: interface.clone(),
+        methods: interface.methods.iter().map(|method| {
+            if !is_virtual(method) {
+                return Selected::Abstract;
+            }
+            return match vtable.iter().find(|s| same_signature(&s.method, method)) {
+                Some(slot) => Selected::Method(slot.clone()),
+                None => maximally_specific(&interfaces, &method.name, &method.descriptor),
+            };
+        }).collect(),
+    }).collect();
+
+    *class.vtable.borrow_mut() = vtable;
+    *class.itables.borrow_mut() = itables;
+}
+
+// the instance method with this signature an instance of `class` runs, if any
+pub fn find_in_vtable(class: &Class, name: &str, descriptor: &str) -> Option<MethodSlot> {
+    return class.vtable.borrow().iter().find(|s| s.method.name == name && s.method.descriptor == descriptor).cloned();
+}
+
+fn abstract_method_error(receiver: &Class, method: &Method) -> JavaError {
+    return JavaError::new("java/lang/AbstractMethodError",
+        format!("Receiver class {} does not define or inherit an implementation of the resolved method {}{}",
+            receiver.name.replace('/', "."), method.name, method.descriptor));
+}
+
+/*
+The method that invokevirtual or invokeinterface of `resolved` runs for an
+instance of `receiver`, and the class declaring it. Private methods aren't
+overridden and run as they are resolved.
+ */
+pub fn select_method(receiver: &Class, resolved: &ResolvedMethod) -> Result<(Rc<Class>, Rc<Method>), JavaError> {
+    if resolved.method.is_private() {
+        return Ok((resolved.class.clone(), resolved.method.clone()));
+    }
+
+    let slot = if resolved.class.is_interface() {
+        let itables = receiver.itables.borrow();
+        let itable = itables.iter().find(|t| Rc::ptr_eq(&t.interface, &resolved.class)).ok_or_else(|| {
+            JavaError::new("java/lang/IncompatibleClassChangeError",
+                format!("Class {} does not implement the requested interface {}",
+                    receiver.name.replace('/', "."), resolved.class.name.replace('/', ".")))
+        })?;
+        let index = resolved.class.methods.iter().position(|m| Rc::ptr_eq(m, &resolved.method)).unwrap();
+        match &itable.methods[index] {
+            Selected::Method(slot) => slot.clone(),
+            Selected::Abstract => return Err(abstract_method_error(receiver, &resolved.method)),
+            Selected::Conflict => {
+                return Err(JavaError::new("java/lang/IncompatibleClassChangeError",
+                    format!("Conflicting default methods: {}{}", resolved.method.name, resolved.method.descriptor)));
+            }
+        }
+    } else {
+        let index = match resolved.method.vtable_index.get() {
+            Some(index) => index,
+            None => return Ok((resolved.class.clone(), resolved.method.clone())),
+        };
+        match receiver.vtable.borrow().get(index) {
+            Some(slot) if receiver.is_subclass_of(&resolved.class) => slot.clone(),
+            _ => {
+                return Err(JavaError::new("java/lang/IncompatibleClassChangeError",
+                    format!("Class {} is not a subclass of {}", receiver.name.replace('/', "."), resolved.class.name.replace('/', "."))));
+            }
+        }
+    };
+
+    if slot.method.is_abstract() {
+        return Err(abstract_method_error(receiver, &slot.method));
+    }
+    return Ok((slot.class.upgrade().unwrap(), slot.method));
+}
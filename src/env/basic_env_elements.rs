@@ -1,34 +1,62 @@
-use crate::env::object::Object;
-use std::borrow::Borrow;
-use std::ops::Deref;
+use std::rc::Rc;
+use crate::class::Class;
+use crate::class::member::Method;
+use crate::env::error::JavaError;
+use crate::env::object::ObjRef;
 
 pub struct Frame {
     pub local_vars: LocalVars,
     pub operand_stack: OperandStack,
+    pub class: Rc<Class>,
+    pub method: Rc<Method>,
+    // the instruction executing, or the invoke a callee is running for
+    pub pc: usize,
+    // where the method goes on, written back by the interpreter
+    pub next_pc: usize,
+    // the monitor a synchronized method entered, exited when the frame is popped
+    pub monitor: Option<ObjRef>,
 }
 
 impl Frame {
-    pub fn new(max_locals: usize, max_stack: usize) -> Frame {
+    pub fn new(class: Rc<Class>, method: Rc<Method>) -> Frame {
         return Frame {
-            local_vars: LocalVars::new(max_locals),
-            operand_stack: OperandStack::new(max_stack),
+            local_vars: LocalVars::new(method.max_locals),
+            operand_stack: OperandStack::new(method.max_stack),
+            class,
+            method,
+            pc: 0,
+            next_pc: 0,
+            monitor: None,
         }
     }
 }
 
+// a reference slot holding None is null
+#[derive(Clone, Copy, Default, Debug)]
 pub struct Slot {
     pub val_num: i32,
-    pub val_ref: Option<Object>
+    pub val_ref: Option<ObjRef>
 }
 
+/*
+Slots indexed by number: the local variables and operand stack of a frame,
+and the fields of objects and classes. The verifier keeps the byte code of
+a method within its frame, but the accessors still check, so that code it
+lets through by mistake throws VerifyError instead of bringing the process
+down.
+ */
 pub struct LocalVars {
     vec: Vec<Slot>
 }
 
+fn bad_local(n: usize) -> JavaError {
+    return JavaError::new("java/lang/VerifyError", format!("Illegal local variable number {}", n));
+}
+
 impl LocalVars {
     pub fn new(size: usize) -> LocalVars {
         let mut vec = Vec::new();
-        for i in 0..size {
+        for _ in 0..size {
             vec.push(Slot { val_num: 0, val_ref: None });
         }
 
@@ -37,79 +65,83 @@ impl LocalVars {
         }
     }
 
-    pub fn set_i64(&mut self, n: usize, v: i64) {
+    pub fn set_i64(&mut self, n: usize, v: i64) -> Result<(), JavaError> {
+        if n + 1 >= self.vec.len() {
+            return Err(bad_local(n));
+        }
         let bytes:[u8; 8] = v.to_be_bytes();
         let a: [u8; 4] = [bytes[0], bytes[1], bytes[2], bytes[3]];
         let b: [u8; 4] = [bytes[4], bytes[5], bytes[6], bytes[7]];
-        self.set_i32(n, i32::from_be_bytes(a));
-        self.set_i32(n + 1, i32::from_be_bytes(b));
+        self.set_i32(n, i32::from_be_bytes(a))?;
+        return self.set_i32(n + 1, i32::from_be_bytes(b));
     }
 
-    pub fn set_i32(&mut self, n: usize, v: i32) {
-        let mut slot = self.vec.get_mut(n).unwrap();
-        slot.val_num = v;
+    pub fn set_i32(&mut self, n: usize, v: i32) -> Result<(), JavaError> {
+        return self.set_slot(n, Slot { val_num: v, val_ref: None });
     }
 
-    pub fn get_i32(&self, n: usize) -> Option<i32> {
-        if let Some(v) = self.vec.get(n) {
-            let val = v.val_num;
-            return Some(val);
-        } else {
-            return None;
-        }
+    pub fn get_i32(&self, n: usize) -> Result<i32, JavaError> {
+        return Ok(self.get_slot(n)?.val_num);
     }
 
-    pub fn get_i64(&self, n: usize) -> Option<i64> {
-        if let Some(va) = self.vec.get(n) {
-            if let Some(vb) = self.vec.get(n + 1) {
-                let ab = va.val_num.to_be_bytes();
-                let bb = vb.val_num.to_be_bytes();
-                let bytes = [ab[0], ab[1], ab[2], ab[3], bb[0], bb[1], bb[2], bb[3]];
-                return Some(i64::from_be_bytes(bytes));
-            } else {
-                return None;
-            }
-        } else {
-            return None;
-        }
+    pub fn get_i64(&self, n: usize) -> Result<i64, JavaError> {
+        let ab = self.get_i32(n)?.to_be_bytes();
+        let bb = self.get_i32(n + 1)?.to_be_bytes();
+        let bytes = [ab[0], ab[1], ab[2], ab[3], bb[0], bb[1], bb[2], bb[3]];
+        return Ok(i64::from_be_bytes(bytes));
     }
 
-    pub fn set_f32(&mut self, n: usize, v: f32) {
-        self.set_i32(n, i32::from_be_bytes(v.to_be_bytes()));
+    pub fn set_f32(&mut self, n: usize, v: f32) -> Result<(), JavaError> {
+        return self.set_i32(n, i32::from_be_bytes(v.to_be_bytes()));
     }
 
-    pub fn get_f32(&mut self, n: usize) -> Option<f32> {
-        if let Some(v) = self.get_i32(n) {
-            return Some(f32::from_be_bytes(v.to_be_bytes()));
-        } else {
-            return None;
-        }
+    pub fn get_f32(&self, n: usize) -> Result<f32, JavaError> {
+        return Ok(f32::from_be_bytes(self.get_i32(n)?.to_be_bytes()));
     }
 
-    pub fn set_f64(&mut self, n: usize, v: f64) {
-        self.set_i64(n, i64::from_be_bytes(v.to_be_bytes()));
+    pub fn set_f64(&mut self, n: usize, v: f64) -> Result<(), JavaError> {
+        return self.set_i64(n, i64::from_be_bytes(v.to_be_bytes()));
     }
 
-    pub fn get_f64(&mut self, n: usize) -> Option<f64> {
-        if let Some(v) = self.get_i64(n) {
-            return Some(f64::from_be_bytes(v.to_be_bytes()));
-        } else {
-            return None;
-        }
+    pub fn get_f64(&self, n: usize) -> Result<f64, JavaError> {
+        return Ok(f64::from_be_bytes(self.get_i64(n)?.to_be_bytes()));
     }
 
-    pub fn set_ref(&mut self, n: usize, v: Object) {
-        let mut slot = self.vec.get_mut(n).unwrap();
-        slot.val_ref = Option::Some(v);
+    pub fn set_ref(&mut self, n: usize, v: Option<ObjRef>) -> Result<(), JavaError> {
+        return self.set_slot(n, Slot { val_num: 0, val_ref: v });
     }
 
-    pub fn get_ref(&self, n: usize) -> Option<Object> {
-        if let Some(v) = self.vec.get(n) {
-            let a = v.val_ref;
-            return None;
-        } else {
-            return None;
-        }
+    pub fn set_slot(&mut self, n: usize, slot: Slot) -> Result<(), JavaError> {
+        let v = self.vec.get_mut(n).ok_or_else(|| bad_local(n))?;
+        *v = slot;
+        return Ok(());
+    }
+
+    pub fn get_slot(&self, n: usize) -> Result<Slot, JavaError> {
+        return self.vec.get(n).copied().ok_or_else(|| bad_local(n));
+    }
+
+    // moves a slot out, leaving a zeroed one behind
+    pub fn take_slot(&mut self, n: usize) -> Result<Slot, JavaError> {
+        let v = self.vec.get_mut(n).ok_or_else(|| bad_local(n))?;
+        return Ok(std::mem::take(v));
+    }
+
+    pub fn get_ref(&self, n: usize) -> Result<Option<ObjRef>, JavaError> {
+        return Ok(self.get_slot(n)?.val_ref);
+    }
+
+    pub fn len(&self) -> usize {
+        return self.vec.len();
+    }
+
+    // the non-null references it holds
+    pub fn refs(&self) -> impl Iterator<Item = ObjRef> + '_ {
+        return self.vec.iter().filter_map(|slot| slot.val_ref);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.vec.is_empty();
     }
 }
 
@@ -118,6 +150,14 @@ pub struct OperandStack {
     index: usize,
 }
 
+fn stack_overflow() -> JavaError {
+    return JavaError::new("java/lang/VerifyError", "Operand stack overflow".to_string());
+}
+
+fn stack_underflow() -> JavaError {
+    return JavaError::new("java/lang/VerifyError", "Unable to pop operand off an empty stack".to_string());
+}
+
 impl OperandStack {
     pub fn new(size: usize) -> OperandStack {
         return OperandStack {
@@ -126,60 +166,118 @@ impl OperandStack {
         }
     }
 
-    pub fn push_i32(&mut self, v: i32) {
-        self.local_vars.set_i32(self.index, v);
-        self.index += 1;
+    // makes room for `n` more slots, returning where they start
+    fn grow(&mut self, n: usize) -> Result<usize, JavaError> {
+        if self.index + n > self.local_vars.len() {
+            return Err(stack_overflow());
+        }
+        self.index += n;
+        return Ok(self.index - n);
+    }
+
+    // gives up the top `n` slots, returning where they start
+    fn shrink(&mut self, n: usize) -> Result<usize, JavaError> {
+        if self.index < n {
+            return Err(stack_underflow());
+        }
+        self.index -= n;
+        return Ok(self.index);
+    }
+
+    pub fn push_i32(&mut self, v: i32) -> Result<(), JavaError> {
+        let n = self.grow(1)?;
+        return self.local_vars.set_i32(n, v);
+    }
+
+    pub fn pop_i32(&mut self) -> Result<i32, JavaError> {
+        let n = self.shrink(1)?;
+        return self.local_vars.get_i32(n);
+    }
+
+    pub fn push_i64(&mut self, v: i64) -> Result<(), JavaError> {
+        let n = self.grow(2)?;
+        return self.local_vars.set_i64(n, v);
+    }
+
+    pub fn pop_i64(&mut self) -> Result<i64, JavaError> {
+        let n = self.shrink(2)?;
+        return self.local_vars.get_i64(n);
+    }
+
+    pub fn push_f32(&mut self, v: f32) -> Result<(), JavaError> {
+        let n = self.grow(1)?;
+        return self.local_vars.set_f32(n, v);
+    }
+
+    pub fn pop_f32(&mut self) -> Result<f32, JavaError> {
+        let n = self.shrink(1)?;
+        return self.local_vars.get_f32(n);
+    }
+
+    pub fn push_f64(&mut self, v: f64) -> Result<(), JavaError> {
+        let n = self.grow(2)?;
+        return self.local_vars.set_f64(n, v);
+    }
+
+    pub fn pop_f64(&mut self) -> Result<f64, JavaError> {
+        let n = self.shrink(2)?;
+        return self.local_vars.get_f64(n);
     }
 
-    pub fn pop_i32(&mut self) -> Option<i32> {
-        self.index -= 1;
-        return self.local_vars.get_i32(self.index);
+    pub fn push_ref(&mut self, obj: Option<ObjRef>) -> Result<(), JavaError> {
+        let n = self.grow(1)?;
+        return self.local_vars.set_ref(n, obj);
     }
 
-    pub fn push_i64(&mut self, v: i64) {
-        self.local_vars.set_i64(self.index, v);
-        self.index += 2;
+    pub fn push_null(&mut self) -> Result<(), JavaError> {
+        return self.push_slot(Slot { val_num: 0, val_ref: None });
     }
 
-    pub fn pop_i64(&mut self) -> Option<i64> {
-        self.index -= 2;
-        return self.local_vars.get_i64(self.index);
+    pub fn pop_ref(&mut self) -> Result<Option<ObjRef>, JavaError> {
+        let n = self.shrink(1)?;
+        return self.local_vars.get_ref(n);
     }
 
-    pub fn push_f32(&mut self, v: f32) {
-        self.local_vars.set_f32(self.index, v);
-        self.index += 1;
+    pub fn push_slot(&mut self, slot: Slot) -> Result<(), JavaError> {
+        let n = self.grow(1)?;
+        return self.local_vars.set_slot(n, slot);
     }
 
-    pub fn pop_f32(&mut self) -> Option<f32> {
-        self.index -= 1;
-        return self.local_vars.get_f32(self.index);
+    pub fn pop_slot(&mut self) -> Result<Slot, JavaError> {
+        let n = self.shrink(1)?;
+        return self.local_vars.take_slot(n);
     }
 
-    pub fn push_f64(&mut self, v: f64) {
-        self.local_vars.set_f64(self.index, v);
-        self.index += 2;
+    // the slot `n` slots below the top, 0 for the top one
+    pub fn peek_slot(&self, n: usize) -> Result<Slot, JavaError> {
+        if n >= self.index {
+            return Err(stack_underflow());
+        }
+        return self.local_vars.get_slot(self.index - 1 - n);
     }
 
-    pub fn pop_f64(&mut self) -> Option<f64> {
-        self.index -= 2;
-        return self.local_vars.get_f64(self.index);
+    // empties it, as entering an exception handler does
+    pub fn clear(&mut self) {
+        while self.index > 0 {
+            self.index -= 1;
+            self.local_vars.vec[self.index] = Slot::default();
+        }
     }
 
-    pub fn push_ref(&mut self, obj: Object) {
-        self.local_vars.set_ref(self.index, obj);
-        self.index -= 1;
+    // the number of slots in use
+    pub fn size(&self) -> usize {
+        return self.index;
     }
 
-    pub fn pop_ref(&mut self) -> Option<Object> {
-        self.index -= 1;
-        return self.local_vars.get_ref(self.index);
+    // the non-null references in the slots in use
+    pub fn refs(&self) -> impl Iterator<Item = ObjRef> + '_ {
+        return self.local_vars.vec[..self.index].iter().filter_map(|slot| slot.val_ref);
     }
 }
 
 pub struct Stack {
     max_size: usize,
-    vec: Vec<Box<Frame>>
+    vec: Vec<Frame>
 }
 
 impl Stack {
@@ -190,24 +288,63 @@ impl Stack {
         }
     }
 
-    pub fn push(&mut self, frame: Box<Frame>) {
-        if self.vec.len() == self.max_size {
+    // callers check is_full first, a new frame that doesn't fit is a StackOverflowError
+    pub fn push(&mut self, frame: Frame) {
+        if self.is_full() {
             panic!("The stack is overflowed.");
         }
 
         self.vec.push(frame);
     }
 
-    pub fn pop(&mut self) -> Option<Box<Frame>> {
+    pub fn is_full(&self) -> bool {
+        return self.vec.len() >= self.max_size;
+    }
+
+    pub fn pop(&mut self) -> Option<Frame> {
         self.vec.pop()
     }
 
-    pub fn get_current_frame(&mut self) -> Option<&Box<Frame>> {
-        self.vec.get(self.vec.len() - 1)
+    // the frames from the bottom of the stack up
+    pub fn frames(&self) -> impl DoubleEndedIterator<Item = &Frame> {
+        return self.vec.iter();
+    }
+
+    pub fn get_current_frame(&mut self) -> Option<&Frame> {
+        self.vec.last()
+    }
+
+    pub fn get_current_frame_mut(&mut self) -> Option<&mut Frame> {
+        self.vec.last_mut()
+    }
+
+    pub fn len(&self) -> usize {
+        return self.vec.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.vec.is_empty();
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ThreadId(pub u32);
+
 pub struct Thread {
-    pub (crate) pc: u32,
-    pub (crate) stack: Stack
-}
\ No newline at end of file
+    pub id: ThreadId,
+    // what uncaught exceptions are reported with
+    pub name: String,
+    pub pc: u32,
+    pub stack: Stack
+}
+
+impl Thread {
+    pub fn new(id: ThreadId, max_stack_depth: usize) -> Thread {
+        return Thread {
+            id,
+            name: format!("Thread-{}", id.0),
+            pc: 0,
+            stack: Stack::new(max_stack_depth),
+        }
+    }
+}
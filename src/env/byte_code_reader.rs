@@ -1,12 +1,10 @@
-use std::convert::TryFrom;
-
-pub struct ByteCodeReader {
-    code: Vec<u8>,
+pub struct ByteCodeReader<'a> {
+    code: &'a [u8],
     pub pc: usize,
 }
 
-impl ByteCodeReader {
-    pub fn new(code: Vec<u8>) -> ByteCodeReader {
+impl<'a> ByteCodeReader<'a> {
+    pub fn new(code: &'a [u8]) -> ByteCodeReader<'a> {
         return ByteCodeReader {
             code,
             pc: 0,
@@ -23,20 +21,20 @@ impl ByteCodeReader {
 
     pub fn read_i32s(&mut self, n: usize) -> Vec<i32> {
         let mut vec: Vec<i32> = Vec::new();
-        for i in 0..n {
+        for _ in 0..n {
             vec.push(self.read_i32());
         }
         return vec;
     }
 
     pub fn skip_padding(&mut self) {
-        while self.pc%4 != 0 {
+        while !self.pc.is_multiple_of(4) {
             self.read_u8();
         }
     }
 
     pub fn read_i16(&mut self) -> i16 {
-        return i16::try_from(self.read_u16()).unwrap();
+        return self.read_u16() as i16;
     }
 
     pub fn read_u16(&mut self) -> u16 {
@@ -46,16 +44,25 @@ impl ByteCodeReader {
     }
 
     pub fn read_i8(&mut self) -> i8 {
-        return i8::try_from(self.read_u8()).unwrap();
+        return self.read_u8() as i8;
     }
 
+    // past the end of the code this reads zeros; callers check `pc` against `len`
     pub fn read_u8(&mut self) -> u8 {
-        let d = self.code[self.pc];
+        let d = self.code.get(self.pc).copied().unwrap_or(0);
         self.pc += 1;
         return d;
     }
 
-    pub fn reset(&mut self, code: Vec<u8>, pc: usize) {
+    pub fn len(&self) -> usize {
+        return self.code.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.code.is_empty();
+    }
+
+    pub fn reset(&mut self, code: &'a [u8], pc: usize) {
         self.code = code;
         self.pc = pc;
     }
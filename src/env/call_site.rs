@@ -0,0 +1,389 @@
+use std::rc::Rc;
+use crate::class::Class;
+use crate::class::access_flags::ACC_VARARGS;
+use crate::class::descriptor::{slot_size, MethodDescriptor};
+use crate::class::linker::{resolve_class, resolve_method_handle, resolve_method_type};
+use crate::class::registry::LoaderId;
+use crate::class::runtime_constant_pool::*;
+use crate::env::basic_env_elements::{LocalVars, Slot};
+use crate::env::env::Environment;
+use crate::env::error::JavaError;
+use crate::env::inst::ExecContext;
+use crate::env::object::ObjRef;
+
+/*
+invokedynamic (JVMS 5.4.3.6, 6.5): the first time an invokedynamic
+instruction runs, its call site is linked by the bootstrap method the
+constant pool entry names, and the method handle that returns is what the
+instruction invokes from then on. Each instruction is a call site of its
+own, even if it shares the constant pool entry with others.
+
+Bootstrap methods are Java methods taking a Lookup, a String and a
+MethodType object, which return a CallSite. The ones javac relies on
+spin classes with java.lang.invoke machinery the VM doesn't have, so the
+VM implements those itself, see Environment::register_bootstrap; other
+bootstrap methods are run.
+ */
+
+// a static argument of a bootstrap method, resolved from the constant pool
+#[derive(Clone)]
+pub enum BootstrapArg {
+    Int(i32),
+    Float(f32),
+    Long(i64),
+    Double(f64),
+    String(String),
+    Class(Rc<Class>),
+    // a method descriptor
+    MethodType(String),
+    MethodHandle(ResolvedMethodHandle),
+}
+
+// what a bootstrap method is called with
+pub struct BootstrapCall {
+    // the class of the invokedynamic instruction
+    pub caller: Rc<Class>,
+    pub name: String,
+    // the type of the call site: the arguments it takes off the stack and what it leaves
+    pub descriptor: String,
+    pub args: Vec<BootstrapArg>,
+}
+
+pub type Bootstrap = fn(&mut ExecContext, &BootstrapCall) -> Result<MethodHandle, JavaError>;
+
+// what a call site, or a MethodHandle object, invokes
+#[derive(Clone)]
+pub enum MethodHandle {
+    // invokes a method the way the invoke instruction of the reference kind does,
+    // a constructor as new and invokespecial do
+    Direct(u8, ResolvedMethod),
+    // gets or puts a field the way the field instruction of the reference kind does
+    Field(u8, ResolvedField),
+    // drops the arguments and leaves a constant, e.g. a lambda that captures nothing
+    Constant(Slot),
+    // concatenates the arguments to a String by a recipe and its constants, see concat.rs
+    Concat(String, Vec<String>),
+}
+
+impl MethodHandle {
+    pub fn of(resolved: ResolvedMethodHandle) -> MethodHandle {
+        return match resolved {
+            ResolvedMethodHandle::Method(kind, method) => MethodHandle::Direct(kind, method),
+            ResolvedMethodHandle::Field(kind, field) => MethodHandle::Field(kind, field),
+        };
+    }
+
+    // the method descriptor of what the handle takes and returns, None if it takes anything
+    pub fn method_type(&self) -> Option<String> {
+        return match self {
+            MethodHandle::Direct(REF_INVOKE_STATIC, resolved) => Some(resolved.method.descriptor.clone()),
+            MethodHandle::Direct(REF_NEW_INVOKE_SPECIAL, resolved) => {
+                // it returns the object it makes
+                let descriptor = &resolved.method.descriptor;
+                Some(format!("{}L{};", &descriptor[..descriptor.rfind(')')? + 1], resolved.class.name))
+            }
+            MethodHandle::Direct(_, resolved) => {
+                // the receiver comes first
+                Some(format!("(L{};{}", resolved.class.name, &resolved.method.descriptor[1..]))
+            }
+            MethodHandle::Field(kind, resolved) => {
+                let (holder, field_type) = (&resolved.class.name, &resolved.field().descriptor);
+                Some(match *kind {
+                    REF_GET_FIELD => format!("(L{};){}", holder, field_type),
+                    REF_GET_STATIC => format!("(){}", field_type),
+                    REF_PUT_FIELD => format!("(L{};{})V", holder, field_type),
+                    _ => format!("({})V", field_type),
+                })
+            }
+            MethodHandle::Constant(_) | MethodHandle::Concat(..) => None,
+        };
+    }
+}
+
+// a linked call site, it never changes its target
+pub struct CallSite {
+    pub descriptor: String,
+    pub target: MethodHandle,
+}
+
+fn bootstrap_class(env: &mut Environment, name: &str) -> Result<Rc<Class>, JavaError> {
+    return env.load_class_from(LoaderId::BOOTSTRAP, name)?
+        .ok_or_else(|| JavaError::new("java/lang/NoClassDefFoundError", name.to_string()));
+}
+
+// the slot of an instance field the class declares or inherits
+fn field_slot(class: &Rc<Class>, name: &str, descriptor: &str) -> Option<usize> {
+    let mut holder = Some(class.clone());
+    while let Some(c) = holder {
+        if let Some(field) = c.get_field(name, descriptor) {
+            return Some(field.slot_id);
+        }
+        holder = c.super_class.clone();
+    }
+    return None;
+}
+
+/*
+The java.lang.invoke.MethodType object of a method descriptor, made the
+first time it is asked for and kept by the heap from then on, as interned
+strings are. As with mirrors its Java fields stay zeroed, the VM goes by
+the descriptor.
+ */
+pub fn method_type_object(ctx: &mut ExecContext, descriptor: &str) -> Result<ObjRef, JavaError> {
+    if let Some(method_type) = ctx.env.heap.method_type(descriptor) {
+        return Ok(method_type);
+    }
+    let class = bootstrap_class(ctx.env, "java/lang/invoke/MethodType")?;
+    let method_type = ctx.alloc_object(&class)?;
+    ctx.env.heap.set_method_type(descriptor, method_type);
+    return Ok(method_type);
+}
+
+/*
+A new java.lang.invoke.MethodHandle object for `target`: a
+DirectMethodHandle, as HotSpot makes for constants, whose `type` field
+holds its MethodType. The heap keeps the target by the object, for the
+VM to invoke it.
+ */
+pub fn method_handle_object(ctx: &mut ExecContext, target: MethodHandle) -> Result<ObjRef, JavaError> {
+    let method_type = match target.method_type() {
+        Some(descriptor) => Some(method_type_object(ctx, &descriptor)?),
+        None => None,
+    };
+    let class = bootstrap_class(ctx.env, "java/lang/invoke/DirectMethodHandle")?;
+    // the MethodType is kept by the heap, so it survives a collection here
+    let handle = ctx.alloc_object(&class)?;
+    if let Some(slot) = field_slot(&class, "type", "Ljava/lang/invoke/MethodType;") {
+        ctx.env.heap.set_field_ref(handle, slot, method_type)?;
+    }
+    ctx.env.heap.set_method_handle(handle, target);
+    return Ok(handle);
+}
+
+fn bootstrap_error(message: String) -> JavaError {
+    return JavaError::new("java/lang/BootstrapMethodError", message);
+}
+
+/*
+Whether a target of type `method_type` fits a call site of type
+`descriptor`: the arguments have to be the same, a reference returned
+may be of a subclass, as the constructor of a lambda class returns an
+instance of the interface the call site wants.
+ */
+fn types_match(method_type: &str, descriptor: &str) -> bool {
+    let (arguments, returned) = method_type.split_at(method_type.rfind(')').unwrap_or(0) + 1);
+    let (wanted_arguments, wanted) = descriptor.split_at(descriptor.rfind(')').unwrap_or(0) + 1);
+    return arguments == wanted_arguments && (returned == wanted || (returned.len() > 1 && wanted.len() > 1));
+}
+
+fn bootstrap_arg(env: &mut Environment, caller: &Rc<Class>, index: u16) -> Result<BootstrapArg, JavaError> {
+    let arg = match caller.constant_pool.get(index as usize) {
+        Some(Constant::Integer(v)) => BootstrapArg::Int(*v),
+        Some(Constant::Float(v)) => BootstrapArg::Float(*v),
+        Some(Constant::Long(v)) => BootstrapArg::Long(*v),
+        Some(Constant::Double(v)) => BootstrapArg::Double(*v),
+        Some(Constant::String(s)) => BootstrapArg::String(s.clone()),
+        Some(Constant::Class(_)) => BootstrapArg::Class(resolve_class(env, caller, index)?),
+        Some(Constant::MethodType(_)) => BootstrapArg::MethodType(resolve_method_type(env, caller, index)?),
+        Some(Constant::MethodHandle(_)) => BootstrapArg::MethodHandle(resolve_method_handle(env, caller, index)?),
+        _ => return Err(bootstrap_error(format!("Bad static argument #{} in class {}", index, caller.name))),
+    };
+    return Ok(arg);
+}
+
+/*
+The call site of the invokedynamic instruction executing in `ctx`, linked
+with the call site specifier at `index` if this is its first run. Failures
+aren't cached, the next run tries again. Errors from the bootstrap method
+pass through, other exceptions are wrapped in a BootstrapMethodError.
+ */
+pub fn link_call_site(ctx: &mut ExecContext, index: u16) -> Result<Rc<CallSite>, JavaError> {
+    let method = ctx.method().clone();
+    if let Some(site) = method.call_sites.borrow().get(&ctx.pc) {
+        return Ok(site.clone());
+    }
+
+    let caller = ctx.class().clone();
+    let (specifier, name, descriptor) = match caller.constant_pool.get(index as usize) {
+        Some(Constant::InvokeDynamic(r)) => (r.bootstrap_method_attr_index, r.name.clone(), r.descriptor.clone()),
+        _ => {
+            return Err(JavaError::new("java/lang/VerifyError",
+                format!("Illegal type at constant pool entry {} in class {}, invokedynamic expected", index, caller.name)));
+        }
+    };
+    let bootstrap_method = caller.bootstrap_methods.get(specifier as usize).cloned().ok_or_else(|| {
+        bootstrap_error(format!("Bootstrap method #{} out of range in class {}", specifier, caller.name))
+    })?;
+
+    let resolved = match resolve_method_handle(ctx.env, &caller, bootstrap_method.bootstrap_method_ref)? {
+        ResolvedMethodHandle::Method(REF_INVOKE_STATIC, resolved) => resolved,
+        _ => return Err(bootstrap_error(format!("Bootstrap method of call site {}{} is not a static method", name, descriptor))),
+    };
+    let mut args = vec![];
+    for index in &bootstrap_method.bootstrap_arguments {
+        args.push(bootstrap_arg(ctx.env, &caller, *index)?);
+    }
+
+    let call = BootstrapCall {
+        caller,
+        name,
+        descriptor,
+        args,
+    };
+    let linked = match ctx.env.get_bootstrap(&resolved.class.name, &resolved.method.name) {
+        Some(bootstrap) => bootstrap(ctx, &call),
+        None => run_bootstrap(ctx, &resolved, &call),
+    };
+    let target = match linked {
+        Ok(target) => target,
+        Err(e) if e.is_error(ctx.env) => return Err(e),
+        Err(e) => return Err(bootstrap_error(format!("call site initialization exception: {}", e))),
+    };
+    if let Some(method_type) = target.method_type() {
+        if !types_match(&method_type, &call.descriptor) {
+            return Err(bootstrap_error(format!("call site type {} doesn't match target type {}", call.descriptor, method_type)));
+        }
+    }
+
+    let site = Rc::new(CallSite {
+        descriptor: call.descriptor,
+        target,
+    });
+    method.call_sites.borrow_mut().insert(ctx.pc, site.clone());
+    return Ok(site);
+}
+
+/*
+Runs a bootstrap method written in Java. It is passed a Lookup with full
+access to the caller, the name and the MethodType of the call site, and
+the static arguments as objects where it takes objects, trailing ones in
+an array if it has variable arity. The CallSite it returns has to target
+a method handle the VM made. The arguments are kept alive by global
+references until the method has them.
+ */
+fn run_bootstrap(ctx: &mut ExecContext, bootstrap: &ResolvedMethod, call: &BootstrapCall) -> Result<MethodHandle, JavaError> {
+    let method = &bootstrap.method;
+    let unfit = || bootstrap_error(format!("Bootstrap method {}.{}{} can't link call site {}{}",
+        bootstrap.class.name.replace('/', "."), method.name, method.descriptor, call.name, call.descriptor));
+    let descriptor = MethodDescriptor::parse(&method.descriptor).ok_or_else(unfit)?;
+    let types = &descriptor.parameter_types;
+    let varargs = method.access_flags & ACC_VARARGS != 0 && types.last().is_some_and(|t| t.starts_with("[L"));
+    let fixed = if varargs { types.len() - 1 } else { types.len() };
+    if types.len() < 3 || call.args.len() < fixed - 3 || (!varargs && call.args.len() != fixed - 3) {
+        return Err(unfit());
+    }
+
+    let mut global_refs = vec![];
+    let result = bootstrap_args(ctx, call, types, fixed, varargs, &mut global_refs)
+        .and_then(|args| {
+            ctx.initialize(&bootstrap.class)?;
+            ctx.run(bootstrap.class.clone(), method.clone(), args)
+        });
+    for id in global_refs {
+        ctx.env.heap.delete_global_ref(id);
+    }
+    let call_site = result?.first().and_then(|slot| slot.val_ref)
+        .ok_or_else(|| bootstrap_error(format!("Bootstrap method returned null for call site {}{}", call.name, call.descriptor)))?;
+
+    let call_site_class = bootstrap_class(ctx.env, "java/lang/invoke/CallSite")?;
+    let class = ctx.env.heap.get(call_site).class().clone();
+    if !class.is_subclass_of(&call_site_class) {
+        return Err(bootstrap_error(format!("Bootstrap method returned a {} instead of a CallSite", class.name.replace('/', "."))));
+    }
+    let target = field_slot(&class, "target", "Ljava/lang/invoke/MethodHandle;")
+        .and_then(|slot| ctx.env.heap.get(call_site).fields()?.get_ref(slot).ok()?);
+    return target.and_then(|target| ctx.env.heap.method_handle(target).cloned())
+        .ok_or_else(|| bootstrap_error(format!("Target of call site {}{} is no method handle of the VM", call.name, call.descriptor)));
+}
+
+// the arguments of a bootstrap method taking `types`, the first `fixed` of them one by one
+fn bootstrap_args(ctx: &mut ExecContext, call: &BootstrapCall, types: &[String], fixed: usize, varargs: bool,
+                  global_refs: &mut Vec<usize>) -> Result<Vec<Slot>, JavaError> {
+    let mut keep = |ctx: &mut ExecContext, object: ObjRef| {
+        global_refs.push(ctx.env.heap.new_global_ref(object));
+        return object;
+    };
+    let arg_slot_count: usize = types.iter().map(|t| slot_size(t)).sum();
+    let mut vars = LocalVars::new(arg_slot_count);
+
+    let lookup_class = bootstrap_class(ctx.env, "java/lang/invoke/MethodHandles$Lookup")?;
+    let lookup = ctx.alloc_object(&lookup_class)?;
+    keep(ctx, lookup);
+    let mirror = ctx.class_mirror(&call.caller)?;
+    if let Some(slot) = field_slot(&lookup_class, "lookupClass", "Ljava/lang/Class;") {
+        ctx.env.heap.set_field_ref(lookup, slot, Some(mirror))?;
+    }
+    if let (Some(slot), Some(fields)) = (field_slot(&lookup_class, "allowedModes", "I"), ctx.env.heap.get_mut(lookup).fields_mut()) {
+        fields.set_i32(slot, FULL_POWER_MODES)?;
+    }
+    vars.set_ref(0, Some(lookup))?;
+    // interned and MethodType objects are kept by the heap
+    vars.set_ref(1, Some(ctx.intern_string(&call.name)?))?;
+    vars.set_ref(2, Some(method_type_object(ctx, &call.descriptor)?))?;
+
+    let mut slot = 3;
+    for (arg, field_type) in call.args.iter().zip(&types[3..fixed]) {
+        match (arg, field_type.as_str()) {
+            (BootstrapArg::Int(v), "I" | "Z" | "B" | "C" | "S") => vars.set_i32(slot, *v)?,
+            (BootstrapArg::Long(v), "J") => vars.set_i64(slot, *v)?,
+            (BootstrapArg::Float(v), "F") => vars.set_f32(slot, *v)?,
+            (BootstrapArg::Double(v), "D") => vars.set_f64(slot, *v)?,
+            (arg, t) if t.len() > 1 => {
+                let object = bootstrap_arg_object(ctx, arg)?;
+                vars.set_ref(slot, Some(keep(ctx, object)))?;
+            }
+            _ => return Err(bootstrap_error(format!("Static argument of call site {}{} isn't a {}", call.name, call.descriptor, field_type))),
+        }
+        slot += slot_size(field_type);
+    }
+    if varargs {
+        let rest = &call.args[fixed - 3..];
+        let array_class = ctx.env.load_class_from(LoaderId::BOOTSTRAP, &types[fixed])?
+            .ok_or_else(|| JavaError::new("java/lang/NoClassDefFoundError", types[fixed].clone()))?;
+        let array = ctx.alloc_array(&array_class, rest.len())?;
+        keep(ctx, array);
+        for (n, arg) in rest.iter().enumerate() {
+            let object = bootstrap_arg_object(ctx, arg)?;
+            ctx.env.heap.set_element_ref(array, n, Some(object))?;
+        }
+        vars.set_ref(slot, Some(array))?;
+    }
+    return (0..arg_slot_count).map(|n| vars.take_slot(n)).collect();
+}
+
+// the access modes of a Lookup made by MethodHandles.lookup() in the caller
+const FULL_POWER_MODES: i32 = 0x5f;
+
+// a static argument as the object a bootstrap method taking an Object gets, numbers boxed
+fn bootstrap_arg_object(ctx: &mut ExecContext, arg: &BootstrapArg) -> Result<ObjRef, JavaError> {
+    let mut vars = LocalVars::new(2);
+    let (wrapper, descriptor) = match arg {
+        BootstrapArg::String(s) => return ctx.intern_string(s),
+        BootstrapArg::Class(class) => return ctx.class_mirror(class),
+        BootstrapArg::MethodType(descriptor) => return method_type_object(ctx, descriptor),
+        BootstrapArg::MethodHandle(resolved) => return method_handle_object(ctx, MethodHandle::of(resolved.clone())),
+        BootstrapArg::Int(v) => {
+            vars.set_i32(0, *v)?;
+            ("java/lang/Integer", "(I)Ljava/lang/Integer;")
+        }
+        BootstrapArg::Float(v) => {
+            vars.set_f32(0, *v)?;
+            ("java/lang/Float", "(F)Ljava/lang/Float;")
+        }
+        BootstrapArg::Long(v) => {
+            vars.set_i64(0, *v)?;
+            ("java/lang/Long", "(J)Ljava/lang/Long;")
+        }
+        BootstrapArg::Double(v) => {
+            vars.set_f64(0, *v)?;
+            ("java/lang/Double", "(D)Ljava/lang/Double;")
+        }
+    };
+    let class = bootstrap_class(ctx.env, wrapper)?;
+    let value_of = class.get_method("valueOf", descriptor)
+        .ok_or_else(|| JavaError::new("java/lang/NoSuchMethodError", format!("{}.valueOf{}", wrapper.replace('/', "."), descriptor)))?;
+    ctx.initialize(&class)?;
+    let args = (0..value_of.arg_slot_count).map(|n| vars.take_slot(n)).collect::<Result<_, _>>()?;
+    return ctx.run(class, value_of, args)?.first().and_then(|slot| slot.val_ref)
+        .ok_or_else(|| bootstrap_error(format!("{}.valueOf returned null", wrapper.replace('/', "."))));
+}
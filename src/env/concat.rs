@@ -0,0 +1,159 @@
+use crate::class::descriptor::{slot_size, MethodDescriptor};
+use crate::class::registry::LoaderId;
+use crate::class::runtime_constant_pool::ResolvedMethod;
+use crate::class::vtable::select_method;
+use crate::env::basic_env_elements::{LocalVars, Slot};
+use crate::env::call_site::{BootstrapArg, BootstrapCall, MethodHandle};
+use crate::env::error::JavaError;
+use crate::env::inst::ExecContext;
+use crate::env::object::ObjRef;
+use crate::env::string::value_of;
+
+/*
+StringConcatFactory.makeConcatWithConstants links the call sites javac
+emits for string concatenation. The call site takes the values to
+concatenate and returns the String. The first static argument is the
+recipe, the text of the String with \1 in place of each argument and \2
+in place of each of the other static arguments, which are constants. The
+JDK spins method handles for a recipe; the VM builds the String itself.
+ */
+pub fn make_concat_with_constants(_ctx: &mut ExecContext, call: &BootstrapCall) -> Result<MethodHandle, JavaError> {
+    let (recipe, args) = match call.args.split_first() {
+        Some((BootstrapArg::String(recipe), args)) => (recipe, args),
+        _ => return Err(concat_error(format!("Bad arguments for concatenation {}", call.descriptor))),
+    };
+    let site = MethodDescriptor::parse(&call.descriptor)
+        .ok_or_else(|| concat_error(format!("Illegal call site type {}", call.descriptor)))?;
+    if site.return_type != "Ljava/lang/String;" {
+        return Err(concat_error(format!("Concatenation of type {} doesn't return a String", call.descriptor)));
+    }
+    if recipe.matches('\u{1}').count() != site.parameter_types.len() {
+        return Err(concat_error(format!("Mismatched number of concat arguments: recipe wants {}, but signature provides {}",
+            recipe.matches('\u{1}').count(), site.parameter_types.len())));
+    }
+    if recipe.matches('\u{2}').count() != args.len() {
+        return Err(concat_error(format!("Mismatched number of concat constants: recipe wants {}, but only {} are passed",
+            recipe.matches('\u{2}').count(), args.len())));
+    }
+
+    let mut constants = vec![];
+    for arg in args {
+        constants.push(match arg {
+            BootstrapArg::String(s) => s.clone(),
+            BootstrapArg::Int(v) => v.to_string(),
+            BootstrapArg::Long(v) => v.to_string(),
+            BootstrapArg::Float(v) => float_to_string(*v),
+            BootstrapArg::Double(v) => double_to_string(*v),
+            _ => return Err(concat_error(format!("Unsupported concat constant in {}", call.descriptor))),
+        });
+    }
+    return Ok(MethodHandle::Concat(recipe.clone(), constants));
+}
+
+fn concat_error(message: String) -> JavaError {
+    return JavaError::new("java/lang/invoke/StringConcatException", message);
+}
+
+/*
+Concatenates the arguments on the operand stack, which have the types of
+`descriptor`, as `recipe` says, and returns the new String. Objects other
+than Strings are turned into text by their toString method. The arguments
+stay on the operand stack, where the collector sees them, until the text
+is made.
+ */
+pub fn concat(ctx: &mut ExecContext, recipe: &str, constants: &[String], descriptor: &str) -> Result<ObjRef, JavaError> {
+    let site = MethodDescriptor::parse(descriptor)
+        .ok_or_else(|| concat_error(format!("Illegal call site type {}", descriptor)))?;
+    let arg_slot_count = site.arg_slot_count();
+    let mut args = LocalVars::new(arg_slot_count);
+    for n in 0..arg_slot_count {
+        args.set_slot(n, ctx.frame.operand_stack.peek_slot(arg_slot_count - 1 - n)?)?;
+    }
+
+    let mut text = String::new();
+    let (mut arg, mut slot, mut constant) = (0, 0, 0);
+    for c in recipe.chars() {
+        match c {
+            '\u{1}' => {
+                let field_type = &site.parameter_types[arg];
+                text.push_str(&match field_type.as_str() {
+                    "Z" => (args.get_i32(slot)? != 0).to_string(),
+                    "C" => char::from_u32(args.get_i32(slot)? as u16 as u32).unwrap_or('\u{fffd}').to_string(),
+                    "B" | "S" | "I" => args.get_i32(slot)?.to_string(),
+                    "J" => args.get_i64(slot)?.to_string(),
+                    "F" => float_to_string(args.get_f32(slot)?),
+                    "D" => double_to_string(args.get_f64(slot)?),
+                    _ => object_to_string(ctx, args.get_ref(slot)?)?,
+                });
+                arg += 1;
+                slot += slot_size(field_type);
+            }
+            '\u{2}' => {
+                text.push_str(&constants[constant]);
+                constant += 1;
+            }
+            c => text.push(c),
+        }
+    }
+
+    for _ in 0..arg_slot_count {
+        ctx.frame.operand_stack.pop_slot()?;
+    }
+    return ctx.new_string(&text);
+}
+
+// String.valueOf(Object): "null" for null, else what toString returns
+fn object_to_string(ctx: &mut ExecContext, object: Option<ObjRef>) -> Result<String, JavaError> {
+    let object = match object {
+        Some(object) => object,
+        None => return Ok("null".to_string()),
+    };
+    if let Some(value) = value_of(ctx.env, object) {
+        return Ok(value);
+    }
+
+    let object_class = ctx.env.load_class_from(LoaderId::BOOTSTRAP, "java/lang/Object")?
+        .ok_or_else(|| JavaError::new("java/lang/NoClassDefFoundError", "java/lang/Object".to_string()))?;
+    let to_string = object_class.get_method("toString", "()Ljava/lang/String;")
+        .ok_or_else(|| JavaError::new("java/lang/NoSuchMethodError", "java.lang.Object.toString()".to_string()))?;
+    let receiver = ctx.env.heap.get(object).class().clone();
+    let (class, method) = select_method(&receiver, &ResolvedMethod { class: object_class, method: to_string })?;
+    let result = ctx.run(class, method, vec![Slot { val_num: 0, val_ref: Some(object) }])?;
+    return Ok(match result.first().and_then(|slot| slot.val_ref) {
+        Some(string) => value_of(ctx.env, string).unwrap_or_default(),
+        None => "null".to_string(),
+    });
+}
+
+// Float.toString
+fn float_to_string(value: f32) -> String {
+    return floating_point_text(value as f64, format!("{}", value), format!("{:e}", value));
+}
+
+// Double.toString
+fn double_to_string(value: f64) -> String {
+    return floating_point_text(value, format!("{}", value), format!("{:e}", value));
+}
+
+/*
+Java writes floating point numbers with the shortest digits that tell the
+value apart, as Rust does, but always with a fraction, and in scientific
+notation with an E below 10^-3 and from 10^7 on.
+ */
+fn floating_point_text(value: f64, plain: String, scientific: String) -> String {
+    if value.is_nan() {
+        return "NaN".to_string();
+    }
+    if value.is_infinite() {
+        return if value > 0.0 { "Infinity" } else { "-Infinity" }.to_string();
+    }
+    let magnitude = value.abs();
+    if magnitude == 0.0 || (1e-3..1e7).contains(&magnitude) {
+        return if plain.contains('.') { plain } else { plain + ".0" };
+    }
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    return match mantissa.contains('.') {
+        true => format!("{}E{}", mantissa, exponent),
+        false => format!("{}.0E{}", mantissa, exponent),
+    };
+}
@@ -1,24 +1,235 @@
+use std::collections::HashMap;
+use std::rc::Rc;
 use crate::class::*;
+use crate::class::linker::link_class;
+use crate::class::registry::{ClassRegistry, LoaderId};
+use crate::env::call_site::Bootstrap;
+use crate::env::concat::make_concat_with_constants;
+use crate::env::error::JavaError;
+use crate::env::heap::Heap;
+use crate::env::lambda::metafactory;
+use crate::env::native::{no_op, Native};
+use crate::env::stack_trace::{fill_in_stack_trace, init_stack_trace_elements};
 
+// a loader of the tree, with the loader it delegates to first
+struct LoaderNode {
+    loader: Box<dyn ClassLoader>,
+    parent: Option<LoaderId>,
+}
+
+/*
+Class loaders form a tree with parent-first delegation, as in Java: the
+bootstrap loader at the root, the platform loader below it and the
+application (class path) loader below that. Further loaders, e.g. one
+per plugin, can be added anywhere in the tree. Each loader has its own
+namespace, so two loaders that don't delegate to each other can define
+different classes of the same name.
+ */
 pub struct Environment {
-    class_loaders: Vec<Box<dyn ClassLoader>>
+    loaders: Vec<LoaderNode>,
+    pub registry: ClassRegistry,
+    pub heap: Heap,
+    // classes whose super classes are being loaded, to catch circularities
+    loading: Vec<(LoaderId, String)>,
+    // bootstrap methods the VM implements itself, by class and method name
+    bootstraps: HashMap<(String, String), Bootstrap>,
+    // native methods by class name, method name and descriptor
+    natives: HashMap<(String, String, String), Native>,
+    // how many lambda classes have been made up, to name the next one
+    lambda_count: usize,
 }
 
 impl Environment {
-    pub fn new(class_loaders: Vec<Box<dyn ClassLoader>>) -> Environment {
-        return Environment {
-            class_loaders,
+    pub fn new(bootstrap: Box<dyn ClassLoader>, platform: Box<dyn ClassLoader>, app: Box<dyn ClassLoader>) -> Environment {
+        return Environment::with_heap(bootstrap, platform, app, Heap::default());
+    }
+
+    // an Environment whose objects live on `heap`, e.g. one with another size or collector
+    pub fn with_heap(bootstrap: Box<dyn ClassLoader>, platform: Box<dyn ClassLoader>, app: Box<dyn ClassLoader>, heap: Heap) -> Environment {
+        let mut env = Environment {
+            loaders: vec![LoaderNode { loader: bootstrap, parent: None }],
+            registry: ClassRegistry::new(),
+            heap,
+            loading: vec![],
+            bootstraps: HashMap::new(),
+            natives: HashMap::new(),
+            lambda_count: 0,
+        };
+        env.add_loader(LoaderId::BOOTSTRAP, platform);
+        env.add_loader(LoaderId::PLATFORM, app);
+        env.register_bootstrap("java/lang/invoke/LambdaMetafactory", "metafactory", metafactory);
+        env.register_bootstrap("java/lang/invoke/StringConcatFactory", "makeConcatWithConstants", make_concat_with_constants);
+        env.register_native("java/lang/Throwable", "fillInStackTrace", "(I)Ljava/lang/Throwable;", fill_in_stack_trace);
+        env.register_native("java/lang/StackTraceElement", "initStackTraceElements",
+            "([Ljava/lang/StackTraceElement;Ljava/lang/Throwable;)V", init_stack_trace_elements);
+        env.register_native("jdk/internal/misc/VM", "initialize", "()V", no_op);
+        return env;
+    }
+
+    // plugs a loader into the tree below `parent`
+    pub fn add_loader(&mut self, parent: LoaderId, loader: Box<dyn ClassLoader>) -> LoaderId {
+        self.loaders.push(LoaderNode {
+            loader,
+            parent: Some(parent),
+        });
+        return LoaderId(self.loaders.len() - 1);
+    }
+
+    /*
+    Call sites whose bootstrap method is `class_name.method_name` are linked
+    by `bootstrap` instead of running the Java method. A later registration
+    replaces an earlier one.
+     */
+    pub fn register_bootstrap(&mut self, class_name: &str, method_name: &str, bootstrap: Bootstrap) {
+        self.bootstraps.insert((class_name.to_string(), method_name.to_string()), bootstrap);
+    }
+
+    pub fn get_bootstrap(&self, class_name: &str, method_name: &str) -> Option<Bootstrap> {
+        return self.bootstraps.get(&(class_name.to_string(), method_name.to_string())).copied();
+    }
+
+    // `native` implements the native method `class_name.method_name` of type `descriptor`, replacing any earlier one
+    pub fn register_native(&mut self, class_name: &str, method_name: &str, descriptor: &str, native: Native) {
+        self.natives.insert((class_name.to_string(), method_name.to_string(), descriptor.to_string()), native);
+    }
+
+    pub fn get_native(&self, class_name: &str, method_name: &str, descriptor: &str) -> Option<Native> {
+        return self.natives.get(&(class_name.to_string(), method_name.to_string(), descriptor.to_string())).copied();
+    }
+
+    pub fn next_lambda_id(&mut self) -> usize {
+        self.lambda_count += 1;
+        return self.lambda_count;
+    }
+
+    pub fn get_parent(&self, loader: LoaderId) -> Option<LoaderId> {
+        return self.loaders.get(loader.0)?.parent;
+    }
+
+    // loads a class the way the application class loader does
+    pub fn load_class(&mut self, class_name: &str) -> Result<Option<Rc<Class>>, JavaError> {
+        return self.load_class_from(LoaderId::APP, class_name);
+    }
+
+    // loads a class with `initiating` as the initiating loader
+    pub fn load_class_from(&mut self, initiating: LoaderId, class_name: &str) -> Result<Option<Rc<Class>>, JavaError> {
+        if let Some(class) = self.registry.find_loaded(initiating, class_name) {
+            return Ok(Some(class));
+        }
+        if class_name.starts_with('[') {
+            return self.load_array_class(initiating, class_name);
         }
+
+        let class = self.delegate(initiating, class_name)?;
+        if let Some(class) = &class {
+            self.registry.record_initiating(initiating, class);
+        }
+        return Ok(class);
+    }
+
+    /*
+    Array classes have no class file, the registry makes them up the first
+    time one is asked for (JVMS 5.3.3). The element type is loaded first by
+    the same initiating loader, as its defining loader defines the array
+    class too.
+     */
+    fn load_array_class(&mut self, initiating: LoaderId, class_name: &str) -> Result<Option<Rc<Class>>, JavaError> {
+        let component_type = &class_name[1..];
+        let element_name = match component_type.as_bytes() {
+            [b'Z'] | [b'B'] | [b'C'] | [b'S'] | [b'I'] | [b'J'] | [b'F'] | [b'D'] => None,
+            [b'[', ..] => Some(component_type),
+            [b'L', .., b';'] => Some(&component_type[1..component_type.len() - 1]),
+            _ => return Ok(None),
+        };
+        let component = match element_name {
+            Some(name) => match self.load_class_from(initiating, name)? {
+                Some(component) => Some(component),
+                None => return Ok(None),
+            },
+            None => None,
+        };
+
+        let object = self.load_required(LoaderId::BOOTSTRAP, "java/lang/Object")?;
+        let interfaces = vec![
+            self.load_required(LoaderId::BOOTSTRAP, "java/lang/Cloneable")?,
+            self.load_required(LoaderId::BOOTSTRAP, "java/io/Serializable")?,
+        ];
+        let class = self.registry.define_array(class_name, component, object, interfaces);
+        link_class(self, &class)?;
+        self.registry.record_initiating(initiating, &class);
+        return Ok(Some(class));
     }
 
-    pub fn load_class(&self, class_name: &str) -> Option<&Class> {
-        let len = self.class_loaders.len();
-        for n in 0..len {
-            let opt = self.class_loaders[n].load_class(class_name);
-            if let Some(_) = opt {
-                return opt;
+    // asks the parent first, and defines the class with `loader` only if no ancestor has it
+    fn delegate(&mut self, loader: LoaderId, class_name: &str) -> Result<Option<Rc<Class>>, JavaError> {
+        if let Some(class) = self.registry.find_defined(loader, class_name) {
+            return Ok(Some(class));
+        }
+        if let Some(parent) = self.get_parent(loader) {
+            if let Some(class) = self.delegate(parent, class_name)? {
+                return Ok(Some(class));
             }
         }
-        return None;
+
+        let node = match self.loaders.get(loader.0) {
+            Some(node) => node,
+            None => return Ok(None),
+        };
+        let opt = node.loader.load_class(class_name).map_err(|e| {
+            JavaError::new("java/lang/ClassFormatError", format!("{} ({})", class_name, e))
+        })?;
+        return match opt {
+            Some(class) => Ok(Some(self.define_class(loader, class_name, class)?)),
+            None => Ok(None),
+        };
     }
-}
\ No newline at end of file
+
+    // derives a class from what `loader` read for `class_name` (JVMS 5.3.5)
+    fn define_class(&mut self, loader: LoaderId, class_name: &str, mut class: Class) -> Result<Rc<Class>, JavaError> {
+        if class.name != class_name {
+            return Err(JavaError::new("java/lang/NoClassDefFoundError",
+                format!("{} (wrong name: {})", class_name, class.name)));
+        }
+        class.loader = loader;
+
+        let key = (loader, class.name.clone());
+        if self.loading.contains(&key) {
+            return Err(JavaError::new("java/lang/ClassCircularityError", class.name.replace('/', ".")));
+        }
+        self.loading.push(key);
+        let result = self.load_supertypes(loader, &mut class);
+        self.loading.pop();
+        result?;
+
+        return Ok(self.registry.define(class));
+    }
+
+    fn load_supertypes(&mut self, loader: LoaderId, class: &mut Class) -> Result<(), JavaError> {
+        if let Some(super_name) = class.super_class_name.clone() {
+            let super_class = self.load_required(loader, &super_name)?;
+            if super_class.is_interface() {
+                return Err(JavaError::new("java/lang/IncompatibleClassChangeError",
+                    format!("class {} has interface {} as super class", class.name, super_name)));
+            }
+            class.set_super_class(super_class);
+        }
+
+        for interface_name in class.interface_names.clone() {
+            let interface = self.load_required(loader, &interface_name)?;
+            if !interface.is_interface() {
+                return Err(JavaError::new("java/lang/IncompatibleClassChangeError",
+                    format!("class {} can not implement {}, because it is not an interface", class.name, interface_name)));
+            }
+            class.interfaces.push(interface);
+        }
+        return Ok(());
+    }
+
+    // a class that has to exist, e.g. a super class
+    fn load_required(&mut self, loader: LoaderId, class_name: &str) -> Result<Rc<Class>, JavaError> {
+        return match self.load_class_from(loader, class_name)? {
+            Some(class) => Ok(class),
+            None => Err(JavaError::new("java/lang/NoClassDefFoundError", class_name.to_string())),
+        };
+    }
+}
@@ -0,0 +1,82 @@
+use std::fmt;
+use crate::class::registry::LoaderId;
+use crate::env::env::Environment;
+use crate::env::object::ObjRef;
+use crate::env::stack_trace::StackTraceElement;
+
+/*
+A throwable on its way to a handler. One the VM raises itself is named by
+the binary name of its class, e.g. "java/lang/ClassFormatError", until the
+interpreter turns it into an object on the Java heap; one thrown by
+`athrow` is that object already.
+ */
+#[derive(Debug)]
+pub struct JavaError {
+    pub class_name: String,
+    pub message: String,
+    // the Throwable on the heap, None until the interpreter has made one
+    pub object: Option<ObjRef>,
+    // where it was thrown from, the innermost frame first; empty until it's thrown
+    pub stack_trace: Vec<StackTraceElement>,
+}
+
+/*
+What the VM knows of a Throwable object beyond its fields, as HotSpot
+keeps the backtrace in a hidden one: the message of an exception the VM
+raised and where it was thrown from, so rethrowing the object keeps them.
+ */
+#[derive(Clone, Debug)]
+pub struct ThrowableInfo {
+    pub message: String,
+    pub stack_trace: Vec<StackTraceElement>,
+}
+
+impl JavaError {
+    pub fn new(class_name: &str, message: String) -> JavaError {
+        return JavaError {
+            class_name: class_name.to_string(),
+            message,
+            object: None,
+            stack_trace: vec![],
+        }
+    }
+
+    // the Throwable `object` on its way to a handler, as athrow throws it
+    pub fn thrown(env: &Environment, object: ObjRef) -> JavaError {
+        let info = env.heap.throwable_info(object).cloned();
+        return JavaError {
+            class_name: env.heap.get(object).class().name.clone(),
+            message: info.as_ref().map(|i| i.message.clone()).unwrap_or_default(),
+            object: Some(object),
+            stack_trace: info.map(|i| i.stack_trace).unwrap_or_default(),
+        }
+    }
+
+    /*
+    Whether its class is java/lang/Error or a subclass, which callers pass
+    on instead of wrapping. A thrown object's own class is checked, as it may
+    come from any loader; the VM's own errors are boot classes.
+     */
+    pub fn is_error(&self, env: &mut Environment) -> bool {
+        let mut next = match self.object {
+            Some(object) => Some(env.heap.get(object).class().clone()),
+            None => env.load_class_from(LoaderId::BOOTSTRAP, &self.class_name).ok().flatten(),
+        };
+        while let Some(class) = next {
+            if class.name == "java/lang/Error" {
+                return true;
+            }
+            next = class.super_class.clone();
+        }
+        return false;
+    }
+}
+
+impl fmt::Display for JavaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.message.is_empty() {
+            return write!(f, "{}", self.class_name.replace('/', "."));
+        }
+        return write!(f, "{}: {}", self.class_name.replace('/', "."), self.message);
+    }
+}
@@ -0,0 +1,366 @@
+use std::mem;
+use crate::env::gc::{Collector, GcStats};
+use crate::env::object::{ObjRef, Object};
+
+// the old places a card covers
+const CARD_SIZE: usize = 32;
+
+// what HotSpot's MaxTenuringThreshold defaults to
+pub const DEFAULT_TENURING_THRESHOLD: u8 = 15;
+
+// where an object is, survivors are always in the from-space between collections
+#[derive(Clone, Copy)]
+enum Place {
+    Eden(u32),
+    Survivor(u32),
+    Old(u32),
+}
+
+struct Entry {
+    r: ObjRef,
+    // the minor collections it has survived
+    age: u8,
+    object: Object,
+}
+
+/*
+A generational collector, as HotSpot's serial one. New objects are
+allocated in eden by bumping its top; a minor collection copies the live
+ones to the empty survivor space, and the ones in the other survivor space
+along with them, then both eden and that space are empty again. Objects
+that have survived `tenuring_threshold` minor collections, or don't fit in
+the survivor space, are promoted to the old generation, which is only
+collected by a full mark-sweep collection. Objects too big for eden are
+allocated in the old generation right away.
+
+References are handles, so moving an object only changes where its
+handle points. A minor collection traces from the roots through young
+objects only: references from old objects to young ones are found through
+a card table, the cards of the old objects that may hold one are dirty.
+The write barrier dirties the card of an old object a reference is stored
+into, the collection cleans the cards that no longer cover one.
+
+When an object can't be copied for lack of room it stays in eden and a
+full collection follows, as it does when the old generation fills up.
+ */
+pub struct Generational {
+    // the place of every object by its reference
+    places: Vec<Option<Place>>,
+    free_refs: Vec<u32>,
+    eden: Vec<Option<Entry>>,
+    // the bytes allocated in eden
+    eden_top: usize,
+    eden_size: usize,
+    // the from-space and the to-space, which is empty
+    survivors: [Vec<Option<Entry>>; 2],
+    from: usize,
+    survivor_top: usize,
+    survivor_size: usize,
+    old: Vec<Option<Entry>>,
+    free_old: Vec<u32>,
+    old_used: usize,
+    old_size: usize,
+    cards: Vec<bool>,
+    tenuring_threshold: u8,
+    // an object didn't fit in the old generation since the last collection
+    old_alloc_failed: bool,
+}
+
+impl Generational {
+    /*
+    A heap of at most `max_size` bytes, sized as HotSpot sizes it: a third
+    is the young generation, of which eden takes eight tenths and each
+    survivor space one.
+     */
+    pub fn new(max_size: usize) -> Generational {
+        let young_size = max_size / 3;
+        let survivor_size = young_size / 10;
+        return Generational::with_sizes(young_size - 2 * survivor_size, survivor_size, max_size - young_size, DEFAULT_TENURING_THRESHOLD);
+    }
+
+    pub fn with_sizes(eden_size: usize, survivor_size: usize, old_size: usize, tenuring_threshold: u8) -> Generational {
+        return Generational {
+            places: vec![],
+            free_refs: vec![],
+            eden: vec![],
+            eden_top: 0,
+            eden_size,
+            survivors: [vec![], vec![]],
+            from: 0,
+            survivor_top: 0,
+            survivor_size,
+            old: vec![],
+            free_old: vec![],
+            old_used: 0,
+            old_size,
+            cards: vec![],
+            tenuring_threshold,
+            old_alloc_failed: false,
+        }
+    }
+
+    // whether `r` has been promoted to the old generation
+    pub fn is_old(&self, r: ObjRef) -> bool {
+        return matches!(self.place(r), Some(Place::Old(_)));
+    }
+
+    fn place(&self, r: ObjRef) -> Option<Place> {
+        return *self.places.get(r.0 as usize)?;
+    }
+
+    fn is_young(&self, r: ObjRef) -> bool {
+        return matches!(self.place(r), Some(Place::Eden(_)) | Some(Place::Survivor(_)));
+    }
+
+    fn new_ref(&mut self, place: Place) -> ObjRef {
+        if let Some(n) = self.free_refs.pop() {
+            self.places[n as usize] = Some(place);
+            return ObjRef(n);
+        }
+        self.places.push(Some(place));
+        return ObjRef((self.places.len() - 1) as u32);
+    }
+
+    fn free_ref(&mut self, r: ObjRef) {
+        self.places[r.0 as usize] = None;
+        self.free_refs.push(r.0);
+    }
+
+    // stores an entry in the old generation, there's room for it
+    fn put_old(&mut self, entry: Entry) -> u32 {
+        self.old_used += entry.object.size();
+        let r = entry.r;
+        let n = match self.free_old.pop() {
+            Some(n) => {
+                self.old[n as usize] = Some(entry);
+                n
+            }
+            None => {
+                self.old.push(Some(entry));
+                self.cards.resize(self.old.len().div_ceil(CARD_SIZE), false);
+                (self.old.len() - 1) as u32
+            }
+        };
+        self.places[r.0 as usize] = Some(Place::Old(n));
+        return n;
+    }
+
+    // cleans the card of the old place `n` unless an object it covers refers to a young one
+    fn update_card(&mut self, n: usize) {
+        let card = n / CARD_SIZE;
+        let end = ((card + 1) * CARD_SIZE).min(self.old.len());
+        self.cards[card] = self.old[card * CARD_SIZE..end].iter().flatten()
+            .any(|entry| entry.object.references().into_iter().any(|r| self.is_young(r)));
+    }
+
+    fn dirty_cards(&self) -> Vec<usize> {
+        return (0..self.cards.len()).filter(|&card| self.cards[card]).collect();
+    }
+
+    fn get_entry(&self, place: Place) -> Option<&Entry> {
+        return match place {
+            Place::Eden(n) => self.eden[n as usize].as_ref(),
+            Place::Survivor(n) => self.survivors[self.from][n as usize].as_ref(),
+            Place::Old(n) => self.old[n as usize].as_ref(),
+        };
+    }
+
+    fn get_entry_mut(&mut self, place: Place) -> Option<&mut Entry> {
+        return match place {
+            Place::Eden(n) => self.eden[n as usize].as_mut(),
+            Place::Survivor(n) => self.survivors[self.from][n as usize].as_mut(),
+            Place::Old(n) => self.old[n as usize].as_mut(),
+        };
+    }
+
+    /*
+    Marks the objects reachable from `pending`; a minor collection only
+    follows young objects, assuming every old one is live.
+     */
+    fn mark(&self, mut pending: Vec<ObjRef>, young_only: bool) -> Vec<bool> {
+        let mut marks = vec![false; self.places.len()];
+        while let Some(r) = pending.pop() {
+            let place = match self.place(r) {
+                Some(Place::Old(_)) if young_only => continue,
+                Some(place) => place,
+                None => continue,
+            };
+            if marks[r.0 as usize] {
+                continue;
+            }
+            marks[r.0 as usize] = true;
+            if let Some(entry) = self.get_entry(place) {
+                pending.extend(entry.object.references());
+            }
+        }
+        return marks;
+    }
+
+    // copies the live young objects out of eden and the from-space, returns whether they all could be
+    fn minor(&mut self, roots: &[ObjRef], stats: &mut GcStats) -> bool {
+        stats.minor_collections += 1;
+        let dirty = self.dirty_cards();
+        let mut pending = roots.to_vec();
+        for &card in &dirty {
+            let end = ((card + 1) * CARD_SIZE).min(self.old.len());
+            for entry in self.old[card * CARD_SIZE..end].iter().flatten() {
+                pending.extend(entry.object.references());
+            }
+        }
+        let marks = self.mark(pending, true);
+
+        let eden = mem::take(&mut self.eden);
+        let from = mem::take(&mut self.survivors[self.from]);
+        let mut to: Vec<Option<Entry>> = vec![];
+        let mut to_top = 0;
+        let mut promoted: Vec<u32> = vec![];
+        let mut stuck: Vec<Option<Entry>> = vec![];
+        let mut stuck_size = 0;
+        for mut entry in eden.into_iter().chain(from).flatten() {
+            if !marks[entry.r.0 as usize] {
+                self.free_ref(entry.r);
+                continue;
+            }
+            let (r, size) = (entry.r, entry.object.size());
+            entry.age = entry.age.saturating_add(1);
+            let fits_in_survivor = to_top + size <= self.survivor_size;
+            // old enough objects stay young if the old generation is full
+            if fits_in_survivor && (entry.age < self.tenuring_threshold || self.old_used + size > self.old_size) {
+                to_top += size;
+                to.push(Some(entry));
+                self.places[r.0 as usize] = Some(Place::Survivor((to.len() - 1) as u32));
+            } else if self.old_used + size <= self.old_size {
+                stats.bytes_promoted += size as u64;
+                promoted.push(self.put_old(entry));
+            } else {
+                stuck_size += size;
+                stuck.push(Some(entry));
+                self.places[r.0 as usize] = Some(Place::Eden((stuck.len() - 1) as u32));
+            }
+        }
+        self.from = 1 - self.from;
+        self.survivors[self.from] = to;
+        self.survivor_top = to_top;
+        self.eden = stuck;
+        self.eden_top = stuck_size;
+
+        for card in dirty {
+            self.update_card(card * CARD_SIZE);
+        }
+        for n in promoted {
+            self.update_card(n as usize);
+        }
+        return self.eden.is_empty();
+    }
+
+    // frees every unreachable object, young or old
+    fn full(&mut self, roots: &[ObjRef], stats: &mut GcStats) {
+        stats.full_collections += 1;
+        let marks = self.mark(roots.to_vec(), false);
+        for n in 0..self.old.len() {
+            let dead = match &self.old[n] {
+                Some(entry) => !marks[entry.r.0 as usize],
+                None => false,
+            };
+            if dead {
+                let entry = self.old[n].take().unwrap();
+                self.old_used -= entry.object.size();
+                self.free_ref(entry.r);
+                self.free_old.push(n as u32);
+            }
+        }
+
+        // the young objects that are left slide down to the bottom of their space
+        let eden = mem::take(&mut self.eden);
+        self.eden_top = 0;
+        for entry in eden.into_iter().flatten() {
+            if !marks[entry.r.0 as usize] {
+                self.free_ref(entry.r);
+                continue;
+            }
+            self.eden_top += entry.object.size();
+            self.places[entry.r.0 as usize] = Some(Place::Eden(self.eden.len() as u32));
+            self.eden.push(Some(entry));
+        }
+        let survivors = mem::take(&mut self.survivors[self.from]);
+        self.survivor_top = 0;
+        for entry in survivors.into_iter().flatten() {
+            if !marks[entry.r.0 as usize] {
+                self.free_ref(entry.r);
+                continue;
+            }
+            self.survivor_top += entry.object.size();
+            self.places[entry.r.0 as usize] = Some(Place::Survivor(self.survivors[self.from].len() as u32));
+            self.survivors[self.from].push(Some(entry));
+        }
+
+        for card in 0..self.cards.len() {
+            self.update_card(card * CARD_SIZE);
+        }
+    }
+}
+
+impl Collector for Generational {
+    fn alloc(&mut self, object: Object) -> Result<ObjRef, Object> {
+        let size = object.size();
+        if size > self.eden_size {
+            if self.old_used + size > self.old_size {
+                self.old_alloc_failed = true;
+                return Err(object);
+            }
+            let r = self.new_ref(Place::Old(0));
+            self.put_old(Entry { r, age: 0, object });
+            return Ok(r);
+        }
+        if self.eden_top + size > self.eden_size {
+            return Err(object);
+        }
+        self.eden_top += size;
+        self.eden.push(Some(Entry { r: ObjRef(0), age: 0, object }));
+        let r = self.new_ref(Place::Eden((self.eden.len() - 1) as u32));
+        self.eden.last_mut().unwrap().as_mut().unwrap().r = r;
+        return Ok(r);
+    }
+
+    fn get(&self, r: ObjRef) -> Option<&Object> {
+        return Some(&self.get_entry(self.place(r)?)?.object);
+    }
+
+    fn get_mut(&mut self, r: ObjRef) -> Option<&mut Object> {
+        let place = self.place(r)?;
+        return Some(&mut self.get_entry_mut(place)?.object);
+    }
+
+    /*
+    A minor collection, followed by a full one if it couldn't copy every
+    live object, if the old generation had no room for an object or if it
+    may not have room for the young generation at the next one.
+     */
+    fn collect(&mut self, roots: &[ObjRef], stats: &mut GcStats) {
+        let copied = self.minor(roots, stats);
+        if !copied || self.old_alloc_failed || self.old_size - self.old_used < self.eden_size + self.survivor_size {
+            self.full(roots, stats);
+        }
+        self.old_alloc_failed = false;
+    }
+
+    // the card of an old object is dirtied, young ones are traced anyway
+    fn write_barrier(&mut self, holder: ObjRef) {
+        if let Some(Place::Old(n)) = self.place(holder) {
+            self.cards[n as usize / CARD_SIZE] = true;
+        }
+    }
+
+    fn used(&self) -> usize {
+        return self.eden_top + self.survivor_top + self.old_used;
+    }
+
+    // a survivor space is always empty
+    fn capacity(&self) -> usize {
+        return self.eden_size + self.survivor_size + self.old_size;
+    }
+
+    fn len(&self) -> usize {
+        return self.places.len() - self.free_refs.len();
+    }
+}
@@ -0,0 +1,98 @@
+use crate::env::gc::{Collector, GcStats};
+use crate::env::object::{ObjRef, Object};
+
+/*
+A non-moving mark-sweep collector. Objects stay where they were
+allocated; marking follows the references from the roots and sweeping
+frees every object left unmarked, whose places are reused by later
+allocations.
+ */
+pub struct MarkSweep {
+    objects: Vec<Option<Object>>,
+    // the places of freed objects
+    free: Vec<u32>,
+    used: usize,
+    capacity: usize,
+}
+
+impl MarkSweep {
+    // a heap of at most `capacity` bytes
+    pub fn new(capacity: usize) -> MarkSweep {
+        return MarkSweep {
+            objects: vec![],
+            free: vec![],
+            used: 0,
+            capacity,
+        }
+    }
+
+    fn mark(&self, roots: &[ObjRef]) -> Vec<bool> {
+        let mut marks = vec![false; self.objects.len()];
+        let mut pending: Vec<ObjRef> = roots.to_vec();
+        while let Some(r) = pending.pop() {
+            let n = r.0 as usize;
+            if marks[n] {
+                continue;
+            }
+            marks[n] = true;
+            if let Some(object) = &self.objects[n] {
+                pending.extend(object.references());
+            }
+        }
+        return marks;
+    }
+}
+
+impl Collector for MarkSweep {
+    fn alloc(&mut self, object: Object) -> Result<ObjRef, Object> {
+        let size = object.size();
+        if self.used + size > self.capacity {
+            return Err(object);
+        }
+        self.used += size;
+        return match self.free.pop() {
+            Some(n) => {
+                self.objects[n as usize] = Some(object);
+                Ok(ObjRef(n))
+            }
+            None => {
+                self.objects.push(Some(object));
+                Ok(ObjRef((self.objects.len() - 1) as u32))
+            }
+        };
+    }
+
+    fn get(&self, r: ObjRef) -> Option<&Object> {
+        return self.objects.get(r.0 as usize)?.as_ref();
+    }
+
+    fn get_mut(&mut self, r: ObjRef) -> Option<&mut Object> {
+        return self.objects.get_mut(r.0 as usize)?.as_mut();
+    }
+
+    fn collect(&mut self, roots: &[ObjRef], stats: &mut GcStats) {
+        stats.full_collections += 1;
+        let marks = self.mark(roots);
+        for (n, marked) in marks.into_iter().enumerate() {
+            if marked {
+                continue;
+            }
+            if let Some(object) = self.objects[n].take() {
+                self.used -= object.size();
+                self.free.push(n as u32);
+            }
+        }
+    }
+
+    fn used(&self) -> usize {
+        return self.used;
+    }
+
+    fn capacity(&self) -> usize {
+        return self.capacity;
+    }
+
+    fn len(&self) -> usize {
+        return self.objects.len() - self.free.len();
+    }
+}
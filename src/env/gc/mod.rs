@@ -0,0 +1,94 @@
+pub mod generational;
+pub mod mark_sweep;
+
+use std::time::Duration;
+use crate::env::basic_env_elements::{Frame, Thread};
+use crate::env::call_site::MethodHandle;
+use crate::env::env::Environment;
+use crate::env::object::{ObjRef, Object};
+
+/*
+Garbage collection. A Collector owns the objects of the heap: it stores
+new ones, finds them by reference and frees the ones that can't be
+reached any more. What can be reached is traced from the roots, which are
+precise: only slots that hold a reference are followed. They are
+  - the local variables and operand stacks of every thread's frames,
+  - the static fields and the java.lang.Class objects of the loaded
+    classes, and the objects the call sites of their methods were linked to,
+  - the interned strings and the references native code holds, which
+    the Heap keeps itself.
+A collector can move objects as long as their references stay the same.
+
+Static fields are scanned at every collection, so only stores into objects
+need a write barrier: Heap::set_field_ref and Heap::set_element_ref tell the
+collector about them.
+ */
+pub trait Collector {
+    // stores a new object, or hands it back if there's no room for it until garbage is collected
+    fn alloc(&mut self, object: Object) -> Result<ObjRef, Object>;
+
+    fn get(&self, r: ObjRef) -> Option<&Object>;
+
+    fn get_mut(&mut self, r: ObjRef) -> Option<&mut Object>;
+
+    // frees every object that can't be reached from `roots`, counting the collections it made in `stats`
+    fn collect(&mut self, roots: &[ObjRef], stats: &mut GcStats);
+
+    // called once a reference has been stored into `holder`
+    fn write_barrier(&mut self, _holder: ObjRef) {}
+
+    // the bytes the objects on the heap take, and the most they may take
+    fn used(&self) -> usize;
+
+    fn capacity(&self) -> usize;
+
+    // the number of objects on the heap
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        return self.len() == 0;
+    }
+}
+
+// what the collections of a heap have done so far
+#[derive(Clone, Copy, Default, Debug)]
+pub struct GcStats {
+    // collections of the young generation only
+    pub minor_collections: u64,
+    // collections of the whole heap
+    pub full_collections: u64,
+    // the time the program was stopped for collections, in all and at most once
+    pub total_pause: Duration,
+    pub max_pause: Duration,
+    pub last_pause: Duration,
+    // the bytes of objects copied to the old generation
+    pub bytes_promoted: u64,
+    pub bytes_freed: u64,
+}
+
+pub fn frame_roots(frame: &Frame, roots: &mut Vec<ObjRef>) {
+    roots.extend(frame.local_vars.refs());
+    roots.extend(frame.operand_stack.refs());
+    roots.extend(frame.monitor);
+}
+
+pub fn thread_roots(thread: &Thread, roots: &mut Vec<ObjRef>) {
+    for frame in thread.stack.frames() {
+        frame_roots(frame, roots);
+    }
+}
+
+// the references held by the classes of `env`
+pub fn class_roots(env: &Environment, roots: &mut Vec<ObjRef>) {
+    for class in env.registry.classes() {
+        roots.extend(class.static_vars.borrow().refs());
+        roots.extend(class.mirror.get());
+        for method in &class.methods {
+            for site in method.call_sites.borrow().values() {
+                if let MethodHandle::Constant(value) = &site.target {
+                    roots.extend(value.val_ref);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::Instant;
+use crate::class::Class;
+use crate::env::call_site::MethodHandle;
+use crate::env::error::{JavaError, ThrowableInfo};
+use crate::env::gc::{Collector, GcStats};
+use crate::env::gc::generational::Generational;
+use crate::env::gc::mark_sweep::MarkSweep;
+use crate::env::object::*;
+
+// the heap size without -Xmx
+pub const DEFAULT_MAX_HEAP_SIZE: usize = 256 << 20;
+
+/*
+Parses the size of an -Xmx option, e.g. "512m": bytes, or kilobytes,
+megabytes or gigabytes with a k, m or g suffix in either case.
+ */
+pub fn parse_heap_size(size: &str) -> Option<usize> {
+    let (digits, unit) = match size.chars().last()?.to_ascii_lowercase() {
+        'k' => (&size[..size.len() - 1], 1 << 10),
+        'm' => (&size[..size.len() - 1], 1 << 20),
+        'g' => (&size[..size.len() - 1], 1 << 30),
+        _ => (size, 1),
+    };
+    return digits.parse::<usize>().ok()?.checked_mul(unit);
+}
+
+/*
+The Java heap: the objects are kept by a Collector, which decides how
+they are laid out and collected. The heap checks allocations against the
+collector's capacity, holds the roots that belong to no thread or class,
+interned strings, method types and native references, and keeps the statistics of the
+collections.
+
+Allocating here never collects garbage, as only the caller knows the
+roots; see ExecContext::alloc_object for allocation that does.
+ */
+pub struct Heap {
+    collector: Box<dyn Collector>,
+    // String objects by their value
+    interned: HashMap<String, ObjRef>,
+    // references held by native code, as JNI global references are
+    global_refs: Vec<Option<ObjRef>>,
+    // the state of the identity hash generator
+    hash_seed: u32,
+    // by the Throwables they are about
+    throwables: HashMap<ObjRef, ThrowableInfo>,
+    // MethodType objects by their descriptor
+    method_types: HashMap<String, ObjRef>,
+    // what MethodHandle objects invoke
+    method_handles: HashMap<ObjRef, MethodHandle>,
+    stats: GcStats,
+}
+
+impl Heap {
+    pub fn new(collector: Box<dyn Collector>) -> Heap {
+        return Heap {
+            collector,
+            interned: HashMap::new(),
+            global_refs: vec![],
+            hash_seed: 0x2545_f491,
+            throwables: HashMap::new(),
+            method_types: HashMap::new(),
+            method_handles: HashMap::new(),
+            stats: GcStats::default(),
+        }
+    }
+
+    // a mark-sweep heap of at most `max_size` bytes, what -Xmx sets
+    pub fn with_max_size(max_size: usize) -> Heap {
+        return Heap::new(Box::new(MarkSweep::new(max_size)));
+    }
+
+    // a heap of at most `max_size` bytes with a young and an old generation
+    pub fn generational(max_size: usize) -> Heap {
+        return Heap::new(Box::new(Generational::new(max_size)));
+    }
+
+    // a Marsaglia xor-shift, as HotSpot uses for identity hash codes; never 0
+    fn next_hash_code(&mut self) -> i32 {
+        let mut x = self.hash_seed;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.hash_seed = x;
+        return match (x & 0x7fff_ffff) as i32 {
+            0 => 0xbad,
+            hash_code => hash_code,
+        };
+    }
+
+    fn out_of_memory() -> JavaError {
+        return JavaError::new("java/lang/OutOfMemoryError", "Java heap space".to_string());
+    }
+
+    fn alloc(&mut self, class: &Rc<Class>, length: usize) -> Result<ObjRef, JavaError> {
+        // not even built if it can't fit now, a huge array would run the VM out of memory
+        if Body::size_of(class, length) > self.collector.capacity() - self.collector.used() {
+            return Err(Heap::out_of_memory());
+        }
+        let object = Object {
+            header: Header {
+                class: class.clone(),
+                hash_code: self.next_hash_code(),
+                monitor: Monitor::default(),
+            },
+            body: Body::new(class, length),
+        };
+        return self.collector.alloc(object).map_err(|_| Heap::out_of_memory());
+    }
+
+    // a new instance of `class` with its fields zeroed, the class has been checked to be instantiable
+    pub fn alloc_object(&mut self, class: &Rc<Class>) -> Result<ObjRef, JavaError> {
+        return self.alloc(class, 0);
+    }
+
+    // a new array of the array class `class` with zeroed elements
+    pub fn alloc_array(&mut self, class: &Rc<Class>, length: usize) -> Result<ObjRef, JavaError> {
+        return self.alloc(class, length);
+    }
+
+    pub fn get(&self, r: ObjRef) -> &Object {
+        return self.collector.get(r).expect("dangling reference");
+    }
+
+    pub fn get_mut(&mut self, r: ObjRef) -> &mut Object {
+        return self.collector.get_mut(r).expect("dangling reference");
+    }
+
+    /*
+    Stores a reference in a field or an element of an object. References
+    have to be stored into objects through these, so the collector sees
+    the store. The verifier doesn't check types, so the holder may turn
+    out to be the wrong kind of object.
+     */
+    pub fn set_field_ref(&mut self, holder: ObjRef, slot: usize, value: Option<ObjRef>) -> Result<(), JavaError> {
+        let object = self.get_mut(holder);
+        let class_name = object.class().name.replace('/', ".");
+        let fields = object.fields_mut().ok_or_else(|| {
+            JavaError::new("java/lang/IncompatibleClassChangeError", format!("{} has no fields", class_name))
+        })?;
+        fields.set_ref(slot, value)?;
+        self.collector.write_barrier(holder);
+        return Ok(());
+    }
+
+    pub fn set_element_ref(&mut self, array: ObjRef, index: usize, value: Option<ObjRef>) -> Result<(), JavaError> {
+        let object = self.get_mut(array);
+        let class_name = object.class().name.replace('/', ".");
+        let length = object.array_length().unwrap_or(0);
+        match &mut object.body {
+            Body::Refs(elements) => match elements.get_mut(index) {
+                Some(element) => *element = value,
+                None => {
+                    return Err(JavaError::new("java/lang/ArrayIndexOutOfBoundsException",
+                        format!("Index {} out of bounds for length {}", index, length)));
+                }
+            },
+            _ => {
+                return Err(JavaError::new("java/lang/IncompatibleClassChangeError",
+                    format!("{} is not an array of references", class_name)));
+            }
+        }
+        self.collector.write_barrier(array);
+        return Ok(());
+    }
+
+    // frees the objects that can't be reached from `roots` or the heap's own roots
+    pub fn collect(&mut self, mut roots: Vec<ObjRef>) {
+        roots.extend(self.interned.values().copied());
+        roots.extend(self.method_types.values().copied());
+        roots.extend(self.global_refs.iter().flatten().copied());
+        let used = self.collector.used();
+        let start = Instant::now();
+        self.collector.collect(&roots, &mut self.stats);
+        // before a freed reference can be handed out again
+        let collector = &self.collector;
+        self.throwables.retain(|r, _| collector.get(*r).is_some());
+        self.method_handles.retain(|r, _| collector.get(*r).is_some());
+        let pause = start.elapsed();
+        self.stats.total_pause += pause;
+        self.stats.max_pause = self.stats.max_pause.max(pause);
+        self.stats.last_pause = pause;
+        self.stats.bytes_freed += (used - self.collector.used()) as u64;
+    }
+
+    pub fn stats(&self) -> &GcStats {
+        return &self.stats;
+    }
+
+    // keeps an object alive until the reference is deleted, returns its id
+    pub fn new_global_ref(&mut self, r: ObjRef) -> usize {
+        if let Some(n) = self.global_refs.iter().position(|g| g.is_none()) {
+            self.global_refs[n] = Some(r);
+            return n;
+        }
+        self.global_refs.push(Some(r));
+        return self.global_refs.len() - 1;
+    }
+
+    pub fn delete_global_ref(&mut self, id: usize) {
+        if let Some(g) = self.global_refs.get_mut(id) {
+            *g = None;
+        }
+    }
+
+    pub fn throwable_info(&self, throwable: ObjRef) -> Option<&ThrowableInfo> {
+        return self.throwables.get(&throwable);
+    }
+
+    pub fn set_throwable_info(&mut self, throwable: ObjRef, info: ThrowableInfo) {
+        self.throwables.insert(throwable, info);
+    }
+
+    // the String object interned for `value`, if any
+    pub fn interned(&self, value: &str) -> Option<ObjRef> {
+        return self.interned.get(value).copied();
+    }
+
+    pub fn intern(&mut self, value: &str, string: ObjRef) {
+        self.interned.insert(value.to_string(), string);
+    }
+
+    // the MethodType object of `descriptor`, if there is one yet
+    pub fn method_type(&self, descriptor: &str) -> Option<ObjRef> {
+        return self.method_types.get(descriptor).copied();
+    }
+
+    pub fn set_method_type(&mut self, descriptor: &str, method_type: ObjRef) {
+        self.method_types.insert(descriptor.to_string(), method_type);
+    }
+
+    pub fn method_handle(&self, handle: ObjRef) -> Option<&MethodHandle> {
+        return self.method_handles.get(&handle);
+    }
+
+    pub fn set_method_handle(&mut self, handle: ObjRef, target: MethodHandle) {
+        self.method_handles.insert(handle, target);
+    }
+
+    // the bytes the objects take, and the most they may take
+    pub fn used(&self) -> usize {
+        return self.collector.used();
+    }
+
+    pub fn max_size(&self) -> usize {
+        return self.collector.capacity();
+    }
+
+    // the number of objects on the heap
+    pub fn len(&self) -> usize {
+        return self.collector.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.len() == 0;
+    }
+}
+
+impl Default for Heap {
+    fn default() -> Heap {
+        return Heap::with_max_size(DEFAULT_MAX_HEAP_SIZE);
+    }
+}
@@ -1,8 +1,179 @@
+use std::rc::Rc;
+use crate::class::Class;
+use crate::class::class::InitState;
+use crate::class::initializer::initialize_class;
+use crate::class::member::Method;
+use crate::class::runtime_constant_pool::RuntimeConstantPool;
+use crate::env::basic_env_elements::{Frame, LocalVars, OperandStack, Slot, Thread};
 use crate::env::byte_code_reader::ByteCodeReader;
-use crate::env::basic_env_elements::Frame;
+use crate::env::env::Environment;
+use crate::env::error::JavaError;
+use crate::env::gc::{class_roots, frame_roots, thread_roots};
+use crate::env::mirror::class_mirror;
+use crate::env::object::ObjRef;
+use crate::env::stack_trace::StackTraceElement;
+use crate::env::string::{intern, new_string};
+use crate::int::interpreter::{clinit_runner, invoke};
+
+// what the interpreter should do once an instruction has executed
+pub enum Flow {
+    // go on with the next instruction
+    Next,
+    // jump by an offset from the start of the branch instruction
+    Branch(i32),
+    // call a method, its arguments are on top of the operand stack
+    Invoke(Rc<Class>, Rc<Method>),
+    // leave the current method, its return value (if any) is on top of the operand stack
+    Return,
+    // raise an exception in the current method
+    Throw(JavaError),
+}
+
+/*
+The value of a Result in an instruction's `execute`, or a throw of its
+error from the instruction: `?` can't be used there as it returns a Flow.
+ */
+macro_rules! or_throw {
+    ($result:expr) => {
+        match $result {
+            Ok(v) => v,
+            Err(e) => return Flow::Throw(e),
+        }
+    };
+}
+pub(crate) use or_throw;
+
+/*
+What an instruction gets to work with. The interpreter takes the current
+frame off the thread's stack while the instruction runs, so `thread` only
+holds the callers, and it's free to run other code on the thread, e.g. a
+class initializer.
+ */
+pub struct ExecContext<'a> {
+    pub env: &'a mut Environment,
+    pub thread: &'a mut Thread,
+    pub frame: &'a mut Frame,
+    // the start of the executing instruction, `frame.next_pc` is the one after it
+    pub pc: usize,
+}
+
+impl<'a> ExecContext<'a> {
+    pub fn class(&self) -> &Rc<Class> {
+        return &self.frame.class;
+    }
+
+    pub fn method(&self) -> &Rc<Method> {
+        return &self.frame.method;
+    }
+
+    pub fn constant_pool(&self) -> &RuntimeConstantPool {
+        return &self.frame.class.constant_pool;
+    }
+
+    /*
+    Runs `f` with the current frame back on the thread's stack, for code
+    that runs more Java code on the thread: the frame has to be seen by the
+    garbage collector and in stack traces meanwhile.
+     */
+    fn with_frame_on_stack<R>(&mut self, f: impl FnOnce(&mut Environment, &mut Thread) -> R) -> R {
+        let placeholder = Frame {
+            local_vars: LocalVars::new(0),
+            operand_stack: OperandStack::new(0),
+            class: self.frame.class.clone(),
+            method: self.frame.method.clone(),
+            pc: self.frame.pc,
+            next_pc: self.frame.next_pc,
+            monitor: None,
+        };
+        let frame = std::mem::replace(self.frame, placeholder);
+        self.thread.stack.push(frame);
+        let result = f(self.env, self.thread);
+        *self.frame = self.thread.stack.pop().unwrap();
+        return result;
+    }
+
+    // the frames of the thread, the current one first
+    pub fn frames(&self) -> impl Iterator<Item = &Frame> {
+        return std::iter::once(&*self.frame).chain(self.thread.stack.frames().rev());
+    }
+
+    // the Java stack of the thread, where the instruction executes first
+    pub fn stack_trace(&self) -> Vec<StackTraceElement> {
+        return self.frames().map(StackTraceElement::of_frame).collect();
+    }
+
+    // initializes a class before its first active use, running <clinit> on this thread
+    pub fn initialize(&mut self, class: &Rc<Class>) -> Result<(), JavaError> {
+        if *class.init_state.borrow() == InitState::Initialized {
+            return Ok(());
+        }
+        // the only thread there is can't be blocked by another one
+        self.with_frame_on_stack(|env, thread| initialize_class(env, class, thread.id, &mut clinit_runner(thread)))?;
+        return Ok(());
+    }
+
+    // runs a method to completion on this thread, e.g. the constructor a method handle invokes
+    pub fn run(&mut self, class: Rc<Class>, method: Rc<Method>, args: Vec<Slot>) -> Result<Vec<Slot>, JavaError> {
+        return self.with_frame_on_stack(|env, thread| invoke(env, thread, class, method, args));
+    }
+
+    // collects the garbage with the roots of this thread, the current frame included
+    pub fn collect_garbage(&mut self) {
+        let mut roots: Vec<ObjRef> = vec![];
+        class_roots(self.env, &mut roots);
+        thread_roots(self.thread, &mut roots);
+        frame_roots(self.frame, &mut roots);
+        self.env.heap.collect(roots);
+    }
+
+    // a new instance of `class`, collecting garbage first if the heap is full
+    pub fn alloc_object(&mut self, class: &Rc<Class>) -> Result<ObjRef, JavaError> {
+        if let Ok(r) = self.env.heap.alloc_object(class) {
+            return Ok(r);
+        }
+        self.collect_garbage();
+        return self.env.heap.alloc_object(class);
+    }
+
+    // a new array of the array class `class`, collecting garbage first if the heap is full
+    pub fn alloc_array(&mut self, class: &Rc<Class>, length: usize) -> Result<ObjRef, JavaError> {
+        if let Ok(r) = self.env.heap.alloc_array(class, length) {
+            return Ok(r);
+        }
+        self.collect_garbage();
+        return self.env.heap.alloc_array(class, length);
+    }
+
+    // the interned String for `value`, collecting garbage first if the heap is full
+    pub fn intern_string(&mut self, value: &str) -> Result<ObjRef, JavaError> {
+        if let Ok(r) = intern(self.env, value) {
+            return Ok(r);
+        }
+        self.collect_garbage();
+        return intern(self.env, value);
+    }
+
+    // a new String holding `value`, collecting garbage first if the heap is full
+    pub fn new_string(&mut self, value: &str) -> Result<ObjRef, JavaError> {
+        if let Ok(r) = new_string(self.env, value) {
+            return Ok(r);
+        }
+        self.collect_garbage();
+        return new_string(self.env, value);
+    }
+
+    // the java.lang.Class object of `class`, collecting garbage first if the heap is full
+    pub fn class_mirror(&mut self, class: &Rc<Class>) -> Result<ObjRef, JavaError> {
+        if let Ok(r) = class_mirror(self.env, class) {
+            return Ok(r);
+        }
+        self.collect_garbage();
+        return class_mirror(self.env, class);
+    }
+}
 
 pub trait Instruction {
     fn fetch_operands(&mut self, reader: &mut ByteCodeReader);
 
-    fn execute(&mut self, frame: &mut Frame);
-}
\ No newline at end of file
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow;
+}
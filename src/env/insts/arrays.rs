@@ -0,0 +1,500 @@
+use std::rc::Rc;
+use crate::class::Class;
+use crate::class::linker::resolve_class;
+use crate::class::registry::LoaderId;
+use crate::env::basic_env_elements::OperandStack;
+use crate::env::inst::{ExecContext, Instruction, Flow, or_throw};
+use crate::env::byte_code_reader::ByteCodeReader;
+use crate::env::error::JavaError;
+use crate::env::object::{Body, ObjRef, Object};
+
+/*
+Arrays are objects whose body holds the elements, typed by the element
+type of their class: booleans and bytes share Body::Bytes, which is why
+baload and bastore work on both. The element instructions check the
+array for null and the index against its length before touching it.
+ */
+
+fn flow_of(result: Result<(), JavaError>) -> Flow {
+    return match result {
+        Ok(()) => Flow::Next,
+        Err(e) => Flow::Throw(e),
+    };
+}
+
+fn array_class(ctx: &mut ExecContext, loader: LoaderId, name: &str) -> Result<Rc<Class>, JavaError> {
+    return ctx.env.load_class_from(loader, name)?
+        .ok_or_else(|| JavaError::new("java/lang/NoClassDefFoundError", name.to_string()));
+}
+
+fn check_length(count: i32) -> Result<usize, JavaError> {
+    if count < 0 {
+        return Err(JavaError::new("java/lang/NegativeArraySizeException", count.to_string()));
+    }
+    return Ok(count as usize);
+}
+
+// a body of another type than the instruction expects, which the verifier would have rejected
+fn wrong_type(kind: &str) -> JavaError {
+    return JavaError::new("java/lang/VerifyError", format!("Bad type in {} array access", kind));
+}
+
+// the array and index on top of the operand stack, checked; `access` and `kind` are for the messages
+fn element(ctx: &mut ExecContext, access: &str, kind: &str) -> Result<(ObjRef, usize), JavaError> {
+    let index = ctx.frame.operand_stack.pop_i32()?;
+    let array = ctx.frame.operand_stack.pop_ref()?.ok_or_else(|| {
+        JavaError::new("java/lang/NullPointerException", format!("Cannot {} {} array because the array is null", access, kind))
+    })?;
+    let length = ctx.env.heap.get(array).array_length().ok_or_else(|| wrong_type(kind))?;
+    if index < 0 || index as usize >= length {
+        return Err(JavaError::new("java/lang/ArrayIndexOutOfBoundsException",
+            format!("Index {} out of bounds for length {}", index, length)));
+    }
+    return Ok((array, index as usize));
+}
+
+// pops the array and index and pushes the element `get` finds in the body
+fn load(ctx: &mut ExecContext, kind: &str, get: fn(&Body, usize, &mut OperandStack) -> Option<Result<(), JavaError>>) -> Result<(), JavaError> {
+    let (array, index) = element(ctx, "load from", kind)?;
+    return get(&ctx.env.heap.get(array).body, index, &mut ctx.frame.operand_stack).ok_or_else(|| wrong_type(kind))?;
+}
+
+// pops the array and index below the value, which `set` has been given already
+fn store(ctx: &mut ExecContext, kind: &str, set: impl FnOnce(&mut Object, usize) -> bool) -> Result<(), JavaError> {
+    let (array, index) = element(ctx, "store to", kind)?;
+    if !set(ctx.env.heap.get_mut(array), index) {
+        return Err(wrong_type(kind));
+    }
+    return Ok(());
+}
+
+/*
+A reference may only be stored into an array whose element type it is
+assignable to, as arrays are covariant: an Object[] may be a String[].
+ */
+fn aastore(ctx: &mut ExecContext) -> Result<(), JavaError> {
+    let value = ctx.frame.operand_stack.pop_ref()?;
+    let (array, index) = element(ctx, "store to", "object")?;
+    if let Some(value) = value {
+        let component = ctx.env.heap.get(array).class().component_class.clone().ok_or_else(|| wrong_type("object"))?;
+        let class = ctx.env.heap.get(value).class();
+        if !class.is_assignable_to(&component) {
+            return Err(JavaError::new("java/lang/ArrayStoreException", class.name.replace('/', ".")));
+        }
+    }
+    return ctx.env.heap.set_element_ref(array, index, value);
+}
+
+pub struct IALoad {
+}
+
+impl Instruction for IALoad {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        return flow_of(load(ctx, "int", |body, n, stack| match body {
+            Body::Ints(v) => Some(stack.push_i32(v[n])),
+            _ => None,
+        }));
+    }
+}
+
+pub struct LALoad {
+}
+
+impl Instruction for LALoad {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        return flow_of(load(ctx, "long", |body, n, stack| match body {
+            Body::Longs(v) => Some(stack.push_i64(v[n])),
+            _ => None,
+        }));
+    }
+}
+
+pub struct FALoad {
+}
+
+impl Instruction for FALoad {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        return flow_of(load(ctx, "float", |body, n, stack| match body {
+            Body::Floats(v) => Some(stack.push_f32(v[n])),
+            _ => None,
+        }));
+    }
+}
+
+pub struct DALoad {
+}
+
+impl Instruction for DALoad {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        return flow_of(load(ctx, "double", |body, n, stack| match body {
+            Body::Doubles(v) => Some(stack.push_f64(v[n])),
+            _ => None,
+        }));
+    }
+}
+
+pub struct AALoad {
+}
+
+impl Instruction for AALoad {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        return flow_of(load(ctx, "object", |body, n, stack| match body {
+            Body::Refs(v) => Some(stack.push_ref(v[n])),
+            _ => None,
+        }));
+    }
+}
+
+// bytes are sign-extended, booleans are 0 or 1 anyway
+pub struct BALoad {
+}
+
+impl Instruction for BALoad {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        return flow_of(load(ctx, "byte/boolean", |body, n, stack| match body {
+            Body::Bytes(v) => Some(stack.push_i32(v[n] as i32)),
+            _ => None,
+        }));
+    }
+}
+
+// chars are zero-extended
+pub struct CALoad {
+}
+
+impl Instruction for CALoad {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        return flow_of(load(ctx, "char", |body, n, stack| match body {
+            Body::Chars(v) => Some(stack.push_i32(v[n] as i32)),
+            _ => None,
+        }));
+    }
+}
+
+pub struct SALoad {
+}
+
+impl Instruction for SALoad {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        return flow_of(load(ctx, "short", |body, n, stack| match body {
+            Body::Shorts(v) => Some(stack.push_i32(v[n] as i32)),
+            _ => None,
+        }));
+    }
+}
+
+pub struct IAStore {
+}
+
+impl Instruction for IAStore {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let value = or_throw!(ctx.frame.operand_stack.pop_i32());
+        return flow_of(store(ctx, "int", |object, n| match &mut object.body {
+            Body::Ints(v) => { v[n] = value; true }
+            _ => false,
+        }));
+    }
+}
+
+pub struct LAStore {
+}
+
+impl Instruction for LAStore {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let value = or_throw!(ctx.frame.operand_stack.pop_i64());
+        return flow_of(store(ctx, "long", |object, n| match &mut object.body {
+            Body::Longs(v) => { v[n] = value; true }
+            _ => false,
+        }));
+    }
+}
+
+pub struct FAStore {
+}
+
+impl Instruction for FAStore {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let value = or_throw!(ctx.frame.operand_stack.pop_f32());
+        return flow_of(store(ctx, "float", |object, n| match &mut object.body {
+            Body::Floats(v) => { v[n] = value; true }
+            _ => false,
+        }));
+    }
+}
+
+pub struct DAStore {
+}
+
+impl Instruction for DAStore {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let value = or_throw!(ctx.frame.operand_stack.pop_f64());
+        return flow_of(store(ctx, "double", |object, n| match &mut object.body {
+            Body::Doubles(v) => { v[n] = value; true }
+            _ => false,
+        }));
+    }
+}
+
+pub struct AAStore {
+}
+
+impl Instruction for AAStore {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        return flow_of(aastore(ctx));
+    }
+}
+
+// the int is truncated to a byte, or to its lowest bit for a boolean array
+pub struct BAStore {
+}
+
+impl Instruction for BAStore {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let value = or_throw!(ctx.frame.operand_stack.pop_i32());
+        return flow_of(store(ctx, "byte/boolean", |object, n| {
+            let value = match object.class().name.as_str() {
+                "[Z" => value & 1,
+                _ => value,
+            };
+            match &mut object.body {
+                Body::Bytes(v) => { v[n] = value as i8; true }
+                _ => false,
+            }
+        }));
+    }
+}
+
+pub struct CAStore {
+}
+
+impl Instruction for CAStore {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let value = or_throw!(ctx.frame.operand_stack.pop_i32());
+        return flow_of(store(ctx, "char", |object, n| match &mut object.body {
+            Body::Chars(v) => { v[n] = value as u16; true }
+            _ => false,
+        }));
+    }
+}
+
+pub struct SAStore {
+}
+
+impl Instruction for SAStore {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let value = or_throw!(ctx.frame.operand_stack.pop_i32());
+        return flow_of(store(ctx, "short", |object, n| match &mut object.body {
+            Body::Shorts(v) => { v[n] = value as i16; true }
+            _ => false,
+        }));
+    }
+}
+
+// the array classes newarray creates, by its atype operand
+fn primitive_array_name(atype: u8) -> Option<&'static str> {
+    return match atype {
+        4 => Some("[Z"),
+        5 => Some("[C"),
+        6 => Some("[F"),
+        7 => Some("[D"),
+        8 => Some("[B"),
+        9 => Some("[S"),
+        10 => Some("[I"),
+        11 => Some("[J"),
+        _ => None,
+    };
+}
+
+fn new_array(ctx: &mut ExecContext, atype: u8) -> Result<(), JavaError> {
+    let name = primitive_array_name(atype)
+        .ok_or_else(|| JavaError::new("java/lang/VerifyError", format!("Bad array type {} for newarray", atype)))?;
+    let class = array_class(ctx, LoaderId::BOOTSTRAP, name)?;
+    let length = check_length(ctx.frame.operand_stack.pop_i32()?)?;
+    let array = ctx.alloc_array(&class, length)?;
+    ctx.frame.operand_stack.push_ref(Some(array))?;
+    return Ok(());
+}
+
+fn anew_array(ctx: &mut ExecContext, index: u16) -> Result<(), JavaError> {
+    let class = ctx.class().clone();
+    let component = resolve_class(ctx.env, &class, index)?;
+    let class = array_class(ctx, class.loader, &component.array_class_name())?;
+    let length = check_length(ctx.frame.operand_stack.pop_i32()?)?;
+    let array = ctx.alloc_array(&class, length)?;
+    ctx.frame.operand_stack.push_ref(Some(array))?;
+    return Ok(());
+}
+
+/*
+The arrays of the inner dimensions are created before they are stored in
+the outer one, so the outer array is held by a global reference meanwhile
+in case an allocation collects garbage.
+ */
+fn new_multi_array(ctx: &mut ExecContext, class: &Rc<Class>, lengths: &[usize]) -> Result<ObjRef, JavaError> {
+    let array = ctx.alloc_array(class, lengths[0])?;
+    if lengths.len() == 1 {
+        return Ok(array);
+    }
+    let component = class.component_class.clone().unwrap();
+    let global = ctx.env.heap.new_global_ref(array);
+    for n in 0..lengths[0] {
+        let stored = new_multi_array(ctx, &component, &lengths[1..])
+            .and_then(|inner| ctx.env.heap.set_element_ref(array, n, Some(inner)));
+        if let Err(e) = stored {
+            ctx.env.heap.delete_global_ref(global);
+            return Err(e);
+        }
+    }
+    ctx.env.heap.delete_global_ref(global);
+    return Ok(array);
+}
+
+fn multi_anew_array(ctx: &mut ExecContext, index: u16, dimensions: u8) -> Result<(), JavaError> {
+    let class = ctx.class().clone();
+    let class = resolve_class(ctx.env, &class, index)?;
+    let depth = class.name.bytes().take_while(|c| *c == b'[').count();
+    if dimensions == 0 || dimensions as usize > depth {
+        return Err(JavaError::new("java/lang/VerifyError",
+            format!("Bad dimensions {} of multianewarray for {}", dimensions, class.name)));
+    }
+    let mut counts = vec![0; dimensions as usize];
+    for count in counts.iter_mut().rev() {
+        *count = ctx.frame.operand_stack.pop_i32()?;
+    }
+    let lengths = counts.into_iter().map(check_length).collect::<Result<Vec<usize>, JavaError>>()?;
+    let array = new_multi_array(ctx, &class, &lengths)?;
+    ctx.frame.operand_stack.push_ref(Some(array))?;
+    return Ok(());
+}
+
+pub struct NewArray {
+    pub(crate) atype: u8,
+}
+
+impl Instruction for NewArray {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.atype = reader.read_u8();
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        return flow_of(new_array(ctx, self.atype));
+    }
+}
+
+pub struct ANewArray {
+    pub(crate) index: u16,
+}
+
+impl Instruction for ANewArray {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.index = reader.read_u16();
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        return flow_of(anew_array(ctx, self.index));
+    }
+}
+
+pub struct ArrayLength {
+}
+
+impl Instruction for ArrayLength {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let array = match or_throw!(ctx.frame.operand_stack.pop_ref()) {
+            Some(array) => array,
+            None => return Flow::Throw(JavaError::new("java/lang/NullPointerException",
+                "Cannot read the array length because the array is null".to_string())),
+        };
+        return match ctx.env.heap.get(array).array_length() {
+            Some(length) => {
+                or_throw!(ctx.frame.operand_stack.push_i32(length as i32));
+                Flow::Next
+            }
+            None => Flow::Throw(wrong_type("any")),
+        };
+    }
+}
+
+pub struct MultiANewArray {
+    pub(crate) index: u16,
+    pub(crate) dimensions: u8,
+}
+
+impl Instruction for MultiANewArray {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.index = reader.read_u16();
+        self.dimensions = reader.read_u8();
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        return flow_of(multi_anew_array(ctx, self.index, self.dimensions));
+    }
+}
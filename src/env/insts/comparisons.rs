@@ -0,0 +1,507 @@
+use crate::env::inst::{ExecContext, Instruction, Flow, or_throw};
+use crate::env::basic_env_elements::Slot;
+use crate::env::byte_code_reader::ByteCodeReader;
+
+/*
+Branch offsets are relative to the start of the branch instruction, which
+is how Flow::Branch hands them to the interpreter.
+ */
+
+fn branch_if(cond: bool, offset: i32) -> Flow {
+    if cond {
+        return Flow::Branch(offset);
+    }
+    return Flow::Next;
+}
+
+fn same_ref(a: &Slot, b: &Slot) -> bool {
+    return a.val_ref == b.val_ref;
+}
+
+pub struct LCmp {
+}
+
+impl Instruction for LCmp {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_i64());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_i64());
+        or_throw!(ctx.frame.operand_stack.push_i32(v1.cmp(&v2) as i32));
+        return Flow::Next;
+    }
+}
+
+/*
+The float comparisons differ only in what a NaN operand gives: -1 for the
+*l forms, 1 for the *g forms, so that either way the following branch
+isn't taken.
+ */
+fn fcmp(v1: f64, v2: f64, nan: i32) -> i32 {
+    return match v1.partial_cmp(&v2) {
+        Some(ordering) => ordering as i32,
+        None => nan,
+    };
+}
+
+pub struct FCmpL {
+}
+
+impl Instruction for FCmpL {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_f32());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_f32());
+        or_throw!(ctx.frame.operand_stack.push_i32(fcmp(v1 as f64, v2 as f64, -1)));
+        return Flow::Next;
+    }
+}
+
+pub struct FCmpG {
+}
+
+impl Instruction for FCmpG {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_f32());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_f32());
+        or_throw!(ctx.frame.operand_stack.push_i32(fcmp(v1 as f64, v2 as f64, 1)));
+        return Flow::Next;
+    }
+}
+
+pub struct DCmpL {
+}
+
+impl Instruction for DCmpL {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_f64());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_f64());
+        or_throw!(ctx.frame.operand_stack.push_i32(fcmp(v1, v2, -1)));
+        return Flow::Next;
+    }
+}
+
+pub struct DCmpG {
+}
+
+impl Instruction for DCmpG {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_f64());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_f64());
+        or_throw!(ctx.frame.operand_stack.push_i32(fcmp(v1, v2, 1)));
+        return Flow::Next;
+    }
+}
+
+pub struct IfEq {
+    pub(crate) offset: i32,
+}
+
+impl Instruction for IfEq {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.offset = reader.read_i16() as i32;
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v = or_throw!(ctx.frame.operand_stack.pop_i32());
+        return branch_if(v == 0, self.offset);
+    }
+}
+
+pub struct IfNe {
+    pub(crate) offset: i32,
+}
+
+impl Instruction for IfNe {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.offset = reader.read_i16() as i32;
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v = or_throw!(ctx.frame.operand_stack.pop_i32());
+        return branch_if(v != 0, self.offset);
+    }
+}
+
+pub struct IfLt {
+    pub(crate) offset: i32,
+}
+
+impl Instruction for IfLt {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.offset = reader.read_i16() as i32;
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v = or_throw!(ctx.frame.operand_stack.pop_i32());
+        return branch_if(v < 0, self.offset);
+    }
+}
+
+pub struct IfGe {
+    pub(crate) offset: i32,
+}
+
+impl Instruction for IfGe {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.offset = reader.read_i16() as i32;
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v = or_throw!(ctx.frame.operand_stack.pop_i32());
+        return branch_if(v >= 0, self.offset);
+    }
+}
+
+pub struct IfGt {
+    pub(crate) offset: i32,
+}
+
+impl Instruction for IfGt {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.offset = reader.read_i16() as i32;
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v = or_throw!(ctx.frame.operand_stack.pop_i32());
+        return branch_if(v > 0, self.offset);
+    }
+}
+
+pub struct IfLe {
+    pub(crate) offset: i32,
+}
+
+impl Instruction for IfLe {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.offset = reader.read_i16() as i32;
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v = or_throw!(ctx.frame.operand_stack.pop_i32());
+        return branch_if(v <= 0, self.offset);
+    }
+}
+
+pub struct If_ICmpEq {
+    pub(crate) offset: i32,
+}
+
+impl Instruction for If_ICmpEq {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.offset = reader.read_i16() as i32;
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        return branch_if(v1 == v2, self.offset);
+    }
+}
+
+pub struct If_ICmpNe {
+    pub(crate) offset: i32,
+}
+
+impl Instruction for If_ICmpNe {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.offset = reader.read_i16() as i32;
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        return branch_if(v1 != v2, self.offset);
+    }
+}
+
+pub struct If_ICmpLt {
+    pub(crate) offset: i32,
+}
+
+impl Instruction for If_ICmpLt {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.offset = reader.read_i16() as i32;
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        return branch_if(v1 < v2, self.offset);
+    }
+}
+
+pub struct If_ICmpGe {
+    pub(crate) offset: i32,
+}
+
+impl Instruction for If_ICmpGe {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.offset = reader.read_i16() as i32;
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        return branch_if(v1 >= v2, self.offset);
+    }
+}
+
+pub struct If_ICmpGt {
+    pub(crate) offset: i32,
+}
+
+impl Instruction for If_ICmpGt {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.offset = reader.read_i16() as i32;
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        return branch_if(v1 > v2, self.offset);
+    }
+}
+
+pub struct If_ICmpLe {
+    pub(crate) offset: i32,
+}
+
+impl Instruction for If_ICmpLe {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.offset = reader.read_i16() as i32;
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        return branch_if(v1 <= v2, self.offset);
+    }
+}
+
+pub struct If_ACmpEq {
+    pub(crate) offset: i32,
+}
+
+impl Instruction for If_ACmpEq {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.offset = reader.read_i16() as i32;
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_slot());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_slot());
+        return branch_if(same_ref(&v1, &v2), self.offset);
+    }
+}
+
+pub struct If_ACmpNe {
+    pub(crate) offset: i32,
+}
+
+impl Instruction for If_ACmpNe {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.offset = reader.read_i16() as i32;
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_slot());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_slot());
+        return branch_if(!same_ref(&v1, &v2), self.offset);
+    }
+}
+
+pub struct IfNull {
+    pub(crate) offset: i32,
+}
+
+impl Instruction for IfNull {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.offset = reader.read_i16() as i32;
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v = or_throw!(ctx.frame.operand_stack.pop_slot());
+        return branch_if(v.val_ref.is_none(), self.offset);
+    }
+}
+
+pub struct IfNonNull {
+    pub(crate) offset: i32,
+}
+
+impl Instruction for IfNonNull {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.offset = reader.read_i16() as i32;
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v = or_throw!(ctx.frame.operand_stack.pop_slot());
+        return branch_if(v.val_ref.is_some(), self.offset);
+    }
+}
+
+pub struct Goto {
+    pub(crate) offset: i32,
+}
+
+impl Instruction for Goto {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.offset = reader.read_i16() as i32;
+    }
+
+    fn execute(&mut self, _ctx: &mut ExecContext) -> Flow {
+        return Flow::Branch(self.offset);
+    }
+}
+
+pub struct Goto_W {
+    pub(crate) offset: i32,
+}
+
+impl Instruction for Goto_W {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.offset = reader.read_i32();
+    }
+
+    fn execute(&mut self, _ctx: &mut ExecContext) -> Flow {
+        return Flow::Branch(self.offset);
+    }
+}
+
+/*
+jsr and ret, for finally blocks in class files older than version 51. The
+return address pushed by jsr is the pc following it, ret jumps back to it
+from a local variable.
+ */
+pub struct Jsr {
+    pub(crate) offset: i32,
+}
+
+impl Instruction for Jsr {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.offset = reader.read_i16() as i32;
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(ctx.frame.operand_stack.push_i32(ctx.frame.next_pc as i32));
+        return Flow::Branch(self.offset);
+    }
+}
+
+pub struct Jsr_W {
+    pub(crate) offset: i32,
+}
+
+impl Instruction for Jsr_W {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.offset = reader.read_i32();
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(ctx.frame.operand_stack.push_i32(ctx.frame.next_pc as i32));
+        return Flow::Branch(self.offset);
+    }
+}
+
+pub struct Ret {
+    pub(crate) index: u16,
+}
+
+impl Instruction for Ret {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.index = reader.read_u8() as u16;
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        ctx.frame.next_pc = or_throw!(ctx.frame.local_vars.get_i32(self.index as usize)) as usize;
+        return Flow::Next;
+    }
+}
+
+// the number of 4 byte words left in the code, to bound tables read from it
+fn words_left(reader: &ByteCodeReader) -> usize {
+    return reader.len().saturating_sub(reader.pc) / 4;
+}
+
+/*
+tableswitch
+<0-3 byte pad>
+defaultbyte1..4
+lowbyte1..4
+highbyte1..4
+jump offsets[high - low + 1]
+ */
+pub struct TableSwitch {
+    pub(crate) default_offset: i32,
+    pub(crate) low: i32,
+    pub(crate) high: i32,
+    pub(crate) jump_offsets: Vec<i32>,
+}
+
+impl Instruction for TableSwitch {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        reader.skip_padding();
+        self.default_offset = reader.read_i32();
+        self.low = reader.read_i32();
+        self.high = reader.read_i32();
+        // a corrupt table would run past the end of the code, which the decoder reports
+        let count = (self.high as i64 - self.low as i64 + 1).clamp(0, words_left(reader) as i64 + 1);
+        self.jump_offsets = reader.read_i32s(count as usize);
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let index = or_throw!(ctx.frame.operand_stack.pop_i32());
+        if index >= self.low && index <= self.high {
+            return Flow::Branch(self.jump_offsets[(index as i64 - self.low as i64) as usize]);
+        }
+        return Flow::Branch(self.default_offset);
+    }
+}
+
+/*
+lookupswitch
+<0-3 byte pad>
+defaultbyte1..4
+npairs1..4
+match-offset pairs[npairs], sorted by match
+ */
+pub struct LookupSwitch {
+    pub(crate) default_offset: i32,
+    pub(crate) match_offsets: Vec<(i32, i32)>,
+}
+
+impl Instruction for LookupSwitch {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        reader.skip_padding();
+        self.default_offset = reader.read_i32();
+        let npairs = (reader.read_i32() as i64).clamp(0, words_left(reader) as i64 / 2 + 1);
+        let pairs = reader.read_i32s(npairs as usize * 2);
+        self.match_offsets = pairs.chunks(2).map(|p| (p[0], p[1])).collect();
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let key = or_throw!(ctx.frame.operand_stack.pop_i32());
+        return match self.match_offsets.binary_search_by_key(&key, |(m, _)| *m) {
+            Ok(n) => Flow::Branch(self.match_offsets[n].1),
+            Err(_) => Flow::Branch(self.default_offset),
+        };
+    }
+}
@@ -0,0 +1,349 @@
+use crate::class::linker::{resolve_class, resolve_method_handle, resolve_method_type};
+use crate::class::runtime_constant_pool::Constant;
+use crate::env::call_site::{method_handle_object, method_type_object, MethodHandle};
+use crate::env::inst::{ExecContext, Instruction, Flow, or_throw};
+use crate::env::byte_code_reader::ByteCodeReader;
+use crate::env::error::JavaError;
+
+pub struct Nop {
+}
+
+impl Instruction for Nop {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, _ctx: &mut ExecContext) -> Flow {
+        return Flow::Next;
+    }
+}
+
+pub struct AConst_Null {
+}
+
+impl Instruction for AConst_Null {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(ctx.frame.operand_stack.push_null());
+        return Flow::Next;
+    }
+}
+
+pub struct IConst_M1 {
+}
+
+impl Instruction for IConst_M1 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(ctx.frame.operand_stack.push_i32(-1));
+        return Flow::Next;
+    }
+}
+
+pub struct IConst_0 {
+}
+
+impl Instruction for IConst_0 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(ctx.frame.operand_stack.push_i32(0));
+        return Flow::Next;
+    }
+}
+
+pub struct IConst_1 {
+}
+
+impl Instruction for IConst_1 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(ctx.frame.operand_stack.push_i32(1));
+        return Flow::Next;
+    }
+}
+
+pub struct IConst_2 {
+}
+
+impl Instruction for IConst_2 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(ctx.frame.operand_stack.push_i32(2));
+        return Flow::Next;
+    }
+}
+
+pub struct IConst_3 {
+}
+
+impl Instruction for IConst_3 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(ctx.frame.operand_stack.push_i32(3));
+        return Flow::Next;
+    }
+}
+
+pub struct IConst_4 {
+}
+
+impl Instruction for IConst_4 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(ctx.frame.operand_stack.push_i32(4));
+        return Flow::Next;
+    }
+}
+
+pub struct IConst_5 {
+}
+
+impl Instruction for IConst_5 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(ctx.frame.operand_stack.push_i32(5));
+        return Flow::Next;
+    }
+}
+
+pub struct LConst_0 {
+}
+
+impl Instruction for LConst_0 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(ctx.frame.operand_stack.push_i64(0));
+        return Flow::Next;
+    }
+}
+
+pub struct LConst_1 {
+}
+
+impl Instruction for LConst_1 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(ctx.frame.operand_stack.push_i64(1));
+        return Flow::Next;
+    }
+}
+
+pub struct FConst_0 {
+}
+
+impl Instruction for FConst_0 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(ctx.frame.operand_stack.push_f32(0.0));
+        return Flow::Next;
+    }
+}
+
+pub struct FConst_1 {
+}
+
+impl Instruction for FConst_1 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(ctx.frame.operand_stack.push_f32(1.0));
+        return Flow::Next;
+    }
+}
+
+pub struct FConst_2 {
+}
+
+impl Instruction for FConst_2 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(ctx.frame.operand_stack.push_f32(2.0));
+        return Flow::Next;
+    }
+}
+
+pub struct DConst_0 {
+}
+
+impl Instruction for DConst_0 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(ctx.frame.operand_stack.push_f64(0.0));
+        return Flow::Next;
+    }
+}
+
+pub struct DConst_1 {
+}
+
+impl Instruction for DConst_1 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(ctx.frame.operand_stack.push_f64(1.0));
+        return Flow::Next;
+    }
+}
+
+// pushes a byte, sign extended
+pub struct BiPush {
+    pub(crate) val: i8,
+}
+
+impl Instruction for BiPush {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.val = reader.read_i8();
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(ctx.frame.operand_stack.push_i32(self.val as i32));
+        return Flow::Next;
+    }
+}
+
+// pushes a short, sign extended
+pub struct SiPush {
+    pub(crate) val: i16,
+}
+
+impl Instruction for SiPush {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.val = reader.read_i16();
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(ctx.frame.operand_stack.push_i32(self.val as i32));
+        return Flow::Next;
+    }
+}
+
+/*
+ldc and ldc_w push an int, a float, a String, a Class, a MethodType or a
+MethodHandle constant; ldc2_w a long or a double. Which one the index
+points at was checked by the class file parser. Strings are interned,
+classes are resolved and pushed as their java.lang.Class objects, method
+types and method handles as the objects call_site makes for them.
+ */
+fn ldc(ctx: &mut ExecContext, index: u16) -> Flow {
+    let class = ctx.class().clone();
+    let stack = &mut ctx.frame.operand_stack;
+    let object = match class.constant_pool.get(index as usize) {
+        Some(Constant::Integer(v)) => {
+            or_throw!(stack.push_i32(*v));
+            return Flow::Next;
+        }
+        Some(Constant::Float(v)) => {
+            or_throw!(stack.push_f32(*v));
+            return Flow::Next;
+        }
+        Some(Constant::Long(v)) => {
+            or_throw!(stack.push_i64(*v));
+            return Flow::Next;
+        }
+        Some(Constant::Double(v)) => {
+            or_throw!(stack.push_f64(*v));
+            return Flow::Next;
+        }
+        Some(Constant::String(value)) => ctx.intern_string(value),
+        Some(Constant::Class(_)) => resolve_class(ctx.env, &class, index)
+            .and_then(|c| ctx.class_mirror(&c)),
+        Some(Constant::MethodType(_)) => resolve_method_type(ctx.env, &class, index)
+            .and_then(|descriptor| method_type_object(ctx, &descriptor)),
+        Some(Constant::MethodHandle(_)) => resolve_method_handle(ctx.env, &class, index)
+            .and_then(|resolved| method_handle_object(ctx, MethodHandle::of(resolved))),
+        _ => Err(JavaError::new("java/lang/VerifyError",
+            format!("Illegal type at constant pool entry {} in class {}", index, class.name))),
+    };
+    return match object {
+        Ok(object) => {
+            or_throw!(ctx.frame.operand_stack.push_ref(Some(object)));
+            Flow::Next
+        }
+        Err(e) => Flow::Throw(e),
+    };
+}
+
+pub struct Ldc {
+    pub(crate) index: u16,
+}
+
+impl Instruction for Ldc {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.index = reader.read_u8() as u16;
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        return ldc(ctx, self.index);
+    }
+}
+
+pub struct Ldc_W {
+    pub(crate) index: u16,
+}
+
+impl Instruction for Ldc_W {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.index = reader.read_u16();
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        return ldc(ctx, self.index);
+    }
+}
+
+pub struct Ldc2_W {
+    pub(crate) index: u16,
+}
+
+impl Instruction for Ldc2_W {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.index = reader.read_u16();
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        return ldc(ctx, self.index);
+    }
+}
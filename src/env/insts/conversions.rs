@@ -0,0 +1,233 @@
+use crate::env::inst::{ExecContext, Instruction, Flow, or_throw};
+use crate::env::byte_code_reader::ByteCodeReader;
+
+/*
+Rust's `as` casts have Java's semantics: integers are truncated or sign
+extended, and float to integer conversions round towards zero, saturate
+at the bounds of the target type and turn NaN into zero.
+ */
+
+pub struct I2L {
+}
+
+impl Instruction for I2L {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v = or_throw!(ctx.frame.operand_stack.pop_i32());
+        or_throw!(ctx.frame.operand_stack.push_i64(v as i64));
+        return Flow::Next;
+    }
+}
+
+pub struct I2F {
+}
+
+impl Instruction for I2F {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v = or_throw!(ctx.frame.operand_stack.pop_i32());
+        or_throw!(ctx.frame.operand_stack.push_f32(v as f32));
+        return Flow::Next;
+    }
+}
+
+pub struct I2D {
+}
+
+impl Instruction for I2D {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v = or_throw!(ctx.frame.operand_stack.pop_i32());
+        or_throw!(ctx.frame.operand_stack.push_f64(v as f64));
+        return Flow::Next;
+    }
+}
+
+pub struct L2I {
+}
+
+impl Instruction for L2I {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v = or_throw!(ctx.frame.operand_stack.pop_i64());
+        or_throw!(ctx.frame.operand_stack.push_i32(v as i32));
+        return Flow::Next;
+    }
+}
+
+pub struct L2F {
+}
+
+impl Instruction for L2F {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v = or_throw!(ctx.frame.operand_stack.pop_i64());
+        or_throw!(ctx.frame.operand_stack.push_f32(v as f32));
+        return Flow::Next;
+    }
+}
+
+pub struct L2D {
+}
+
+impl Instruction for L2D {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v = or_throw!(ctx.frame.operand_stack.pop_i64());
+        or_throw!(ctx.frame.operand_stack.push_f64(v as f64));
+        return Flow::Next;
+    }
+}
+
+pub struct F2I {
+}
+
+impl Instruction for F2I {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v = or_throw!(ctx.frame.operand_stack.pop_f32());
+        or_throw!(ctx.frame.operand_stack.push_i32(v as i32));
+        return Flow::Next;
+    }
+}
+
+pub struct F2L {
+}
+
+impl Instruction for F2L {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v = or_throw!(ctx.frame.operand_stack.pop_f32());
+        or_throw!(ctx.frame.operand_stack.push_i64(v as i64));
+        return Flow::Next;
+    }
+}
+
+pub struct F2D {
+}
+
+impl Instruction for F2D {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v = or_throw!(ctx.frame.operand_stack.pop_f32());
+        or_throw!(ctx.frame.operand_stack.push_f64(v as f64));
+        return Flow::Next;
+    }
+}
+
+pub struct D2I {
+}
+
+impl Instruction for D2I {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v = or_throw!(ctx.frame.operand_stack.pop_f64());
+        or_throw!(ctx.frame.operand_stack.push_i32(v as i32));
+        return Flow::Next;
+    }
+}
+
+pub struct D2L {
+}
+
+impl Instruction for D2L {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v = or_throw!(ctx.frame.operand_stack.pop_f64());
+        or_throw!(ctx.frame.operand_stack.push_i64(v as i64));
+        return Flow::Next;
+    }
+}
+
+pub struct D2F {
+}
+
+impl Instruction for D2F {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v = or_throw!(ctx.frame.operand_stack.pop_f64());
+        or_throw!(ctx.frame.operand_stack.push_f32(v as f32));
+        return Flow::Next;
+    }
+}
+
+pub struct I2B {
+}
+
+impl Instruction for I2B {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v = or_throw!(ctx.frame.operand_stack.pop_i32());
+        or_throw!(ctx.frame.operand_stack.push_i32(v as i8 as i32));
+        return Flow::Next;
+    }
+}
+
+pub struct I2C {
+}
+
+impl Instruction for I2C {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v = or_throw!(ctx.frame.operand_stack.pop_i32());
+        or_throw!(ctx.frame.operand_stack.push_i32(v as u16 as i32));
+        return Flow::Next;
+    }
+}
+
+pub struct I2S {
+}
+
+impl Instruction for I2S {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v = or_throw!(ctx.frame.operand_stack.pop_i32());
+        or_throw!(ctx.frame.operand_stack.push_i32(v as i16 as i32));
+        return Flow::Next;
+    }
+}
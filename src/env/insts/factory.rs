@@ -0,0 +1,311 @@
+use crate::env::error::JavaError;
+use crate::env::inst::Instruction;
+use crate::env::byte_code_reader::ByteCodeReader;
+use crate::env::insts::arrays::*;
+use crate::env::insts::comparisons::*;
+use crate::env::insts::constants::*;
+use crate::env::insts::conversions::*;
+use crate::env::insts::invokes::*;
+use crate::env::insts::loads::*;
+use crate::env::insts::math::*;
+use crate::env::insts::references::*;
+use crate::env::insts::returns::*;
+use crate::env::insts::stack::*;
+use crate::env::insts::stores::*;
+
+const WIDE: u8 = 0xc4;
+
+// the mnemonics of opcodes 0x00..=0xc9 (JVMS 7); the rest are reserved or unused
+const MNEMONICS: [&str; 202] = [
+    "nop", "aconst_null", "iconst_m1", "iconst_0", "iconst_1", "iconst_2", "iconst_3", "iconst_4",
+    "iconst_5", "lconst_0", "lconst_1", "fconst_0", "fconst_1", "fconst_2", "dconst_0", "dconst_1",
+    "bipush", "sipush", "ldc", "ldc_w", "ldc2_w", "iload", "lload", "fload",
+    "dload", "aload", "iload_0", "iload_1", "iload_2", "iload_3", "lload_0", "lload_1",
+    "lload_2", "lload_3", "fload_0", "fload_1", "fload_2", "fload_3", "dload_0", "dload_1",
+    "dload_2", "dload_3", "aload_0", "aload_1", "aload_2", "aload_3", "iaload", "laload",
+    "faload", "daload", "aaload", "baload", "caload", "saload", "istore", "lstore",
+    "fstore", "dstore", "astore", "istore_0", "istore_1", "istore_2", "istore_3", "lstore_0",
+    "lstore_1", "lstore_2", "lstore_3", "fstore_0", "fstore_1", "fstore_2", "fstore_3", "dstore_0",
+    "dstore_1", "dstore_2", "dstore_3", "astore_0", "astore_1", "astore_2", "astore_3", "iastore",
+    "lastore", "fastore", "dastore", "aastore", "bastore", "castore", "sastore", "pop",
+    "pop2", "dup", "dup_x1", "dup_x2", "dup2", "dup2_x1", "dup2_x2", "swap",
+    "iadd", "ladd", "fadd", "dadd", "isub", "lsub", "fsub", "dsub",
+    "imul", "lmul", "fmul", "dmul", "idiv", "ldiv", "fdiv", "ddiv",
+    "irem", "lrem", "frem", "drem", "ineg", "lneg", "fneg", "dneg",
+    "ishl", "lshl", "ishr", "lshr", "iushr", "lushr", "iand", "land",
+    "ior", "lor", "ixor", "lxor", "iinc", "i2l", "i2f", "i2d",
+    "l2i", "l2f", "l2d", "f2i", "f2l", "f2d", "d2i", "d2l",
+    "d2f", "i2b", "i2c", "i2s", "lcmp", "fcmpl", "fcmpg", "dcmpl",
+    "dcmpg", "ifeq", "ifne", "iflt", "ifge", "ifgt", "ifle", "if_icmpeq",
+    "if_icmpne", "if_icmplt", "if_icmpge", "if_icmpgt", "if_icmple", "if_acmpeq", "if_acmpne", "goto",
+    "jsr", "ret", "tableswitch", "lookupswitch", "ireturn", "lreturn", "freturn", "dreturn",
+    "areturn", "return", "getstatic", "putstatic", "getfield", "putfield", "invokevirtual", "invokespecial",
+    "invokestatic", "invokeinterface", "invokedynamic", "new", "newarray", "anewarray", "arraylength", "athrow",
+    "checkcast", "instanceof", "monitorenter", "monitorexit", "wide", "multianewarray", "ifnull", "ifnonnull",
+    "goto_w", "jsr_w",
+];
+
+pub fn mnemonic(opcode: u8) -> Option<&'static str> {
+    return MNEMONICS.get(opcode as usize).copied();
+}
+
+fn bad_instruction(opcode: u8) -> JavaError {
+    return JavaError::new("java/lang/VerifyError", format!("Bad instruction: {:#04x}", opcode));
+}
+
+// the instruction for an opcode, with its operands still to be fetched
+pub fn decode(opcode: u8) -> Result<Box<dyn Instruction>, JavaError> {
+    let inst: Box<dyn Instruction> = match opcode {
+        0x00 => Box::new(Nop {}),
+        0x01 => Box::new(AConst_Null {}),
+        0x02 => Box::new(IConst_M1 {}),
+        0x03 => Box::new(IConst_0 {}),
+        0x04 => Box::new(IConst_1 {}),
+        0x05 => Box::new(IConst_2 {}),
+        0x06 => Box::new(IConst_3 {}),
+        0x07 => Box::new(IConst_4 {}),
+        0x08 => Box::new(IConst_5 {}),
+        0x09 => Box::new(LConst_0 {}),
+        0x0a => Box::new(LConst_1 {}),
+        0x0b => Box::new(FConst_0 {}),
+        0x0c => Box::new(FConst_1 {}),
+        0x0d => Box::new(FConst_2 {}),
+        0x0e => Box::new(DConst_0 {}),
+        0x0f => Box::new(DConst_1 {}),
+        0x10 => Box::new(BiPush { val: 0 }),
+        0x11 => Box::new(SiPush { val: 0 }),
+        0x12 => Box::new(Ldc { index: 0 }),
+        0x13 => Box::new(Ldc_W { index: 0 }),
+        0x14 => Box::new(Ldc2_W { index: 0 }),
+        0x15 => Box::new(ILoad { index: 0 }),
+        0x16 => Box::new(LLoad { index: 0 }),
+        0x17 => Box::new(FLoad { index: 0 }),
+        0x18 => Box::new(DLoad { index: 0 }),
+        0x19 => Box::new(ALoad { index: 0 }),
+        0x1a => Box::new(ILoad_0 {}),
+        0x1b => Box::new(ILoad_1 {}),
+        0x1c => Box::new(ILoad_2 {}),
+        0x1d => Box::new(ILoad_3 {}),
+        0x1e => Box::new(LLoad_0 {}),
+        0x1f => Box::new(LLoad_1 {}),
+        0x20 => Box::new(LLoad_2 {}),
+        0x21 => Box::new(LLoad_3 {}),
+        0x22 => Box::new(FLoad_0 {}),
+        0x23 => Box::new(FLoad_1 {}),
+        0x24 => Box::new(FLoad_2 {}),
+        0x25 => Box::new(FLoad_3 {}),
+        0x26 => Box::new(DLoad_0 {}),
+        0x27 => Box::new(DLoad_1 {}),
+        0x28 => Box::new(DLoad_2 {}),
+        0x29 => Box::new(DLoad_3 {}),
+        0x2a => Box::new(ALoad_0 {}),
+        0x2b => Box::new(ALoad_1 {}),
+        0x2c => Box::new(ALoad_2 {}),
+        0x2d => Box::new(ALoad_3 {}),
+        0x2e => Box::new(IALoad {}),
+        0x2f => Box::new(LALoad {}),
+        0x30 => Box::new(FALoad {}),
+        0x31 => Box::new(DALoad {}),
+        0x32 => Box::new(AALoad {}),
+        0x33 => Box::new(BALoad {}),
+        0x34 => Box::new(CALoad {}),
+        0x35 => Box::new(SALoad {}),
+        0x36 => Box::new(IStore { index: 0 }),
+        0x37 => Box::new(LStore { index: 0 }),
+        0x38 => Box::new(FStore { index: 0 }),
+        0x39 => Box::new(DStore { index: 0 }),
+        0x3a => Box::new(AStore { index: 0 }),
+        0x3b => Box::new(IStore_0 {}),
+        0x3c => Box::new(IStore_1 {}),
+        0x3d => Box::new(IStore_2 {}),
+        0x3e => Box::new(IStore_3 {}),
+        0x3f => Box::new(LStore_0 {}),
+        0x40 => Box::new(LStore_1 {}),
+        0x41 => Box::new(LStore_2 {}),
+        0x42 => Box::new(LStore_3 {}),
+        0x43 => Box::new(FStore_0 {}),
+        0x44 => Box::new(FStore_1 {}),
+        0x45 => Box::new(FStore_2 {}),
+        0x46 => Box::new(FStore_3 {}),
+        0x47 => Box::new(DStore_0 {}),
+        0x48 => Box::new(DStore_1 {}),
+        0x49 => Box::new(DStore_2 {}),
+        0x4a => Box::new(DStore_3 {}),
+        0x4b => Box::new(AStore_0 {}),
+        0x4c => Box::new(AStore_1 {}),
+        0x4d => Box::new(AStore_2 {}),
+        0x4e => Box::new(AStore_3 {}),
+        0x4f => Box::new(IAStore {}),
+        0x50 => Box::new(LAStore {}),
+        0x51 => Box::new(FAStore {}),
+        0x52 => Box::new(DAStore {}),
+        0x53 => Box::new(AAStore {}),
+        0x54 => Box::new(BAStore {}),
+        0x55 => Box::new(CAStore {}),
+        0x56 => Box::new(SAStore {}),
+        0x57 => Box::new(Pop {}),
+        0x58 => Box::new(Pop2 {}),
+        0x59 => Box::new(Dup {}),
+        0x5a => Box::new(Dup_X1 {}),
+        0x5b => Box::new(Dup_X2 {}),
+        0x5c => Box::new(Dup2 {}),
+        0x5d => Box::new(Dup2_X1 {}),
+        0x5e => Box::new(Dup2_X2 {}),
+        0x5f => Box::new(Swap {}),
+        0x60 => Box::new(IAdd {}),
+        0x61 => Box::new(LAdd {}),
+        0x62 => Box::new(FAdd {}),
+        0x63 => Box::new(DAdd {}),
+        0x64 => Box::new(ISub {}),
+        0x65 => Box::new(LSub {}),
+        0x66 => Box::new(FSub {}),
+        0x67 => Box::new(DSub {}),
+        0x68 => Box::new(IMul {}),
+        0x69 => Box::new(LMul {}),
+        0x6a => Box::new(FMul {}),
+        0x6b => Box::new(DMul {}),
+        0x6c => Box::new(IDiv {}),
+        0x6d => Box::new(LDiv {}),
+        0x6e => Box::new(FDiv {}),
+        0x6f => Box::new(DDiv {}),
+        0x70 => Box::new(IRem {}),
+        0x71 => Box::new(LRem {}),
+        0x72 => Box::new(FRem {}),
+        0x73 => Box::new(DRem {}),
+        0x74 => Box::new(INeg {}),
+        0x75 => Box::new(LNeg {}),
+        0x76 => Box::new(FNeg {}),
+        0x77 => Box::new(DNeg {}),
+        0x78 => Box::new(IShl {}),
+        0x79 => Box::new(LShl {}),
+        0x7a => Box::new(IShr {}),
+        0x7b => Box::new(LShr {}),
+        0x7c => Box::new(IUShr {}),
+        0x7d => Box::new(LUShr {}),
+        0x7e => Box::new(IAnd {}),
+        0x7f => Box::new(LAnd {}),
+        0x80 => Box::new(IOr {}),
+        0x81 => Box::new(LOr {}),
+        0x82 => Box::new(IXor {}),
+        0x83 => Box::new(LXor {}),
+        0x84 => Box::new(IInc { index: 0, constant: 0 }),
+        0x85 => Box::new(I2L {}),
+        0x86 => Box::new(I2F {}),
+        0x87 => Box::new(I2D {}),
+        0x88 => Box::new(L2I {}),
+        0x89 => Box::new(L2F {}),
+        0x8a => Box::new(L2D {}),
+        0x8b => Box::new(F2I {}),
+        0x8c => Box::new(F2L {}),
+        0x8d => Box::new(F2D {}),
+        0x8e => Box::new(D2I {}),
+        0x8f => Box::new(D2L {}),
+        0x90 => Box::new(D2F {}),
+        0x91 => Box::new(I2B {}),
+        0x92 => Box::new(I2C {}),
+        0x93 => Box::new(I2S {}),
+        0x94 => Box::new(LCmp {}),
+        0x95 => Box::new(FCmpL {}),
+        0x96 => Box::new(FCmpG {}),
+        0x97 => Box::new(DCmpL {}),
+        0x98 => Box::new(DCmpG {}),
+        0x99 => Box::new(IfEq { offset: 0 }),
+        0x9a => Box::new(IfNe { offset: 0 }),
+        0x9b => Box::new(IfLt { offset: 0 }),
+        0x9c => Box::new(IfGe { offset: 0 }),
+        0x9d => Box::new(IfGt { offset: 0 }),
+        0x9e => Box::new(IfLe { offset: 0 }),
+        0x9f => Box::new(If_ICmpEq { offset: 0 }),
+        0xa0 => Box::new(If_ICmpNe { offset: 0 }),
+        0xa1 => Box::new(If_ICmpLt { offset: 0 }),
+        0xa2 => Box::new(If_ICmpGe { offset: 0 }),
+        0xa3 => Box::new(If_ICmpGt { offset: 0 }),
+        0xa4 => Box::new(If_ICmpLe { offset: 0 }),
+        0xa5 => Box::new(If_ACmpEq { offset: 0 }),
+        0xa6 => Box::new(If_ACmpNe { offset: 0 }),
+        0xa7 => Box::new(Goto { offset: 0 }),
+        0xa8 => Box::new(Jsr { offset: 0 }),
+        0xa9 => Box::new(Ret { index: 0 }),
+        0xaa => Box::new(TableSwitch { default_offset: 0, low: 0, high: 0, jump_offsets: vec![] }),
+        0xab => Box::new(LookupSwitch { default_offset: 0, match_offsets: vec![] }),
+        0xac => Box::new(IReturn {}),
+        0xad => Box::new(LReturn {}),
+        0xae => Box::new(FReturn {}),
+        0xaf => Box::new(DReturn {}),
+        0xb0 => Box::new(AReturn {}),
+        0xb1 => Box::new(Return {}),
+        0xb2 => Box::new(GetStatic { index: 0 }),
+        0xb3 => Box::new(PutStatic { index: 0 }),
+        0xb4 => Box::new(GetField { index: 0 }),
+        0xb5 => Box::new(PutField { index: 0 }),
+        0xb6 => Box::new(InvokeVirtual { index: 0 }),
+        0xb7 => Box::new(InvokeSpecial { index: 0 }),
+        0xb8 => Box::new(InvokeStatic { index: 0 }),
+        0xb9 => Box::new(InvokeInterface { index: 0 }),
+        0xba => Box::new(InvokeDynamic { index: 0 }),
+        0xbb => Box::new(New { index: 0 }),
+        0xbc => Box::new(NewArray { atype: 0 }),
+        0xbd => Box::new(ANewArray { index: 0 }),
+        0xbe => Box::new(ArrayLength {}),
+        0xbf => Box::new(AThrow {}),
+        0xc0 => Box::new(CheckCast { index: 0 }),
+        0xc1 => Box::new(InstanceOf { index: 0 }),
+        0xc2 => Box::new(MonitorEnter {}),
+        0xc3 => Box::new(MonitorExit {}),
+        0xc5 => Box::new(MultiANewArray { index: 0, dimensions: 0 }),
+        0xc6 => Box::new(IfNull { offset: 0 }),
+        0xc7 => Box::new(IfNonNull { offset: 0 }),
+        0xc8 => Box::new(Goto_W { offset: 0 }),
+        0xc9 => Box::new(Jsr_W { offset: 0 }),
+        // wide only ever comes as a prefix, see decode_wide
+        WIDE => return Err(bad_instruction(opcode)),
+        _ => return Err(bad_instruction(opcode)),
+    };
+    return Ok(inst);
+}
+
+/*
+wide <opcode> indexbyte1 indexbyte2 [constbyte1 constbyte2 for iinc]
+The prefixed instruction takes a 16 bit local variable index.
+ */
+fn decode_wide(reader: &mut ByteCodeReader) -> Result<Box<dyn Instruction>, JavaError> {
+    let opcode = reader.read_u8();
+    let index = reader.read_u16();
+    let inst: Box<dyn Instruction> = match opcode {
+        0x15 => Box::new(ILoad { index }),
+        0x16 => Box::new(LLoad { index }),
+        0x17 => Box::new(FLoad { index }),
+        0x18 => Box::new(DLoad { index }),
+        0x19 => Box::new(ALoad { index }),
+        0x36 => Box::new(IStore { index }),
+        0x37 => Box::new(LStore { index }),
+        0x38 => Box::new(FStore { index }),
+        0x39 => Box::new(DStore { index }),
+        0x3a => Box::new(AStore { index }),
+        0xa9 => Box::new(Ret { index }),
+        0x84 => Box::new(IInc { index, constant: reader.read_i16() }),
+        _ => return Err(JavaError::new("java/lang/VerifyError", format!("Bad wide instruction: {:#04x}", opcode))),
+    };
+    return Ok(inst);
+}
+
+/*
+Decodes the instruction at the reader's pc, operands included, and leaves
+the pc at the next instruction. Opcodes that don't exist and instructions
+that run past the end of the code are a VerifyError.
+ */
+pub fn fetch_instruction(reader: &mut ByteCodeReader) -> Result<Box<dyn Instruction>, JavaError> {
+    let opcode = reader.read_u8();
+    let inst = if opcode == WIDE {
+        decode_wide(reader)?
+    } else {
+        let mut inst = decode(opcode)?;
+        inst.fetch_operands(reader);
+        inst
+    };
+
+    if reader.pc > reader.len() {
+        return Err(JavaError::new("java/lang/VerifyError", format!("Instruction {} runs past the end of the code", mnemonic(opcode).unwrap_or("?"))));
+    }
+    return Ok(inst);
+}
@@ -0,0 +1,304 @@
+use std::rc::Rc;
+use crate::class::Class;
+use crate::class::access_flags::ACC_SUPER;
+use crate::class::linker::{resolve_interface_method, resolve_method};
+use crate::class::member::Method;
+use crate::class::descriptor::MethodDescriptor;
+use crate::class::runtime_constant_pool::*;
+use crate::class::vtable::{find_in_vtable, select_method};
+use crate::env::basic_env_elements::Slot;
+use crate::env::call_site::{link_call_site, MethodHandle};
+use crate::env::concat::concat;
+use crate::env::inst::{ExecContext, Instruction, Flow};
+use crate::env::insts::references::{get_instance_field, get_static_field, put_instance_field, put_static_field};
+use crate::env::byte_code_reader::ByteCodeReader;
+use crate::env::error::JavaError;
+
+/*
+The invoke instructions resolve the method, select the one to run and
+leave the call to the interpreter, which moves the arguments off the
+operand stack into the locals of the new frame. Native methods are run
+here, see Environment::register_native.
+ */
+
+// invokestatic and invokespecial take both kinds of method references
+fn resolve_any(ctx: &mut ExecContext, index: u16) -> Result<ResolvedMethod, JavaError> {
+    let class = ctx.class().clone();
+    return match class.constant_pool.get(index as usize) {
+        Some(Constant::InterfaceMethodRef(_)) => resolve_interface_method(ctx.env, &class, index),
+        _ => resolve_method(ctx.env, &class, index),
+    };
+}
+
+fn expect_instance(resolved: &ResolvedMethod) -> Result<(), JavaError> {
+    if resolved.method.is_static() {
+        return Err(JavaError::new("java/lang/IncompatibleClassChangeError",
+            format!("Expecting non-static method {}.{}{}", resolved.class.name.replace('/', "."), resolved.method.name, resolved.method.descriptor)));
+    }
+    return Ok(());
+}
+
+// the class of the object a method is invoked on, `this` is below the other arguments
+fn receiver_class(ctx: &ExecContext, resolved: &ResolvedMethod) -> Result<Rc<Class>, JavaError> {
+    let receiver = ctx.frame.operand_stack.peek_slot(resolved.method.arg_slot_count - 1)?;
+    return match receiver.val_ref {
+        Some(object) => Ok(ctx.env.heap.get(object).class().clone()),
+        None => Err(JavaError::new("java/lang/NullPointerException",
+            format!("Cannot invoke \"{}.{}()\" because the receiver is null", resolved.class.name.replace('/', "."), resolved.method.name))),
+    };
+}
+
+// native methods run right away, the arguments stay on the operand stack meanwhile so they are seen by the collector
+fn call(ctx: &mut ExecContext, class: Rc<Class>, method: Rc<Method>) -> Result<Flow, JavaError> {
+    if method.is_abstract() {
+        return Err(JavaError::new("java/lang/AbstractMethodError", format!("{}.{}{}", class.name, method.name, method.descriptor)));
+    }
+    if method.is_native() {
+        let native = ctx.env.get_native(&class.name, &method.name, &method.descriptor).ok_or_else(|| {
+            JavaError::new("java/lang/UnsatisfiedLinkError", format!("'{}.{}{}'", class.name.replace('/', "."), method.name, method.descriptor))
+        })?;
+        let args: Vec<Slot> = (0..method.arg_slot_count).rev().map(|n| ctx.frame.operand_stack.peek_slot(n)).collect::<Result<_, _>>()?;
+        // a synchronized native holds its monitor while it runs, as the interpreter does for other methods
+        let monitor = match (method.is_synchronized(), method.is_static()) {
+            (false, _) => None,
+            (true, true) => Some(ctx.class_mirror(&class)?),
+            (true, false) => args[0].val_ref,
+        };
+        if let Some(object) = monitor {
+            ctx.env.heap.get_mut(object).header.monitor.enter(ctx.thread.id)?;
+        }
+        let value = native(ctx, &args);
+        if let Some(object) = monitor {
+            ctx.env.heap.get_mut(object).header.monitor.exit(ctx.thread.id)?;
+        }
+        let value = value?;
+        for _ in 0..method.arg_slot_count {
+            ctx.frame.operand_stack.pop_slot()?;
+        }
+        for slot in value {
+            ctx.frame.operand_stack.push_slot(slot)?;
+        }
+        return Ok(Flow::Next);
+    }
+    return Ok(Flow::Invoke(class, method));
+}
+
+fn flow_of(result: Result<Flow, JavaError>) -> Flow {
+    return match result {
+        Ok(flow) => flow,
+        Err(e) => Flow::Throw(e),
+    };
+}
+
+fn invoke_static(ctx: &mut ExecContext, index: u16) -> Result<Flow, JavaError> {
+    let resolved = resolve_any(ctx, index)?;
+    if !resolved.method.is_static() {
+        return Err(JavaError::new("java/lang/IncompatibleClassChangeError",
+            format!("Expected static method {}.{}{}", resolved.class.name.replace('/', "."), resolved.method.name, resolved.method.descriptor)));
+    }
+    ctx.initialize(&resolved.class)?;
+    return call(ctx, resolved.class, resolved.method);
+}
+
+/*
+invokespecial calls constructors, private methods and super methods
+without selecting an override. For `super.m()` in a class with ACC_SUPER
+the method is looked up again from the direct super class, as that may
+have overridden the resolved one since the code was compiled.
+ */
+fn invoke_special(ctx: &mut ExecContext, index: u16) -> Result<Flow, JavaError> {
+    let resolved = resolve_any(ctx, index)?;
+    expect_instance(&resolved)?;
+    receiver_class(ctx, &resolved)?;
+
+    let current = ctx.class().clone();
+    let is_super_call = resolved.method.name != "<init>"
+        && !resolved.class.is_interface()
+        && current.access_flags & ACC_SUPER != 0
+        && !Rc::ptr_eq(&current, &resolved.class)
+        && current.is_subclass_of(&resolved.class);
+    if is_super_call {
+        let found = current.super_class.as_ref()
+            .and_then(|s| find_in_vtable(s, &resolved.method.name, &resolved.method.descriptor));
+        if let Some(slot) = found {
+            return call(ctx, slot.class.upgrade().unwrap(), slot.method);
+        }
+    }
+    return call(ctx, resolved.class, resolved.method);
+}
+
+fn invoke_virtual(ctx: &mut ExecContext, index: u16) -> Result<Flow, JavaError> {
+    let class = ctx.class().clone();
+    let resolved = resolve_method(ctx.env, &class, index)?;
+    expect_instance(&resolved)?;
+    let receiver = receiver_class(ctx, &resolved)?;
+    let (class, method) = select_method(&receiver, &resolved)?;
+    return call(ctx, class, method);
+}
+
+fn invoke_interface(ctx: &mut ExecContext, index: u16) -> Result<Flow, JavaError> {
+    let class = ctx.class().clone();
+    let resolved = resolve_interface_method(ctx.env, &class, index)?;
+    expect_instance(&resolved)?;
+    let receiver = receiver_class(ctx, &resolved)?;
+    if !receiver.is_implementing(&resolved.class) {
+        return Err(JavaError::new("java/lang/IncompatibleClassChangeError",
+            format!("Class {} does not implement the requested interface {}",
+                receiver.name.replace('/', "."), resolved.class.name.replace('/', "."))));
+    }
+    let (class, method) = select_method(&receiver, &resolved)?;
+    return call(ctx, class, method);
+}
+
+/*
+Invokes a method handle on the arguments on the operand stack, which
+have the type `descriptor`. Direct and field handles do what the
+instructions of their reference kind would. A constructor handle runs
+the constructor right away on a new object, which it leaves instead of
+the arguments: there may be no room on the operand stack for the two
+copies of it new and dup would push. A concatenation leaves the String
+it makes.
+ */
+pub fn invoke_handle(ctx: &mut ExecContext, handle: &MethodHandle, descriptor: &str) -> Result<Flow, JavaError> {
+    let (kind, resolved) = match handle {
+        MethodHandle::Direct(kind, resolved) => (*kind, resolved),
+        MethodHandle::Field(kind, resolved) => {
+            match *kind {
+                REF_GET_FIELD => get_instance_field(ctx, resolved)?,
+                REF_GET_STATIC => get_static_field(ctx, resolved)?,
+                REF_PUT_FIELD => put_instance_field(ctx, resolved)?,
+                _ => put_static_field(ctx, resolved)?,
+            }
+            return Ok(Flow::Next);
+        }
+        MethodHandle::Constant(value) => {
+            let arg_slot_count = MethodDescriptor::parse(descriptor).map_or(0, |d| d.arg_slot_count());
+            for _ in 0..arg_slot_count {
+                ctx.frame.operand_stack.pop_slot()?;
+            }
+            ctx.frame.operand_stack.push_slot(*value)?;
+            return Ok(Flow::Next);
+        }
+        MethodHandle::Concat(recipe, constants) => {
+            let string = concat(ctx, recipe, constants, descriptor)?;
+            ctx.frame.operand_stack.push_ref(Some(string))?;
+            return Ok(Flow::Next);
+        }
+    };
+    return match kind {
+        REF_INVOKE_STATIC => {
+            ctx.initialize(&resolved.class)?;
+            call(ctx, resolved.class.clone(), resolved.method.clone())
+        }
+        REF_INVOKE_SPECIAL => {
+            receiver_class(ctx, resolved)?;
+            call(ctx, resolved.class.clone(), resolved.method.clone())
+        }
+        REF_INVOKE_VIRTUAL | REF_INVOKE_INTERFACE => {
+            let receiver = receiver_class(ctx, resolved)?;
+            let (class, method) = select_method(&receiver, resolved)?;
+            call(ctx, class, method)
+        }
+        _ => {
+            if resolved.class.is_interface() || resolved.class.is_abstract() {
+                return Err(JavaError::new("java/lang/InstantiationError", resolved.class.name.replace('/', ".")));
+            }
+            ctx.initialize(&resolved.class)?;
+            // allocated while the arguments are still on the operand stack, where the collector sees them
+            let object = ctx.alloc_object(&resolved.class)?;
+            let mut args = vec![Slot { val_num: 0, val_ref: Some(object) }; resolved.method.arg_slot_count];
+            for n in (1..args.len()).rev() {
+                args[n] = ctx.frame.operand_stack.pop_slot()?;
+            }
+            ctx.run(resolved.class.clone(), resolved.method.clone(), args)?;
+            ctx.frame.operand_stack.push_ref(Some(object))?;
+            Ok(Flow::Next)
+        }
+    };
+}
+
+fn invoke_dynamic(ctx: &mut ExecContext, index: u16) -> Result<Flow, JavaError> {
+    let site = link_call_site(ctx, index)?;
+    return invoke_handle(ctx, &site.target, &site.descriptor);
+}
+
+pub struct InvokeStatic {
+    pub(crate) index: u16,
+}
+
+impl Instruction for InvokeStatic {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.index = reader.read_u16();
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        return flow_of(invoke_static(ctx, self.index));
+    }
+}
+
+pub struct InvokeSpecial {
+    pub(crate) index: u16,
+}
+
+impl Instruction for InvokeSpecial {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.index = reader.read_u16();
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        return flow_of(invoke_special(ctx, self.index));
+    }
+}
+
+pub struct InvokeVirtual {
+    pub(crate) index: u16,
+}
+
+impl Instruction for InvokeVirtual {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.index = reader.read_u16();
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        return flow_of(invoke_virtual(ctx, self.index));
+    }
+}
+
+/*
+invokeinterface indexbyte1 indexbyte2 count 0
+The count of argument slots is redundant with the descriptor and ignored.
+ */
+pub struct InvokeInterface {
+    pub(crate) index: u16,
+}
+
+impl Instruction for InvokeInterface {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.index = reader.read_u16();
+        reader.read_u8();
+        reader.read_u8();
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        return flow_of(invoke_interface(ctx, self.index));
+    }
+}
+
+/*
+invokedynamic indexbyte1 indexbyte2 0 0
+ */
+pub struct InvokeDynamic {
+    pub(crate) index: u16,
+}
+
+impl Instruction for InvokeDynamic {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.index = reader.read_u16();
+        reader.read_u8();
+        reader.read_u8();
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        return flow_of(invoke_dynamic(ctx, self.index));
+    }
+}
@@ -1,23 +1,25 @@
-use crate::env::inst::Instruction;
+use crate::env::error::JavaError;
+use crate::env::inst::{ExecContext, Instruction, Flow, or_throw};
 use crate::env::basic_env_elements::Frame;
 use crate::env::byte_code_reader::ByteCodeReader;
 
-fn iload(frame: &mut Frame, index: u8) {
-    let val = frame.local_vars.get_i32(index as usize);
-    frame.operand_stack.push_i32(val.unwrap());
+fn iload(frame: &mut Frame, index: u16) -> Result<(), JavaError> {
+    let val = frame.local_vars.get_i32(index as usize)?;
+    return frame.operand_stack.push_i32(val);
 }
 
 pub struct ILoad {
-    index: u8,
+    pub(crate) index: u16,
 }
 
 impl Instruction for ILoad {
     fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
-        self.index = reader.read_u8();
+        self.index = reader.read_u8() as u16;
     }
 
-    fn execute(&mut self, frame: &mut Frame) {
-        iload(frame, self.index);
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(iload(ctx.frame, self.index));
+        return Flow::Next;
     }
 }
 
@@ -25,12 +27,13 @@ pub struct ILoad_0 {
 }
 
 impl Instruction for ILoad_0 {
-    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
         // nothing to do
     }
 
-    fn execute(&mut self, frame: &mut Frame) {
-        iload(frame, 0);
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(iload(ctx.frame, 0));
+        return Flow::Next;
     }
 }
 
@@ -38,12 +41,13 @@ pub struct ILoad_1 {
 }
 
 impl Instruction for ILoad_1 {
-    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
         // nothing to do
     }
 
-    fn execute(&mut self, frame: &mut Frame) {
-        iload(frame, 1);
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(iload(ctx.frame, 1));
+        return Flow::Next;
     }
 }
 
@@ -51,12 +55,13 @@ pub struct ILoad_2 {
 }
 
 impl Instruction for ILoad_2 {
-    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
         // nothing to do
     }
 
-    fn execute(&mut self, frame: &mut Frame) {
-        iload(frame, 2);
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(iload(ctx.frame, 2));
+        return Flow::Next;
     }
 }
 
@@ -64,31 +69,33 @@ pub struct ILoad_3 {
 }
 
 impl Instruction for ILoad_3 {
-    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
         // nothing to do
     }
 
-    fn execute(&mut self, frame: &mut Frame) {
-        iload(frame, 3);
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(iload(ctx.frame, 3));
+        return Flow::Next;
     }
 }
 
-fn lload(frame: &mut Frame, index: u8) {
-    let val = frame.local_vars.get_i64(index as usize);
-    frame.operand_stack.push_i64(val.unwrap());
+fn lload(frame: &mut Frame, index: u16) -> Result<(), JavaError> {
+    let val = frame.local_vars.get_i64(index as usize)?;
+    return frame.operand_stack.push_i64(val);
 }
 
 pub struct LLoad {
-    index: u8,
+    pub(crate) index: u16,
 }
 
 impl Instruction for LLoad {
     fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
-        self.index = reader.read_u8();
+        self.index = reader.read_u8() as u16;
     }
 
-    fn execute(&mut self, frame: &mut Frame) {
-        lload(frame, self.index);
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(lload(ctx.frame, self.index));
+        return Flow::Next;
     }
 }
 
@@ -96,12 +103,13 @@ pub struct LLoad_0 {
 }
 
 impl Instruction for LLoad_0 {
-    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
         // nothing to do
     }
 
-    fn execute(&mut self, frame: &mut Frame) {
-        lload(frame, 0);
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(lload(ctx.frame, 0));
+        return Flow::Next;
     }
 }
 
@@ -109,12 +117,13 @@ pub struct LLoad_1 {
 }
 
 impl Instruction for LLoad_1 {
-    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
         // nothing to do
     }
 
-    fn execute(&mut self, frame: &mut Frame) {
-        lload(frame, 1);
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(lload(ctx.frame, 1));
+        return Flow::Next;
     }
 }
 
@@ -122,12 +131,13 @@ pub struct LLoad_2 {
 }
 
 impl Instruction for LLoad_2 {
-    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
         // nothing to do
     }
 
-    fn execute(&mut self, frame: &mut Frame) {
-        lload(frame, 2);
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(lload(ctx.frame, 2));
+        return Flow::Next;
     }
 }
 
@@ -135,31 +145,33 @@ pub struct LLoad_3 {
 }
 
 impl Instruction for LLoad_3 {
-    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
         // nothing to do
     }
 
-    fn execute(&mut self, frame: &mut Frame) {
-        lload(frame, 3);
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(lload(ctx.frame, 3));
+        return Flow::Next;
     }
 }
 
-fn fload(frame: &mut Frame, index: u8) {
-    let val = frame.local_vars.get_f32(index as usize);
-    frame.operand_stack.push_f32(val.unwrap());
+fn fload(frame: &mut Frame, index: u16) -> Result<(), JavaError> {
+    let val = frame.local_vars.get_f32(index as usize)?;
+    return frame.operand_stack.push_f32(val);
 }
 
 pub struct FLoad {
-    index: u8,
+    pub(crate) index: u16,
 }
 
 impl Instruction for FLoad {
     fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
-        self.index = reader.read_u8();
+        self.index = reader.read_u8() as u16;
     }
 
-    fn execute(&mut self, frame: &mut Frame) {
-        fload(frame, self.index);
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(fload(ctx.frame, self.index));
+        return Flow::Next;
     }
 }
 
@@ -167,12 +179,13 @@ pub struct FLoad_0 {
 }
 
 impl Instruction for FLoad_0 {
-    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
         // nothing to do
     }
 
-    fn execute(&mut self, frame: &mut Frame) {
-        fload(frame, 0);
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(fload(ctx.frame, 0));
+        return Flow::Next;
     }
 }
 
@@ -180,12 +193,13 @@ pub struct FLoad_1 {
 }
 
 impl Instruction for FLoad_1 {
-    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
         // nothing to do
     }
 
-    fn execute(&mut self, frame: &mut Frame) {
-        fload(frame, 1);
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(fload(ctx.frame, 1));
+        return Flow::Next;
     }
 }
 
@@ -193,12 +207,13 @@ pub struct FLoad_2 {
 }
 
 impl Instruction for FLoad_2 {
-    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
         // nothing to do
     }
 
-    fn execute(&mut self, frame: &mut Frame) {
-        fload(frame, 2);
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(fload(ctx.frame, 2));
+        return Flow::Next;
     }
 }
 
@@ -206,31 +221,33 @@ pub struct FLoad_3 {
 }
 
 impl Instruction for FLoad_3 {
-    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
         // nothing to do
     }
 
-    fn execute(&mut self, frame: &mut Frame) {
-        fload(frame, 3);
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(fload(ctx.frame, 3));
+        return Flow::Next;
     }
 }
 
-fn dload(frame: &mut Frame, index: u8) {
-    let val = frame.local_vars.get_f64(index as usize);
-    frame.operand_stack.push_f64(val.unwrap());
+fn dload(frame: &mut Frame, index: u16) -> Result<(), JavaError> {
+    let val = frame.local_vars.get_f64(index as usize)?;
+    return frame.operand_stack.push_f64(val);
 }
 
 pub struct DLoad {
-    index: u8,
+    pub(crate) index: u16,
 }
 
 impl Instruction for DLoad {
     fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
-        self.index = reader.read_u8();
+        self.index = reader.read_u8() as u16;
     }
 
-    fn execute(&mut self, frame: &mut Frame) {
-        dload(frame, self.index);
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(dload(ctx.frame, self.index));
+        return Flow::Next;
     }
 }
 
@@ -238,12 +255,13 @@ pub struct DLoad_0 {
 }
 
 impl Instruction for DLoad_0 {
-    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
         // nothing to do
     }
 
-    fn execute(&mut self, frame: &mut Frame) {
-        dload(frame, 0);
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(dload(ctx.frame, 0));
+        return Flow::Next;
     }
 }
 
@@ -251,12 +269,13 @@ pub struct DLoad_1 {
 }
 
 impl Instruction for DLoad_1 {
-    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
         // nothing to do
     }
 
-    fn execute(&mut self, frame: &mut Frame) {
-        dload(frame, 1);
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(dload(ctx.frame, 1));
+        return Flow::Next;
     }
 }
 
@@ -264,12 +283,13 @@ pub struct DLoad_2 {
 }
 
 impl Instruction for DLoad_2 {
-    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
         // nothing to do
     }
 
-    fn execute(&mut self, frame: &mut Frame) {
-        dload(frame, 2);
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(dload(ctx.frame, 2));
+        return Flow::Next;
     }
 }
 
@@ -277,31 +297,34 @@ pub struct DLoad_3 {
 }
 
 impl Instruction for DLoad_3 {
-    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
         // nothing to do
     }
 
-    fn execute(&mut self, frame: &mut Frame) {
-        dload(frame, 3);
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(dload(ctx.frame, 3));
+        return Flow::Next;
     }
 }
 
-fn aload(frame: &mut Frame, index: u8) {
-    let val = frame.local_vars.get_ref(index as usize);
-    frame.operand_stack.push_ref(val.unwrap());
+// the slot is copied as it is, null included
+fn aload(frame: &mut Frame, index: u16) -> Result<(), JavaError> {
+    let slot = frame.local_vars.get_slot(index as usize)?;
+    return frame.operand_stack.push_slot(slot);
 }
 
 pub struct ALoad {
-    index: u8,
+    pub(crate) index: u16,
 }
 
 impl Instruction for ALoad {
     fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
-        self.index = reader.read_u8();
+        self.index = reader.read_u8() as u16;
     }
 
-    fn execute(&mut self, frame: &mut Frame) {
-        aload(frame, self.index);
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(aload(ctx.frame, self.index));
+        return Flow::Next;
     }
 }
 
@@ -309,12 +332,13 @@ pub struct ALoad_0 {
 }
 
 impl Instruction for ALoad_0 {
-    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
         // nothing to do
     }
 
-    fn execute(&mut self, frame: &mut Frame) {
-        aload(frame, 0);
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(aload(ctx.frame, 0));
+        return Flow::Next;
     }
 }
 
@@ -322,12 +346,13 @@ pub struct ALoad_1 {
 }
 
 impl Instruction for ALoad_1 {
-    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
         // nothing to do
     }
 
-    fn execute(&mut self, frame: &mut Frame) {
-        aload(frame, 1);
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(aload(ctx.frame, 1));
+        return Flow::Next;
     }
 }
 
@@ -335,12 +360,13 @@ pub struct ALoad_2 {
 }
 
 impl Instruction for ALoad_2 {
-    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
         // nothing to do
     }
 
-    fn execute(&mut self, frame: &mut Frame) {
-        aload(frame, 2);
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(aload(ctx.frame, 2));
+        return Flow::Next;
     }
 }
 
@@ -348,11 +374,12 @@ pub struct ALoad_3 {
 }
 
 impl Instruction for ALoad_3 {
-    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
         // nothing to do
     }
 
-    fn execute(&mut self, frame: &mut Frame) {
-        aload(frame, 3);
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(aload(ctx.frame, 3));
+        return Flow::Next;
     }
 }
\ No newline at end of file
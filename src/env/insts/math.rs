@@ -0,0 +1,622 @@
+use crate::env::error::JavaError;
+use crate::env::inst::{ExecContext, Instruction, Flow, or_throw};
+use crate::env::byte_code_reader::ByteCodeReader;
+
+/*
+Integer arithmetic wraps around on overflow as Java's does, so the
+wrapping_* operations are used throughout. Float arithmetic is IEEE 754
+with round to nearest, which Rust's f32 and f64 already give; `%` on
+floats truncates like fmod, which is what frem and drem are.
+ */
+
+fn divide_by_zero() -> JavaError {
+    return JavaError::new("java/lang/ArithmeticException", "/ by zero".to_string());
+}
+
+pub struct IAdd {
+}
+
+impl Instruction for IAdd {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        or_throw!(ctx.frame.operand_stack.push_i32(v1.wrapping_add(v2)));
+        return Flow::Next;
+    }
+}
+
+pub struct LAdd {
+}
+
+impl Instruction for LAdd {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_i64());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_i64());
+        or_throw!(ctx.frame.operand_stack.push_i64(v1.wrapping_add(v2)));
+        return Flow::Next;
+    }
+}
+
+pub struct FAdd {
+}
+
+impl Instruction for FAdd {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_f32());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_f32());
+        or_throw!(ctx.frame.operand_stack.push_f32(v1 + v2));
+        return Flow::Next;
+    }
+}
+
+pub struct DAdd {
+}
+
+impl Instruction for DAdd {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_f64());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_f64());
+        or_throw!(ctx.frame.operand_stack.push_f64(v1 + v2));
+        return Flow::Next;
+    }
+}
+
+pub struct ISub {
+}
+
+impl Instruction for ISub {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        or_throw!(ctx.frame.operand_stack.push_i32(v1.wrapping_sub(v2)));
+        return Flow::Next;
+    }
+}
+
+pub struct LSub {
+}
+
+impl Instruction for LSub {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_i64());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_i64());
+        or_throw!(ctx.frame.operand_stack.push_i64(v1.wrapping_sub(v2)));
+        return Flow::Next;
+    }
+}
+
+pub struct FSub {
+}
+
+impl Instruction for FSub {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_f32());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_f32());
+        or_throw!(ctx.frame.operand_stack.push_f32(v1 - v2));
+        return Flow::Next;
+    }
+}
+
+pub struct DSub {
+}
+
+impl Instruction for DSub {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_f64());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_f64());
+        or_throw!(ctx.frame.operand_stack.push_f64(v1 - v2));
+        return Flow::Next;
+    }
+}
+
+pub struct IMul {
+}
+
+impl Instruction for IMul {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        or_throw!(ctx.frame.operand_stack.push_i32(v1.wrapping_mul(v2)));
+        return Flow::Next;
+    }
+}
+
+pub struct LMul {
+}
+
+impl Instruction for LMul {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_i64());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_i64());
+        or_throw!(ctx.frame.operand_stack.push_i64(v1.wrapping_mul(v2)));
+        return Flow::Next;
+    }
+}
+
+pub struct FMul {
+}
+
+impl Instruction for FMul {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_f32());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_f32());
+        or_throw!(ctx.frame.operand_stack.push_f32(v1 * v2));
+        return Flow::Next;
+    }
+}
+
+pub struct DMul {
+}
+
+impl Instruction for DMul {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_f64());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_f64());
+        or_throw!(ctx.frame.operand_stack.push_f64(v1 * v2));
+        return Flow::Next;
+    }
+}
+
+// the minimum value divided by -1 overflows back to the minimum value
+pub struct IDiv {
+}
+
+impl Instruction for IDiv {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        if v2 == 0 {
+            return Flow::Throw(divide_by_zero());
+        }
+        or_throw!(ctx.frame.operand_stack.push_i32(v1.wrapping_div(v2)));
+        return Flow::Next;
+    }
+}
+
+pub struct LDiv {
+}
+
+impl Instruction for LDiv {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_i64());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_i64());
+        if v2 == 0 {
+            return Flow::Throw(divide_by_zero());
+        }
+        or_throw!(ctx.frame.operand_stack.push_i64(v1.wrapping_div(v2)));
+        return Flow::Next;
+    }
+}
+
+pub struct FDiv {
+}
+
+impl Instruction for FDiv {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_f32());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_f32());
+        or_throw!(ctx.frame.operand_stack.push_f32(v1 / v2));
+        return Flow::Next;
+    }
+}
+
+pub struct DDiv {
+}
+
+impl Instruction for DDiv {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_f64());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_f64());
+        or_throw!(ctx.frame.operand_stack.push_f64(v1 / v2));
+        return Flow::Next;
+    }
+}
+
+pub struct IRem {
+}
+
+impl Instruction for IRem {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        if v2 == 0 {
+            return Flow::Throw(divide_by_zero());
+        }
+        or_throw!(ctx.frame.operand_stack.push_i32(v1.wrapping_rem(v2)));
+        return Flow::Next;
+    }
+}
+
+pub struct LRem {
+}
+
+impl Instruction for LRem {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_i64());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_i64());
+        if v2 == 0 {
+            return Flow::Throw(divide_by_zero());
+        }
+        or_throw!(ctx.frame.operand_stack.push_i64(v1.wrapping_rem(v2)));
+        return Flow::Next;
+    }
+}
+
+pub struct FRem {
+}
+
+impl Instruction for FRem {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_f32());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_f32());
+        or_throw!(ctx.frame.operand_stack.push_f32(v1 % v2));
+        return Flow::Next;
+    }
+}
+
+pub struct DRem {
+}
+
+impl Instruction for DRem {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_f64());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_f64());
+        or_throw!(ctx.frame.operand_stack.push_f64(v1 % v2));
+        return Flow::Next;
+    }
+}
+
+pub struct INeg {
+}
+
+impl Instruction for INeg {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v = or_throw!(ctx.frame.operand_stack.pop_i32());
+        or_throw!(ctx.frame.operand_stack.push_i32(v.wrapping_neg()));
+        return Flow::Next;
+    }
+}
+
+pub struct LNeg {
+}
+
+impl Instruction for LNeg {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v = or_throw!(ctx.frame.operand_stack.pop_i64());
+        or_throw!(ctx.frame.operand_stack.push_i64(v.wrapping_neg()));
+        return Flow::Next;
+    }
+}
+
+pub struct FNeg {
+}
+
+impl Instruction for FNeg {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v = or_throw!(ctx.frame.operand_stack.pop_f32());
+        or_throw!(ctx.frame.operand_stack.push_f32(-v));
+        return Flow::Next;
+    }
+}
+
+pub struct DNeg {
+}
+
+impl Instruction for DNeg {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v = or_throw!(ctx.frame.operand_stack.pop_f64());
+        or_throw!(ctx.frame.operand_stack.push_f64(-v));
+        return Flow::Next;
+    }
+}
+
+/*
+The shift distance is an int of which only the low 5 bits (6 for longs)
+count, as wrapping_shl and wrapping_shr mask it.
+ */
+pub struct IShl {
+}
+
+impl Instruction for IShl {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        or_throw!(ctx.frame.operand_stack.push_i32(v1.wrapping_shl(v2 as u32)));
+        return Flow::Next;
+    }
+}
+
+pub struct LShl {
+}
+
+impl Instruction for LShl {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_i64());
+        or_throw!(ctx.frame.operand_stack.push_i64(v1.wrapping_shl(v2 as u32)));
+        return Flow::Next;
+    }
+}
+
+pub struct IShr {
+}
+
+impl Instruction for IShr {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        or_throw!(ctx.frame.operand_stack.push_i32(v1.wrapping_shr(v2 as u32)));
+        return Flow::Next;
+    }
+}
+
+pub struct LShr {
+}
+
+impl Instruction for LShr {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_i64());
+        or_throw!(ctx.frame.operand_stack.push_i64(v1.wrapping_shr(v2 as u32)));
+        return Flow::Next;
+    }
+}
+
+pub struct IUShr {
+}
+
+impl Instruction for IUShr {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        or_throw!(ctx.frame.operand_stack.push_i32((v1 as u32).wrapping_shr(v2 as u32) as i32));
+        return Flow::Next;
+    }
+}
+
+pub struct LUShr {
+}
+
+impl Instruction for LUShr {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_i64());
+        or_throw!(ctx.frame.operand_stack.push_i64((v1 as u64).wrapping_shr(v2 as u32) as i64));
+        return Flow::Next;
+    }
+}
+
+pub struct IAnd {
+}
+
+impl Instruction for IAnd {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        or_throw!(ctx.frame.operand_stack.push_i32(v1 & v2));
+        return Flow::Next;
+    }
+}
+
+pub struct LAnd {
+}
+
+impl Instruction for LAnd {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_i64());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_i64());
+        or_throw!(ctx.frame.operand_stack.push_i64(v1 & v2));
+        return Flow::Next;
+    }
+}
+
+pub struct IOr {
+}
+
+impl Instruction for IOr {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        or_throw!(ctx.frame.operand_stack.push_i32(v1 | v2));
+        return Flow::Next;
+    }
+}
+
+pub struct LOr {
+}
+
+impl Instruction for LOr {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_i64());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_i64());
+        or_throw!(ctx.frame.operand_stack.push_i64(v1 | v2));
+        return Flow::Next;
+    }
+}
+
+pub struct IXor {
+}
+
+impl Instruction for IXor {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_i32());
+        or_throw!(ctx.frame.operand_stack.push_i32(v1 ^ v2));
+        return Flow::Next;
+    }
+}
+
+pub struct LXor {
+}
+
+impl Instruction for LXor {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_i64());
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_i64());
+        or_throw!(ctx.frame.operand_stack.push_i64(v1 ^ v2));
+        return Flow::Next;
+    }
+}
+
+pub struct IInc {
+    pub(crate) index: u16,
+    pub(crate) constant: i16,
+}
+
+impl Instruction for IInc {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.index = reader.read_u8() as u16;
+        self.constant = reader.read_i8() as i16;
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let index = self.index as usize;
+        let val = or_throw!(ctx.frame.local_vars.get_i32(index));
+        or_throw!(ctx.frame.local_vars.set_i32(index, val.wrapping_add(self.constant as i32)));
+        return Flow::Next;
+    }
+}
@@ -1,2 +1,12 @@
 pub mod comparisons;
-pub mod loads;
\ No newline at end of file
+pub mod constants;
+pub mod loads;
+pub mod stores;
+pub mod stack;
+pub mod math;
+pub mod conversions;
+pub mod returns;
+pub mod invokes;
+pub mod references;
+pub mod arrays;
+pub mod factory;
@@ -0,0 +1,372 @@
+use std::rc::Rc;
+use crate::class::Class;
+use crate::class::linker::{resolve_class, resolve_field};
+use crate::class::runtime_constant_pool::ResolvedField;
+use crate::env::basic_env_elements::Slot;
+use crate::env::inst::{ExecContext, Instruction, Flow, or_throw};
+use crate::env::byte_code_reader::ByteCodeReader;
+use crate::env::error::JavaError;
+use crate::env::object::ObjRef;
+
+// throws the Throwable on top of the operand stack, the interpreter looks for its handler
+pub struct AThrow {
+}
+
+impl Instruction for AThrow {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        return match or_throw!(ctx.frame.operand_stack.pop_ref()) {
+            Some(object) => Flow::Throw(JavaError::thrown(ctx.env, object)),
+            None => Flow::Throw(JavaError::new("java/lang/NullPointerException",
+                "Cannot throw exception because the value is null".to_string())),
+        };
+    }
+}
+
+/*
+The field instructions resolve the field, the slots of which are found
+by its slot_id: in the static vars of the class declaring it, or in the
+fields of the object, where the super classes' come first. Longs and
+doubles take two slots, which are moved as they are.
+ */
+
+fn flow_of(result: Result<(), JavaError>) -> Flow {
+    return match result {
+        Ok(()) => Flow::Next,
+        Err(e) => Flow::Throw(e),
+    };
+}
+
+fn resolve(ctx: &mut ExecContext, index: u16, is_static: bool) -> Result<ResolvedField, JavaError> {
+    let class = ctx.class().clone();
+    let resolved = resolve_field(ctx.env, &class, index)?;
+    if resolved.field().is_static() != is_static {
+        let expected = if is_static { "static" } else { "non-static" };
+        return Err(JavaError::new("java/lang/IncompatibleClassChangeError",
+            format!("Expected {} field {}.{}", expected, resolved.class.name.replace('/', "."), resolved.field().name)));
+    }
+    return Ok(resolved);
+}
+
+/*
+A final field may only be set by its own class, and since class files of
+Java 9 only by the initializer setting it, <init> for instance fields and
+<clinit> for static ones (JVMS 6.5 putfield, putstatic).
+ */
+fn check_final_write(ctx: &ExecContext, resolved: &ResolvedField, initializer: &str) -> Result<(), JavaError> {
+    let field = resolved.field();
+    if !field.is_final() {
+        return Ok(());
+    }
+    let kind = if field.is_static() { "static" } else { "non-static" };
+    let name = format!("{}.{}", resolved.class.name.replace('/', "."), field.name);
+    if !Rc::ptr_eq(ctx.class(), &resolved.class) {
+        return Err(JavaError::new("java/lang/IllegalAccessError",
+            format!("Update to {} final field {} attempted from a different class ({}) than the field's declaring class",
+                kind, name, ctx.class().name.replace('/', "."))));
+    }
+    if ctx.class().major_version >= 53 && ctx.method().name != initializer {
+        return Err(JavaError::new("java/lang/IllegalAccessError",
+            format!("Update to {} final field {} attempted from a different method ({}) than the initializer method {}",
+                kind, name, ctx.method().name, initializer)));
+    }
+    return Ok(());
+}
+
+// the int stored into a boolean, byte, char or short field is narrowed to its type
+fn narrow(descriptor: &str, value: Slot) -> Slot {
+    let val_num = match descriptor {
+        "Z" => value.val_num & 1,
+        "B" => value.val_num as i8 as i32,
+        "C" => value.val_num as u16 as i32,
+        "S" => value.val_num as i16 as i32,
+        _ => return value,
+    };
+    return Slot { val_num, val_ref: None };
+}
+
+/*
+The object a field is accessed on, `depth` slots below the top. The
+verifier doesn't check types, so it may be an array or an object of an
+unrelated class, which would have other fields in its slots.
+ */
+fn object_at(ctx: &ExecContext, depth: usize, resolved: &ResolvedField, access: &str) -> Result<ObjRef, JavaError> {
+    let object = ctx.frame.operand_stack.peek_slot(depth)?.val_ref.ok_or_else(|| {
+        JavaError::new("java/lang/NullPointerException",
+            format!("Cannot {} field \"{}\" because the object is null", access, resolved.field().name))
+    })?;
+    let class = ctx.env.heap.get(object).class();
+    if class.is_array() || !class.is_subclass_of(&resolved.class) {
+        return Err(not_an_instance(class, resolved));
+    }
+    return Ok(object);
+}
+
+fn not_an_instance(class: &Class, resolved: &ResolvedField) -> JavaError {
+    return JavaError::new("java/lang/IncompatibleClassChangeError",
+        format!("Field {}.{} accessed on an instance of {}",
+            resolved.class.name.replace('/', "."), resolved.field().name, class.name.replace('/', ".")));
+}
+
+fn get_static(ctx: &mut ExecContext, index: u16) -> Result<(), JavaError> {
+    let resolved = resolve(ctx, index, true)?;
+    return get_static_field(ctx, &resolved);
+}
+
+pub(crate) fn get_static_field(ctx: &mut ExecContext, resolved: &ResolvedField) -> Result<(), JavaError> {
+    ctx.initialize(&resolved.class)?;
+    let field = resolved.field();
+    let vars = resolved.class.static_vars.borrow();
+    for n in 0..field.slot_size() {
+        ctx.frame.operand_stack.push_slot(vars.get_slot(field.slot_id + n)?)?;
+    }
+    return Ok(());
+}
+
+fn put_static(ctx: &mut ExecContext, index: u16) -> Result<(), JavaError> {
+    let resolved = resolve(ctx, index, true)?;
+    return put_static_field(ctx, &resolved);
+}
+
+// static fields are roots of every collection, so there's no write barrier
+pub(crate) fn put_static_field(ctx: &mut ExecContext, resolved: &ResolvedField) -> Result<(), JavaError> {
+    check_final_write(ctx, resolved, "<clinit>")?;
+    ctx.initialize(&resolved.class)?;
+    let field = resolved.field();
+    let mut vars = resolved.class.static_vars.borrow_mut();
+    for n in (0..field.slot_size()).rev() {
+        let value = ctx.frame.operand_stack.pop_slot()?;
+        vars.set_slot(field.slot_id + n, narrow(&field.descriptor, value))?;
+    }
+    return Ok(());
+}
+
+fn get_field(ctx: &mut ExecContext, index: u16) -> Result<(), JavaError> {
+    let resolved = resolve(ctx, index, false)?;
+    return get_instance_field(ctx, &resolved);
+}
+
+pub(crate) fn get_instance_field(ctx: &mut ExecContext, resolved: &ResolvedField) -> Result<(), JavaError> {
+    let object = object_at(ctx, 0, resolved, "read")?;
+    ctx.frame.operand_stack.pop_slot()?;
+    let field = resolved.field();
+    let instance = ctx.env.heap.get(object);
+    let fields = instance.fields().ok_or_else(|| not_an_instance(instance.class(), resolved))?;
+    for n in 0..field.slot_size() {
+        ctx.frame.operand_stack.push_slot(fields.get_slot(field.slot_id + n)?)?;
+    }
+    return Ok(());
+}
+
+fn put_field(ctx: &mut ExecContext, index: u16) -> Result<(), JavaError> {
+    let resolved = resolve(ctx, index, false)?;
+    return put_instance_field(ctx, &resolved);
+}
+
+pub(crate) fn put_instance_field(ctx: &mut ExecContext, resolved: &ResolvedField) -> Result<(), JavaError> {
+    check_final_write(ctx, resolved, "<init>")?;
+    let field = resolved.field();
+    let object = object_at(ctx, field.slot_size(), resolved, "assign")?;
+    if field.descriptor.starts_with('L') || field.descriptor.starts_with('[') {
+        let value = ctx.frame.operand_stack.pop_ref()?;
+        ctx.env.heap.set_field_ref(object, field.slot_id, value)?;
+    } else {
+        let instance = ctx.env.heap.get_mut(object);
+        let class = instance.class().clone();
+        let fields = instance.fields_mut().ok_or_else(|| not_an_instance(&class, resolved))?;
+        for n in (0..field.slot_size()).rev() {
+            let value = ctx.frame.operand_stack.pop_slot()?;
+            fields.set_slot(field.slot_id + n, narrow(&field.descriptor, value))?;
+        }
+    }
+    ctx.frame.operand_stack.pop_slot()?;
+    return Ok(());
+}
+
+pub struct GetStatic {
+    pub(crate) index: u16,
+}
+
+impl Instruction for GetStatic {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.index = reader.read_u16();
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        return flow_of(get_static(ctx, self.index));
+    }
+}
+
+pub struct PutStatic {
+    pub(crate) index: u16,
+}
+
+impl Instruction for PutStatic {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.index = reader.read_u16();
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        return flow_of(put_static(ctx, self.index));
+    }
+}
+
+pub struct GetField {
+    pub(crate) index: u16,
+}
+
+impl Instruction for GetField {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.index = reader.read_u16();
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        return flow_of(get_field(ctx, self.index));
+    }
+}
+
+pub struct PutField {
+    pub(crate) index: u16,
+}
+
+impl Instruction for PutField {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.index = reader.read_u16();
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        return flow_of(put_field(ctx, self.index));
+    }
+}
+
+// a new instance of the class with its fields zeroed, its constructor is invoked next
+fn new_object(ctx: &mut ExecContext, index: u16) -> Result<(), JavaError> {
+    let class = ctx.class().clone();
+    let class = resolve_class(ctx.env, &class, index)?;
+    if class.is_interface() || class.is_abstract() {
+        return Err(JavaError::new("java/lang/InstantiationError", class.name.replace('/', ".")));
+    }
+    ctx.initialize(&class)?;
+    let object = ctx.alloc_object(&class)?;
+    ctx.frame.operand_stack.push_ref(Some(object))?;
+    return Ok(());
+}
+
+/*
+checkcast leaves the reference on the operand stack and instanceof
+replaces it with the answer. Neither resolves the class for null, which
+passes checkcast and isn't an instance of anything.
+ */
+fn check_cast(ctx: &mut ExecContext, index: u16) -> Result<(), JavaError> {
+    let object = match ctx.frame.operand_stack.peek_slot(0)?.val_ref {
+        Some(object) => object,
+        None => return Ok(()),
+    };
+    let class = ctx.class().clone();
+    let class = resolve_class(ctx.env, &class, index)?;
+    let object_class = ctx.env.heap.get(object).class();
+    if !object_class.is_assignable_to(&class) {
+        return Err(JavaError::new("java/lang/ClassCastException",
+            format!("class {} cannot be cast to class {}", object_class.name.replace('/', "."), class.name.replace('/', "."))));
+    }
+    return Ok(());
+}
+
+fn instance_of(ctx: &mut ExecContext, index: u16) -> Result<(), JavaError> {
+    let object = match ctx.frame.operand_stack.pop_ref()? {
+        Some(object) => object,
+        None => {
+            ctx.frame.operand_stack.push_i32(0)?;
+            return Ok(());
+        }
+    };
+    let class = ctx.class().clone();
+    let class = resolve_class(ctx.env, &class, index)?;
+    let is_instance = ctx.env.heap.get(object).class().is_assignable_to(&class);
+    ctx.frame.operand_stack.push_i32(is_instance as i32)?;
+    return Ok(());
+}
+
+pub struct New {
+    pub(crate) index: u16,
+}
+
+impl Instruction for New {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.index = reader.read_u16();
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        return flow_of(new_object(ctx, self.index));
+    }
+}
+
+pub struct CheckCast {
+    pub(crate) index: u16,
+}
+
+impl Instruction for CheckCast {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.index = reader.read_u16();
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        return flow_of(check_cast(ctx, self.index));
+    }
+}
+
+pub struct InstanceOf {
+    pub(crate) index: u16,
+}
+
+impl Instruction for InstanceOf {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.index = reader.read_u16();
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        return flow_of(instance_of(ctx, self.index));
+    }
+}
+
+// every object has a monitor in its header, see Monitor
+pub struct MonitorEnter {
+}
+
+impl Instruction for MonitorEnter {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let object = match or_throw!(ctx.frame.operand_stack.pop_ref()) {
+            Some(object) => object,
+            None => return Flow::Throw(JavaError::new("java/lang/NullPointerException",
+                "Cannot enter synchronized block because the value is null".to_string())),
+        };
+        or_throw!(ctx.env.heap.get_mut(object).header.monitor.enter(ctx.thread.id));
+        return Flow::Next;
+    }
+}
+
+pub struct MonitorExit {
+}
+
+impl Instruction for MonitorExit {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let object = match or_throw!(ctx.frame.operand_stack.pop_ref()) {
+            Some(object) => object,
+            None => return Flow::Throw(JavaError::new("java/lang/NullPointerException",
+                "Cannot exit synchronized block because the value is null".to_string())),
+        };
+        or_throw!(ctx.env.heap.get_mut(object).header.monitor.exit(ctx.thread.id));
+        return Flow::Next;
+    }
+}
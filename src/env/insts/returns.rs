@@ -0,0 +1,85 @@
+use crate::env::inst::{ExecContext, Instruction, Flow};
+use crate::env::byte_code_reader::ByteCodeReader;
+
+/*
+The value to return is left on top of the operand stack; the interpreter
+moves it to the caller, taking as many slots as the method's return type.
+ */
+
+pub struct IReturn {
+}
+
+impl Instruction for IReturn {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, _ctx: &mut ExecContext) -> Flow {
+        return Flow::Return;
+    }
+}
+
+pub struct LReturn {
+}
+
+impl Instruction for LReturn {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, _ctx: &mut ExecContext) -> Flow {
+        return Flow::Return;
+    }
+}
+
+pub struct FReturn {
+}
+
+impl Instruction for FReturn {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, _ctx: &mut ExecContext) -> Flow {
+        return Flow::Return;
+    }
+}
+
+pub struct DReturn {
+}
+
+impl Instruction for DReturn {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, _ctx: &mut ExecContext) -> Flow {
+        return Flow::Return;
+    }
+}
+
+pub struct AReturn {
+}
+
+impl Instruction for AReturn {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, _ctx: &mut ExecContext) -> Flow {
+        return Flow::Return;
+    }
+}
+
+pub struct Return {
+}
+
+impl Instruction for Return {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, _ctx: &mut ExecContext) -> Flow {
+        return Flow::Return;
+    }
+}
@@ -0,0 +1,175 @@
+use crate::env::inst::{ExecContext, Instruction, Flow, or_throw};
+use crate::env::byte_code_reader::ByteCodeReader;
+
+/*
+Longs and doubles take two slots, so the category 2 forms of pop2 and the
+dup2 family come down to moving slots just as the category 1 forms do.
+ */
+
+pub struct Pop {
+}
+
+impl Instruction for Pop {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(ctx.frame.operand_stack.pop_slot());
+        return Flow::Next;
+    }
+}
+
+pub struct Pop2 {
+}
+
+impl Instruction for Pop2 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(ctx.frame.operand_stack.pop_slot());
+        or_throw!(ctx.frame.operand_stack.pop_slot());
+        return Flow::Next;
+    }
+}
+
+pub struct Dup {
+}
+
+impl Instruction for Dup {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_slot());
+        or_throw!(ctx.frame.operand_stack.push_slot(v1));
+        or_throw!(ctx.frame.operand_stack.push_slot(v1));
+        return Flow::Next;
+    }
+}
+
+// ..., v2, v1 -> ..., v1, v2, v1
+pub struct Dup_X1 {
+}
+
+impl Instruction for Dup_X1 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_slot());
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_slot());
+        or_throw!(ctx.frame.operand_stack.push_slot(v1));
+        or_throw!(ctx.frame.operand_stack.push_slot(v2));
+        or_throw!(ctx.frame.operand_stack.push_slot(v1));
+        return Flow::Next;
+    }
+}
+
+// ..., v3, v2, v1 -> ..., v1, v3, v2, v1
+pub struct Dup_X2 {
+}
+
+impl Instruction for Dup_X2 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_slot());
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_slot());
+        let v3 = or_throw!(ctx.frame.operand_stack.pop_slot());
+        or_throw!(ctx.frame.operand_stack.push_slot(v1));
+        or_throw!(ctx.frame.operand_stack.push_slot(v3));
+        or_throw!(ctx.frame.operand_stack.push_slot(v2));
+        or_throw!(ctx.frame.operand_stack.push_slot(v1));
+        return Flow::Next;
+    }
+}
+
+// ..., v2, v1 -> ..., v2, v1, v2, v1
+pub struct Dup2 {
+}
+
+impl Instruction for Dup2 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_slot());
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_slot());
+        or_throw!(ctx.frame.operand_stack.push_slot(v2));
+        or_throw!(ctx.frame.operand_stack.push_slot(v1));
+        or_throw!(ctx.frame.operand_stack.push_slot(v2));
+        or_throw!(ctx.frame.operand_stack.push_slot(v1));
+        return Flow::Next;
+    }
+}
+
+// ..., v3, v2, v1 -> ..., v2, v1, v3, v2, v1
+pub struct Dup2_X1 {
+}
+
+impl Instruction for Dup2_X1 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_slot());
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_slot());
+        let v3 = or_throw!(ctx.frame.operand_stack.pop_slot());
+        or_throw!(ctx.frame.operand_stack.push_slot(v2));
+        or_throw!(ctx.frame.operand_stack.push_slot(v1));
+        or_throw!(ctx.frame.operand_stack.push_slot(v3));
+        or_throw!(ctx.frame.operand_stack.push_slot(v2));
+        or_throw!(ctx.frame.operand_stack.push_slot(v1));
+        return Flow::Next;
+    }
+}
+
+// ..., v4, v3, v2, v1 -> ..., v2, v1, v4, v3, v2, v1
+pub struct Dup2_X2 {
+}
+
+impl Instruction for Dup2_X2 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_slot());
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_slot());
+        let v3 = or_throw!(ctx.frame.operand_stack.pop_slot());
+        let v4 = or_throw!(ctx.frame.operand_stack.pop_slot());
+        or_throw!(ctx.frame.operand_stack.push_slot(v2));
+        or_throw!(ctx.frame.operand_stack.push_slot(v1));
+        or_throw!(ctx.frame.operand_stack.push_slot(v4));
+        or_throw!(ctx.frame.operand_stack.push_slot(v3));
+        or_throw!(ctx.frame.operand_stack.push_slot(v2));
+        or_throw!(ctx.frame.operand_stack.push_slot(v1));
+        return Flow::Next;
+    }
+}
+
+pub struct Swap {
+}
+
+impl Instruction for Swap {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        let v1 = or_throw!(ctx.frame.operand_stack.pop_slot());
+        let v2 = or_throw!(ctx.frame.operand_stack.pop_slot());
+        or_throw!(ctx.frame.operand_stack.push_slot(v1));
+        or_throw!(ctx.frame.operand_stack.push_slot(v2));
+        return Flow::Next;
+    }
+}
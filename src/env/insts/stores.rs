@@ -0,0 +1,385 @@
+use crate::env::error::JavaError;
+use crate::env::inst::{ExecContext, Instruction, Flow, or_throw};
+use crate::env::basic_env_elements::Frame;
+use crate::env::byte_code_reader::ByteCodeReader;
+
+fn istore(frame: &mut Frame, index: u16) -> Result<(), JavaError> {
+    let val = frame.operand_stack.pop_i32()?;
+    return frame.local_vars.set_i32(index as usize, val);
+}
+
+pub struct IStore {
+    pub(crate) index: u16,
+}
+
+impl Instruction for IStore {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.index = reader.read_u8() as u16;
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(istore(ctx.frame, self.index));
+        return Flow::Next;
+    }
+}
+
+pub struct IStore_0 {
+}
+
+impl Instruction for IStore_0 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(istore(ctx.frame, 0));
+        return Flow::Next;
+    }
+}
+
+pub struct IStore_1 {
+}
+
+impl Instruction for IStore_1 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(istore(ctx.frame, 1));
+        return Flow::Next;
+    }
+}
+
+pub struct IStore_2 {
+}
+
+impl Instruction for IStore_2 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(istore(ctx.frame, 2));
+        return Flow::Next;
+    }
+}
+
+pub struct IStore_3 {
+}
+
+impl Instruction for IStore_3 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(istore(ctx.frame, 3));
+        return Flow::Next;
+    }
+}
+
+fn lstore(frame: &mut Frame, index: u16) -> Result<(), JavaError> {
+    let val = frame.operand_stack.pop_i64()?;
+    return frame.local_vars.set_i64(index as usize, val);
+}
+
+pub struct LStore {
+    pub(crate) index: u16,
+}
+
+impl Instruction for LStore {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.index = reader.read_u8() as u16;
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(lstore(ctx.frame, self.index));
+        return Flow::Next;
+    }
+}
+
+pub struct LStore_0 {
+}
+
+impl Instruction for LStore_0 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(lstore(ctx.frame, 0));
+        return Flow::Next;
+    }
+}
+
+pub struct LStore_1 {
+}
+
+impl Instruction for LStore_1 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(lstore(ctx.frame, 1));
+        return Flow::Next;
+    }
+}
+
+pub struct LStore_2 {
+}
+
+impl Instruction for LStore_2 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(lstore(ctx.frame, 2));
+        return Flow::Next;
+    }
+}
+
+pub struct LStore_3 {
+}
+
+impl Instruction for LStore_3 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(lstore(ctx.frame, 3));
+        return Flow::Next;
+    }
+}
+
+fn fstore(frame: &mut Frame, index: u16) -> Result<(), JavaError> {
+    let val = frame.operand_stack.pop_f32()?;
+    return frame.local_vars.set_f32(index as usize, val);
+}
+
+pub struct FStore {
+    pub(crate) index: u16,
+}
+
+impl Instruction for FStore {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.index = reader.read_u8() as u16;
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(fstore(ctx.frame, self.index));
+        return Flow::Next;
+    }
+}
+
+pub struct FStore_0 {
+}
+
+impl Instruction for FStore_0 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(fstore(ctx.frame, 0));
+        return Flow::Next;
+    }
+}
+
+pub struct FStore_1 {
+}
+
+impl Instruction for FStore_1 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(fstore(ctx.frame, 1));
+        return Flow::Next;
+    }
+}
+
+pub struct FStore_2 {
+}
+
+impl Instruction for FStore_2 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(fstore(ctx.frame, 2));
+        return Flow::Next;
+    }
+}
+
+pub struct FStore_3 {
+}
+
+impl Instruction for FStore_3 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(fstore(ctx.frame, 3));
+        return Flow::Next;
+    }
+}
+
+fn dstore(frame: &mut Frame, index: u16) -> Result<(), JavaError> {
+    let val = frame.operand_stack.pop_f64()?;
+    return frame.local_vars.set_f64(index as usize, val);
+}
+
+pub struct DStore {
+    pub(crate) index: u16,
+}
+
+impl Instruction for DStore {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.index = reader.read_u8() as u16;
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(dstore(ctx.frame, self.index));
+        return Flow::Next;
+    }
+}
+
+pub struct DStore_0 {
+}
+
+impl Instruction for DStore_0 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(dstore(ctx.frame, 0));
+        return Flow::Next;
+    }
+}
+
+pub struct DStore_1 {
+}
+
+impl Instruction for DStore_1 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(dstore(ctx.frame, 1));
+        return Flow::Next;
+    }
+}
+
+pub struct DStore_2 {
+}
+
+impl Instruction for DStore_2 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(dstore(ctx.frame, 2));
+        return Flow::Next;
+    }
+}
+
+pub struct DStore_3 {
+}
+
+impl Instruction for DStore_3 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(dstore(ctx.frame, 3));
+        return Flow::Next;
+    }
+}
+
+// astore also takes the return addresses of jsr, so the slot is moved as it is
+fn astore(frame: &mut Frame, index: u16) -> Result<(), JavaError> {
+    let slot = frame.operand_stack.pop_slot()?;
+    return frame.local_vars.set_slot(index as usize, slot);
+}
+
+pub struct AStore {
+    pub(crate) index: u16,
+}
+
+impl Instruction for AStore {
+    fn fetch_operands(&mut self, reader: &mut ByteCodeReader) {
+        self.index = reader.read_u8() as u16;
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(astore(ctx.frame, self.index));
+        return Flow::Next;
+    }
+}
+
+pub struct AStore_0 {
+}
+
+impl Instruction for AStore_0 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(astore(ctx.frame, 0));
+        return Flow::Next;
+    }
+}
+
+pub struct AStore_1 {
+}
+
+impl Instruction for AStore_1 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(astore(ctx.frame, 1));
+        return Flow::Next;
+    }
+}
+
+pub struct AStore_2 {
+}
+
+impl Instruction for AStore_2 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(astore(ctx.frame, 2));
+        return Flow::Next;
+    }
+}
+
+pub struct AStore_3 {
+}
+
+impl Instruction for AStore_3 {
+    fn fetch_operands(&mut self, _reader: &mut ByteCodeReader) {
+        // nothing to do
+    }
+
+    fn execute(&mut self, ctx: &mut ExecContext) -> Flow {
+        or_throw!(astore(ctx.frame, 3));
+        return Flow::Next;
+    }
+}
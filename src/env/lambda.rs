@@ -0,0 +1,378 @@
+use std::cell::OnceCell;
+use std::rc::Rc;
+use crate::class::Class;
+use crate::class::access_flags::*;
+use crate::class::descriptor::{slot_size, MethodDescriptor};
+use crate::class::linker::link_class;
+use crate::class::member::{Field, Method};
+use crate::class::registry::LoaderId;
+use crate::class::runtime_constant_pool::*;
+use crate::env::basic_env_elements::Slot;
+use crate::env::call_site::{BootstrapArg, BootstrapCall, MethodHandle};
+use crate::env::error::JavaError;
+use crate::env::inst::ExecContext;
+
+/*
+LambdaMetafactory.metafactory links the call sites javac emits for lambdas
+and method references. The call site is named after the method of the
+functional interface, the arguments of its type are the captured values
+and it returns the interface. The static arguments are
+  0: the erased type of the interface method
+  1: the implementation, a lambda$ method or the referenced one
+  2: the type of the interface method with its type variables filled in
+As HotSpot does, the VM makes up a class implementing the interface, with
+a final field for each captured value, a constructor storing them and a
+method passing them and its own arguments on to the implementation. The
+call site is linked to the constructor; a lambda that captures nothing is
+the same instance every time, so its call site is linked to that constant.
+ */
+pub fn metafactory(ctx: &mut ExecContext, call: &BootstrapCall) -> Result<MethodHandle, JavaError> {
+    let (interface_type, kind, implementation, instantiated) = match call.args.as_slice() {
+        [BootstrapArg::MethodType(erased), BootstrapArg::MethodHandle(ResolvedMethodHandle::Method(kind, m)), BootstrapArg::MethodType(instantiated)] => {
+            (erased, *kind, m, instantiated)
+        }
+        _ => return Err(conversion_error(format!("Bad arguments for lambda {}{}", call.name, call.descriptor))),
+    };
+    let site = MethodDescriptor::parse(&call.descriptor)
+        .ok_or_else(|| conversion_error(format!("Illegal call site type {}", call.descriptor)))?;
+
+    let interface_name = site.return_type.strip_prefix('L').map(|n| n.trim_end_matches(';')).unwrap_or("");
+    let interface = ctx.env.load_class_from(call.caller.loader, interface_name)?
+        .ok_or_else(|| JavaError::new("java/lang/NoClassDefFoundError", interface_name.to_string()))?;
+    if !interface.is_interface() {
+        return Err(conversion_error(format!("{} is not an interface", site.return_type)));
+    }
+    let object = ctx.env.load_class_from(LoaderId::BOOTSTRAP, "java/lang/Object")?
+        .ok_or_else(|| JavaError::new("java/lang/NoClassDefFoundError", "java/lang/Object".to_string()))?;
+
+    let name = format!("{}$$Lambda${}", call.caller.name, ctx.env.next_lambda_id());
+    let captured = &site.parameter_types;
+    let mut constants = Constants(vec![Constant::Empty, implementation_ref(implementation)]);
+    let (max_stack, code) = forwarding_code(&mut constants, &name, captured, interface_type, instantiated, kind, implementation)?;
+    let method = Method::synthesized(ACC_PUBLIC | ACC_FINAL | ACC_SYNTHETIC, &call.name, interface_type, max_stack, code);
+    let constructor_type = format!("({})V", captured.concat());
+    let (max_stack, code) = constructor_code(&mut constants, &name, captured);
+    let constructor = Method::synthesized(ACC_PRIVATE, "<init>", &constructor_type, max_stack, code);
+
+    let mut class = Class::synthesized(&call.caller, name, object, vec![interface],
+        RuntimeConstantPool::from_constants(constants.0), vec![method, constructor]);
+    class.add_instance_fields(captured.iter().enumerate().map(|(n, field_type)| Field {
+        access_flags: ACC_PRIVATE | ACC_FINAL,
+        name: format!("arg${}", n + 1),
+        descriptor: field_type.clone(),
+        slot_id: 0,
+        const_value_index: None,
+    }).collect());
+    let class = Rc::new(class);
+    link_class(ctx.env, &class)?;
+
+    if captured.is_empty() {
+        // Object.<init> is all its constructor would run
+        return Ok(MethodHandle::Constant(Slot {
+            val_num: 0,
+            val_ref: Some(ctx.alloc_object(&class)?),
+        }));
+    }
+    let method = class.get_method("<init>", &constructor_type).unwrap();
+    return Ok(MethodHandle::Direct(REF_NEW_INVOKE_SPECIAL, ResolvedMethod { class, method }));
+}
+
+fn conversion_error(message: String) -> JavaError {
+    return JavaError::new("java/lang/invoke/LambdaConversionException", message);
+}
+
+// constant #1 of a lambda class, resolved already as the implementation has been
+fn implementation_ref(implementation: &ResolvedMethod) -> Constant {
+    let sym = MemberSymRef {
+        class_name: implementation.class.name.clone(),
+        name: implementation.method.name.clone(),
+        descriptor: implementation.method.descriptor.clone(),
+        resolved: OnceCell::new(),
+    };
+    let _ = sym.resolved.set(implementation.clone());
+    return match implementation.class.is_interface() {
+        true => Constant::InterfaceMethodRef(sym),
+        false => Constant::MethodRef(sym),
+    };
+}
+
+// the constant pool of a lambda class as its code is generated
+struct Constants(Vec<Constant>);
+
+impl Constants {
+    // adds a constant, returning its index as the instructions take it
+    fn add(&mut self, constant: Constant) -> [u8; 2] {
+        self.0.push(constant);
+        return ((self.0.len() - 1) as u16).to_be_bytes();
+    }
+
+    fn class_ref(&mut self, class_name: &str) -> [u8; 2] {
+        return self.add(Constant::Class(ClassSymRef {
+            class_name: class_name.to_string(),
+            resolved: OnceCell::new(),
+        }));
+    }
+
+    fn method_ref(&mut self, class_name: &str, name: &str, descriptor: &str) -> [u8; 2] {
+        return self.add(Constant::MethodRef(member_ref(class_name, name, descriptor)));
+    }
+
+    fn field_ref(&mut self, class_name: &str, name: &str, descriptor: &str) -> [u8; 2] {
+        return self.add(Constant::FieldRef(member_ref(class_name, name, descriptor)));
+    }
+}
+
+fn member_ref<T>(class_name: &str, name: &str, descriptor: &str) -> MemberSymRef<T> {
+    return MemberSymRef {
+        class_name: class_name.to_string(),
+        name: name.to_string(),
+        descriptor: descriptor.to_string(),
+        resolved: OnceCell::new(),
+    };
+}
+
+// generated code, and the deepest the operand stack gets running it
+struct Code {
+    bytes: Vec<u8>,
+    depth: usize,
+    max_stack: usize,
+}
+
+impl Code {
+    fn new() -> Code {
+        return Code { bytes: vec![], depth: 0, max_stack: 0 };
+    }
+
+    // an instruction taking `popped` slots off the operand stack and pushing `pushed`
+    fn emit(&mut self, instruction: &[u8], popped: usize, pushed: usize) {
+        self.bytes.extend_from_slice(instruction);
+        self.depth = self.depth - popped + pushed;
+        self.max_stack = self.max_stack.max(self.depth);
+    }
+
+    fn load(&mut self, field_type: &str, local: usize) {
+        let opcode = match computational_type(field_type) {
+            b'J' => 0x16,
+            b'F' => 0x17,
+            b'D' => 0x18,
+            b'L' => 0x19,
+            _ => 0x15,
+        };
+        if local > u8::MAX as usize {
+            self.emit(&[0xc4, opcode, (local >> 8) as u8, local as u8], 0, slot_size(field_type));
+        } else {
+            self.emit(&[opcode, local as u8], 0, slot_size(field_type));
+        }
+    }
+}
+
+// the types the interpreter tells apart: booleans, bytes, chars and shorts are ints
+fn computational_type(field_type: &str) -> u8 {
+    return match field_type.as_bytes()[0] {
+        b'Z' | b'B' | b'C' | b'S' => b'I',
+        b'[' => b'L',
+        c => c,
+    };
+}
+
+// the primitive types with their wrapper classes and the methods unboxing them
+const WRAPPERS: [(&str, &str, &str); 8] = [
+    ("Z", "java/lang/Boolean", "booleanValue"),
+    ("B", "java/lang/Byte", "byteValue"),
+    ("C", "java/lang/Character", "charValue"),
+    ("S", "java/lang/Short", "shortValue"),
+    ("I", "java/lang/Integer", "intValue"),
+    ("J", "java/lang/Long", "longValue"),
+    ("F", "java/lang/Float", "floatValue"),
+    ("D", "java/lang/Double", "doubleValue"),
+];
+
+fn wrapper_of(field_type: &str) -> Option<(&'static str, &'static str, &'static str)> {
+    return WRAPPERS.iter().find(|(primitive, wrapper, _)| *primitive == field_type || field_type == format!("L{};", wrapper)).copied();
+}
+
+// widens the primitive on top of the operand stack (JLS 5.1.2), false if it can't be
+fn widen(code: &mut Code, from: &str, to: &str) -> bool {
+    if from == to {
+        return true;
+    }
+    let wider = match from {
+        "B" => "SIJFD",
+        "S" | "C" => "IJFD",
+        "I" => "JFD",
+        "J" => "FD",
+        "F" => "D",
+        _ => "",
+    };
+    if !wider.contains(to) {
+        return false;
+    }
+    let opcode = match (computational_type(from), to) {
+        (b'I', "J") => 0x85,
+        (b'I', "F") => 0x86,
+        (b'I', "D") => 0x87,
+        (b'J', "F") => 0x89,
+        (b'J', "D") => 0x8a,
+        (b'F', "D") => 0x8d,
+        // a byte, short or char is an int already
+        _ => return true,
+    };
+    code.emit(&[opcode], slot_size(from), slot_size(to));
+    return true;
+}
+
+/*
+Converts the value on top of the operand stack from type `from` to type
+`to` as LambdaMetafactory does: primitives are widened, boxed or unboxed
+and references cast. `functional` is the type with the type variables of
+the interface filled in, it tells which wrapper an erased reference is
+unboxed from. False if there is no such conversion.
+ */
+fn convert(code: &mut Code, constants: &mut Constants, from: &str, to: &str, functional: &str) -> bool {
+    if from == to {
+        return true;
+    }
+    let is_primitive = |field_type: &str| field_type.len() == 1;
+    match (is_primitive(from), is_primitive(to)) {
+        (true, true) => return widen(code, from, to),
+        (true, false) => {
+            let (_, wrapper, _) = match wrapper_of(from) {
+                Some(w) => w,
+                None => return false,
+            };
+            let value_of = constants.method_ref(wrapper, "valueOf", &format!("({})L{};", from, wrapper));
+            code.emit(&[0xb8, value_of[0], value_of[1]], slot_size(from), 1);
+            return true;
+        }
+        (false, true) => {
+            let (primitive, wrapper, unbox) = match wrapper_of(functional).or_else(|| wrapper_of(from)).or_else(|| wrapper_of(to)) {
+                Some(w) => w,
+                None => return false,
+            };
+            if from != format!("L{};", wrapper) {
+                let class = constants.class_ref(wrapper);
+                code.emit(&[0xc0, class[0], class[1]], 1, 1);
+            }
+            let value = constants.method_ref(wrapper, unbox, &format!("(){}", primitive));
+            code.emit(&[0xb6, value[0], value[1]], 1, slot_size(primitive));
+            return widen(code, primitive, to);
+        }
+        (false, false) => {
+            if to != "Ljava/lang/Object;" {
+                let class_name = to.strip_prefix('L').map_or(to, |n| n.trim_end_matches(';'));
+                let class = constants.class_ref(class_name);
+                code.emit(&[0xc0, class[0], class[1]], 1, 1);
+            }
+            return true;
+        }
+    }
+}
+
+/*
+The code of the interface method: it loads the captured values and its
+arguments, converted to the types the implementation takes, calls the
+implementation with them, the first one being the receiver unless the
+implementation is static, and returns what it returns, converted to the
+type the interface method returns. A constructor reference makes the new
+object first and returns that.
+ */
+fn forwarding_code(constants: &mut Constants, class_name: &str, captured: &[String], interface_type: &str, instantiated: &str,
+                   kind: u8, implementation: &ResolvedMethod) -> Result<(usize, Vec<u8>), JavaError> {
+    let method = &implementation.method;
+    let mismatch = || conversion_error(format!("Lambda of type {} for {}.{}{}", interface_type, implementation.class.name, method.name, method.descriptor));
+    let interface_method = MethodDescriptor::parse(interface_type).ok_or_else(mismatch)?;
+    let functional = MethodDescriptor::parse(instantiated).ok_or_else(mismatch)?;
+    let target = MethodDescriptor::parse(&method.descriptor).ok_or_else(mismatch)?;
+    if functional.parameter_types.len() != interface_method.parameter_types.len() {
+        return Err(mismatch());
+    }
+
+    let mut code = Code::new();
+    let mut target_types = target.parameter_types.clone();
+    let mut returned = target.return_type.clone();
+    match kind {
+        REF_INVOKE_STATIC => {}
+        REF_NEW_INVOKE_SPECIAL => {
+            let class = constants.class_ref(&implementation.class.name);
+            code.emit(&[0xbb, class[0], class[1]], 0, 1);
+            code.emit(&[0x59], 1, 2);
+            returned = format!("L{};", implementation.class.name);
+        }
+        _ => target_types.insert(0, format!("L{};", implementation.class.name)),
+    }
+    if target_types.len() != captured.len() + interface_method.parameter_types.len() {
+        return Err(mismatch());
+    }
+
+    // the captured values come first, of the types the call site had them
+    for (n, field_type) in captured.iter().enumerate() {
+        let field = constants.field_ref(class_name, &format!("arg${}", n + 1), field_type);
+        code.emit(&[0x2a], 0, 1);
+        code.emit(&[0xb4, field[0], field[1]], 1, slot_size(field_type));
+        if !convert(&mut code, constants, field_type, &target_types[n], field_type) {
+            return Err(mismatch());
+        }
+    }
+    let mut local = 1;
+    for (n, field_type) in interface_method.parameter_types.iter().enumerate() {
+        code.load(field_type, local);
+        local += slot_size(field_type);
+        if !convert(&mut code, constants, field_type, &target_types[captured.len() + n], &functional.parameter_types[n]) {
+            return Err(mismatch());
+        }
+    }
+
+    let arg_slot_count: usize = target_types.iter().map(|t| slot_size(t)).sum();
+    let return_slot_count = slot_size(&target.return_type);
+    match kind {
+        REF_INVOKE_STATIC => code.emit(&[0xb8, 0, 1], arg_slot_count, return_slot_count),
+        REF_INVOKE_SPECIAL => code.emit(&[0xb7, 0, 1], arg_slot_count, return_slot_count),
+        // the constructor takes the copy of the new object, the other one is returned
+        REF_NEW_INVOKE_SPECIAL => code.emit(&[0xb7, 0, 1], arg_slot_count + 1, 0),
+        REF_INVOKE_INTERFACE => code.emit(&[0xb9, 0, 1, arg_slot_count as u8, 0], arg_slot_count, return_slot_count),
+        _ => code.emit(&[0xb6, 0, 1], arg_slot_count, return_slot_count),
+    }
+
+    match interface_method.return_type.as_str() {
+        "V" => {
+            match slot_size(&returned) {
+                2 => code.emit(&[0x58], 2, 0),
+                1 => code.emit(&[0x57], 1, 0),
+                _ => {}
+            }
+            code.emit(&[0xb1], 0, 0);
+        }
+        field_type => {
+            if returned == "V" || !convert(&mut code, constants, &returned, field_type, &functional.return_type) {
+                return Err(mismatch());
+            }
+            let opcode = match computational_type(field_type) {
+                b'J' => 0xad,
+                b'F' => 0xae,
+                b'D' => 0xaf,
+                b'L' => 0xb0,
+                _ => 0xac,
+            };
+            code.emit(&[opcode], slot_size(field_type), 0);
+        }
+    }
+    return Ok((code.max_stack, code.bytes));
+}
+
+// the code of the constructor of a lambda class: it stores its arguments in the fields
+fn constructor_code(constants: &mut Constants, class_name: &str, captured: &[String]) -> (usize, Vec<u8>) {
+    let mut code = Code::new();
+    let object_init = constants.method_ref("java/lang/Object", "<init>", "()V");
+    code.emit(&[0x2a], 0, 1);
+    code.emit(&[0xb7, object_init[0], object_init[1]], 1, 0);
+    let mut local = 1;
+    for (n, field_type) in captured.iter().enumerate() {
+        let field = constants.field_ref(class_name, &format!("arg${}", n + 1), field_type);
+        code.emit(&[0x2a], 0, 1);
+        code.load(field_type, local);
+        code.emit(&[0xb5, field[0], field[1]], 1 + slot_size(field_type), 0);
+        local += slot_size(field_type);
+    }
+    code.emit(&[0xb1], 0, 0);
+    return (code.max_stack, code.bytes);
+}
@@ -0,0 +1,23 @@
+use std::rc::Rc;
+use crate::class::Class;
+use crate::class::registry::LoaderId;
+use crate::env::env::Environment;
+use crate::env::error::JavaError;
+use crate::env::object::ObjRef;
+
+/*
+The java.lang.Class object of `class`, made the first time it is asked
+for and kept by the class from then on, which makes it a root. Its Java
+fields stay zeroed, what the VM knows about the class is in Class.
+Allocating here never collects garbage.
+ */
+pub fn class_mirror(env: &mut Environment, class: &Rc<Class>) -> Result<ObjRef, JavaError> {
+    if let Some(mirror) = class.mirror.get() {
+        return Ok(mirror);
+    }
+    let java_lang_class = env.load_class_from(LoaderId::BOOTSTRAP, "java/lang/Class")?
+        .ok_or_else(|| JavaError::new("java/lang/NoClassDefFoundError", "java/lang/Class".to_string()))?;
+    let mirror = env.heap.alloc_object(&java_lang_class)?;
+    class.mirror.set(Some(mirror));
+    return Ok(mirror);
+}
@@ -1,6 +1,16 @@
 pub mod object;
+pub mod heap;
+pub mod gc;
 pub mod basic_env_elements;
 pub mod env;
+pub mod error;
 pub mod inst;
 pub mod insts;
-mod byte_code_reader;
+pub mod byte_code_reader;
+pub mod call_site;
+pub mod concat;
+pub mod lambda;
+pub mod native;
+pub mod stack_trace;
+pub mod string;
+pub mod mirror;
@@ -0,0 +1,17 @@
+use crate::env::basic_env_elements::Slot;
+use crate::env::error::JavaError;
+use crate::env::inst::ExecContext;
+
+/*
+The implementation of a native method, in Rust. It gets the arguments,
+`this` first for instance methods, and returns the slots of the return
+value, none for void. There's no frame for it: it runs on the frame of
+the invoke instruction, which is ctx.frame, with the arguments still on
+its operand stack.
+ */
+pub type Native = fn(&mut ExecContext, &[Slot]) -> Result<Vec<Slot>, JavaError>;
+
+// for the natives there's nothing to do for, e.g. setting up from a class data archive this VM doesn't have
+pub fn no_op(_ctx: &mut ExecContext, _args: &[Slot]) -> Result<Vec<Slot>, JavaError> {
+    return Ok(vec![]);
+}
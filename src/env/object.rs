@@ -1,3 +1,175 @@
+use std::rc::Rc;
+use crate::class::Class;
+use crate::env::basic_env_elements::{LocalVars, ThreadId};
+use crate::env::error::JavaError;
+
+/*
+Objects live on the Heap and are used through references to them, which
+are cheap to copy: a Slot holds Option<ObjRef>, None being null. An object
+is a header followed by its instance fields, or by the elements of an
+array.
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ObjRef(pub u32);
+
+// the lock of an object, unlocked without an owner
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Monitor {
+    pub owner: Option<ThreadId>,
+    // how many times the owner has entered it
+    pub count: u32,
+}
+
+/*
+A thread may enter a monitor again while it owns it; it is released once
+it has been exited as many times. Threads don't run concurrently and
+there's no scheduler to let an owner run until it releases its monitor,
+so entering one another thread owns fails rather than waiting forever.
+ */
+impl Monitor {
+    pub fn enter(&mut self, thread: ThreadId) -> Result<(), JavaError> {
+        match self.owner {
+            Some(owner) if owner != thread => {
+                return Err(JavaError::new("java/lang/InternalError",
+                    format!("monitor is owned by thread #{}, which can't run until thread #{} is done", owner.0, thread.0)));
+            }
+            _ => {
+                self.owner = Some(thread);
+                self.count += 1;
+            }
+        }
+        return Ok(());
+    }
+
+    pub fn exit(&mut self, thread: ThreadId) -> Result<(), JavaError> {
+        if self.owner != Some(thread) {
+            return Err(JavaError::new("java/lang/IllegalMonitorStateException", "current thread is not owner".to_string()));
+        }
+        self.count -= 1;
+        if self.count == 0 {
+            self.owner = None;
+        }
+        return Ok(());
+    }
+}
+
+pub struct Header {
+    pub class: Rc<Class>,
+    // the identity hash code, what Object.hashCode returns unless overridden
+    pub hash_code: i32,
+    pub monitor: Monitor,
+}
+
+// the typed storage of an object
+pub enum Body {
+    // instance fields, at the slots of the class's field layout
+    Fields(LocalVars),
+    // boolean and byte arrays, as baload and bastore work on both
+    Bytes(Vec<i8>),
+    Chars(Vec<u16>),
+    Shorts(Vec<i16>),
+    Ints(Vec<i32>),
+    Longs(Vec<i64>),
+    Floats(Vec<f32>),
+    Doubles(Vec<f64>),
+    // arrays of classes, interfaces or arrays
+    Refs(Vec<Option<ObjRef>>),
+}
+
+// the bytes of the header: the class pointer, the hash code and the monitor word
+const HEADER_SIZE: usize = 16;
+
+impl Body {
+    // zeroed storage for an instance of `class`, or an array of it with `length` elements
+    pub fn new(class: &Class, length: usize) -> Body {
+        let component = match class.component_type() {
+            Some(component) => component,
+            None => return Body::Fields(LocalVars::new(class.instance_slot_count)),
+        };
+        return match component.as_bytes()[0] {
+            b'Z' | b'B' => Body::Bytes(vec![0; length]),
+            b'C' => Body::Chars(vec![0; length]),
+            b'S' => Body::Shorts(vec![0; length]),
+            b'I' => Body::Ints(vec![0; length]),
+            b'J' => Body::Longs(vec![0; length]),
+            b'F' => Body::Floats(vec![0.0; length]),
+            b'D' => Body::Doubles(vec![0.0; length]),
+            _ => Body::Refs(vec![None; length]),
+        };
+    }
+
+    /*
+    The bytes an instance of `class`, or an array of it with `length`
+    elements, takes on the heap, rounded up to 8. Fields take a slot of 4
+    bytes each, arrays store their length after the header.
+     */
+    pub fn size_of(class: &Class, length: usize) -> usize {
+        let size = match class.component_type() {
+            None => HEADER_SIZE + class.instance_slot_count * 4,
+            Some(component) => {
+                let element_size = match component.as_bytes()[0] {
+                    b'Z' | b'B' => 1,
+                    b'C' | b'S' => 2,
+                    b'J' | b'D' => 8,
+                    _ => 4,
+                };
+                HEADER_SIZE + 4 + element_size * length
+            }
+        };
+        return (size + 7) & !7;
+    }
+}
+
 pub struct Object {
-    // todo
-}
\ No newline at end of file
+    pub header: Header,
+    pub body: Body,
+}
+
+impl Object {
+    pub fn class(&self) -> &Rc<Class> {
+        return &self.header.class;
+    }
+
+    // the instance fields, None for arrays
+    pub fn fields(&self) -> Option<&LocalVars> {
+        return match &self.body {
+            Body::Fields(fields) => Some(fields),
+            _ => None,
+        };
+    }
+
+    pub fn fields_mut(&mut self) -> Option<&mut LocalVars> {
+        return match &mut self.body {
+            Body::Fields(fields) => Some(fields),
+            _ => None,
+        };
+    }
+
+    pub fn size(&self) -> usize {
+        return Body::size_of(&self.header.class, self.array_length().unwrap_or(0));
+    }
+
+    // the references it holds, what tracing follows
+    pub fn references(&self) -> Vec<ObjRef> {
+        return match &self.body {
+            Body::Fields(fields) => fields.refs().collect(),
+            Body::Refs(elements) => elements.iter().flatten().copied().collect(),
+            _ => vec![],
+        };
+    }
+
+    // the number of elements, None if it isn't an array
+    pub fn array_length(&self) -> Option<usize> {
+        return match &self.body {
+            Body::Fields(_) => None,
+            Body::Bytes(v) => Some(v.len()),
+            Body::Chars(v) => Some(v.len()),
+            Body::Shorts(v) => Some(v.len()),
+            Body::Ints(v) => Some(v.len()),
+            Body::Longs(v) => Some(v.len()),
+            Body::Floats(v) => Some(v.len()),
+            Body::Doubles(v) => Some(v.len()),
+            Body::Refs(v) => Some(v.len()),
+        };
+    }
+}
@@ -0,0 +1,137 @@
+use std::fmt;
+use crate::env::basic_env_elements::{Frame, Slot, Thread};
+use crate::env::error::{JavaError, ThrowableInfo};
+use crate::env::inst::ExecContext;
+use crate::env::object::Body;
+
+// a frame of a Java stack trace, as java.lang.StackTraceElement has it
+#[derive(Clone, PartialEq, Debug)]
+pub struct StackTraceElement {
+    // the binary name with '.', e.g. "java.lang.String"
+    pub class_name: String,
+    pub method_name: String,
+    // the SourceFile of the class
+    pub file_name: Option<String>,
+    // None without a LineNumberTable
+    pub line_number: Option<u16>,
+}
+
+impl StackTraceElement {
+    // where `frame` is, at the instruction it executes or the invoke it waits on
+    pub fn of_frame(frame: &Frame) -> StackTraceElement {
+        return StackTraceElement {
+            class_name: frame.class.name.replace('/', "."),
+            method_name: frame.method.name.clone(),
+            file_name: frame.class.source_file.clone(),
+            line_number: frame.method.get_line_number(frame.pc),
+        }
+    }
+}
+
+// printed as Throwable.printStackTrace prints it, e.g. "fox.Calc.div(Calc.java:12)"
+impl fmt::Display for StackTraceElement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match (&self.file_name, self.line_number) {
+            (Some(file), Some(line)) => write!(f, "{}.{}({}:{})", self.class_name, self.method_name, file, line),
+            (Some(file), None) => write!(f, "{}.{}({})", self.class_name, self.method_name, file),
+            (None, _) => write!(f, "{}.{}(Unknown Source)", self.class_name, self.method_name),
+        };
+    }
+}
+
+// the frames of `thread`, the innermost first
+pub fn capture(thread: &Thread) -> Vec<StackTraceElement> {
+    return thread.stack.frames().rev().map(StackTraceElement::of_frame).collect();
+}
+
+// the Java stack of `thread` as jstack prints it, for diagnostics
+pub fn thread_dump(thread: &Thread) -> String {
+    let mut dump = format!("\"{}\" #{}\n", thread.name, thread.id.0);
+    for element in capture(thread) {
+        dump.push_str(&format!("\tat {}\n", element));
+    }
+    return dump;
+}
+
+/*
+Throwable.fillInStackTrace(int), which Throwable's constructors call: it
+records the frames of the thread, leaving out the ones of fillInStackTrace
+and of the constructors of the Throwable's class and its super classes, as
+HotSpot does. The frames are kept with its ThrowableInfo, the Java fields
+only get their number.
+ */
+pub fn fill_in_stack_trace(ctx: &mut ExecContext, args: &[Slot]) -> Result<Vec<Slot>, JavaError> {
+    // invokevirtual has checked it isn't null
+    let this = args[0].val_ref.unwrap();
+    let class = ctx.env.heap.get(this).class().clone();
+    let frames: Vec<&Frame> = ctx.frames().collect();
+    let mut skip = frames.iter().take_while(|f| f.method.name == "fillInStackTrace" && class.is_subclass_of(&f.class)).count();
+    skip += frames[skip..].iter().take_while(|f| f.method.name == "<init>" && class.is_subclass_of(&f.class)).count();
+    let stack_trace: Vec<StackTraceElement> = frames[skip..].iter().map(|f| StackTraceElement::of_frame(f)).collect();
+
+    let mut throwable = Some(class);
+    while let Some(c) = throwable {
+        if c.name == "java/lang/Throwable" {
+            if let Some(depth) = c.get_field("depth", "I") {
+                ctx.env.heap.get_mut(this).fields_mut().unwrap().set_i32(depth.slot_id, stack_trace.len() as i32)?;
+            }
+            break;
+        }
+        throwable = c.super_class.clone();
+    }
+    let message = ctx.env.heap.throwable_info(this).map(|i| i.message.clone()).unwrap_or_default();
+    ctx.env.heap.set_throwable_info(this, ThrowableInfo { message, stack_trace });
+    return Ok(vec![args[0]]);
+}
+
+/*
+StackTraceElement.initStackTraceElements(StackTraceElement[], Throwable),
+which Throwable.getStackTrace gets to through StackTraceElement.of: it
+fills in the elements, made by the caller, from the frames fillInStackTrace
+recorded. declaringClassObject is the mirror of the class if it is still
+loaded, StackTraceElement.computeFormat needs it; the line is -1 unknown.
+ */
+pub fn init_stack_trace_elements(ctx: &mut ExecContext, args: &[Slot]) -> Result<Vec<Slot>, JavaError> {
+    let (elements, throwable) = match (args[0].val_ref, args[1].val_ref) {
+        (Some(elements), Some(throwable)) => (elements, throwable),
+        _ => return Err(JavaError::new("java/lang/NullPointerException", String::new())),
+    };
+    let stack_trace = ctx.env.heap.throwable_info(throwable).map(|i| i.stack_trace.clone()).unwrap_or_default();
+    let class = ctx.env.heap.get(elements).class().component_class.clone()
+        .ok_or_else(|| JavaError::new("java/lang/IncompatibleClassChangeError", "not an array".to_string()))?;
+    let field = |name: &str, descriptor: &str| class.get_field(name, descriptor).map(|f| f.slot_id).ok_or_else(|| {
+        JavaError::new("java/lang/NoSuchFieldError", format!("java.lang.StackTraceElement.{}", name))
+    });
+    let (declaring_class, method_name, file_name) = (field("declaringClass", "Ljava/lang/String;")?,
+        field("methodName", "Ljava/lang/String;")?, field("fileName", "Ljava/lang/String;")?);
+    let (line_number, declaring_class_object) = (field("lineNumber", "I")?, field("declaringClassObject", "Ljava/lang/Class;")?);
+
+    let length = ctx.env.heap.get(elements).array_length().unwrap_or(0);
+    for (n, frame) in stack_trace.iter().enumerate().take(length) {
+        let element = match &ctx.env.heap.get(elements).body {
+            Body::Refs(refs) => refs[n],
+            _ => None,
+        };
+        let element = element.ok_or_else(|| JavaError::new("java/lang/NullPointerException", String::new()))?;
+        let value = ctx.intern_string(&frame.class_name)?;
+        ctx.env.heap.set_field_ref(element, declaring_class, Some(value))?;
+        let value = ctx.intern_string(&frame.method_name)?;
+        ctx.env.heap.set_field_ref(element, method_name, Some(value))?;
+        if let Some(file) = &frame.file_name {
+            let value = ctx.intern_string(file)?;
+            ctx.env.heap.set_field_ref(element, file_name, Some(value))?;
+        }
+        let binary_name = frame.class_name.replace('.', "/");
+        let declaring = ctx.env.registry.classes().find(|c| c.name == binary_name).cloned();
+        let mirror = match declaring {
+            Some(class) => Some(ctx.class_mirror(&class)?),
+            None => None,
+        };
+        ctx.env.heap.set_field_ref(element, declaring_class_object, mirror)?;
+        let line = frame.line_number.map_or(-1, |line| line as i32);
+        if let Some(fields) = ctx.env.heap.get_mut(element).fields_mut() {
+            fields.set_i32(line_number, line)?;
+        }
+    }
+    return Ok(vec![]);
+}
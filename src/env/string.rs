@@ -0,0 +1,94 @@
+use std::rc::Rc;
+use crate::class::Class;
+use crate::class::registry::LoaderId;
+use crate::env::env::Environment;
+use crate::env::error::JavaError;
+use crate::env::object::{Body, ObjRef};
+
+/*
+String objects laid out as java.lang.String has them since JDK 9: the
+characters are in the byte[] `value`, a byte each if they are all
+Latin-1 (coder 0), else two bytes of UTF-16 each (coder 1) in the byte
+order of the platform, little-endian as on x86. Class libraries before
+that keep a char[] instead, which is filled in if that is what the
+String class declares.
+ */
+
+const LATIN1: i32 = 0;
+const UTF16: i32 = 1;
+
+fn bootstrap_class(env: &mut Environment, name: &str) -> Result<Rc<Class>, JavaError> {
+    return env.load_class_from(LoaderId::BOOTSTRAP, name)?
+        .ok_or_else(|| JavaError::new("java/lang/NoClassDefFoundError", name.to_string()));
+}
+
+// an array of the bootstrap array class `name` holding `elements`
+fn new_array(env: &mut Environment, name: &str, length: usize, elements: Body) -> Result<ObjRef, JavaError> {
+    let class = bootstrap_class(env, name)?;
+    let array = env.heap.alloc_array(&class, length)?;
+    env.heap.get_mut(array).body = elements;
+    return Ok(array);
+}
+
+/*
+The String interned for `value`, made the first time it is asked for, as
+ldc and ConstantValue attributes of String type need.
+ */
+pub fn intern(env: &mut Environment, value: &str) -> Result<ObjRef, JavaError> {
+    if let Some(string) = env.heap.interned(value) {
+        return Ok(string);
+    }
+    let string = new_string(env, value)?;
+    env.heap.intern(value, string);
+    return Ok(string);
+}
+
+/*
+A new String holding `value`, e.g. the result of a concatenation.
+Allocating here never collects garbage, so the new value array stays
+alive until the String holds it.
+ */
+pub fn new_string(env: &mut Environment, value: &str) -> Result<ObjRef, JavaError> {
+    let class = bootstrap_class(env, "java/lang/String")?;
+    let (field, array, coder) = if let Some(field) = class.get_field("value", "[B") {
+        let (bytes, coder): (Vec<i8>, i32) = match value.chars().all(|c| (c as u32) <= 0xff) {
+            true => (value.chars().map(|c| c as u32 as i8).collect(), LATIN1),
+            false => (value.encode_utf16().flat_map(|c| c.to_le_bytes()).map(|b| b as i8).collect(), UTF16),
+        };
+        (field, new_array(env, "[B", bytes.len(), Body::Bytes(bytes))?, Some(coder))
+    } else if let Some(field) = class.get_field("value", "[C") {
+        let chars: Vec<u16> = value.encode_utf16().collect();
+        (field, new_array(env, "[C", chars.len(), Body::Chars(chars))?, None)
+    } else {
+        return Err(JavaError::new("java/lang/InternalError", "java.lang.String has no value field".to_string()));
+    };
+
+    let string = env.heap.alloc_object(&class)?;
+    env.heap.set_field_ref(string, field.slot_id, Some(array))?;
+    if let (Some(coder), Some(coder_field)) = (coder, class.get_field("coder", "B")) {
+        env.heap.get_mut(string).fields_mut().unwrap().set_i32(coder_field.slot_id, coder)?;
+    }
+    return Ok(string);
+}
+
+// the characters of a String object, None if it isn't one
+pub fn value_of(env: &Environment, string: ObjRef) -> Option<String> {
+    let class = env.heap.get(string).class();
+    if class.name != "java/lang/String" {
+        return None;
+    }
+    let array = class.get_field("value", "[B").or_else(|| class.get_field("value", "[C"))
+        .and_then(|field| env.heap.get(string).fields()?.get_ref(field.slot_id).ok()?)?;
+    let coder = class.get_field("coder", "B")
+        .and_then(|field| env.heap.get(string).fields()?.get_i32(field.slot_id).ok())
+        .unwrap_or(LATIN1);
+    return match &env.heap.get(array).body {
+        Body::Bytes(bytes) if coder == LATIN1 => Some(bytes.iter().map(|b| *b as u8 as char).collect()),
+        Body::Bytes(bytes) => {
+            let chars: Vec<u16> = bytes.chunks(2).map(|c| u16::from_le_bytes([c[0] as u8, c[1] as u8])).collect();
+            Some(String::from_utf16_lossy(&chars))
+        }
+        Body::Chars(chars) => Some(String::from_utf16_lossy(chars)),
+        _ => None,
+    };
+}
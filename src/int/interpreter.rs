@@ -1,9 +1,325 @@
-use crate::env::basic_env_elements::Thread;
+use std::rc::Rc;
+use crate::class::Class;
+use crate::class::linker::resolve_class;
+use crate::class::member::Method;
+use crate::class::registry::LoaderId;
+use crate::env::basic_env_elements::{Frame, Slot, Thread};
+use crate::env::byte_code_reader::ByteCodeReader;
+use crate::env::env::Environment;
+use crate::env::error::{JavaError, ThrowableInfo};
+use crate::env::gc::{class_roots, thread_roots};
+use crate::env::inst::{ExecContext, Flow};
+use crate::env::mirror::class_mirror;
+use crate::env::insts::factory::fetch_instruction;
+use crate::env::object::ObjRef;
+use crate::env::stack_trace::capture;
 
-pub fn interpret(thread: Thread) {
+fn stack_overflow() -> JavaError {
+    return JavaError::new("java/lang/StackOverflowError", String::new());
+}
+
+fn collect_garbage(env: &mut Environment, thread: &Thread) {
+    let mut roots: Vec<ObjRef> = vec![];
+    class_roots(env, &mut roots);
+    thread_roots(thread, &mut roots);
+    env.heap.collect(roots);
+}
+
+/*
+A synchronized method holds a monitor while it runs, that of `this` or,
+for a static method, that of its class's mirror (JVMS 2.11.10). It is
+entered once the method's frame is on top of the stack, where the
+arguments are roots should making the mirror collect garbage, and exited
+when the frame is popped, whether the method returns or throws.
+ */
+fn enter_method_monitor(env: &mut Environment, thread: &mut Thread) -> Result<(), JavaError> {
+    let frame = thread.stack.get_current_frame_mut().unwrap();
+    if !frame.method.is_synchronized() {
+        return Ok(());
+    }
+    let object = match frame.method.is_static() {
+        true => {
+            let class = frame.class.clone();
+            match class_mirror(env, &class) {
+                Ok(mirror) => mirror,
+                Err(_) => {
+                    collect_garbage(env, thread);
+                    class_mirror(env, &class)?
+                }
+            }
+        }
+        false => frame.local_vars.get_ref(0)?.ok_or_else(|| {
+            JavaError::new("java/lang/NullPointerException", "Cannot enter synchronized method because \"this\" is null".to_string())
+        })?,
+    };
+    env.heap.get_mut(object).header.monitor.enter(thread.id)?;
+    thread.stack.get_current_frame_mut().unwrap().monitor = Some(object);
+    return Ok(());
+}
+
+fn exit_method_monitor(env: &mut Environment, thread: &mut Thread) -> Result<(), JavaError> {
+    let id = thread.id;
+    if let Some(object) = thread.stack.get_current_frame_mut().unwrap().monitor.take() {
+        env.heap.get_mut(object).header.monitor.exit(id)?;
+    }
+    return Ok(());
+}
+
+// pops the frame on top of the stack as an exception goes through it
+fn pop_frame(env: &mut Environment, thread: &mut Thread) {
+    // the exception on its way out wins over an unbalanced monitor
+    let _ = exit_method_monitor(env, thread);
+    thread.stack.pop();
+}
+
+// pushes a frame for `method`, moving its arguments off the caller's operand stack
+fn push_frame(env: &mut Environment, thread: &mut Thread, class: Rc<Class>, method: Rc<Method>) -> Result<(), JavaError> {
+    if thread.stack.is_full() {
+        return Err(stack_overflow());
+    }
+    let mut frame = Frame::new(class, method);
+    if let Some(caller) = thread.stack.get_current_frame_mut() {
+        // the last argument is on top, longs and doubles take two slots
+        for n in (0..frame.method.arg_slot_count).rev() {
+            frame.local_vars.set_slot(n, caller.operand_stack.pop_slot()?)?;
+        }
+    }
+    thread.stack.push(frame);
+    if let Err(e) = enter_method_monitor(env, thread) {
+        thread.stack.pop();
+        return Err(e);
+    }
+    return Ok(());
+}
+
+// takes the return value off the operand stack of a returning frame
+fn return_value(frame: &mut Frame) -> Result<Vec<Slot>, JavaError> {
+    let mut value: Vec<Slot> = vec![];
+    for _ in 0..frame.method.return_slot_count {
+        value.insert(0, frame.operand_stack.pop_slot()?);
+    }
+    return Ok(value);
+}
+
+// pops the frames down to the one at `depth`, that one included
+fn unwind(env: &mut Environment, thread: &mut Thread, depth: usize) {
+    while thread.stack.len() >= depth {
+        pop_frame(env, thread);
+    }
+}
+
+/*
+The pc of the handler of the first entry of the exception table that
+catches `class` where `frame` is (JVMS 2.10). A catch type that can't be
+resolved fails the search with the resolution error, along with the
+handler pc of its entry: as in HotSpot, that error is thrown instead,
+from there.
+ */
+fn find_handler(env: &mut Environment, frame: &Frame, class: &Class) -> Result<Option<usize>, (usize, JavaError)> {
+    for entry in &frame.method.exception_table {
+        if frame.pc < entry.start_pc as usize || frame.pc >= entry.end_pc as usize {
+            continue;
+        }
+        // 0 catches everything, as finally does
+        let catches = entry.catch_type == 0 || match resolve_class(env, &frame.class, entry.catch_type) {
+            Ok(catch_type) => class.is_subclass_of(&catch_type),
+            Err(e) => return Err((entry.handler_pc as usize, e)),
+        };
+        if catches {
+            return Ok(Some(entry.handler_pc as usize));
+        }
+    }
+    return Ok(None);
+}
+
+// an instance of a Throwable class the VM raises, without running its constructor
+fn new_throwable(env: &mut Environment, thread: &Thread, class_name: &str) -> Option<ObjRef> {
+    let class = env.load_class_from(LoaderId::BOOTSTRAP, class_name).ok()??;
+    if let Ok(r) = env.heap.alloc_object(&class) {
+        return Some(r);
+    }
+    collect_garbage(env, thread);
+    return env.heap.alloc_object(&class).ok();
+}
+
+/*
+Throws `e` from the frame on top of `thread`: the frames are searched for
+a handler from the top down to the one at `depth`, and popped until there
+is one. Once found, its frame goes on at the handler with the exception
+alone on its operand stack. If there's none `e` is handed back, and the
+frames are gone. The VM's own exceptions are made objects first; one
+whose class can't be loaded, or that there's no memory for, can't be
+caught.
+ */
+fn throw(env: &mut Environment, thread: &mut Thread, depth: usize, mut e: JavaError) -> Result<(), JavaError> {
+    if e.stack_trace.is_empty() {
+        e.stack_trace = capture(thread);
+    }
+    if e.object.is_none() {
+        e.object = new_throwable(env, thread, &e.class_name);
+    }
+    let object = match e.object {
+        Some(object) => object,
+        None => {
+            unwind(env, thread, depth);
+            return Err(e);
+        }
+    };
+    if env.heap.throwable_info(object).is_none() {
+        env.heap.set_throwable_info(object, ThrowableInfo {
+            message: e.message.clone(),
+            stack_trace: e.stack_trace.clone(),
+        });
+    }
+
+    let class = env.heap.get(object).class().clone();
+    while thread.stack.len() >= depth {
+        let frame = thread.stack.get_current_frame_mut().unwrap();
+        match find_handler(env, frame, &class) {
+            Ok(Some(handler_pc)) => {
+                frame.operand_stack.clear();
+                // the verifier has made room for it in methods with handlers
+                if frame.operand_stack.push_ref(Some(object)).is_ok() {
+                    frame.next_pc = handler_pc;
+                    return Ok(());
+                }
+            }
+            Ok(None) => {}
+            Err((handler_pc, resolution_error)) => {
+                frame.pc = handler_pc;
+                return throw(env, thread, depth, resolution_error);
+            }
+        }
+        pop_frame(env, thread);
+    }
+    return Err(e);
+}
+
+/*
+Runs `method` to completion on `thread` and returns its return value, as
+many slots as the return type takes. The arguments fill the first local
+variables, `this` first for instance methods. There has to be room for
+the frame on the stack, else it is a StackOverflowError.
+ */
+pub fn invoke(env: &mut Environment, thread: &mut Thread, class: Rc<Class>, method: Rc<Method>, args: Vec<Slot>) -> Result<Vec<Slot>, JavaError> {
+    if thread.stack.is_full() {
+        return Err(stack_overflow());
+    }
+    let mut frame = Frame::new(class, method);
+    for (n, arg) in args.into_iter().enumerate() {
+        frame.local_vars.set_slot(n, arg)?;
+    }
+    thread.stack.push(frame);
+    if let Err(e) = enter_method_monitor(env, thread) {
+        thread.stack.pop();
+        return Err(e);
+    }
+    return interpret(env, thread);
+}
+
+/*
+The fetch-decode-execute loop. It runs the frame on top of the stack, and
+the frames it calls, until that frame returns; the return value is handed
+back instead of being pushed onto a caller. Exceptions go to the handlers
+of the frames it entered; byte code that doesn't decode is a VerifyError
+and, as exceptions no handler catches, it is handed back as an error and
+the frames entered are dropped.
+ */
+pub fn interpret(env: &mut Environment, thread: &mut Thread) -> Result<Vec<Slot>, JavaError> {
+    let depth = thread.stack.len();
     loop {
-        let pc = thread.pc;
+        let mut frame = thread.stack.pop().unwrap();
+        let method = frame.method.clone();
+        let pc = frame.next_pc;
+        thread.pc = pc as u32;
+
+        let mut reader = ByteCodeReader::new(&method.code);
+        reader.pc = pc;
+        let mut inst = match fetch_instruction(&mut reader) {
+            Ok(inst) => inst,
+            Err(e) => {
+                thread.stack.push(frame);
+                unwind(env, thread, depth);
+                return Err(e);
+            }
+        };
+        frame.pc = pc;
+        frame.next_pc = reader.pc;
 
+        let flow = inst.execute(&mut ExecContext {
+            env,
+            thread,
+            frame: &mut frame,
+            pc,
+        });
+        thread.stack.push(frame);
 
+        match flow {
+            Flow::Next => {}
+            Flow::Branch(offset) => {
+                let frame = thread.stack.get_current_frame_mut().unwrap();
+                frame.next_pc = (pc as i64 + offset as i64) as usize;
+            }
+            // a frame that doesn't fit is thrown from the invoke
+            Flow::Invoke(class, method) => {
+                if let Err(e) = push_frame(env, thread, class, method) {
+                    throw(env, thread, depth, e)?;
+                }
+            }
+            // a return value that isn't there, or a monitor that isn't owned, is thrown from the return, one that doesn't fit from the invoke
+            Flow::Return => {
+                let value = return_value(thread.stack.get_current_frame_mut().unwrap())
+                    .and_then(|value| exit_method_monitor(env, thread).map(|_| value));
+                let value = match value {
+                    Ok(value) => value,
+                    Err(e) => {
+                        throw(env, thread, depth, e)?;
+                        continue;
+                    }
+                };
+                thread.stack.pop();
+
+                if thread.stack.len() < depth {
+                    return Ok(value);
+                }
+                let caller = thread.stack.get_current_frame_mut().unwrap();
+                if let Err(e) = value.into_iter().try_for_each(|slot| caller.operand_stack.push_slot(slot)) {
+                    throw(env, thread, depth, e)?;
+                }
+            }
+            Flow::Throw(e) => {
+                throw(env, thread, depth, e)?;
+            }
+        }
     }
-}
\ No newline at end of file
+}
+
+/*
+Runs `method` as all `thread` does, e.g. main on the main thread. An
+exception that gets out of it kills the thread, which reports it on
+stderr as the default uncaught exception handler does.
+ */
+pub fn run_thread(env: &mut Environment, thread: &mut Thread, class: Rc<Class>, method: Rc<Method>, args: Vec<Slot>) -> Result<Vec<Slot>, JavaError> {
+    let result = invoke(env, thread, class, method, args);
+    if let Err(e) = &result {
+        eprint!("{}", uncaught_exception_report(thread, e));
+    }
+    return result;
+}
+
+// what Throwable.printStackTrace prints, after the name of the thread it killed
+pub fn uncaught_exception_report(thread: &Thread, e: &JavaError) -> String {
+    let mut report = format!("Exception in thread \"{}\" {}\n", thread.name, e);
+    for element in &e.stack_trace {
+        report.push_str(&format!("\tat {}\n", element));
+    }
+    return report;
+}
+
+// runs class initializers on `thread`, to be handed to initialize_class
+pub fn clinit_runner(thread: &mut Thread) -> impl FnMut(&mut Environment, &Rc<Class>, &Rc<Method>) -> Result<(), JavaError> + '_ {
+    return move |env: &mut Environment, class: &Rc<Class>, method: &Rc<Method>| {
+        invoke(env, thread, class.clone(), method.clone(), vec![])?;
+        return Ok(());
+    };
+}
@@ -1,3 +1,6 @@
+// explicit returns and JVMS-style instruction names such as `ILoad_0` are the house style
+#![allow(clippy::needless_return, clippy::module_inception, non_camel_case_types)]
+
 pub mod env;
 pub mod class;
 pub mod int;
@@ -5,22 +8,2156 @@ pub mod int;
 #[cfg(test)]
 mod tests {
     use super::class::simple_loader::simple_loader::*;
+    use super::class::simple_loader::class_reader::{ClassReader, ClassFile};
+    use super::class::simple_loader::class_format_error::FormatErrorReason;
+    use super::class::simple_loader::constant_pool::{ConstantPool, ConstantClassInfo};
+    use super::class::jimage_loader::jimage_loader::JImageClassLoader;
     use super::class::*;
+    use super::class::registry::LoaderId;
+    use super::class::memory_loader::MemoryClassLoader;
+    use super::class::linker::*;
+    use super::class::runtime_constant_pool::*;
+    use super::class::initializer::*;
+    use super::class::class::InitState;
+    use super::class::member::Method;
+    use super::env::basic_env_elements::{Frame, LocalVars, OperandStack, Slot, Thread, ThreadId};
+    use super::env::byte_code_reader::ByteCodeReader;
+    use super::class::simple_loader::attribute_info::BootstrapMethod;
+    use super::env::call_site::{method_handle_object, BootstrapArg, BootstrapCall, MethodHandle};
+    use super::env::inst::{ExecContext, Flow, Instruction};
+    use super::env::insts::comparisons::{TableSwitch, LookupSwitch};
+    use super::env::insts::constants::AConst_Null;
+    use super::env::insts::stack::{Dup, Dup2, Dup_X2};
+    use super::env::insts::factory::{fetch_instruction, mnemonic};
+    use super::env::insts::invokes::invoke_handle;
+    use super::int::interpreter::{invoke, uncaught_exception_report};
+    use super::env::error::JavaError;
+    use super::env::stack_trace::{thread_dump, StackTraceElement};
+    use super::env::object::{Body, ObjRef};
+    use super::env::heap::{parse_heap_size, Heap};
+    use super::env::string::{intern, value_of};
+    use super::env::gc::class_roots;
+    use super::env::gc::generational::Generational;
     use super::env::env::*;
-    use crate::env::basic_env_elements::OperandStack;
+    use std::rc::Rc;
+    use std::fs;
+    use std::io::Read;
+    use std::path::{Path, PathBuf};
+
+    fn fixture(path: &str) -> PathBuf {
+        return Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join(path);
+    }
+
+    // a fresh directory under the system temp dir, removed when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let dir = std::env::temp_dir().join(format!("fox_jvm_{}_{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            return TempDir(dir);
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn classes_are_loaded_from_class_path() {
+        let temp = TempDir::new("class_path");
+        let classes = temp.0.join("classes");
+        fs::create_dir_all(classes.join("fox")).unwrap();
+        fs::copy(fixture("classes/fox/Hello.class"), classes.join("fox").join("Hello.class")).unwrap();
+        fs::create_dir_all(temp.0.join("lib")).unwrap();
+        fs::copy(fixture("lib/hello.jar"), temp.0.join("lib").join("hello.jar")).unwrap();
+
+        let class_path = std::env::join_paths([classes, temp.0.join("lib").join("*")]).unwrap();
+        let loaders = SimpleClassLoader::parse_class_path(class_path.to_str().unwrap());
+        assert_eq!(loaders.len(), 2);
+
+        // fox/Greeter is only in the jar
+        let hello = fs::read(fixture("classes/fox/Hello.class")).unwrap();
+        assert_eq!(loaders[0].read_class_bytes("fox/Hello").unwrap(), Some(hello));
+        assert_eq!(loaders[0].read_class_bytes("fox/Greeter").unwrap(), None);
+        assert!(loaders[1].read_class_bytes("fox/Greeter").unwrap().is_some());
+
+        // the fox classes extend java/lang/Object, which only the JDK has
+        let boot = match boot_loader() {
+            Some(boot) => boot,
+            None => return,
+        };
+        let mut env : Environment = Environment::new(Box::new(boot), no_classes(), Box::new(loaders));
+        assert!(env.load_class("fox/Hello").unwrap().is_some());
+        assert!(env.load_class("fox/Greeter").unwrap().is_some());
+        assert!(env.load_class("fox/Missing").unwrap().is_none());
+    }
+
+    // magic, minor 0, major 58 (Java 14) and the constant_pool_count
+    fn class_header(cp_count: u16) -> Vec<u8> {
+        let mut data = vec![0xCA, 0xFE, 0xBA, 0xBE, 0, 0, 0, 58];
+        data.extend_from_slice(&cp_count.to_be_bytes());
+        return data;
+    }
+
+    #[test]
+    fn bad_magic_is_rejected() {
+        let mut data = class_header(1);
+        data[3] = 0xBF;
+        let err = ClassFile::new(&mut ClassReader::new(data)).err().unwrap();
+        assert_eq!(err.offset, 0);
+        assert_eq!(err.structure, "ClassFile");
+        assert_eq!(err.reason, FormatErrorReason::BadMagic(0xCAFEBABF));
+    }
+
+    #[test]
+    fn truncated_class_is_rejected() {
+        let mut data = class_header(2);
+        data.extend_from_slice(&[1, 0, 5, b'a', b'b']);
+        let err = ClassFile::new(&mut ClassReader::new(data)).err().unwrap();
+        assert_eq!(err.offset, 13);
+        assert_eq!(err.structure, "constant_pool");
+        assert_eq!(err.reason, FormatErrorReason::UnexpectedEnd { wanted: 5, remaining: 2 });
+    }
+
+    #[test]
+    fn unknown_constant_tag_is_rejected() {
+        let mut data = class_header(2);
+        data.extend_from_slice(&[99, 0, 0]);
+        let err = ClassFile::new(&mut ClassReader::new(data)).err().unwrap();
+        assert_eq!(err.offset, 10);
+        assert_eq!(err.reason, FormatErrorReason::UnknownConstantTag(99));
+    }
+
+    #[test]
+    fn malformed_utf8_is_rejected() {
+        let mut data = class_header(2);
+        data.extend_from_slice(&[1, 0, 2, 0xC3, 0x28]);
+        let err = ClassFile::new(&mut ClassReader::new(data)).err().unwrap();
+        assert_eq!(err.offset, 14);
+        assert_eq!(err.reason, FormatErrorReason::MalformedUtf8("malformed input"));
+    }
+
+    #[test]
+    fn multi_byte_utf8_is_decoded() {
+        let data = vec![0, 3, 1, 0, 2, 0xC3, 0xA9, 1, 0, 3, 0xE2, 0x82, 0xAC];
+        let cp = ConstantPool::new(&mut ClassReader::new(data)).ok().unwrap();
+        assert_eq!(cp.get_utf8(1).unwrap().get_str(), "\u{e9}");
+        assert_eq!(cp.get_utf8(2).unwrap().get_str(), "\u{20ac}");
+    }
+
+    #[test]
+    fn class_format_error_is_surfaced_by_environment() {
+        let temp = TempDir::new("bad_class");
+        fs::write(temp.0.join("Bad.class"), [0xCA, 0xFE, 0xBA, 0xBE, 0, 0]).unwrap();
+
+        let app = SimpleClassLoader::new(temp.0.clone(), ClassPathType::Folder);
+        let mut env = Environment::new(no_classes(), no_classes(), Box::new(app));
+        let err = env.load_class("Bad").err().unwrap();
+
+        assert_eq!(err.class_name, "java/lang/ClassFormatError");
+        assert!(err.message.contains("offset 6"));
+    }
+
+    #[test]
+    fn classes_are_read_from_jar() {
+        let loaders = SimpleClassLoader::from_entry(&fixture("lib/hello.jar"));
+        assert_eq!(loaders.len(), 1);
+
+        // Hello.class is deflated in the jar, Greeter.class is stored
+        for name in ["fox/Hello", "fox/Greeter"] {
+            let expected = fs::read(fixture(&format!("classes/{}.class", name))).unwrap();
+            assert_eq!(loaders[0].read_class_bytes(name).unwrap(), Some(expected));
+            assert!(loaders[0].load_class(name).is_ok());
+        }
+        assert_eq!(loaders[0].read_class_bytes("fox/Missing").unwrap(), None);
+    }
+
+    #[test]
+    fn unreadable_archives_are_errors() {
+        let temp = TempDir::new("bad_jar");
+        fs::write(temp.0.join("tiny.jar"), [b'P', b'K']).unwrap();
+        let loader = SimpleClassLoader::new(temp.0.join("tiny.jar"), ClassPathType::Zip);
+        assert_eq!(loader.read_class_bytes("fox/Hello").err().unwrap().kind(), std::io::ErrorKind::InvalidData);
+        let err = loader.load_class("fox/Hello").err().unwrap();
+        assert!(err.to_string().ends_with("tiny.jar: too short for a zip archive"));
+
+        // a deflated entry whose data is broken
+        let mut jar = fs::read(fixture("lib/hello.jar")).unwrap();
+        let name = jar.windows(15).position(|w| w == b"fox/Hello.class").unwrap();
+        let extra_len = u16::from_le_bytes([jar[name - 2], jar[name - 1]]) as usize;
+        jar[name + 15 + extra_len] = 0xff;
+        fs::write(temp.0.join("broken.jar"), jar).unwrap();
+        let loader = SimpleClassLoader::new(temp.0.join("broken.jar"), ClassPathType::Zip);
+        assert!(loader.read_class_bytes("fox/Hello").is_err());
+        assert!(loader.read_class_bytes("fox/Greeter").unwrap().is_some());
+        let mut env = Environment::new(no_classes(), no_classes(), Box::new(loader));
+        assert_eq!(env.load_class("fox/Hello").err().unwrap().class_name, "java/lang/ClassFormatError");
+
+        // sizes in the headers that run past the end of the file are rejected before allocating
+        let jar = fs::read(fixture("lib/hello.jar")).unwrap();
+        let eocd = jar.len() - 22;
+        let mut huge_directory = jar.clone();
+        huge_directory[eocd + 12..eocd + 16].copy_from_slice(&0x7fff_fff0u32.to_le_bytes());
+        fs::write(temp.0.join("huge_directory.jar"), huge_directory).unwrap();
+        let loader = SimpleClassLoader::new(temp.0.join("huge_directory.jar"), ClassPathType::Zip);
+        let err = loader.read_class_bytes("fox/Hello").err().unwrap();
+        assert!(err.to_string().ends_with("central directory past the end of the file"));
+
+        let mut huge_entry = jar;
+        let headers: Vec<usize> = huge_entry.windows(4).enumerate().filter(|(_, w)| *w == b"PK\x01\x02").map(|(at, _)| at).collect();
+        for at in headers {
+            huge_entry[at + 20..at + 24].copy_from_slice(&0x7fff_fff0u32.to_le_bytes());
+            huge_entry[at + 24..at + 28].copy_from_slice(&0xffff_fff0u32.to_le_bytes());
+        }
+        fs::write(temp.0.join("huge_entry.jar"), huge_entry).unwrap();
+        let loader = SimpleClassLoader::new(temp.0.join("huge_entry.jar"), ClassPathType::Zip);
+        let err = loader.read_class_bytes("fox/Hello").err().unwrap();
+        assert!(err.to_string().ends_with("fox/Hello.class: entry past the end of the file"));
+
+        // an entry that doesn't exist has no classes
+        let loader = SimpleClassLoader::new(temp.0.join("missing.jar"), ClassPathType::Zip);
+        assert_eq!(loader.read_class_bytes("fox/Hello").unwrap(), None);
+    }
+
+    #[test]
+    fn wildcard_entry_expands_to_jars() {
+        let loaders = SimpleClassLoader::from_entry(&fixture("lib/*"));
+        assert_eq!(loaders.len(), 1);
+        assert!(loaders[0].read_class_bytes("fox/Hello").unwrap().is_some());
+
+        assert!(SimpleClassLoader::from_entry(&fixture("classes/*")).is_empty());
+    }
+
+    // the JDK the tests run under, if any, for the tests that need a real modules image
+    fn installed_java_home() -> Option<PathBuf> {
+        if let Some(home) = std::env::var_os("JAVA_HOME") {
+            return Some(PathBuf::from(home));
+        }
+        let path = std::env::var_os("PATH")?;
+        let java = std::env::split_paths(&path).map(|dir| dir.join("java")).find(|java| java.is_file())?;
+        return Some(fs::canonicalize(java).ok()?.parent()?.parent()?.to_path_buf());
+    }
+
+    // a loader without any classes, for the levels of the tree a test doesn't need
+    fn no_classes() -> Box<dyn ClassLoader> {
+        return Box::new(Vec::<Box<dyn ClassLoader>>::new());
+    }
+
+    // for code that loads no classes
+    fn empty_env() -> Environment {
+        return Environment::new(no_classes(), no_classes(), no_classes());
+    }
+
+    fn boot_loader() -> Option<JImageClassLoader> {
+        return match installed_java_home() {
+            Some(home) if home.join("lib/modules").is_file() => Some(JImageClassLoader::from_java_home(&home)),
+            _ => None,
+        };
+    }
+
+    #[test]
+    fn classes_are_read_from_jimage() {
+        let java_home = match installed_java_home() {
+            Some(home) if home.join("lib/modules").is_file() => home,
+            _ => return,
+        };
+        let loader = JImageClassLoader::from_java_home(&java_home);
+
+        let data = loader.read_class_bytes("java/lang/String").unwrap().unwrap();
+        let file = ClassFile::new(&mut ClassReader::new(data)).ok().unwrap();
+        let this_class = file.constant_pool.get_any(file.this_class as usize).unwrap();
+        let name_index = this_class.downcast_ref::<ConstantClassInfo>().unwrap().name_index;
+        assert_eq!(file.constant_pool.get_utf8(name_index as usize).unwrap().get_str(), "java/lang/String");
+
+        assert!(loader.read_class_bytes("java/util/concurrent/ConcurrentHashMap").unwrap().is_some());
+        assert!(loader.read_class_bytes("java/lang/NoSuchClass").unwrap().is_none());
+        assert!(loader.read_class_bytes("Unnamed").unwrap().is_none());
+
+        // a JDK without an image has no classes
+        assert_eq!(JImageClassLoader::from_java_home(&fixture("classes")).read_class_bytes("java/lang/String").unwrap(), None);
+    }
+
+    #[test]
+    fn truncated_jimage_is_an_error() {
+        let java_home = match installed_java_home() {
+            Some(home) if home.join("lib/modules").is_file() => home,
+            _ => return,
+        };
+        let mut image = vec![0u8; 0];
+        fs::File::open(java_home.join("lib/modules")).unwrap().take(1 << 22).read_to_end(&mut image).unwrap();
+        let temp = TempDir::new("truncated_jimage");
+        fs::create_dir(temp.0.join("lib")).unwrap();
+
+        // cut inside the index
+        fs::write(temp.0.join("lib/modules"), &image[..1000]).unwrap();
+        let loader = JImageClassLoader::from_java_home(&temp.0);
+        let err = loader.read_class_bytes("java/lang/String").err().unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().ends_with("image index past the end of the file"));
+        let mut env = Environment::new(Box::new(loader), no_classes(), no_classes());
+        assert_eq!(env.load_class("java/lang/String").err().unwrap().class_name, "java/lang/ClassFormatError");
+
+        // cut just after the index, so the resources themselves are missing
+        let u4 = |at: usize| u32::from_le_bytes([image[at], image[at + 1], image[at + 2], image[at + 3]]) as usize;
+        let index_size = 28 + u4(16) * 8 + u4(20) + u4(24);
+        fs::write(temp.0.join("lib/modules"), &image[..index_size]).unwrap();
+        let loader = JImageClassLoader::from_java_home(&temp.0);
+        let err = loader.read_class_bytes("java/lang/String").err().unwrap();
+        assert!(err.to_string().ends_with("resource past the end of the file"));
+        assert_eq!(loader.read_class_bytes("Unnamed").unwrap(), None);
+    }
+
+    #[test]
+    fn class_is_built_from_class_file() {
+        let loader = SimpleClassLoader::new(fixture("classes"), ClassPathType::Folder);
+        let class = loader.load_class("fox/Point").ok().unwrap().unwrap();
+        assert_eq!(class.name, "fox/Point");
+        assert_eq!(class.super_class_name.as_deref(), Some("java/lang/Object"));
+        assert_eq!(class.interface_names, vec!["java/lang/Comparable".to_string()]);
+        assert_eq!(class.source_file.as_deref(), Some("Point.java"));
+        assert!(class.is_public() && !class.is_interface());
+
+        // the long fields take two slots each
+        assert_eq!(class.static_slot_count, 3);
+        assert_eq!(class.instance_slot_count, 4);
+        assert_eq!(class.get_field("y", "J").unwrap().slot_id, 1);
+        assert_eq!(class.get_field("label", "Ljava/lang/Object;").unwrap().slot_id, 3);
+        assert!(class.get_field("DIMENSIONS", "I").unwrap().const_value_index.is_some());
+
+        let init = class.get_method("<init>", "(IJ)V").unwrap();
+        assert_eq!(init.arg_slot_count, 4);
+        assert!(!init.code.is_empty() && init.max_locals >= 4);
+        assert_eq!(init.get_line_number(0), Some(11));
+        assert!(class.get_method("compareTo", "(Lfox/Point;)I").is_some());
+        // the bridge method javac adds for Comparable
+        assert!(class.get_method("compareTo", "(Ljava/lang/Object;)I").is_some());
+        assert!(class.get_main_method().is_none());
+
+        let greeter = loader.load_class("fox/Greeter").ok().unwrap().unwrap();
+        assert!(greeter.is_interface() && greeter.is_abstract());
+        assert!(greeter.get_method("greet", "(Ljava/lang/String;)Ljava/lang/String;").unwrap().is_abstract());
+    }
+
+    #[test]
+    fn bad_member_descriptor_is_rejected() {
+        // this_class #2, super_class 0 and one field "f" of type "Q"
+        let mut data = class_header(5);
+        data.extend_from_slice(&[1, 0, 1, b'A', 7, 0, 1, 1, 0, 1, b'f', 1, 0, 1, b'Q']);
+        data.extend_from_slice(&[0, 0x21, 0, 2, 0, 0, 0, 0]);
+        data.extend_from_slice(&[0, 1, 0, 1, 0, 3, 0, 4, 0, 0]);
+        data.extend_from_slice(&[0, 0, 0, 0]);
+        let err = ClassFile::new(&mut ClassReader::new(data)).err().unwrap();
+        assert_eq!(err.offset, 39);
+        assert_eq!(err.structure, "field_info");
+        assert_eq!(err.reason, FormatErrorReason::BadDescriptor("Q".to_string()));
+    }
+
+    #[test]
+    fn classes_are_defined_once_and_shared() {
+        let boot = match boot_loader() {
+            Some(boot) => boot,
+            None => return,
+        };
+        let app = SimpleClassLoader::new(fixture("classes"), ClassPathType::Folder);
+        let mut env = Environment::new(Box::new(boot), no_classes(), Box::new(app));
+
+        let point = env.load_class("fox/Point").unwrap().unwrap();
+        assert!(Rc::ptr_eq(&point, &env.load_class("fox/Point").unwrap().unwrap()));
+        assert_eq!(point.loader, LoaderId::APP);
+
+        // java/lang/Object was defined by the boot loader, asked for by both
+        let object = point.super_class.clone().unwrap();
+        assert_eq!(object.name, "java/lang/Object");
+        assert_eq!(object.loader, LoaderId::BOOTSTRAP);
+        assert!(point.is_subclass_of(&object));
+        assert_eq!(point.interfaces[0].name, "java/lang/Comparable");
+        let initiating: Vec<LoaderId> = env.registry.initiating_loaders("java/lang/Object").iter().map(|(l, _)| *l).collect();
+        assert_eq!(initiating, vec![LoaderId::BOOTSTRAP, LoaderId::APP]);
+        assert!(env.registry.find_defined(LoaderId::APP, "java/lang/Object").is_none());
+
+        // the boot loader can't see the class path
+        assert!(env.load_class_from(LoaderId::BOOTSTRAP, "fox/Point").unwrap().is_none());
+    }
+
+    #[test]
+    fn class_with_wrong_name_is_rejected() {
+        let temp = TempDir::new("wrong_name");
+        fs::create_dir_all(temp.0.join("fox")).unwrap();
+        fs::copy(fixture("classes/fox/Hello.class"), temp.0.join("fox").join("Other.class")).unwrap();
+
+        let app = SimpleClassLoader::new(temp.0.clone(), ClassPathType::Folder);
+        let mut env = Environment::new(no_classes(), no_classes(), Box::new(app));
+        let err = env.load_class("fox/Other").err().unwrap();
+        assert_eq!(err.class_name, "java/lang/NoClassDefFoundError");
+        assert_eq!(err.message, "fox/Other (wrong name: fox/Hello)");
+        assert!(env.registry.is_empty());
+    }
+
+    #[test]
+    fn missing_super_class_is_reported() {
+        let app = SimpleClassLoader::new(fixture("classes"), ClassPathType::Folder);
+        let mut env = Environment::new(no_classes(), no_classes(), Box::new(app));
+        let err = env.load_class("fox/Hello").err().unwrap();
+        assert_eq!(err.class_name, "java/lang/NoClassDefFoundError");
+        assert_eq!(err.message, "java/lang/Object");
+    }
+
+    #[test]
+    fn loaders_delegate_to_their_parents_first() {
+        let boot = match boot_loader() {
+            Some(boot) => boot,
+            None => return,
+        };
+        let app = SimpleClassLoader::new(fixture("classes"), ClassPathType::Folder);
+        let mut env = Environment::new(Box::new(boot), no_classes(), Box::new(app));
+
+        // two plugins below the platform loader, which can't see each other or the class path
+        let hello = fs::read(fixture("classes/fox/Hello.class")).unwrap();
+        let mut plugin = MemoryClassLoader::new();
+        plugin.add_class("fox/Hello", hello.clone());
+        let plugin_a = env.add_loader(LoaderId::PLATFORM, Box::new(plugin));
+        let mut plugin = MemoryClassLoader::new();
+        plugin.add_class("fox/Hello", hello);
+        let plugin_b = env.add_loader(LoaderId::PLATFORM, Box::new(plugin));
+        assert_eq!(env.get_parent(plugin_a), Some(LoaderId::PLATFORM));
+
+        let a = env.load_class_from(plugin_a, "fox/Hello").unwrap().unwrap();
+        let b = env.load_class_from(plugin_b, "fox/Hello").unwrap().unwrap();
+        let app = env.load_class("fox/Hello").unwrap().unwrap();
+        assert_eq!((a.loader, b.loader, app.loader), (plugin_a, plugin_b, LoaderId::APP));
+        assert!(!Rc::ptr_eq(&a, &b) && !Rc::ptr_eq(&a, &app));
+        assert!(!a.is_subclass_of(&b));
+        assert!(env.load_class_from(plugin_a, "fox/Point").unwrap().is_none());
+
+        // but they share what their parents define
+        let object_a = a.super_class.clone().unwrap();
+        assert!(Rc::ptr_eq(&object_a, b.super_class.as_ref().unwrap()));
+        assert_eq!(object_a.loader, LoaderId::BOOTSTRAP);
+
+        // a plugin below the application loader gets the class path's classes
+        let mut plugin = MemoryClassLoader::new();
+        plugin.add_class("fox/Hello", fs::read(fixture("classes/fox/Hello.class")).unwrap());
+        let plugin_c = env.add_loader(LoaderId::APP, Box::new(plugin));
+        assert!(Rc::ptr_eq(&env.load_class_from(plugin_c, "fox/Hello").unwrap().unwrap(), &app));
+    }
+
+    // the JDK's classes plus the fixtures on the class path, None without a JDK
+    fn fixture_env() -> Option<Environment> {
+        let app = SimpleClassLoader::new(fixture("classes"), ClassPathType::Folder);
+        return Some(Environment::new(Box::new(boot_loader()?), no_classes(), Box::new(app)));
+    }
+
+    // the index of the member reference to `name` in the class's constant pool
+    fn member_ref_index(class: &Class, name: &str) -> u16 {
+        return (0..class.constant_pool.len()).find(|n| match class.constant_pool.get(*n) {
+            Some(Constant::FieldRef(sym)) => sym.name == name,
+            Some(Constant::MethodRef(sym)) | Some(Constant::InterfaceMethodRef(sym)) => sym.name == name,
+            _ => false,
+        }).unwrap() as u16;
+    }
+
+    fn patched(data: &[u8], from: &str, to: &str) -> Vec<u8> {
+        let at = data.windows(from.len()).position(|w| w == from.as_bytes()).unwrap();
+        let mut data = data.to_vec();
+        data[at..at + to.len()].copy_from_slice(to.as_bytes());
+        return data;
+    }
+
+    #[test]
+    fn class_is_linked_and_references_resolved() {
+        let mut env = match fixture_env() {
+            Some(env) => env,
+            None => return,
+        };
+        let point = env.load_class("fox/Point").unwrap().unwrap();
+        link_class(&mut env, &point).unwrap();
+        assert!(point.linked.get() && point.super_class.as_ref().unwrap().linked.get());
+        let dimensions = point.get_field("DIMENSIONS", "I").unwrap().slot_id;
+        assert_eq!(point.static_vars.borrow().get_i32(dimensions).ok(), Some(2));
+        // String constants are interned
+        let literals = env.load_class("fox/Literals").unwrap().unwrap();
+        link_class(&mut env, &literals).unwrap();
+        let constant = |name: &str| literals.static_vars.borrow().get_ref(literals.get_field(name, "Ljava/lang/String;").unwrap().slot_id).unwrap();
+        assert_eq!(constant("NAME"), env.heap.interned("fox"));
+        assert_eq!(value_of(&env, constant("NAME").unwrap()).as_deref(), Some("fox"));
+        assert_eq!(value_of(&env, constant("SIGN").unwrap()).as_deref(), Some("\u{20ac}"));
+
+        let linking = env.load_class("fox/Linking").unwrap().unwrap();
+        let x = resolve_field(&mut env, &linking, member_ref_index(&linking, "x")).ok().unwrap();
+        assert!(Rc::ptr_eq(&x.class, &point));
+        assert_eq!(x.field().slot_id, 0);
+
+        let index = member_ref_index(&linking, "compareTo");
+        let compare = resolve_method(&mut env, &linking, index).ok().unwrap();
+        assert_eq!(compare.method.descriptor, "(Lfox/Point;)I");
+        // the second time it comes out of the cache
+        match linking.constant_pool.get(index as usize) {
+            Some(Constant::MethodRef(sym)) => assert!(sym.resolved.get().is_some()),
+            _ => panic!("not a method ref"),
+        }
+        assert!(Rc::ptr_eq(&resolve_method(&mut env, &linking, index).ok().unwrap().method, &compare.method));
+
+        let greet = resolve_interface_method(&mut env, &linking, member_ref_index(&linking, "greet")).ok().unwrap();
+        assert_eq!(greet.class.name, "fox/Greeter");
+        // javac calls Object methods on an interface through a Methodref
+        let hash = resolve_method(&mut env, &linking, member_ref_index(&linking, "hashCode")).ok().unwrap();
+        assert_eq!(hash.class.name, "java/lang/Object");
+
+        // a method ref is not an interface method ref
+        let err = resolve_interface_method(&mut env, &linking, index).err().unwrap();
+        assert_eq!(err.class_name, "java/lang/VerifyError");
+    }
+
+    #[test]
+    fn bad_member_references_are_reported() {
+        let mut env = match fixture_env() {
+            Some(env) => env,
+            None => return,
+        };
+        let point = fs::read(fixture("classes/fox/Point.class")).unwrap();
+        let linking = fs::read(fixture("classes/fox/Linking.class")).unwrap();
+
+        // loaders below the platform loader don't see the class path, so they get their own copies
+        let mut plugin = MemoryClassLoader::new();
+        plugin.add_class("fox/Point", point.clone());
+        plugin.add_class("fox/Linking", patched(&patched(&linking, "created", "creatxd"), "compareTo", "compareTx"));
+        let plugin = env.add_loader(LoaderId::PLATFORM, Box::new(plugin));
+        let broken = env.load_class_from(plugin, "fox/Linking").unwrap().unwrap();
+
+        let err = resolve_field(&mut env, &broken, member_ref_index(&broken, "creatxd")).err().unwrap();
+        assert_eq!((err.class_name.as_str(), err.message.as_str()), ("java/lang/NoSuchFieldError", "creatxd"));
+        let err = resolve_method(&mut env, &broken, member_ref_index(&broken, "compareTx")).err().unwrap();
+        assert_eq!((err.class_name.as_str(), err.message.as_str()), ("java/lang/NoSuchMethodError", "fox/Point.compareTx(Lfox/Point;)I"));
+
+        // fox/Point from the parent loader is in another runtime package than fox/Linking
+        let mut parent = MemoryClassLoader::new();
+        parent.add_class("fox/Point", point);
+        let parent = env.add_loader(LoaderId::PLATFORM, Box::new(parent));
+        let mut child = MemoryClassLoader::new();
+        child.add_class("fox/Linking", linking);
+        let child = env.add_loader(parent, Box::new(child));
+        let linking = env.load_class_from(child, "fox/Linking").unwrap().unwrap();
+
+        let err = resolve_field(&mut env, &linking, member_ref_index(&linking, "x")).err().unwrap();
+        assert_eq!(err.class_name, "java/lang/IllegalAccessError");
+        // public members are fine
+        assert!(resolve_method(&mut env, &linking, member_ref_index(&linking, "compareTo")).is_ok());
+    }
+
+    #[test]
+    fn classes_are_initialized_super_first_and_once() {
+        let mut env = match fixture_env() {
+            Some(env) => env,
+            None => return,
+        };
+        let derived = env.load_class("fox/Derived").unwrap().unwrap();
+        let mut ran: Vec<String> = vec![];
+        let mut runner = |env: &mut Environment, class: &Rc<Class>, _: &Rc<Method>| {
+            ran.push(class.name.clone());
+            // using the class from its own initializer doesn't start over
+            assert_eq!(initialize_class(env, class, ThreadId(1), &mut |_, _, _| Ok(())).ok(), Some(InitStatus::InProgress));
+            assert_eq!(initialize_class(env, class, ThreadId(2), &mut |_, _, _| Ok(())).ok(), Some(InitStatus::Blocked(ThreadId(1))));
+            return Ok(());
+        };
+        assert_eq!(initialize_class(&mut env, &derived, ThreadId(1), &mut runner).ok(), Some(InitStatus::Initialized));
+        assert_eq!(initialize_class(&mut env, &derived, ThreadId(1), &mut runner).ok(), Some(InitStatus::Initialized));
+        // java/lang/Object has no <clinit>, fox/Named is initialized for its default method
+        assert_eq!(ran, vec!["fox/Base", "fox/Named", "fox/Derived"]);
+        assert_eq!(*derived.init_state.borrow(), InitState::Initialized);
+
+        // an interface without default methods isn't initialized along with its implementations
+        let greeter = env.load_class("fox/Greeter").unwrap().unwrap();
+        assert_eq!(*greeter.init_state.borrow(), InitState::Uninitialized);
+
+        // nor is fox/Titled, but fox/Named it extends is, for fox/Titles that implements it
+        let mut env = fixture_env().unwrap();
+        let titles = env.load_class("fox/Titles").unwrap().unwrap();
+        let mut ran: Vec<String> = vec![];
+        let mut runner = |_: &mut Environment, class: &Rc<Class>, _: &Rc<Method>| {
+            ran.push(class.name.clone());
+            return Ok(());
+        };
+        assert_eq!(initialize_class(&mut env, &titles, ThreadId(1), &mut runner).ok(), Some(InitStatus::Initialized));
+        assert_eq!(ran, vec!["fox/Named", "fox/Titles"]);
+        let titled = env.load_class("fox/Titled").unwrap().unwrap();
+        assert_eq!(*titled.init_state.borrow(), InitState::Uninitialized);
+    }
+
+    #[test]
+    fn failed_initializer_makes_class_erroneous() {
+        let mut env = match fixture_env() {
+            Some(env) => env,
+            None => return,
+        };
+        let broken = env.load_class("fox/Broken").unwrap().unwrap();
+        let mut runner = |_: &mut Environment, _: &Rc<Class>, _: &Rc<Method>| {
+            return Err(JavaError::new("java/lang/NumberFormatException", "For input string: \"x\"".to_string()));
+        };
+        let err = initialize_class(&mut env, &broken, ThreadId(1), &mut runner).err().unwrap();
+        assert_eq!(err.class_name, "java/lang/ExceptionInInitializerError");
+        assert!(err.message.contains("java.lang.NumberFormatException"));
+        assert_eq!(*broken.init_state.borrow(), InitState::Erroneous);
+
+        let err = initialize_class(&mut env, &broken, ThreadId(1), &mut runner).err().unwrap();
+        assert_eq!(err.class_name, "java/lang/NoClassDefFoundError");
+        assert_eq!(err.message, "Could not initialize class fox.Broken");
+
+        // errors aren't wrapped
+        let base = env.load_class("fox/Base").unwrap().unwrap();
+        let mut runner = |_: &mut Environment, _: &Rc<Class>, _: &Rc<Method>| {
+            return Err(JavaError::new("java/lang/OutOfMemoryError", "Java heap space".to_string()));
+        };
+        let err = initialize_class(&mut env, &base, ThreadId(1), &mut runner).err().unwrap();
+        assert_eq!(err.class_name, "java/lang/OutOfMemoryError");
+
+        // nor are those of application classes
+        let fault = env.load_class("fox/Fault").unwrap().unwrap();
+        link_class(&mut env, &fault).unwrap();
+        let object = env.heap.alloc_object(&fault).unwrap();
+        let titles = env.load_class("fox/Titles").unwrap().unwrap();
+        let mut runner = |env: &mut Environment, class: &Rc<Class>, _: &Rc<Method>| {
+            if class.name == "fox/Titles" {
+                return Err(JavaError::thrown(env, object));
+            }
+            return Ok(());
+        };
+        let err = initialize_class(&mut env, &titles, ThreadId(1), &mut runner).err().unwrap();
+        assert_eq!(err.class_name, "fox/Fault");
+        assert_eq!(err.object, Some(object));
+    }
+
+    // a fixture class straight from the class path, without super classes, enough to run its code
+    fn fixture_class(name: &str) -> Rc<Class> {
+        let loader = SimpleClassLoader::new(fixture("classes"), ClassPathType::Folder);
+        return Rc::new(loader.load_class(name).ok().unwrap().unwrap());
+    }
+
+    // slots built by writing into local variables, as the interpreter passes them
+    fn slots(size: usize, fill: impl FnOnce(&mut LocalVars) -> Result<(), JavaError>) -> Vec<Slot> {
+        let mut vars = LocalVars::new(size);
+        fill(&mut vars).unwrap();
+        return (0..size).map(|n| vars.take_slot(n).unwrap()).collect();
+    }
+
+    fn vars_of(slots: Vec<Slot>) -> LocalVars {
+        let mut vars = LocalVars::new(slots.len());
+        for (n, slot) in slots.into_iter().enumerate() {
+            vars.set_slot(n, slot).unwrap();
+        }
+        return vars;
+    }
+
+    #[test]
+    fn interpreter_runs_methods_to_their_return() {
+        let calc = fixture_class("fox/Calc");
+        let mut env = empty_env();
+        let mut thread = Thread::new(ThreadId(1), 16);
+
+        let method = calc.get_method("identity", "(I)I").unwrap();
+        let value = invoke(&mut env, &mut thread, calc.clone(), method, slots(1, |v| v.set_i32(0, -7))).unwrap();
+        assert_eq!(vars_of(value).get_i32(0).ok(), Some(-7));
+
+        let method = calc.get_method("second", "(IJ)J").unwrap();
+        let value = invoke(&mut env, &mut thread, calc.clone(), method, slots(3, |v| { v.set_i32(0, 1)?; v.set_i64(1, 1 << 40) })).unwrap();
+        assert_eq!(vars_of(value).get_i64(0).ok(), Some(1 << 40));
+
+        let method = calc.get_method("third", "(FJD)D").unwrap();
+        let value = invoke(&mut env, &mut thread, calc.clone(), method, slots(5, |v| { v.set_f32(0, 1.5)?; v.set_i64(1, 2)?; v.set_f64(3, -0.25) })).unwrap();
+        assert_eq!(vars_of(value).get_f64(0).ok(), Some(-0.25));
+
+        let method = calc.get_method("nothing", "()V").unwrap();
+        assert!(invoke(&mut env, &mut thread, calc.clone(), method, vec![]).unwrap().is_empty());
+        assert!(thread.stack.is_empty());
+        assert_eq!(thread.pc, 0);
+    }
+
+    // decodes `code` instruction by instruction, giving the pc after each one
+    fn decode_all(code: &[u8]) -> Result<Vec<usize>, JavaError> {
+        let mut reader = ByteCodeReader::new(code);
+        let mut pcs = vec![];
+        while reader.pc < reader.len() {
+            fetch_instruction(&mut reader)?;
+            pcs.push(reader.pc);
+        }
+        return Ok(pcs);
+    }
+
+    #[test]
+    fn instructions_decode_with_their_operands() {
+        assert_eq!(mnemonic(0x00), Some("nop"));
+        assert_eq!(mnemonic(0xc4), Some("wide"));
+        assert_eq!(mnemonic(0xc9), Some("jsr_w"));
+        assert_eq!(mnemonic(0xca), None);
+
+        // bipush 1, sipush 2, ldc 1, ldc_w 2, iinc 2, goto 2, invokeinterface 4, multianewarray 3, goto_w 4
+        let code = [0x10, 5, 0x11, 1, 0, 0x12, 1, 0x13, 0, 1, 0x84, 1, 1, 0xa7, 0, 3,
+            0xb9, 0, 1, 1, 0, 0xc5, 0, 1, 2, 0xc8, 0, 0, 0, 0, 0xb1];
+        assert_eq!(decode_all(&code).unwrap(), vec![2, 5, 7, 10, 13, 16, 21, 25, 30, 31]);
+
+        // wide iload and wide iinc take a 16 bit index, iinc a 16 bit constant too
+        let code = [0xc4, 0x15, 1, 0, 0xc4, 0x84, 1, 0, 0xff, 0xff, 0xb1];
+        assert_eq!(decode_all(&code).unwrap(), vec![4, 10, 11]);
+        let err = decode_all(&[0xc4, 0x10, 0, 0]).err().unwrap();
+        assert_eq!(err.class_name, "java/lang/VerifyError");
+
+        // switches are padded to a multiple of 4 from the start of the code
+        let code = [0x00, 0xaa, 0, 0, 0, 0, 0, 20, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 10, 0, 0, 0, 11, 0xb1];
+        assert_eq!(decode_all(&code).unwrap(), vec![1, 24, 25]);
+        let code = [0xab, 0, 0, 0, 0, 0, 0, 9, 0, 0, 0, 1, 0, 0, 0, 7, 0, 0, 0, 5, 0xb1];
+        assert_eq!(decode_all(&code).unwrap(), vec![20, 21]);
+        // a table longer than the code
+        let err = decode_all(&[0xaa, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x7f, 0xff, 0xff, 0xff]).err().unwrap();
+        assert_eq!(err.class_name, "java/lang/VerifyError");
+
+        // unused opcodes and truncated operands
+        let err = decode_all(&[0xca]).err().unwrap();
+        assert_eq!(err.class_name, "java/lang/VerifyError");
+        assert_eq!(err.message, "Bad instruction: 0xca");
+        assert!(decode_all(&[0x11, 1]).is_err());
+    }
+
+    #[test]
+    fn switches_branch_to_the_matching_offset() {
+        let calc = fixture_class("fox/Calc");
+        let method = calc.get_method("nothing", "()V").unwrap();
+        let mut table = TableSwitch { default_offset: 100, low: -1, high: 1, jump_offsets: vec![10, 20, 30] };
+        let mut lookup = LookupSwitch { default_offset: 100, match_offsets: vec![(-5, 10), (0, 20), (1000, 30)] };
+        for (key, from_table, from_lookup) in [(-1, 10, 100), (0, 20, 20), (1, 30, 100), (2, 100, 100), (1000, 100, 30), (i32::MIN, 100, 100)] {
+            let mut frame = Frame::new(calc.clone(), method.clone());
+            frame.operand_stack = OperandStack::new(1);
+            frame.operand_stack.push_i32(key).unwrap();
+            assert!(matches!(run_inst(&mut table, &mut frame), Flow::Branch(n) if n == from_table));
+            frame.operand_stack.push_i32(key).unwrap();
+            assert!(matches!(run_inst(&mut lookup, &mut frame), Flow::Branch(n) if n == from_lookup));
+        }
+    }
+
+    #[test]
+    fn bad_byte_code_fails_verification() {
+        let bytes = fs::read(fixture("classes/fox/Calc.class")).unwrap();
+        // the code of identity(I)I is iload_0 ireturn
+        let at = bytes.windows(2).position(|w| w == [0x1a, 0xac]).unwrap();
+        let mut bytes = bytes.clone();
+        bytes[at + 1] = 0xfe;
+        let file = ClassFile::new(&mut ClassReader::new(bytes)).ok().unwrap();
+        let calc = Rc::new(Class::new(&file));
+        let err = link_class(&mut empty_env(), &calc).err().unwrap();
+        assert_eq!(err.class_name, "java/lang/VerifyError");
+        assert!(err.message.contains("Bad instruction: 0xfe at pc 1 in method identity(I)I"));
+    }
+
+    #[test]
+    fn code_leaving_its_frame_fails_verification() {
+        let bytes = fs::read(fixture("classes/fox/Calc.class")).unwrap();
+        // the code of divide(II)I is iload_0 iload_1 idiv ireturn, max_stack 2 and max_locals 2
+        let at = bytes.windows(4).position(|w| w == [0x1a, 0x1b, 0x6c, 0xac]).unwrap();
+        let verify = |code: [u8; 4]| {
+            let mut bytes = bytes.clone();
+            bytes[at..at + 4].copy_from_slice(&code);
+            let file = ClassFile::new(&mut ClassReader::new(bytes)).ok().unwrap();
+            let err = link_class(&mut empty_env(), &Rc::new(Class::new(&file))).err().unwrap();
+            assert_eq!(err.class_name, "java/lang/VerifyError");
+            return err.message;
+        };
+
+        assert!(verify([0x1a, 0x1d, 0x6c, 0xac]).contains("Illegal local variable number 3 at pc 1 in method divide(II)I"));
+        assert!(verify([0x1a, 0x6c, 0x00, 0xac]).contains("Unable to pop operand off an empty stack at pc 1"));
+        assert!(verify([0x1a, 0x1b, 0x1a, 0xac]).contains("Operand stack overflow at pc 2"));
+        assert!(verify([0xa7, 0x00, 0x01, 0xac]).contains("Illegal target of jump or branch at pc 0"));
+        assert!(verify([0x1a, 0xa7, 0xff, 0xff]).contains("Inconsistent stack height"));
+        assert!(verify([0x1a, 0x1b, 0x6c, 0x00]).contains("Falling off the end of the code"));
+    }
+
+    #[test]
+    fn frame_accessors_throw_instead_of_panicking() {
+        let is_verify_error = |flow: Flow| matches!(flow, Flow::Throw(e) if e.class_name == "java/lang/VerifyError");
+        let mut frame = test_frame();
+        // iadd on an empty stack, iload and istore of local 9 of 6
+        assert!(is_verify_error(exec(&[0x60], &mut frame)));
+        assert!(is_verify_error(exec(&[0x15, 9], &mut frame)));
+        frame.operand_stack.push_i32(1).unwrap();
+        assert!(is_verify_error(exec(&[0x36, 9], &mut frame)));
+        // the failed istore popped its value; a long doesn't fit into the last slot
+        for _ in 0..7 {
+            frame.operand_stack.push_i32(1).unwrap();
+        }
+        assert!(is_verify_error(exec(&[0x0a], &mut frame)));
+        assert!(frame.operand_stack.peek_slot(7).is_err());
+    }
+
+    // a frame with six locals and room for eight slots on its operand stack
+    fn test_frame() -> Frame {
+        let calc = fixture_class("fox/Calc");
+        let mut frame = Frame::new(calc.clone(), calc.get_method("nothing", "()V").unwrap());
+        frame.local_vars = LocalVars::new(6);
+        frame.operand_stack = OperandStack::new(8);
+        return frame;
+    }
+
+    // runs an instruction on `frame` as if it was the only one on a thread
+    fn run_inst(inst: &mut dyn Instruction, frame: &mut Frame) -> Flow {
+        let mut env = empty_env();
+        let mut thread = Thread::new(ThreadId(1), 16);
+        return inst.execute(&mut ExecContext {
+            env: &mut env,
+            thread: &mut thread,
+            frame,
+            pc: 0,
+        });
+    }
+
+    // decodes and runs the single instruction in `code`
+    fn exec(code: &[u8], frame: &mut Frame) -> Flow {
+        let mut reader = ByteCodeReader::new(code);
+        let mut inst = fetch_instruction(&mut reader).unwrap();
+        assert_eq!(reader.pc, code.len());
+        return run_inst(inst.as_mut(), frame);
+    }
+
+    // runs a single instruction on a test frame with the given locals and operand stack, as i32 slots
+    fn run_one(code: &[u8], locals: &[i32], stack: &[i32]) -> (Vec<i32>, Vec<i32>) {
+        let mut frame = test_frame();
+        for (n, v) in locals.iter().enumerate() {
+            frame.local_vars.set_i32(n, *v).unwrap();
+        }
+        for v in stack {
+            frame.operand_stack.push_i32(*v).unwrap();
+        }
+
+        assert!(matches!(exec(code, &mut frame), Flow::Next));
+
+        let mut stack = vec![];
+        while frame.operand_stack.size() > 0 {
+            stack.insert(0, frame.operand_stack.pop_i32().unwrap());
+        }
+        return ((0..6).map(|n| frame.local_vars.get_i32(n).unwrap()).collect(), stack);
+    }
+
+    #[test]
+    fn stores_stack_ops_and_constants_move_slots() {
+        let one_f = 1.0f64.to_bits();
+        #[allow(clippy::type_complexity)]
+        let cases: &[(&str, &[u8], &[i32], &[i32], &[i32], &[i32])] = &[
+            // name, code, locals before, stack before, locals after, stack after
+            ("nop", &[0x00], &[], &[1], &[0, 0, 0, 0, 0, 0], &[1]),
+            ("iconst_m1", &[0x02], &[], &[], &[0, 0, 0, 0, 0, 0], &[-1]),
+            ("iconst_5", &[0x08], &[], &[], &[0, 0, 0, 0, 0, 0], &[5]),
+            ("lconst_1", &[0x0a], &[], &[], &[0, 0, 0, 0, 0, 0], &[0, 1]),
+            ("fconst_2", &[0x0d], &[], &[], &[0, 0, 0, 0, 0, 0], &[2.0f32.to_bits() as i32]),
+            ("dconst_1", &[0x0f], &[], &[], &[0, 0, 0, 0, 0, 0], &[(one_f >> 32) as i32, one_f as i32]),
+            ("bipush", &[0x10, 0xff], &[], &[], &[0, 0, 0, 0, 0, 0], &[-1]),
+            ("sipush", &[0x11, 0x80, 0x00], &[], &[], &[0, 0, 0, 0, 0, 0], &[-32768]),
+            ("istore", &[0x36, 5], &[], &[7], &[0, 0, 0, 0, 0, 7], &[]),
+            ("wide istore", &[0xc4, 0x36, 0, 5], &[], &[7], &[0, 0, 0, 0, 0, 7], &[]),
+            ("istore_2", &[0x3d], &[], &[7], &[0, 0, 7, 0, 0, 0], &[]),
+            ("lstore_0", &[0x3f], &[], &[1, 2], &[1, 2, 0, 0, 0, 0], &[]),
+            ("lstore", &[0x37, 4], &[], &[1, 2], &[0, 0, 0, 0, 1, 2], &[]),
+            ("fstore_3", &[0x46], &[], &[3], &[0, 0, 0, 3, 0, 0], &[]),
+            ("dstore", &[0x39, 1], &[], &[1, 2], &[0, 1, 2, 0, 0, 0], &[]),
+            ("astore_1", &[0x4c], &[], &[9], &[0, 9, 0, 0, 0, 0], &[]),
+            ("astore", &[0x3a, 4], &[], &[9], &[0, 0, 0, 0, 9, 0], &[]),
+            ("aload_1", &[0x2b], &[0, 9], &[], &[0, 9, 0, 0, 0, 0], &[9]),
+            ("pop", &[0x57], &[], &[1, 2], &[0, 0, 0, 0, 0, 0], &[1]),
+            ("pop2", &[0x58], &[], &[1, 2, 3], &[0, 0, 0, 0, 0, 0], &[1]),
+            ("dup", &[0x59], &[], &[1, 2], &[0, 0, 0, 0, 0, 0], &[1, 2, 2]),
+            ("dup_x1", &[0x5a], &[], &[1, 2, 3], &[0, 0, 0, 0, 0, 0], &[1, 3, 2, 3]),
+            ("dup_x2", &[0x5b], &[], &[1, 2, 3, 4], &[0, 0, 0, 0, 0, 0], &[1, 4, 2, 3, 4]),
+            ("dup2", &[0x5c], &[], &[1, 2, 3], &[0, 0, 0, 0, 0, 0], &[1, 2, 3, 2, 3]),
+            ("dup2_x1", &[0x5d], &[], &[1, 2, 3], &[0, 0, 0, 0, 0, 0], &[2, 3, 1, 2, 3]),
+            ("dup2_x2", &[0x5e], &[], &[1, 2, 3, 4], &[0, 0, 0, 0, 0, 0], &[3, 4, 1, 2, 3, 4]),
+            ("swap", &[0x5f], &[], &[1, 2], &[0, 0, 0, 0, 0, 0], &[2, 1]),
+        ];
+        for (name, code, locals, stack, want_locals, want_stack) in cases {
+            let (got_locals, got_stack) = run_one(code, locals, stack);
+            assert_eq!(&got_locals[..], *want_locals, "locals after {}", name);
+            assert_eq!(&got_stack[..], *want_stack, "stack after {}", name);
+        }
+    }
+
+    #[test]
+    fn category_two_values_survive_stack_ops() {
+        let mut frame = test_frame();
+
+        // dup2 on a long, then dup_x2 an int under it
+        frame.operand_stack.push_i64(-1 << 40).unwrap();
+        run_inst(&mut Dup2 {}, &mut frame);
+        assert_eq!(frame.operand_stack.pop_i64().ok(), Some(-1 << 40));
+        frame.operand_stack.push_i32(5).unwrap();
+        run_inst(&mut Dup_X2 {}, &mut frame);
+        assert_eq!(frame.operand_stack.pop_i32().ok(), Some(5));
+        assert_eq!(frame.operand_stack.pop_i64().ok(), Some(-1 << 40));
+        assert_eq!(frame.operand_stack.pop_i32().ok(), Some(5));
+
+        // aconst_null pushes one slot holding no reference, which dup copies
+        run_inst(&mut AConst_Null {}, &mut frame);
+        run_inst(&mut Dup {}, &mut frame);
+        assert_eq!(frame.operand_stack.size(), 2);
+        assert!(frame.operand_stack.pop_ref().unwrap().is_none());
+    }
+
+    #[test]
+    fn ldc_pushes_constants_from_the_pool() {
+        let calc = fixture_class("fox/Calc");
+        let mut env = empty_env();
+        let mut thread = Thread::new(ThreadId(1), 16);
+        let mut call = |name: &str, descriptor: &str| {
+            let method = calc.get_method(name, descriptor).unwrap();
+            return vars_of(invoke(&mut env, &mut thread, calc.clone(), method, vec![]).unwrap());
+        };
+        assert_eq!(call("bigInt", "()I").get_i32(0).ok(), Some(100000));
+        assert_eq!(call("aFloat", "()F").get_f32(0).ok(), Some(1.5));
+        assert_eq!(call("bigLong", "()J").get_i64(0).ok(), Some(1234567890123));
+        assert_eq!(call("aDouble", "()D").get_f64(0).ok(), Some(2.5));
+    }
 
     #[test]
-    fn it_works() {
-        let mut loaders : Vec<Box<dyn ClassLoader>> = vec![];
+    fn ldc_pushes_interned_strings_and_class_objects() {
+        let mut env = match fixture_env() {
+            Some(env) => env,
+            None => return,
+        };
+        let literals = env.load_class("fox/Literals").unwrap().unwrap();
+        let mut thread = Thread::new(ThreadId(1), 16);
+        let call = |env: &mut Environment, thread: &mut Thread, name: &str, descriptor: &str| {
+            let method = literals.get_method(name, descriptor).unwrap();
+            return invoke(env, thread, literals.clone(), method, vec![]).unwrap()[0].val_ref.unwrap();
+        };
+
+        // the same literal is the same String, Latin-1 one byte a character, others UTF-16
+        let hello = call(&mut env, &mut thread, "greeting", "()Ljava/lang/String;");
+        assert_eq!(env.heap.get(hello).class().name, "java/lang/String");
+        assert_eq!(value_of(&env, hello).as_deref(), Some("hello"));
+        assert_eq!(call(&mut env, &mut thread, "greeting", "()Ljava/lang/String;"), hello);
+        assert_eq!(env.heap.interned("hello"), Some(hello));
+        let price = call(&mut env, &mut thread, "price", "()Ljava/lang/String;");
+        assert_eq!(value_of(&env, price).as_deref(), Some("5 \u{20ac}"));
+        let string = env.load_class("java/lang/String").unwrap().unwrap();
+        let value = env.heap.get(price).fields().unwrap().get_ref(string.get_field("value", "[B").unwrap().slot_id).unwrap().unwrap();
+        assert_eq!(env.heap.get(value).array_length(), Some(6));
+
+        // classes are pushed as their java.lang.Class objects, which their classes keep alive
+        let mirror = call(&mut env, &mut thread, "self", "()Ljava/lang/Class;");
+        assert_eq!(env.heap.get(mirror).class().name, "java/lang/Class");
+        assert_eq!(literals.mirror.get(), Some(mirror));
+        let ints = call(&mut env, &mut thread, "ints", "()Ljava/lang/Class;");
+        assert_eq!(env.load_class("[I").unwrap().unwrap().mirror.get(), Some(ints));
+        let mut roots = vec![];
+        class_roots(&env, &mut roots);
+        env.heap.collect(roots);
+        assert_eq!(env.heap.get(mirror).class().name, "java/lang/Class");
+        assert_eq!(value_of(&env, hello).as_deref(), Some("hello"));
+    }
+
+    fn binary_i32(opcode: u8, v1: i32, v2: i32) -> i32 {
+        let mut frame = test_frame();
+        frame.operand_stack.push_i32(v1).unwrap();
+        frame.operand_stack.push_i32(v2).unwrap();
+        assert!(matches!(exec(&[opcode], &mut frame), Flow::Next));
+        return frame.operand_stack.pop_i32().unwrap();
+    }
+
+    // longs, except for the int shift distance of the shifts
+    fn binary_i64(opcode: u8, v1: i64, v2: i64) -> i64 {
+        let mut frame = test_frame();
+        frame.operand_stack.push_i64(v1).unwrap();
+        if (0x79..=0x7d).contains(&opcode) {
+            frame.operand_stack.push_i32(v2 as i32).unwrap();
+        } else {
+            frame.operand_stack.push_i64(v2).unwrap();
+        }
+        assert!(matches!(exec(&[opcode], &mut frame), Flow::Next));
+        return frame.operand_stack.pop_i64().unwrap();
+    }
+
+    fn binary_f64(opcode: u8, v1: f64, v2: f64) -> f64 {
+        let mut frame = test_frame();
+        if opcode % 4 == 2 {
+            frame.operand_stack.push_f32(v1 as f32).unwrap();
+            frame.operand_stack.push_f32(v2 as f32).unwrap();
+            assert!(matches!(exec(&[opcode], &mut frame), Flow::Next));
+            return frame.operand_stack.pop_f32().unwrap() as f64;
+        }
+        frame.operand_stack.push_f64(v1).unwrap();
+        frame.operand_stack.push_f64(v2).unwrap();
+        assert!(matches!(exec(&[opcode], &mut frame), Flow::Next));
+        return frame.operand_stack.pop_f64().unwrap();
+    }
+
+    #[test]
+    fn arithmetic_follows_java_semantics() {
+        let int_cases = [
+            ("iadd", 0x60, i32::MAX, 1, i32::MIN),
+            ("isub", 0x64, i32::MIN, 1, i32::MAX),
+            ("imul", 0x68, 0x10000, 0x10000, 0),
+            ("idiv", 0x6c, -7, 2, -3),
+            ("idiv", 0x6c, i32::MIN, -1, i32::MIN),
+            ("irem", 0x70, -7, 2, -1),
+            ("irem", 0x70, 7, -2, 1),
+            ("irem", 0x70, i32::MIN, -1, 0),
+            ("ishl", 0x78, 1, 33, 2),
+            ("ishr", 0x7a, -16, 2, -4),
+            ("ishr", 0x7a, -16, -30, -4),
+            ("iushr", 0x7c, -1, 28, 15),
+            ("iushr", 0x7c, -1, 32, -1),
+            ("iand", 0x7e, 0b1100, 0b1010, 0b1000),
+            ("ior", 0x80, 0b1100, 0b1010, 0b1110),
+            ("ixor", 0x82, 0b1100, 0b1010, 0b0110),
+        ];
+        for (name, opcode, v1, v2, want) in int_cases {
+            assert_eq!(binary_i32(opcode, v1, v2), want, "{} {} {}", name, v1, v2);
+        }
+
+        let long_cases = [
+            ("ladd", 0x61, i64::MAX, 1, i64::MIN),
+            ("lmul", 0x69, 1 << 32, 1 << 32, 0),
+            ("ldiv", 0x6d, i64::MIN, -1, i64::MIN),
+            ("lrem", 0x71, -7, 2, -1),
+            ("lshl", 0x79, 1, 65, 2),
+            ("lshr", 0x7b, -16, 2, -4),
+            ("lushr", 0x7d, -1, 60, 15),
+            ("land", 0x7f, 0b1100 << 40, 0b1010 << 40, 0b1000 << 40),
+            ("lor", 0x81, 1 << 40, 1, (1 << 40) | 1),
+            ("lxor", 0x83, -1, 1 << 40, !(1 << 40)),
+        ];
+        for (name, opcode, v1, v2, want) in long_cases {
+            assert_eq!(binary_i64(opcode, v1, v2), want, "{} {} {}", name, v1, v2);
+        }
+
+        let float_cases = [
+            ("fadd", 0x62, 0.5, 0.25, 0.75),
+            ("dsub", 0x67, 0.5, 0.25, 0.25),
+            ("fmul", 0x6a, 3.0, -0.5, -1.5),
+            ("fdiv", 0x6e, 1.0, 0.0, f64::INFINITY),
+            ("ddiv", 0x6f, -1.0, 0.0, f64::NEG_INFINITY),
+            ("frem", 0x72, 5.5, 2.0, 1.5),
+            ("drem", 0x73, -5.5, 2.0, -1.5),
+            ("drem", 0x73, 5.5, -2.0, 1.5),
+            ("drem", 0x73, 1.0, f64::INFINITY, 1.0),
+        ];
+        for (name, opcode, v1, v2, want) in float_cases {
+            assert_eq!(binary_f64(opcode, v1, v2), want, "{} {} {}", name, v1, v2);
+        }
+        assert!(binary_f64(0x6f, 0.0, 0.0).is_nan());
+        assert!(binary_f64(0x72, 1.0, 0.0).is_nan());
+
+        let mut frame = test_frame();
+        frame.operand_stack.push_i32(i32::MIN).unwrap();
+        exec(&[0x74], &mut frame);
+        assert_eq!(frame.operand_stack.pop_i32().ok(), Some(i32::MIN));
+        frame.operand_stack.push_f64(0.0).unwrap();
+        exec(&[0x77], &mut frame);
+        assert!(frame.operand_stack.pop_f64().unwrap().is_sign_negative());
+
+        // iinc takes a signed byte, a signed short when wide
+        frame.local_vars.set_i32(2, i32::MAX).unwrap();
+        exec(&[0x84, 2, 1], &mut frame);
+        assert_eq!(frame.local_vars.get_i32(2).ok(), Some(i32::MIN));
+        exec(&[0x84, 2, 0xff], &mut frame);
+        assert_eq!(frame.local_vars.get_i32(2).ok(), Some(i32::MAX));
+        exec(&[0xc4, 0x84, 0, 2, 0x80, 0x00], &mut frame);
+        assert_eq!(frame.local_vars.get_i32(2).ok(), Some(i32::MAX - 32768));
+    }
+
+    #[test]
+    fn integer_division_by_zero_throws() {
+        for (opcode, long) in [(0x6c, false), (0x6d, true), (0x70, false), (0x71, true)] {
+            let mut frame = test_frame();
+            if long {
+                frame.operand_stack.push_i64(1).unwrap();
+                frame.operand_stack.push_i64(0).unwrap();
+            } else {
+                frame.operand_stack.push_i32(1).unwrap();
+                frame.operand_stack.push_i32(0).unwrap();
+            }
+            match exec(&[opcode], &mut frame) {
+                Flow::Throw(e) => {
+                    assert_eq!(e.class_name, "java/lang/ArithmeticException");
+                    assert_eq!(e.message, "/ by zero");
+                }
+                _ => panic!("{} didn't throw", mnemonic(opcode).unwrap()),
+            }
+        }
+
+        let calc = fixture_class("fox/Calc");
+        let mut env = empty_env();
+        let mut thread = Thread::new(ThreadId(1), 16);
+        let method = calc.get_method("divide", "(II)I").unwrap();
+        let value = invoke(&mut env, &mut thread, calc.clone(), method.clone(), slots(2, |v| { v.set_i32(0, 7)?; v.set_i32(1, -2) }));
+        assert_eq!(vars_of(value.unwrap()).get_i32(0).ok(), Some(-3));
+        let err = invoke(&mut env, &mut thread, calc.clone(), method, slots(2, |v| v.set_i32(0, 7))).err().unwrap();
+        assert_eq!(err.class_name, "java/lang/ArithmeticException");
+        assert!(thread.stack.is_empty());
+    }
+
+    // runs a conversion on what `push` leaves on the stack, the result ends up in local 0 (and 1)
+    fn convert(opcode: u8, push: impl FnOnce(&mut OperandStack) -> Result<(), JavaError>) -> LocalVars {
+        let mut frame = test_frame();
+        push(&mut frame.operand_stack).unwrap();
+        assert!(matches!(exec(&[opcode], &mut frame), Flow::Next));
+        let mut result = vec![];
+        while frame.operand_stack.size() > 0 {
+            result.insert(0, frame.operand_stack.pop_slot().unwrap());
+        }
+        return vars_of(result);
+    }
+
+    #[test]
+    fn conversions_truncate_and_saturate() {
+        assert_eq!(convert(0x8b, |s| s.push_f32(f32::NAN)).get_i32(0).ok(), Some(0));
+        assert_eq!(convert(0x8b, |s| s.push_f32(1e20)).get_i32(0).ok(), Some(i32::MAX));
+        assert_eq!(convert(0x8b, |s| s.push_f32(-1e20)).get_i32(0).ok(), Some(i32::MIN));
+        assert_eq!(convert(0x8e, |s| s.push_f64(-2.7)).get_i32(0).ok(), Some(-2));
+        assert_eq!(convert(0x8e, |s| s.push_f64(f64::NEG_INFINITY)).get_i32(0).ok(), Some(i32::MIN));
+        assert_eq!(convert(0x88, |s| s.push_i64(0x1_0000_0005)).get_i32(0).ok(), Some(5));
+        assert_eq!(convert(0x91, |s| s.push_i32(200)).get_i32(0).ok(), Some(-56));
+        assert_eq!(convert(0x92, |s| s.push_i32(-1)).get_i32(0).ok(), Some(65535));
+        assert_eq!(convert(0x93, |s| s.push_i32(40000)).get_i32(0).ok(), Some(-25536));
+
+        assert_eq!(convert(0x85, |s| s.push_i32(-1)).get_i64(0).ok(), Some(-1));
+        assert_eq!(convert(0x8c, |s| s.push_f32(f32::NAN)).get_i64(0).ok(), Some(0));
+        assert_eq!(convert(0x8f, |s| s.push_f64(f64::INFINITY)).get_i64(0).ok(), Some(i64::MAX));
+        assert_eq!(convert(0x8f, |s| s.push_f64(-1e300)).get_i64(0).ok(), Some(i64::MIN));
+
+        assert_eq!(convert(0x87, |s| s.push_i32(i32::MIN)).get_f64(0).ok(), Some(i32::MIN as f64));
+        assert_eq!(convert(0x8d, |s| s.push_f32(0.1)).get_f64(0).ok(), Some(0.1f32 as f64));
+        assert_eq!(convert(0x90, |s| s.push_f64(1e40)).get_f32(0).ok(), Some(f32::INFINITY));
+        // rounds to nearest, 2^24 + 1 isn't representable as a float
+        assert_eq!(convert(0x89, |s| s.push_i64((1 << 24) + 1)).get_f32(0).ok(), Some(16777216.0));
+    }
+
+    fn branch_of(code: &[u8], push: impl FnOnce(&mut OperandStack) -> Result<(), JavaError>) -> Option<i32> {
+        let mut frame = test_frame();
+        push(&mut frame.operand_stack).unwrap();
+        return match exec(code, &mut frame) {
+            Flow::Branch(offset) => Some(offset),
+            _ => None,
+        };
+    }
+
+    #[test]
+    fn comparisons_branch_relative_to_the_instruction() {
+        let cmp = |opcode: u8, push: &dyn Fn(&mut OperandStack) -> Result<(), JavaError>| convert(opcode, push).get_i32(0).unwrap();
+        assert_eq!(cmp(0x94, &|s| { s.push_i64(i64::MIN)?; s.push_i64(1) }), -1);
+        assert_eq!(cmp(0x94, &|s| { s.push_i64(5)?; s.push_i64(5) }), 0);
+        assert_eq!(cmp(0x95, &|s| { s.push_f32(2.0)?; s.push_f32(1.0) }), 1);
+        // NaN gives -1 for fcmpl and dcmpl, 1 for fcmpg and dcmpg
+        assert_eq!(cmp(0x95, &|s| { s.push_f32(f32::NAN)?; s.push_f32(1.0) }), -1);
+        assert_eq!(cmp(0x96, &|s| { s.push_f32(1.0)?; s.push_f32(f32::NAN) }), 1);
+        assert_eq!(cmp(0x97, &|s| { s.push_f64(f64::NAN)?; s.push_f64(f64::NAN) }), -1);
+        assert_eq!(cmp(0x98, &|s| { s.push_f64(0.0)?; s.push_f64(f64::NAN) }), 1);
+        assert_eq!(cmp(0x98, &|s| { s.push_f64(-0.0)?; s.push_f64(0.0) }), 0);
+
+        let int_cases: &[(u8, i32, bool)] = &[
+            (0x99, 0, true), (0x99, 1, false), (0x9a, 1, true), (0x9b, -1, true), (0x9b, 0, false),
+            (0x9c, 0, true), (0x9d, 0, false), (0x9d, 1, true), (0x9e, 0, true), (0x9e, 1, false),
+        ];
+        for (opcode, v, taken) in int_cases {
+            let want = if *taken { Some(-3) } else { None };
+            assert_eq!(branch_of(&[*opcode, 0xff, 0xfd], |s| s.push_i32(*v)), want, "{} {}", mnemonic(*opcode).unwrap(), v);
+        }
+        let icmp_cases: &[(u8, i32, i32, bool)] = &[
+            (0x9f, 1, 1, true), (0xa0, 1, 1, false), (0xa1, -1, 0, true), (0xa2, 0, 0, true),
+            (0xa3, 0, 0, false), (0xa4, i32::MIN, i32::MAX, true),
+        ];
+        for (opcode, v1, v2, taken) in icmp_cases {
+            let want = if *taken { Some(0x100) } else { None };
+            assert_eq!(branch_of(&[*opcode, 0x01, 0x00], |s| { s.push_i32(*v1)?; s.push_i32(*v2) }), want,
+                "{} {} {}", mnemonic(*opcode).unwrap(), v1, v2);
+        }
+
+        assert_eq!(branch_of(&[0xc6, 0, 8], |s| s.push_null()), Some(8));
+        assert_eq!(branch_of(&[0xc7, 0, 8], |s| s.push_null()), None);
+        assert_eq!(branch_of(&[0xa5, 0, 8], |s| { s.push_null()?; s.push_null() }), Some(8));
+        assert_eq!(branch_of(&[0xa6, 0, 8], |s| { s.push_null()?; s.push_null() }), None);
+        assert_eq!(branch_of(&[0xa5, 0, 8], |s| { s.push_ref(Some(ObjRef(1)))?; s.push_ref(Some(ObjRef(1))) }), Some(8));
+        assert_eq!(branch_of(&[0xa6, 0, 8], |s| { s.push_ref(Some(ObjRef(1)))?; s.push_ref(Some(ObjRef(2))) }), Some(8));
+        assert_eq!(branch_of(&[0xa5, 0, 8], |s| { s.push_ref(Some(ObjRef(1)))?; s.push_null() }), None);
+        assert_eq!(branch_of(&[0xa7, 0x80, 0], |_| Ok(())), Some(-32768));
+        assert_eq!(branch_of(&[0xc8, 0, 1, 0, 0], |_| Ok(())), Some(0x10000));
+    }
+
+    #[test]
+    fn jsr_pushes_the_address_ret_returns_to() {
+        let mut frame = test_frame();
+        frame.next_pc = 13;
+        assert!(matches!(exec(&[0xa8, 0, 20], &mut frame), Flow::Branch(20)));
+        exec(&[0x4b], &mut frame);
+        assert_eq!(frame.local_vars.get_i32(0).ok(), Some(13));
+
+        frame.next_pc = 40;
+        assert!(matches!(exec(&[0xa9, 0], &mut frame), Flow::Next));
+        assert_eq!(frame.next_pc, 13);
+        frame.local_vars.set_i32(5, 7).unwrap();
+        exec(&[0xc4, 0xa9, 0, 5], &mut frame);
+        assert_eq!(frame.next_pc, 7);
+    }
+
+    #[test]
+    fn interpreter_follows_loops_and_switches() {
+        let branches = fixture_class("fox/Branches");
+        let mut env = empty_env();
+        let mut thread = Thread::new(ThreadId(1), 16);
+        let mut call = |name: &str, descriptor: &str, args: Vec<Slot>| {
+            let method = branches.get_method(name, descriptor).unwrap();
+            return vars_of(invoke(&mut env, &mut thread, branches.clone(), method, args).unwrap()).get_i32(0).unwrap();
+        };
+        assert_eq!(call("sumTo", "(I)I", slots(1, |v| v.set_i32(0, 100))), 5050);
+        assert_eq!(call("sumTo", "(I)I", slots(1, |v| v.set_i32(0, -1))), 0);
+        for (x, want) in [(0, -1), (1, 10), (3, 30), (4, -1), (i32::MIN, -1)] {
+            assert_eq!(call("dense", "(I)I", slots(1, |v| v.set_i32(0, x))), want);
+        }
+        for (x, want) in [(-1000, 1), (0, 2), (1000000, 3), (1, 0)] {
+            assert_eq!(call("sparse", "(I)I", slots(1, |v| v.set_i32(0, x))), want);
+        }
+        assert_eq!(call("less", "(DD)I", slots(4, |v| { v.set_f64(0, 1.0)?; v.set_f64(2, 2.0) })), 1);
+        assert_eq!(call("less", "(DD)I", slots(4, |v| { v.set_f64(0, f64::NAN)?; v.set_f64(2, 2.0) })), 0);
+        assert_eq!(call("sign", "(J)I", slots(2, |v| v.set_i64(0, -1 << 40))), -1);
+        assert_eq!(call("sign", "(J)I", slots(2, |v| v.set_i64(0, 0))), 0);
+        assert_eq!(call("sign", "(J)I", slots(2, |v| v.set_i64(0, 1 << 40))), 1);
+    }
+
+    // a linked instance of `name` as a reference slot
+    fn instance_of(env: &mut Environment, name: &str) -> Slot {
+        let class = env.load_class(name).unwrap().unwrap();
+        link_class(env, &class).unwrap();
+        return Slot { val_num: 0, val_ref: Some(env.heap.alloc_object(&class).unwrap()) };
+    }
+
+    fn null_slot() -> Slot {
+        return Slot { val_num: 0, val_ref: None };
+    }
+
+    #[test]
+    fn methods_are_selected_through_vtables_and_itables() {
+        let mut env = match fixture_env() {
+            Some(env) => env,
+            None => return,
+        };
+        let dispatch = env.load_class("fox/Dispatch").unwrap().unwrap();
+        let mut thread = Thread::new(ThreadId(1), 16);
+        let mut call = |env: &mut Environment, name: &str, descriptor: &str, args: Vec<Slot>| {
+            let method = dispatch.get_method(name, descriptor).unwrap();
+            return invoke(env, &mut thread, dispatch.clone(), method, args).map(|value| vars_of(value).get_i32(0).unwrap());
+        };
+
+        // invokevirtual, overridden and abstract methods
+        let square = instance_of(&mut env, "fox/Square");
+        let cube = instance_of(&mut env, "fox/Cube");
+        assert_eq!(call(&mut env, "describe", "(Lfox/Shape;)I", vec![square]).ok(), Some(43));
+        assert_eq!(call(&mut env, "describe", "(Lfox/Shape;)I", vec![cube]).ok(), Some(45));
+        // invokeinterface, the more specific default method wins
+        assert_eq!(call(&mut env, "twice", "(Lfox/Sized;)I", vec![square]).ok(), Some(6));
+        assert_eq!(call(&mut env, "twice", "(Lfox/Sized;)I", vec![cube]).ok(), Some(1000));
+        // invokespecial of a super class method and of an interface's default method
+        assert_eq!(call(&mut env, "base", "(Lfox/Cube;)I", vec![cube]).ok(), Some(3));
+        let both = instance_of(&mut env, "fox/Both");
+        assert_eq!(call(&mut env, "side", "(Lfox/Both;)I", vec![both]).ok(), Some(11));
+        // invokestatic, a long and a double between the ints
+        assert_eq!(call(&mut env, "callSum", "()I", vec![]).ok(), Some(4321));
+
+        let shape = instance_of(&mut env, "fox/Shape");
+        let err = call(&mut env, "describe", "(Lfox/Shape;)I", vec![shape]).err().unwrap();
+        assert_eq!(err.class_name, "java/lang/AbstractMethodError");
+        let err = call(&mut env, "describe", "(Lfox/Shape;)I", vec![null_slot()]).err().unwrap();
+        assert_eq!(err.class_name, "java/lang/NullPointerException");
+        let err = call(&mut env, "twice", "(Lfox/Sized;)I", vec![both]).err().unwrap();
+        assert_eq!(err.class_name, "java/lang/IncompatibleClassChangeError");
+
+        // overrides take the index of the overridden method
+        let square = env.load_class("fox/Square").unwrap().unwrap();
+        let cube = env.load_class("fox/Cube").unwrap().unwrap();
+        let index = square.get_method("size", "()I").unwrap().vtable_index.get();
+        assert!(index.is_some());
+        assert_eq!(cube.get_method("size", "()I").unwrap().vtable_index.get(), index);
+        assert_eq!(cube.vtable.borrow().len(), square.vtable.borrow().len() + 1);
+    }
+
+    #[test]
+    fn conflicting_default_methods_are_reported() {
+        let java = match boot_loader() {
+            Some(java) => java,
+            None => return,
+        };
+        // Both without its own side(), so Left and Right each bring one
+        let classes = SimpleClassLoader::new(fixture("classes"), ClassPathType::Folder);
+        let mut torn = MemoryClassLoader::new();
+        torn.add_class("fox/Both", patched(&fs::read(fixture("classes/fox/Both.class")).unwrap(), "side", "sidf"));
+        let app: Vec<Box<dyn ClassLoader>> = vec![Box::new(torn), Box::new(classes)];
+        let mut env = Environment::new(Box::new(java), no_classes(), Box::new(app));
+
+        let dispatch = env.load_class("fox/Dispatch").unwrap().unwrap();
+        let both = instance_of(&mut env, "fox/Both");
+        let mut thread = Thread::new(ThreadId(1), 16);
+        let method = dispatch.get_method("side", "(Lfox/Both;)I").unwrap();
+        let err = invoke(&mut env, &mut thread, dispatch.clone(), method, vec![both]).err().unwrap();
+        assert_eq!(err.class_name, "java/lang/IncompatibleClassChangeError");
+        assert!(err.message.starts_with("Conflicting default methods"));
+    }
+
+    #[test]
+    fn lambdas_are_linked_once_per_call_site() {
+        let mut env = match fixture_env() {
+            Some(env) => env,
+            None => return,
+        };
+        let indy = env.load_class("fox/Indy").unwrap().unwrap();
+        assert_eq!(indy.bootstrap_methods.len(), 12);
+        assert_eq!(indy.bootstrap_methods[0].bootstrap_arguments.len(), 3);
+        let mut thread = Thread::new(ThreadId(1), 16);
+        let mut call = |env: &mut Environment, name: &str, descriptor: &str, args: Vec<Slot>| {
+            let method = indy.get_method(name, descriptor).unwrap();
+            return invoke(env, &mut thread, indy.clone(), method, args).map(vars_of);
+        };
+        let int = |value: Result<LocalVars, JavaError>| value.ok().map(|v| v.get_i32(0).unwrap());
+
+        // a lambda, a static method reference and an interface method reference
+        assert_eq!(int(call(&mut env, "doubled", "(I)I", slots(1, |v| v.set_i32(0, 21)))), Some(42));
+        assert_eq!(int(call(&mut env, "answer", "()I", vec![])), Some(42));
+        let square = instance_of(&mut env, "fox/Square");
+        assert_eq!(int(call(&mut env, "sizeOf", "(Lfox/Sized;)I", vec![square])), Some(3));
+
+        // the call site in the loop is linked on its first run only
+        assert_eq!(int(call(&mut env, "count", "(I)I", slots(1, |v| v.set_i32(0, 5)))), Some(5));
+        assert_eq!(int(call(&mut env, "count", "(I)I", slots(1, |v| v.set_i32(0, 3)))), Some(3));
+        let count = indy.get_method("count", "(I)I").unwrap();
+        assert_eq!(count.call_sites.borrow().len(), 1);
+        // one lambda class for each of the four call sites linked
+        assert_eq!(env.next_lambda_id(), 5);
+
+        // captured values are kept in the lambda object
+        assert_eq!(int(call(&mut env, "captured", "(I)I", slots(1, |v| v.set_i32(0, 1)))), Some(1));
+        assert_eq!(int(call(&mut env, "captured", "(I)I", slots(1, |v| v.set_i32(0, 8)))), Some(8));
+        let sum = call(&mut env, "sum", "(JI)J", slots(3, |v| {
+            v.set_i64(0, 1 << 40)?;
+            v.set_i32(2, 2)
+        })).unwrap();
+        assert_eq!(sum.get_i64(0).ok(), Some((1 << 40) + 2));
+        let square = instance_of(&mut env, "fox/Square");
+        assert_eq!(int(call(&mut env, "boundSize", "(Lfox/Sized;)I", vec![square])), Some(3));
+
+        // a constructor reference, and an int widened to the long the interface returns
+        assert_eq!(int(call(&mut env, "made", "(I)I", slots(1, |v| v.set_i32(0, 6)))), Some(6));
+        assert_eq!(call(&mut env, "widened", "()J", vec![]).unwrap().get_i64(0).ok(), Some(42));
+
+        // the double is unboxed for the implementation and its result boxed again
+        let double = env.load_class("java/lang/Double").unwrap().unwrap();
+        link_class(&mut env, &double).unwrap();
+        // its <clinit> needs natives the VM doesn't have, but valueOf doesn't need what it sets up
+        *double.init_state.borrow_mut() = InitState::Initialized;
+        let halved = call(&mut env, "halved", "(D)D", slots(2, |v| v.set_f64(0, 5.0))).unwrap();
+        assert_eq!(halved.get_f64(0).ok(), Some(2.5));
+
+        // strings are concatenated as Java writes each type, objects by their toString
+        let concat = call(&mut env, "concat", "(I)Ljava/lang/String;", slots(1, |v| v.set_i32(0, 7))).unwrap();
+        assert_eq!(value_of(&env, concat.get_ref(0).unwrap().unwrap()).as_deref(), Some("a7"));
+        let name = Slot { val_num: 0, val_ref: Some(intern(&mut env, "x").unwrap()) };
+        let tag = instance_of(&mut env, "fox/Tag");
+        let describe = "(Ljava/lang/String;JCZFDLjava/lang/Object;)Ljava/lang/String;";
+        for (object, expected) in [(tag, "x=-3!true1.5E10/0.25 tag"), (null_slot(), "x=-3!true1.5E10/0.25 null")] {
+            let mut args = slots(8, |v| {
+                v.set_i64(1, -3)?;
+                v.set_i32(3, '!' as i32)?;
+                v.set_i32(4, 1)?;
+                v.set_f32(5, 1.5e10)?;
+                v.set_f64(6, 0.25)
+            });
+            args[0] = name;
+            args.push(object);
+            let value = call(&mut env, "describe", describe, args).unwrap();
+            assert_eq!(value_of(&env, value.get_ref(0).unwrap().unwrap()).as_deref(), Some(expected));
+        }
+        // and one for each of the six linked since
+        assert_eq!(env.next_lambda_id(), 12);
+    }
+
+    #[test]
+    fn registered_bootstraps_link_call_sites() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        // links `"a" + b` to Indy.label(b)
+        fn concat(_ctx: &mut ExecContext, call: &BootstrapCall) -> Result<MethodHandle, JavaError> {
+            CALLS.fetch_add(1, Ordering::SeqCst);
+            assert_eq!(call.name, "makeConcatWithConstants");
+            assert!(matches!(call.args.as_slice(), [BootstrapArg::String(recipe)] if recipe == "a\u{1}"));
+            let method = call.caller.get_method("label", "(I)Ljava/lang/String;").unwrap();
+            return Ok(MethodHandle::Direct(REF_INVOKE_STATIC, ResolvedMethod { class: call.caller.clone(), method }));
+        }
+
+        let mut env = match fixture_env() {
+            Some(env) => env,
+            None => return,
+        };
+        env.register_bootstrap("java/lang/invoke/StringConcatFactory", "makeConcatWithConstants", concat);
+        let indy = env.load_class("fox/Indy").unwrap().unwrap();
+        let method = indy.get_method("concat", "(I)Ljava/lang/String;").unwrap();
+        let mut thread = Thread::new(ThreadId(1), 16);
+        for _ in 0..2 {
+            let value = invoke(&mut env, &mut thread, indy.clone(), method.clone(), slots(1, |v| v.set_i32(0, 7))).unwrap();
+            assert!(value[0].val_ref.is_none());
+        }
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn bootstrap_methods_written_in_java_are_run() {
+        let mut env = match fixture_env() {
+            Some(env) => env,
+            None => return,
+        };
+        let bootstraps = env.load_class("fox/Bootstraps").unwrap().unwrap();
+        link_class(&mut env, &bootstraps).unwrap();
+        let constants = vec![
+            Constant::Empty,
+            Constant::InvokeDynamic(InvokeDynamicRef {
+                bootstrap_method_attr_index: 0,
+                name: "seven".to_string(),
+                descriptor: "()I".to_string(),
+            }),
+            Constant::MethodHandle(MethodHandleRef {
+                reference_kind: REF_INVOKE_STATIC,
+                reference_index: 3,
+                resolved: Default::default(),
+            }),
+            Constant::MethodRef(MemberSymRef {
+                class_name: "fox/Bootstraps".to_string(),
+                name: "link".to_string(),
+                descriptor: "(Ljava/lang/invoke/MethodHandles$Lookup;Ljava/lang/String;Ljava/lang/invoke/MethodType;I)Ljava/lang/invoke/CallSite;".to_string(),
+                resolved: Default::default(),
+            }),
+            Constant::Integer(3),
+        ];
+        let method = Method::synthesized(access_flags::ACC_STATIC, "call", "()I", 1, vec![0xba, 0, 1, 0, 0, 0xac]);
+        let object = env.load_class("java/lang/Object").unwrap().unwrap();
+        let mut caller = Class::synthesized(&bootstraps, "fox/Bootstraps$Caller".to_string(), object, vec![],
+            RuntimeConstantPool::from_constants(constants), vec![method]);
+        caller.bootstrap_methods = vec![BootstrapMethod { bootstrap_method_ref: 2, bootstrap_arguments: vec![4] }];
+        let caller = Rc::new(caller);
+        link_class(&mut env, &caller).unwrap();
+        let mut thread = Thread::new(ThreadId(1), 16);
+        let method = caller.get_method("call", "()I").unwrap();
+        let static_slot = |name: &str, descriptor: &str| bootstraps.get_field(name, descriptor).unwrap().slot_id;
+
+        // the method ran, but a call site it returns has to be there
+        let err = invoke(&mut env, &mut thread, caller.clone(), method.clone(), vec![]).err().unwrap();
+        assert_eq!(err.class_name, "java/lang/BootstrapMethodError");
+        assert_eq!(bootstraps.static_vars.borrow().get_i32(static_slot("offset", "I")).ok(), Some(3));
+        let name = bootstraps.static_vars.borrow().get_ref(static_slot("name", "Ljava/lang/String;")).unwrap();
+        assert_eq!(value_of(&env, name.unwrap()).as_deref(), Some("seven"));
+        let method_type = bootstraps.static_vars.borrow().get_ref(static_slot("type", "Ljava/lang/invoke/MethodType;")).unwrap();
+        assert!(method_type.is_some() && method_type == env.heap.method_type("()I"));
+        let lookup_class = bootstraps.static_vars.borrow().get_ref(static_slot("caller", "Ljava/lang/Class;")).unwrap();
+        assert!(lookup_class.is_some() && lookup_class == caller.mirror.get());
+
+        // a ConstantCallSite targeting seven(), made as the JDK can't here
+        let call_site_class = env.load_class("java/lang/invoke/ConstantCallSite").unwrap().unwrap();
+        let call_site = env.heap.alloc_object(&call_site_class).unwrap();
+        bootstraps.static_vars.borrow_mut().set_ref(static_slot("site", "Ljava/lang/invoke/CallSite;"), Some(call_site)).unwrap();
+        let seven = ResolvedMethod { class: bootstraps.clone(), method: bootstraps.get_method("seven", "()I").unwrap() };
+        let mut frame = Frame::new(caller.clone(), method.clone());
+        let mut ctx = ExecContext { env: &mut env, thread: &mut thread, frame: &mut frame, pc: 0 };
+        let target = method_handle_object(&mut ctx, MethodHandle::Direct(REF_INVOKE_STATIC, seven)).unwrap();
+        let target_slot = call_site_class.super_class.as_ref().unwrap().get_field("target", "Ljava/lang/invoke/MethodHandle;").unwrap().slot_id;
+        env.heap.set_field_ref(call_site, target_slot, Some(target)).unwrap();
+
+        // linked once, to the target of the call site
+        for _ in 0..2 {
+            let value = invoke(&mut env, &mut thread, caller.clone(), method.clone(), vec![]).unwrap();
+            assert_eq!(vars_of(value).get_i32(0).ok(), Some(7));
+        }
+        assert_eq!(bootstraps.static_vars.borrow().get_i32(static_slot("offset", "I")).ok(), Some(6));
+    }
+
+    #[test]
+    fn method_types_and_handles_are_objects() {
+        let mut env = match fixture_env() {
+            Some(env) => env,
+            None => return,
+        };
+        let point = env.load_class("fox/Point").unwrap().unwrap();
+        link_class(&mut env, &point).unwrap();
+        let member = |class_name: &str, name: &str, descriptor: &str| MemberSymRef {
+            class_name: class_name.to_string(),
+            name: name.to_string(),
+            descriptor: descriptor.to_string(),
+            resolved: Default::default(),
+        };
+        let handle = |reference_kind: u8, reference_index: u16| Constant::MethodHandle(MethodHandleRef {
+            reference_kind,
+            reference_index,
+            resolved: Default::default(),
+        });
+        let constants = vec![
+            Constant::Empty,
+            Constant::MethodType("(I)J".to_string()),
+            handle(REF_NEW_INVOKE_SPECIAL, 3),
+            Constant::MethodRef(member("fox/Point", "<init>", "(IJ)V")),
+            handle(REF_GET_FIELD, 5),
+            Constant::FieldRef(MemberSymRef {
+                class_name: "fox/Point".to_string(),
+                name: "x".to_string(),
+                descriptor: "I".to_string(),
+                resolved: Default::default(),
+            }),
+        ];
+        let methods = vec![
+            Method::synthesized(access_flags::ACC_STATIC, "type", "()Ljava/lang/Object;", 1, vec![0x12, 1, 0xb0]),
+            Method::synthesized(access_flags::ACC_STATIC, "constructor", "()Ljava/lang/Object;", 1, vec![0x12, 2, 0xb0]),
+            Method::synthesized(access_flags::ACC_STATIC, "getter", "()Ljava/lang/Object;", 1, vec![0x12, 4, 0xb0]),
+        ];
+        let object = env.load_class("java/lang/Object").unwrap().unwrap();
+        let constants_class = Rc::new(Class::synthesized(&point, "fox/Point$Constants".to_string(), object, vec![],
+            RuntimeConstantPool::from_constants(constants), methods));
+        let mut thread = Thread::new(ThreadId(1), 16);
+        let mut ldc = |env: &mut Environment, name: &str| {
+            let method = constants_class.get_method(name, "()Ljava/lang/Object;").unwrap();
+            return invoke(env, &mut thread, constants_class.clone(), method, vec![]).unwrap()[0].val_ref.unwrap();
+        };
+
+        // method types are made once for each descriptor
+        let method_type = ldc(&mut env, "type");
+        assert_eq!(env.heap.get(method_type).class().name, "java/lang/invoke/MethodType");
+        assert_eq!(ldc(&mut env, "type"), method_type);
+        assert_eq!(env.heap.method_type("(I)J"), Some(method_type));
+
+        // a method handle knows its type and what it invokes
+        let constructor = ldc(&mut env, "constructor");
+        assert_eq!(env.heap.get(constructor).class().name, "java/lang/invoke/DirectMethodHandle");
+        let method_handle = env.load_class("java/lang/invoke/MethodHandle").unwrap().unwrap();
+        let type_slot = method_handle.get_field("type", "Ljava/lang/invoke/MethodType;").unwrap().slot_id;
+        let constructor_type = env.heap.get(constructor).fields().unwrap().get_ref(type_slot).unwrap();
+        assert!(constructor_type.is_some() && constructor_type == env.heap.method_type("(IJ)Lfox/Point;"));
+        let constructor = env.heap.method_handle(constructor).unwrap().clone();
+        assert!(matches!(&constructor, MethodHandle::Direct(REF_NEW_INVOKE_SPECIAL, resolved) if resolved.method.name == "<init>"));
+        let getter = ldc(&mut env, "getter");
+        let getter = env.heap.method_handle(getter).unwrap().clone();
+        assert_eq!(getter.method_type().as_deref(), Some("(Lfox/Point;)I"));
+
+        // invoking the constructor leaves the new object instead of the arguments
+        let caller = Method::synthesized(access_flags::ACC_STATIC, "caller", "()V", 3, vec![]);
+        let mut frame = Frame::new(constants_class.clone(), Rc::new(caller));
+        frame.operand_stack.push_i32(7).unwrap();
+        frame.operand_stack.push_i64(9).unwrap();
+        let mut ctx = ExecContext { env: &mut env, thread: &mut thread, frame: &mut frame, pc: 0 };
+        assert!(matches!(invoke_handle(&mut ctx, &constructor, "(IJ)Lfox/Point;"), Ok(Flow::Next)));
+        assert!(matches!(invoke_handle(&mut ctx, &getter, "(Lfox/Point;)I"), Ok(Flow::Next)));
+        assert_eq!(frame.operand_stack.pop_i32().ok(), Some(7));
+        assert!(frame.operand_stack.pop_slot().is_err());
+    }
+
+    #[test]
+    fn objects_and_arrays_live_on_the_heap() {
+        let mut env = match fixture_env() {
+            Some(env) => env,
+            None => return,
+        };
+        let point = env.load_class("fox/Point").unwrap().unwrap();
+        let a = env.heap.alloc_object(&point).unwrap();
+        let b = env.heap.alloc_object(&point).unwrap();
+        assert_ne!(a, b);
+        assert_eq!(env.heap.len(), 2);
+        assert!(Rc::ptr_eq(env.heap.get(a).class(), &point));
+        assert_ne!(env.heap.get(a).header.hash_code, env.heap.get(b).header.hash_code);
+        assert!(env.heap.get(a).header.monitor.owner.is_none());
+
+        // fields are zeroed and laid out as the class says, a long takes two slots
+        let y = point.get_field("y", "J").unwrap().slot_id;
+        let label = point.get_field("label", "Ljava/lang/Object;").unwrap().slot_id;
+        env.heap.get_mut(a).fields_mut().unwrap().set_i64(y, 1 << 40).unwrap();
+        env.heap.get_mut(a).fields_mut().unwrap().set_ref(label, Some(b)).unwrap();
+        assert_eq!(env.heap.get(a).fields().unwrap().get_i64(y).ok(), Some(1 << 40));
+        assert_eq!(env.heap.get(a).fields().unwrap().get_ref(label).unwrap(), Some(b));
+        assert_eq!(env.heap.get(b).fields().unwrap().len(), 4);
+        assert_eq!(env.heap.get(b).fields().unwrap().get_ref(label).unwrap(), None);
+        assert_eq!(env.heap.get(b).array_length(), None);
+
+        // arrays are typed by their element type
+        let longs = env.load_class("[J").unwrap().unwrap();
+        let points = env.load_class("[Lfox/Point;").unwrap().unwrap();
+        let r = env.heap.alloc_array(&longs, 3).unwrap();
+        assert!(matches!(&env.heap.get(r).body, Body::Longs(v) if v == &[0, 0, 0]));
+        let r = env.heap.alloc_array(&points, 2).unwrap();
+        if let Body::Refs(v) = &mut env.heap.get_mut(r).body {
+            v[1] = Some(a);
+        }
+        assert!(matches!(&env.heap.get(r).body, Body::Refs(v) if v == &[None, Some(a)]));
+        assert_eq!(env.heap.get(r).array_length(), Some(2));
+        assert!(env.heap.get(r).fields().is_none());
+    }
+
+    // fixture_env with its objects on `heap`
+    fn fixture_env_with_heap(heap: Heap) -> Option<Environment> {
+        let app = SimpleClassLoader::new(fixture("classes"), ClassPathType::Folder);
+        return Some(Environment::with_heap(Box::new(boot_loader()?), no_classes(), Box::new(app), heap));
+    }
+
+    #[test]
+    fn unreachable_objects_are_collected() {
+        let mut env = match fixture_env_with_heap(Heap::with_max_size(1 << 20)) {
+            Some(env) => env,
+            None => return,
+        };
+        let holder = env.load_class("fox/Holder").unwrap().unwrap();
+        link_class(&mut env, &holder).unwrap();
+        let next = holder.get_field("next", "Ljava/lang/Object;").unwrap().slot_id;
+        let kept = holder.get_field("kept", "Ljava/lang/Object;").unwrap().slot_id;
+        let objects: Vec<ObjRef> = (0..6).map(|_| env.heap.alloc_object(&holder).unwrap()).collect();
+        let used = env.heap.used();
+
+        // 0 is a root and holds 1, 2 is in a static field, 3 is held by native code
+        env.heap.get_mut(objects[0]).fields_mut().unwrap().set_ref(next, Some(objects[1])).unwrap();
+        holder.static_vars.borrow_mut().set_ref(kept, Some(objects[2])).unwrap();
+        let global = env.heap.new_global_ref(objects[3]);
+        // 4 and 5 only refer to each other
+        env.heap.get_mut(objects[4]).fields_mut().unwrap().set_ref(next, Some(objects[5])).unwrap();
+        env.heap.get_mut(objects[5]).fields_mut().unwrap().set_ref(next, Some(objects[4])).unwrap();
+
+        let mut roots = vec![objects[0]];
+        class_roots(&env, &mut roots);
+        env.heap.collect(roots);
+        assert_eq!(env.heap.len(), 4);
+        assert_eq!(env.heap.used(), used / 6 * 4);
+        assert_eq!(env.heap.get(objects[0]).fields().unwrap().get_ref(next).unwrap(), Some(objects[1]));
+
+        env.heap.delete_global_ref(global);
+        holder.static_vars.borrow_mut().set_ref(kept, None).unwrap();
+        env.heap.collect(vec![]);
+        assert!(env.heap.is_empty());
+        assert_eq!(env.heap.used(), 0);
+
+        // the frames of the thread are roots, slots popped off the operand stack aren't
+        let mut frame = test_frame();
+        let mut thread = Thread::new(ThreadId(1), 16);
+        let mut caller = test_frame();
+        let in_caller = env.heap.alloc_object(&holder).unwrap();
+        caller.operand_stack.push_ref(Some(in_caller)).unwrap();
+        thread.stack.push(caller);
+        let in_local = env.heap.alloc_object(&holder).unwrap();
+        let on_stack = env.heap.alloc_object(&holder).unwrap();
+        let popped = env.heap.alloc_object(&holder).unwrap();
+        frame.local_vars.set_ref(2, Some(in_local)).unwrap();
+        frame.operand_stack.push_ref(Some(on_stack)).unwrap();
+        frame.operand_stack.push_ref(Some(popped)).unwrap();
+        frame.operand_stack.pop_i32().unwrap();
+        let mut ctx = ExecContext { env: &mut env, thread: &mut thread, frame: &mut frame, pc: 0 };
+        ctx.collect_garbage();
+        assert_eq!(ctx.env.heap.len(), 3);
+        assert_eq!(ctx.env.heap.get(in_local).class().name, "fox/Holder");
+    }
+
+    #[test]
+    fn heap_limit_raises_out_of_memory() {
+        assert_eq!(parse_heap_size("4096"), Some(4096));
+        assert_eq!(parse_heap_size("64k"), Some(64 << 10));
+        assert_eq!(parse_heap_size("512M"), Some(512 << 20));
+        assert_eq!(parse_heap_size("2g"), Some(2 << 30));
+        assert_eq!(parse_heap_size("m"), None);
+        assert_eq!(parse_heap_size("-1m"), None);
+
+        let mut env = match fixture_env_with_heap(Heap::with_max_size(1024)) {
+            Some(env) => env,
+            None => return,
+        };
+        let holder = env.load_class("fox/Holder").unwrap().unwrap();
+        let mut count = 0;
+        let err = loop {
+            match env.heap.alloc_object(&holder) {
+                Ok(_) => count += 1,
+                Err(e) => break e,
+            }
+        };
+        assert_eq!(err.class_name, "java/lang/OutOfMemoryError");
+        assert_eq!(count, 1024 / 24);
+        let longs = env.load_class("[J").unwrap().unwrap();
+        assert!(env.heap.alloc_array(&longs, 1 << 40).is_err());
+
+        // allocating from the interpreter collects the garbage and tries again
+        let mut frame = test_frame();
+        let mut thread = Thread::new(ThreadId(1), 16);
+        let mut ctx = ExecContext { env: &mut env, thread: &mut thread, frame: &mut frame, pc: 0 };
+        let kept = ctx.alloc_object(&holder).unwrap();
+        ctx.frame.local_vars.set_ref(0, Some(kept)).unwrap();
+        assert_eq!(ctx.env.heap.len(), 1);
+        while ctx.frame.operand_stack.size() < 8 {
+            let r = ctx.alloc_array(&longs, 10).unwrap();
+            ctx.frame.operand_stack.push_ref(Some(r)).unwrap();
+        }
+        let r = ctx.alloc_array(&longs, 10).unwrap();
+        ctx.frame.local_vars.set_ref(1, Some(r)).unwrap();
+        // the holder and 9 arrays of 104 bytes leave 64 of the 1024 bytes, all of them live
+        let err = ctx.alloc_array(&longs, 10).err().unwrap();
+        assert_eq!(err.class_name, "java/lang/OutOfMemoryError");
+        assert_eq!(ctx.env.heap.len(), 10);
+    }
+
+    #[test]
+    fn generational_heap_promotes_survivors() {
+        // a 1k eden, survivor spaces of 256 bytes and a 4k old generation, objects are promoted at their second minor collection
+        let mut env = match fixture_env_with_heap(Heap::new(Box::new(Generational::with_sizes(1024, 256, 4096, 2)))) {
+            Some(env) => env,
+            None => return,
+        };
+        let holder = env.load_class("fox/Holder").unwrap().unwrap();
+        link_class(&mut env, &holder).unwrap();
+        let next = holder.get_field("next", "Ljava/lang/Object;").unwrap().slot_id;
+        let a = env.heap.alloc_object(&holder).unwrap();
+        let b = env.heap.alloc_object(&holder).unwrap();
+        env.heap.set_field_ref(a, next, Some(b)).unwrap();
+        env.heap.collect(vec![a]);
+        assert_eq!(env.heap.stats().minor_collections, 1);
+        assert_eq!(env.heap.stats().bytes_promoted, 0);
+
+        // eden fills up with garbage, the survivors are promoted
+        let mut garbage = 0;
+        while env.heap.alloc_object(&holder).is_ok() {
+            garbage += 1;
+        }
+        assert_eq!(garbage, 1024 / 24);
+        env.heap.collect(vec![a]);
+        assert_eq!(env.heap.len(), 2);
+        assert_eq!(env.heap.stats().bytes_promoted, 48);
+        assert_eq!(env.heap.stats().bytes_freed, garbage * 24);
+        assert_eq!(env.heap.used(), 48);
+
+        // a young object only an old one refers to is found through the card table
+        let c = env.heap.alloc_object(&holder).unwrap();
+        env.heap.set_field_ref(a, next, Some(c)).unwrap();
+        env.heap.collect(vec![]);
+        assert_eq!(env.heap.len(), 3);
+        assert_eq!(env.heap.get(a).fields().unwrap().get_ref(next).unwrap(), Some(c));
+        assert_eq!(env.heap.get(c).class().name, "fox/Holder");
+        assert_eq!(env.heap.stats().full_collections, 0);
+
+        // arrays too big for eden go to the old generation, which is collected once they don't fit
+        let longs = env.load_class("[J").unwrap().unwrap();
+        env.heap.alloc_array(&longs, 200).unwrap();
+        env.heap.alloc_array(&longs, 200).unwrap();
+        assert!(env.heap.alloc_array(&longs, 200).is_err());
+        env.heap.collect(vec![a]);
+        assert_eq!(env.heap.stats().full_collections, 1);
+        assert_eq!(env.heap.len(), 2);
+        env.heap.alloc_array(&longs, 200).unwrap();
+
+        // the interpreter collects the young generation when eden is full
+        while env.heap.alloc_object(&holder).is_ok() {}
+        let mut frame = test_frame();
+        let mut thread = Thread::new(ThreadId(1), 16);
+        let mut ctx = ExecContext { env: &mut env, thread: &mut thread, frame: &mut frame, pc: 0 };
+        ctx.alloc_object(&holder).unwrap();
+        let stats = *ctx.env.heap.stats();
+        assert_eq!(stats.minor_collections, 5);
+        assert!(stats.max_pause <= stats.total_pause);
+        assert!(stats.last_pause <= stats.max_pause);
+    }
+
+    #[test]
+    fn exceptions_go_to_their_handlers() {
+        let mut env = match fixture_env() {
+            Some(env) => env,
+            None => return,
+        };
+        let thrower = env.load_class("fox/Thrower").unwrap().unwrap();
+        link_class(&mut env, &thrower).unwrap();
+        let mut thread = Thread::new(ThreadId(1), 16);
+        let mut call = |env: &mut Environment, name: &str, descriptor: &str, args: &[i32]| {
+            let method = thrower.get_method(name, descriptor).unwrap();
+            let args = slots(args.len(), |v| args.iter().enumerate().try_for_each(|(n, a)| v.set_i32(n, *a)));
+            let value = invoke(env, &mut thread, thrower.clone(), method, args);
+            assert!(thread.stack.is_empty());
+            return value.map(|v| vars_of(v).get_i32(0).unwrap());
+        };
+
+        // a handler in the caller, one for a super class, finally rethrowing to an outer handler
+        assert_eq!(call(&mut env, "safeDivide", "(II)I", &[7, 2]).unwrap(), 3);
+        assert_eq!(call(&mut env, "safeDivide", "(II)I", &[7, 0]).unwrap(), -1);
+        assert_eq!(call(&mut env, "caughtAsSuper", "(II)I", &[7, 0]).unwrap(), -2);
+        assert_eq!(call(&mut env, "finallyRethrows", "(II)I", &[7, 0]).unwrap(), 11);
+        // throw null throws a NullPointerException
+        assert_eq!(call(&mut env, "throwNull", "()I", &[]).unwrap(), 1);
+        // running out of stack is an error that can be caught too
+        assert_eq!(call(&mut env, "overflow", "()I", &[]).unwrap(), -1);
+        assert_eq!(call(&mut env, "down", "(I)I", &[0]).err().unwrap().class_name, "java/lang/StackOverflowError");
+
+        let err = call(&mut env, "uncaught", "(I)I", &[7]).err().unwrap();
+        assert_eq!(err.class_name, "java/lang/ArithmeticException");
+        let object = err.object.unwrap();
+        assert_eq!(env.heap.get(object).class().name, "java/lang/ArithmeticException");
+        assert_eq!(env.heap.throwable_info(object).unwrap().message, "/ by zero");
+        let mut thread = Thread::new(ThreadId(1), 16);
+        thread.name = "main".to_string();
+        assert_eq!(uncaught_exception_report(&thread, &err), "Exception in thread \"main\" java.lang.ArithmeticException: / by zero\n\
+            \tat fox.Thrower.divide(Thrower.java:5)\n\
+            \tat fox.Thrower.uncaught(Thrower.java:56)\n");
+    }
+
+    #[test]
+    fn unresolvable_catch_types_throw_their_resolution_error() {
+        let mut env = match fixture_env() {
+            Some(env) => env,
+            None => return,
+        };
+        // fox/Gone is on the class path only, which a loader below the platform loader doesn't see
+        let mut plugin = MemoryClassLoader::new();
+        plugin.add_class("fox/Catcher", fs::read(fixture("classes/fox/Catcher.class")).unwrap());
+        let plugin = env.add_loader(LoaderId::PLATFORM, Box::new(plugin));
+        let catcher = env.load_class_from(plugin, "fox/Catcher").unwrap().unwrap();
+        link_class(&mut env, &catcher).unwrap();
+        let mut thread = Thread::new(ThreadId(1), 16);
+
+        // the ArithmeticException is replaced by the error resolving fox/Gone, which can be caught
+        let missing = catcher.get_method("missing", "(I)I").unwrap();
+        let err = invoke(&mut env, &mut thread, catcher.clone(), missing, slots(1, |v| v.set_i32(0, 0))).err().unwrap();
+        assert_eq!(err.class_name, "java/lang/NoClassDefFoundError");
+        assert!(thread.stack.is_empty());
+        let caught = catcher.get_method("missingThenCaught", "(I)I").unwrap();
+        let value = invoke(&mut env, &mut thread, catcher.clone(), caught, slots(1, |v| v.set_i32(0, 0))).unwrap();
+        assert_eq!(vars_of(value).get_i32(0).ok(), Some(2));
+    }
+
+    // a native of fox/Thrower, which checks where it's called from
+    fn answer(ctx: &mut ExecContext, args: &[Slot]) -> Result<Vec<Slot>, JavaError> {
+        let caller = ctx.stack_trace()[0].to_string();
+        if caller != "fox.Thrower.ask(Thrower.java:62)" {
+            return Err(JavaError::new("java/lang/IllegalStateException", caller));
+        }
+        return Ok(vec![Slot { val_num: args[0].val_num * 2, val_ref: None }]);
+    }
+
+    #[test]
+    fn stack_traces_come_from_the_line_tables() {
+        let mut env = match fixture_env() {
+            Some(env) => env,
+            None => return,
+        };
+        let thrower = env.load_class("fox/Thrower").unwrap().unwrap();
+        link_class(&mut env, &thrower).unwrap();
+        // lines 27, 30, 31 and 33 start at 0, 2, 5 and 9, then it's 31 again from 12 and 33 from 14
+        let method = thrower.get_method("finallyRethrows", "(II)I").unwrap();
+        let lines: Vec<Option<u16>> = [0, 1, 2, 8, 9, 12, 13, 14].iter().map(|pc| method.get_line_number(*pc)).collect();
+        assert_eq!(lines, [27, 27, 30, 31, 33, 31, 31, 33].iter().map(|l| Some(*l)).collect::<Vec<_>>());
+        assert_eq!(thrower.get_method("answer", "(I)I").unwrap().get_line_number(0), None);
+
+        // native methods run in Rust on the frame of their caller
+        let ask = thrower.get_method("ask", "(I)I").unwrap();
+        let mut thread = Thread::new(ThreadId(1), 16);
+        let err = invoke(&mut env, &mut thread, thrower.clone(), ask.clone(), slots(1, |v| v.set_i32(0, 20))).err().unwrap();
+        assert_eq!(err.class_name, "java/lang/UnsatisfiedLinkError");
+        env.register_native("fox/Thrower", "answer", "(I)I", answer);
+        let value = invoke(&mut env, &mut thread, thrower.clone(), ask, slots(1, |v| v.set_i32(0, 20))).unwrap();
+        assert_eq!(vars_of(value).get_i32(0).ok(), Some(41));
+
+        // a Throwable records where it was created, without the frames creating it
+        let mut frames = vec![Frame::new(thrower.clone(), thrower.get_method("divide", "(II)I").unwrap())];
+        frames[0].pc = 2;
+        for (class_name, method_name) in [("java/lang/ArithmeticException", "<init>"), ("java/lang/RuntimeException", "<init>"),
+                ("java/lang/Throwable", "<init>"), ("java/lang/Throwable", "fillInStackTrace")] {
+            let class = env.load_class(class_name).unwrap().unwrap();
+            let method = class.methods.iter().find(|m| m.name == method_name && !m.is_native()).unwrap().clone();
+            frames.push(Frame::new(class, method));
+        }
+        let mut frame = frames.pop().unwrap();
+        for frame in frames {
+            thread.stack.push(frame);
+        }
+        thread.name = "main".to_string();
+        let arithmetic = env.load_class("java/lang/ArithmeticException").unwrap().unwrap();
+        let object = env.heap.alloc_object(&arithmetic).unwrap();
+        let fill_in = env.get_native("java/lang/Throwable", "fillInStackTrace", "(I)Ljava/lang/Throwable;").unwrap();
+        let mut ctx = ExecContext { env: &mut env, thread: &mut thread, frame: &mut frame, pc: 0 };
+        assert_eq!(ctx.stack_trace().len(), 5);
+        let value = fill_in(&mut ctx, &[Slot { val_num: 0, val_ref: Some(object) }, Slot::default()]).unwrap();
+        assert_eq!(value[0].val_ref, Some(object));
+        let divide = StackTraceElement {
+            class_name: "fox.Thrower".to_string(),
+            method_name: "divide".to_string(),
+            file_name: Some("Thrower.java".to_string()),
+            line_number: Some(5),
+        };
+        assert_eq!(env.heap.throwable_info(object).unwrap().stack_trace, vec![divide.clone()]);
+        let throwable = env.load_class("java/lang/Throwable").unwrap().unwrap();
+        let depth = throwable.get_field("depth", "I").unwrap().slot_id;
+        assert_eq!(env.heap.get(object).fields().unwrap().get_i32(depth).ok(), Some(1));
+        // throwing it keeps the frames
+        assert_eq!(JavaError::thrown(&env, object).stack_trace, vec![divide]);
+
+        // StackTraceElement.of, which getStackTrace calls, makes objects of them
+        let element = env.load_class("java/lang/StackTraceElement").unwrap().unwrap();
+        let of = element.get_method("of", "(Ljava/lang/Throwable;I)[Ljava/lang/StackTraceElement;").unwrap();
+        let args = vec![Slot { val_num: 0, val_ref: Some(object) }, Slot { val_num: 1, val_ref: None }];
+        let elements = invoke(&mut env, &mut thread, element.clone(), of, args).unwrap()[0].val_ref.unwrap();
+        assert_eq!(env.heap.get(elements).array_length(), Some(1));
+        let first = env.heap.get(elements).references()[0];
+        let fields = env.heap.get(first).fields().unwrap();
+        let string_field = |name: &str| fields.get_ref(element.get_field(name, "Ljava/lang/String;").unwrap().slot_id).unwrap().unwrap();
+        let values: Vec<String> = ["declaringClass", "methodName", "fileName"].iter()
+            .map(|name| value_of(&env, string_field(name)).unwrap()).collect();
+        assert_eq!(values, ["fox.Thrower", "divide", "Thrower.java"]);
+        assert_eq!(fields.get_i32(element.get_field("lineNumber", "I").unwrap().slot_id).ok(), Some(5));
+
+        while thread.stack.len() > 1 {
+            thread.stack.pop();
+        }
+        assert_eq!(thread_dump(&thread), "\"main\" #1\n\tat fox.Thrower.divide(Thrower.java:5)\n");
+    }
+
+    #[test]
+    fn field_instructions_use_the_field_layout() {
+        let mut env = match fixture_env() {
+            Some(env) => env,
+            None => return,
+        };
+        let counter = env.load_class("fox/Counter").unwrap().unwrap();
+        let point = counter.super_class.clone().unwrap();
+        link_class(&mut env, &counter).unwrap();
+        let mut thread = Thread::new(ThreadId(1), 16);
+        let this = |r: ObjRef| Slot { val_num: 0, val_ref: Some(r) };
+
+        // the constructors set fields of both classes and a static long of Point
+        let a = env.heap.alloc_object(&counter).unwrap();
+        let b = env.heap.alloc_object(&counter).unwrap();
+        let init = counter.get_method("<init>", "(I)V").unwrap();
+        invoke(&mut env, &mut thread, counter.clone(), init.clone(), vec![this(a), Slot { val_num: 5, val_ref: None }]).unwrap();
+        invoke(&mut env, &mut thread, counter.clone(), init, vec![this(b), Slot { val_num: 7, val_ref: None }]).unwrap();
+        let slot_of = |class: &Class, name: &str, descriptor: &str| class.get_field(name, descriptor).unwrap().slot_id;
+        let fields = env.heap.get(a).fields().unwrap();
+        assert_eq!(fields.get_i32(slot_of(&point, "x", "I")).ok(), Some(5));
+        assert_eq!(fields.get_i64(slot_of(&point, "y", "J")).ok(), Some(10));
+        assert_eq!(fields.get_i32(slot_of(&counter, "id", "I")).ok(), Some(5));
+        assert_eq!(point.static_vars.borrow().get_i64(slot_of(&point, "created", "J")).ok(), Some(2));
+
+        // getstatic initializes Counter, whose <clinit> sets its static final field
+        let bump = counter.get_method("bump", "()I").unwrap();
+        assert_eq!(vars_of(invoke(&mut env, &mut thread, counter.clone(), bump.clone(), vec![this(a)]).unwrap()).get_i32(0).ok(), Some(1));
+        assert_eq!(vars_of(invoke(&mut env, &mut thread, counter.clone(), bump, vec![this(b)]).unwrap()).get_i32(0).ok(), Some(2));
+        assert_eq!(counter.static_vars.borrow().get_i64(slot_of(&counter, "BASE", "J")).ok(), Some(40));
+        assert_eq!(env.heap.get(a).fields().unwrap().get_i32(slot_of(&point, "x", "I")).ok(), Some(10));
+
+        let link = counter.get_method("link", "(Lfox/Counter;)V").unwrap();
+        invoke(&mut env, &mut thread, counter.clone(), link.clone(), vec![this(a), this(b)]).unwrap();
+        let following = counter.get_method("following", "()Lfox/Counter;").unwrap();
+        assert_eq!(invoke(&mut env, &mut thread, counter.clone(), following, vec![this(a)]).unwrap()[0].val_ref, Some(b));
+        let id_of = counter.get_method("idOf", "(Lfox/Counter;)I").unwrap();
+        let err = invoke(&mut env, &mut thread, counter.clone(), id_of.clone(), vec![Slot::default()]).err().unwrap();
+        assert_eq!((err.class_name.as_str(), err.message.as_str()), ("java/lang/NullPointerException", "Cannot read field \"id\" because the object is null"));
+
+        // the verifier doesn't check types, so the receiver may be an array or an object without the field
+        let ints = env.load_class("[I").unwrap().unwrap();
+        let array = env.heap.alloc_array(&ints, 2).unwrap();
+        let err = invoke(&mut env, &mut thread, counter.clone(), id_of.clone(), vec![this(array)]).err().unwrap();
+        assert_eq!((err.class_name.as_str(), err.message.as_str()), ("java/lang/IncompatibleClassChangeError", "Field fox.Counter.id accessed on an instance of [I"));
+        let err = invoke(&mut env, &mut thread, counter.clone(), link, vec![this(array), this(b)]).err().unwrap();
+        assert_eq!(err.class_name, "java/lang/IncompatibleClassChangeError");
+        let plain = env.heap.alloc_object(&point).unwrap();
+        let err = invoke(&mut env, &mut thread, counter.clone(), id_of, vec![this(plain)]).err().unwrap();
+        assert_eq!(err.message, "Field fox.Counter.id accessed on an instance of fox.Point");
+        assert_eq!(env.heap.set_field_ref(array, 0, None).err().unwrap().class_name, "java/lang/IncompatibleClassChangeError");
+        assert_eq!(env.heap.set_element_ref(array, 0, None).err().unwrap().class_name, "java/lang/IncompatibleClassChangeError");
+
+        // another class can't set a final field, nor get an instance field as a static one
+        let id_ref = Constant::FieldRef(MemberSymRef {
+            class_name: "fox/Counter".to_string(),
+            name: "id".to_string(),
+            descriptor: "I".to_string(),
+            resolved: Default::default(),
+        });
+        let methods = vec![
+            Method::synthesized(access_flags::ACC_STATIC, "write", "(Lfox/Counter;)V", 2, vec![0x2a, 0x04, 0xb5, 0, 1, 0xb1]),
+            Method::synthesized(access_flags::ACC_STATIC, "read", "()I", 1, vec![0xb2, 0, 1, 0xac]),
+        ];
+        let object = env.load_class("java/lang/Object").unwrap().unwrap();
+        let writer = Rc::new(Class::synthesized(&counter, "fox/Counter$Writer".to_string(), object, vec![],
+            RuntimeConstantPool::from_constants(vec![Constant::Empty, id_ref]), methods));
+        let write = writer.get_method("write", "(Lfox/Counter;)V").unwrap();
+        let err = invoke(&mut env, &mut thread, writer.clone(), write, vec![this(a)]).err().unwrap();
+        assert_eq!(err.class_name, "java/lang/IllegalAccessError");
+        assert_eq!(err.message, "Update to non-static final field fox.Counter.id attempted from a different class (fox.Counter$Writer) than the field's declaring class");
+        let read = writer.get_method("read", "()I").unwrap();
+        let err = invoke(&mut env, &mut thread, writer.clone(), read, vec![]).err().unwrap();
+        assert_eq!((err.class_name.as_str(), err.message.as_str()), ("java/lang/IncompatibleClassChangeError", "Expected static field fox.Counter.id"));
+        assert!(thread.stack.is_empty());
+    }
+
+    #[test]
+    fn array_instructions_check_their_operands() {
+        let mut env = match fixture_env() {
+            Some(env) => env,
+            None => return,
+        };
+        let grid = env.load_class("fox/Grid").unwrap().unwrap();
+        link_class(&mut env, &grid).unwrap();
+        let mut thread = Thread::new(ThreadId(1), 16);
+        let int = |v: i32| Slot { val_num: v, val_ref: None };
+        let array = |r: ObjRef| Slot { val_num: 0, val_ref: Some(r) };
+
+        // array classes are made up by the registry, and belong to the loader of their element type
+        let ints = env.load_class("[I").unwrap().unwrap();
+        assert_eq!(ints.loader, LoaderId::BOOTSTRAP);
+        assert!(Rc::ptr_eq(&ints, &env.load_class_from(LoaderId::BOOTSTRAP, "[I").unwrap().unwrap()));
+        let points = env.load_class("[[Lfox/Point;").unwrap().unwrap();
+        assert_eq!(points.loader, LoaderId::APP);
+        assert_eq!(points.component_class.as_ref().unwrap().name, "[Lfox/Point;");
+        assert!(env.load_class("[Lfox/Missing;").unwrap().is_none());
+        let object = env.load_class("java/lang/Object").unwrap().unwrap();
+        let cloneable = env.load_class("java/lang/Cloneable").unwrap().unwrap();
+        let objects = env.load_class("[[Ljava/lang/Object;").unwrap().unwrap();
+        assert!(points.is_assignable_to(&object) && points.is_assignable_to(&cloneable));
+        assert!(points.is_assignable_to(&objects) && !objects.is_assignable_to(&points));
+        assert!(!ints.is_assignable_to(&env.load_class("[J").unwrap().unwrap()));
+
+        // multianewarray fills in the dimensions it is given counts for
+        let cells = grid.get_method("cells", "(II)[[I").unwrap();
+        let a = invoke(&mut env, &mut thread, grid.clone(), cells, vec![int(3), int(4)]).unwrap()[0].val_ref.unwrap();
+        assert_eq!(env.heap.get(a).class().name, "[[I");
+        assert_eq!(env.heap.get(a).array_length(), Some(3));
+        let sum = grid.get_method("sum", "([[I)I").unwrap();
+        assert_eq!(vars_of(invoke(&mut env, &mut thread, grid.clone(), sum, vec![array(a)]).unwrap()).get_i32(0).ok(), Some(66));
+        let rows = grid.get_method("rows", "(I)[[[J").unwrap();
+        let r = invoke(&mut env, &mut thread, grid.clone(), rows.clone(), vec![int(2)]).unwrap()[0].val_ref.unwrap();
+        let row = match &env.heap.get(r).body {
+            Body::Refs(v) => v[1].unwrap(),
+            _ => panic!("not an array of references"),
+        };
+        assert_eq!(env.heap.get(row).class().name, "[[J");
+        assert!(matches!(&env.heap.get(row).body, Body::Refs(v) if v == &[None, None]));
+        let err = invoke(&mut env, &mut thread, grid.clone(), rows, vec![int(-1)]).err().unwrap();
+        assert_eq!((err.class_name.as_str(), err.message.as_str()), ("java/lang/NegativeArraySizeException", "-1"));
+        let points_of = grid.get_method("points", "(I)[[Lfox/Point;").unwrap();
+        let p = invoke(&mut env, &mut thread, grid.clone(), points_of, vec![int(2)]).unwrap()[0].val_ref.unwrap();
+        assert!(Rc::ptr_eq(env.heap.get(p).class(), &points));
+
+        // elements are narrowed and widened by their type
+        let narrowed = grid.get_method("narrowed", "()I").unwrap();
+        assert_eq!(vars_of(invoke(&mut env, &mut thread, grid.clone(), narrowed, vec![]).unwrap()).get_i32(0).ok(), Some(-56 + 65535 - 25536));
+        let doubles = env.load_class("[D").unwrap().unwrap();
+        let d = env.heap.alloc_array(&doubles, 3).unwrap();
+        env.heap.get_mut(d).body = Body::Doubles(vec![1.0, 2.0, 4.5]);
+        let mean = grid.get_method("mean", "([D)D").unwrap();
+        assert_eq!(vars_of(invoke(&mut env, &mut thread, grid.clone(), mean, vec![array(d)]).unwrap()).get_f64(0).ok(), Some(2.5));
+
+        // null arrays, bad indexes and elements of the wrong type are thrown
+        let sized = grid.get_method("sized", "(I)[I").unwrap();
+        let s = invoke(&mut env, &mut thread, grid.clone(), sized, vec![int(3)]).unwrap()[0].val_ref.unwrap();
+        let at = grid.get_method("at", "([II)I").unwrap();
+        let err = invoke(&mut env, &mut thread, grid.clone(), at.clone(), vec![array(s), int(3)]).err().unwrap();
+        assert_eq!((err.class_name.as_str(), err.message.as_str()), ("java/lang/ArrayIndexOutOfBoundsException", "Index 3 out of bounds for length 3"));
+        let err = invoke(&mut env, &mut thread, grid.clone(), at, vec![Slot::default(), int(0)]).err().unwrap();
+        assert_eq!((err.class_name.as_str(), err.message.as_str()), ("java/lang/NullPointerException", "Cannot load from int array because the array is null"));
+        let length = grid.get_method("length", "([I)I").unwrap();
+        assert_eq!(vars_of(invoke(&mut env, &mut thread, grid.clone(), length.clone(), vec![array(s)]).unwrap()).get_i32(0).ok(), Some(3));
+        let err = invoke(&mut env, &mut thread, grid.clone(), length, vec![Slot::default()]).err().unwrap();
+        assert_eq!(err.message, "Cannot read the array length because the array is null");
+        let mixed = grid.get_method("mixed", "(Ljava/lang/Object;)[Ljava/lang/Object;").unwrap();
+        let m = invoke(&mut env, &mut thread, grid.clone(), mixed.clone(), vec![array(s)]).unwrap()[0].val_ref.unwrap();
+        assert!(matches!(&env.heap.get(m).body, Body::Refs(v) if v[1] == Some(s)));
+        let err = invoke(&mut env, &mut thread, grid.clone(), mixed, vec![array(d)]).err().unwrap();
+        assert_eq!((err.class_name.as_str(), err.message.as_str()), ("java/lang/ArrayStoreException", "[D"));
+        assert!(thread.stack.is_empty());
+    }
+
+    #[test]
+    fn objects_are_created_type_checked_and_locked() {
+        let mut env = match fixture_env() {
+            Some(env) => env,
+            None => return,
+        };
+        let shapes = env.load_class("fox/Shapes").unwrap().unwrap();
+        link_class(&mut env, &shapes).unwrap();
+        let mut thread = Thread::new(ThreadId(1), 16);
+        let reference = |r: Option<ObjRef>| Slot { val_num: 0, val_ref: r };
+
+        // new initializes the class first
+        let counter = env.load_class("fox/Counter").unwrap().unwrap();
+        assert_eq!(*counter.init_state.borrow(), InitState::Uninitialized);
+        let make = shapes.get_method("make", "(I)Lfox/Counter;").unwrap();
+        let c = invoke(&mut env, &mut thread, shapes.clone(), make, vec![Slot { val_num: 5, val_ref: None }]).unwrap()[0].val_ref.unwrap();
+        assert_eq!(*counter.init_state.borrow(), InitState::Initialized);
+        assert!(Rc::ptr_eq(env.heap.get(c).class(), &counter));
+        let id = counter.get_field("id", "I").unwrap().slot_id;
+        assert_eq!(env.heap.get(c).fields().unwrap().get_i32(id).ok(), Some(5));
+
+        // instanceof and checkcast follow super classes, interfaces and array covariance
+        let is = |env: &mut Environment, thread: &mut Thread, name: &str, value: Option<ObjRef>| {
+            let method = shapes.get_method(name, "(Ljava/lang/Object;)Z").unwrap();
+            return vars_of(invoke(env, thread, shapes.clone(), method, vec![reference(value)]).unwrap()).get_i32(0).unwrap() == 1;
+        };
+        let counters = env.load_class("[Lfox/Counter;").unwrap().unwrap();
+        let a = env.heap.alloc_array(&counters, 1).unwrap();
+        assert!(is(&mut env, &mut thread, "isPoint", Some(c)) && is(&mut env, &mut thread, "isComparable", Some(c)));
+        assert!(!is(&mut env, &mut thread, "isPoints", Some(c)) && !is(&mut env, &mut thread, "isCloneable", Some(c)));
+        assert!(is(&mut env, &mut thread, "isPoints", Some(a)) && is(&mut env, &mut thread, "isCloneable", Some(a)));
+        assert!(!is(&mut env, &mut thread, "isPoint", Some(a)) && !is(&mut env, &mut thread, "isPoint", None));
+        let as_point = shapes.get_method("asPoint", "(Ljava/lang/Object;)Lfox/Point;").unwrap();
+        assert_eq!(invoke(&mut env, &mut thread, shapes.clone(), as_point.clone(), vec![reference(Some(c))]).unwrap()[0].val_ref, Some(c));
+        let err = invoke(&mut env, &mut thread, shapes.clone(), as_point, vec![reference(Some(a))]).err().unwrap();
+        assert_eq!((err.class_name.as_str(), err.message.as_str()), ("java/lang/ClassCastException", "class [Lfox.Counter; cannot be cast to class fox.Point"));
+        let as_grid = shapes.get_method("asGrid", "(Ljava/lang/Object;)[[Ljava/lang/Object;").unwrap();
+        let cube = env.load_class("[[[I").unwrap().unwrap();
+        let cube = env.heap.alloc_array(&cube, 1).unwrap();
+        assert_eq!(invoke(&mut env, &mut thread, shapes.clone(), as_grid.clone(), vec![reference(Some(cube))]).unwrap()[0].val_ref, Some(cube));
+        assert_eq!(invoke(&mut env, &mut thread, shapes.clone(), as_grid.clone(), vec![reference(None)]).unwrap()[0].val_ref, None);
+        let grid = env.load_class("[[I").unwrap().unwrap();
+        let grid = env.heap.alloc_array(&grid, 1).unwrap();
+        let err = invoke(&mut env, &mut thread, shapes.clone(), as_grid, vec![reference(Some(grid))]).err().unwrap();
+        assert_eq!(err.class_name, "java/lang/ClassCastException");
+
+        // monitors can be entered again and are released by the handlers javac adds
+        let object = env.load_class("java/lang/Object").unwrap().unwrap();
+        let lock = env.heap.alloc_object(&object).unwrap();
+        let locked = shapes.get_method("locked", "(Ljava/lang/Object;I)I").unwrap();
+        let result = invoke(&mut env, &mut thread, shapes.clone(), locked, vec![reference(Some(lock)), Slot { val_num: 1, val_ref: None }]).unwrap();
+        assert_eq!(vars_of(result).get_i32(0).ok(), Some(2));
+        assert_eq!(env.heap.get(lock).header.monitor.owner, None);
+        let first = shapes.get_method("first", "(Ljava/lang/Object;[I)I").unwrap();
+        let err = invoke(&mut env, &mut thread, shapes.clone(), first, vec![reference(Some(lock)), reference(None)]).err().unwrap();
+        assert_eq!(err.class_name, "java/lang/NullPointerException");
+        assert_eq!((env.heap.get(lock).header.monitor.owner, env.heap.get(lock).header.monitor.count), (None, 0));
 
-        let rt_class_loader : SimpleClassLoader = SimpleClassLoader::new("D:\\JVM\\rt\\".to_string(), ClassPathType::Folder);
-        loaders.push(Box::new(rt_class_loader));
+        // synchronized methods hold the monitor of their class's mirror or of `this`, and release it when they throw
+        let ints = env.load_class("[I").unwrap().unwrap();
+        let values = env.heap.alloc_array(&ints, 1).unwrap();
+        let first_of = shapes.get_method("firstOf", "([I)I").unwrap();
+        let err = invoke(&mut env, &mut thread, shapes.clone(), first_of.clone(), vec![reference(None)]).err().unwrap();
+        assert_eq!(err.class_name, "java/lang/NullPointerException");
+        let mirror = shapes.mirror.get().unwrap();
+        assert_eq!((env.heap.get(mirror).header.monitor.owner, env.heap.get(mirror).header.monitor.count), (None, 0));
+        assert!(invoke(&mut env, &mut thread, shapes.clone(), first_of.clone(), vec![reference(Some(values))]).is_ok());
+        assert_eq!(env.heap.get(mirror).header.monitor.owner, None);
+        let instance = env.heap.alloc_object(&shapes).unwrap();
+        let size = shapes.get_method("size", "([I)I").unwrap();
+        let err = invoke(&mut env, &mut thread, shapes.clone(), size.clone(), vec![reference(Some(instance)), reference(None)]).err().unwrap();
+        assert_eq!(err.class_name, "java/lang/NullPointerException");
+        assert_eq!((env.heap.get(instance).header.monitor.owner, env.heap.get(instance).header.monitor.count), (None, 0));
+        let result = invoke(&mut env, &mut thread, shapes.clone(), size.clone(), vec![reference(Some(instance)), reference(Some(values))]).unwrap();
+        assert_eq!(vars_of(result).get_i32(0).ok(), Some(1));
+        assert_eq!(env.heap.get(instance).header.monitor.owner, None);
 
-        let my_class_loader : SimpleClassLoader = SimpleClassLoader::new("D:\\Rustlang\\JVM\\Test\\out\\production\\Test\\".to_string(), ClassPathType::Folder);
-        loaders.push(Box::new(my_class_loader));
+        // without a scheduler to run the owner, a monitor another thread owns can't be waited for
+        env.heap.get_mut(mirror).header.monitor.enter(ThreadId(2)).unwrap();
+        let err = invoke(&mut env, &mut thread, shapes.clone(), first_of, vec![reference(Some(values))]).err().unwrap();
+        assert_eq!((err.class_name.as_str(), err.message.as_str()), ("java/lang/InternalError", "monitor is owned by thread #2, which can't run until thread #1 is done"));
+        env.heap.get_mut(lock).header.monitor.enter(ThreadId(2)).unwrap();
+        let locked = shapes.get_method("locked", "(Ljava/lang/Object;I)I").unwrap();
+        let err = invoke(&mut env, &mut thread, shapes.clone(), locked, vec![reference(Some(lock)), Slot { val_num: 1, val_ref: None }]).err().unwrap();
+        assert_eq!(err.class_name, "java/lang/InternalError");
+        env.heap.get_mut(lock).header.monitor.exit(ThreadId(2)).unwrap();
 
-        let env : Environment = Environment::new(loaders);
-        env.load_class("test/Main");
-        // env.load_class("java/lang/class");
+        // exiting a monitor the thread doesn't own, and instantiating an interface, are errors
+        let methods = vec![
+            Method::synthesized(access_flags::ACC_STATIC, "exit", "(Ljava/lang/Object;)V", 1, vec![0x2a, 0xc3, 0xb1]),
+            Method::synthesized(access_flags::ACC_STATIC, "named", "()Ljava/lang/Object;", 1, vec![0xbb, 0, 1, 0xb0]),
+        ];
+        let named_ref = Constant::Class(ClassSymRef { class_name: "fox/Named".to_string(), resolved: Default::default() });
+        let misuse = Rc::new(Class::synthesized(&shapes, "fox/Shapes$Misuse".to_string(), object, vec![],
+            RuntimeConstantPool::from_constants(vec![Constant::Empty, named_ref]), methods));
+        let exit = misuse.get_method("exit", "(Ljava/lang/Object;)V").unwrap();
+        let err = invoke(&mut env, &mut thread, misuse.clone(), exit, vec![reference(Some(lock))]).err().unwrap();
+        assert_eq!((err.class_name.as_str(), err.message.as_str()), ("java/lang/IllegalMonitorStateException", "current thread is not owner"));
+        let named = misuse.get_method("named", "()Ljava/lang/Object;").unwrap();
+        let err = invoke(&mut env, &mut thread, misuse.clone(), named, vec![]).err().unwrap();
+        assert_eq!((err.class_name.as_str(), err.message.as_str()), ("java/lang/InstantiationError", "fox.Named"));
+        assert!(thread.stack.is_empty());
     }
-}
\ No newline at end of file
+}
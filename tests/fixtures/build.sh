@@ -0,0 +1,13 @@
+#!/bin/sh
+# Regenerates the compiled fixtures used by the unit tests. Needs a JDK 14+.
+set -e
+cd "$(dirname "$0")"
+
+rm -rf classes lib
+javac --release 14 -g -d classes $(find src -name '*.java')
+
+mkdir lib
+cd classes
+# one deflated and one stored entry
+zip -q -X -9 ../lib/hello.jar fox/Hello.class
+zip -q -X -0 ../lib/hello.jar fox/Greeter.class
@@ -0,0 +1,9 @@
+package fox;
+
+public class Base {
+    static int order;
+
+    static {
+        order = 1;
+    }
+}
@@ -0,0 +1,26 @@
+package fox;
+
+import java.lang.invoke.CallSite;
+import java.lang.invoke.MethodHandles;
+import java.lang.invoke.MethodType;
+
+// a bootstrap method written in Java, for call sites javac doesn't emit
+public class Bootstraps {
+    static CallSite site;
+    static String name;
+    static MethodType type;
+    static Class<?> caller;
+    static int offset;
+
+    public static CallSite link(MethodHandles.Lookup lookup, String name, MethodType type, int offset) {
+        Bootstraps.name = name;
+        Bootstraps.type = type;
+        Bootstraps.caller = lookup.lookupClass();
+        Bootstraps.offset += offset;
+        return site;
+    }
+
+    static int seven() {
+        return 7;
+    }
+}
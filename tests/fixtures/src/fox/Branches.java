@@ -0,0 +1,40 @@
+package fox;
+
+public class Branches {
+    static int sumTo(int n) {
+        int sum = 0;
+        for (int i = 1; i <= n; i++) {
+            sum += i;
+        }
+        return sum;
+    }
+
+    static int dense(int x) {
+        switch (x) {
+            case 1: return 10;
+            case 2: return 20;
+            case 3: return 30;
+            default: return -1;
+        }
+    }
+
+    static int sparse(int x) {
+        switch (x) {
+            case -1000: return 1;
+            case 0: return 2;
+            case 1000000: return 3;
+            default: return 0;
+        }
+    }
+
+    static int less(double a, double b) {
+        return a < b ? 1 : 0;
+    }
+
+    static int sign(long v) {
+        if (v > 0) {
+            return 1;
+        }
+        return v == 0 ? 0 : -1;
+    }
+}
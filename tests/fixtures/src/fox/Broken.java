@@ -0,0 +1,5 @@
+package fox;
+
+public class Broken {
+    static int value = Integer.parseInt("x");
+}
@@ -0,0 +1,38 @@
+package fox;
+
+public class Calc {
+    static int identity(int x) {
+        return x;
+    }
+
+    static long second(int a, long b) {
+        return b;
+    }
+
+    static double third(float a, long b, double c) {
+        return c;
+    }
+
+    static void nothing() {
+    }
+
+    static int bigInt() {
+        return 100000;
+    }
+
+    static float aFloat() {
+        return 1.5f;
+    }
+
+    static long bigLong() {
+        return 1234567890123L;
+    }
+
+    static double aDouble() {
+        return 2.5;
+    }
+
+    static int divide(int a, int b) {
+        return a / b;
+    }
+}
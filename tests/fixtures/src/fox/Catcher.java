@@ -0,0 +1,23 @@
+package fox;
+
+// loaded on its own in the tests, where fox/Gone can't be found
+public class Catcher {
+    static int missing(int zero) {
+        try {
+            return 1 / zero;
+        } catch (Gone e) {
+            return 1;
+        }
+    }
+
+    static int missingThenCaught(int zero) {
+        try {
+            return missing(zero);
+        } catch (NoClassDefFoundError e) {
+            return 2;
+        }
+    }
+}
+
+class Gone extends ArithmeticException {
+}
@@ -0,0 +1,36 @@
+package fox;
+
+public class Counter extends Point {
+    static int count;
+    static final long BASE = base();
+
+    final int id;
+    Counter next;
+
+    Counter(int id) {
+        super(id, id * 2L);
+        this.id = id;
+    }
+
+    static long base() {
+        return 40;
+    }
+
+    int bump() {
+        count++;
+        x += id;
+        return count;
+    }
+
+    void link(Counter other) {
+        next = other;
+    }
+
+    Counter following() {
+        return next;
+    }
+
+    static int idOf(Counter counter) {
+        return counter.id;
+    }
+}
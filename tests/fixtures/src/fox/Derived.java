@@ -0,0 +1,9 @@
+package fox;
+
+public class Derived extends Base implements Named {
+    static int seen;
+
+    static {
+        seen = order;
+    }
+}
@@ -0,0 +1,87 @@
+package fox;
+
+interface Sized {
+    int size();
+
+    default int twice() {
+        return size() * 2;
+    }
+}
+
+interface Big extends Sized {
+    default int twice() {
+        return 1000;
+    }
+}
+
+interface Left {
+    default int side() {
+        return 1;
+    }
+}
+
+interface Right {
+    default int side() {
+        return 2;
+    }
+}
+
+abstract class Shape implements Sized {
+    abstract int corners();
+
+    int describe() {
+        return corners() * 10 + size();
+    }
+}
+
+class Square extends Shape {
+    int corners() {
+        return 4;
+    }
+
+    public int size() {
+        return 3;
+    }
+}
+
+class Cube extends Square implements Big {
+    public int size() {
+        return 5;
+    }
+
+    int base() {
+        return super.size();
+    }
+}
+
+class Both implements Left, Right {
+    public int side() {
+        return Left.super.side() + 10;
+    }
+}
+
+public class Dispatch {
+    static int describe(Shape s) {
+        return s.describe();
+    }
+
+    static int twice(Sized s) {
+        return s.twice();
+    }
+
+    static int base(Cube c) {
+        return c.base();
+    }
+
+    static int side(Both b) {
+        return b.side();
+    }
+
+    static int sum(int a, long b, double c, int d) {
+        return (int) (a + b + c + d);
+    }
+
+    static int callSum() {
+        return sum(1, 20L, 300.0, 4000);
+    }
+}
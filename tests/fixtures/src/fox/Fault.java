@@ -0,0 +1,4 @@
+package fox;
+
+public class Fault extends Error {
+}
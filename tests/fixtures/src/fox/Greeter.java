@@ -0,0 +1,5 @@
+package fox;
+
+public interface Greeter {
+    String greet(String name);
+}
@@ -0,0 +1,65 @@
+package fox;
+
+public class Grid {
+    static int[][] cells(int width, int height) {
+        int[][] cells = new int[width][height];
+        for (int x = 0; x < width; x++) {
+            for (int y = 0; y < height; y++) {
+                cells[x][y] = x * height + y;
+            }
+        }
+        return cells;
+    }
+
+    static int sum(int[][] cells) {
+        int sum = 0;
+        for (int[] column : cells) {
+            for (int cell : column) {
+                sum += cell;
+            }
+        }
+        return sum;
+    }
+
+    static long[][][] rows(int n) {
+        return new long[n][2][];
+    }
+
+    static Point[][] points(int n) {
+        return new Point[n][];
+    }
+
+    static int narrowed() {
+        byte[] bytes = { (byte) 200 };
+        char[] chars = { (char) -1 };
+        short[] shorts = { (short) 40000 };
+        return bytes[0] + chars[0] + shorts[0];
+    }
+
+    static double mean(double[] values) {
+        double sum = 0;
+        for (int n = 0; n < values.length; n++) {
+            sum += values[n];
+        }
+        return sum / values.length;
+    }
+
+    static int at(int[] values, int n) {
+        return values[n];
+    }
+
+    static int length(int[] values) {
+        return values.length;
+    }
+
+    static int[] sized(int n) {
+        return new int[n];
+    }
+
+    static Object[] mixed(Object value) {
+        Object[] values = new int[2][];
+        values[0] = new int[1];
+        values[1] = value;
+        return values;
+    }
+}
@@ -0,0 +1,7 @@
+package fox;
+
+public class Hello {
+    public static int answer() {
+        return 42;
+    }
+}
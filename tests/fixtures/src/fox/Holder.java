@@ -0,0 +1,9 @@
+package fox;
+
+// references kept in a static and an instance field
+public class Holder {
+    static Object kept;
+
+    Object next;
+    int count;
+}
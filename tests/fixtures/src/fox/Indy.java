@@ -0,0 +1,91 @@
+package fox;
+
+import java.util.function.Function;
+import java.util.function.IntFunction;
+import java.util.function.IntSupplier;
+import java.util.function.IntUnaryOperator;
+import java.util.function.LongSupplier;
+import java.util.function.ToIntFunction;
+
+// invokedynamic call sites: lambdas, method references and string concatenation
+public class Indy {
+    static int fortyTwo() {
+        return 42;
+    }
+
+    public static int doubled(int v) {
+        IntUnaryOperator op = x -> x * 2;
+        return op.applyAsInt(v);
+    }
+
+    public static int answer() {
+        IntSupplier s = Indy::fortyTwo;
+        return s.getAsInt();
+    }
+
+    public static int sizeOf(Sized s) {
+        ToIntFunction<Sized> f = Sized::size;
+        return f.applyAsInt(s);
+    }
+
+    public static int count(int n) {
+        int sum = 0;
+        for (int i = 0; i < n; i++) {
+            IntUnaryOperator next = x -> x + 1;
+            sum = next.applyAsInt(sum);
+        }
+        return sum;
+    }
+
+    public static int captured(int k) {
+        IntSupplier s = () -> k;
+        return s.getAsInt();
+    }
+
+    public static long sum(long a, int b) {
+        LongSupplier s = () -> a + b;
+        return s.getAsLong();
+    }
+
+    public static int boundSize(Sized s) {
+        IntSupplier f = s::size;
+        return f.getAsInt();
+    }
+
+    public static long widened() {
+        LongSupplier s = Indy::fortyTwo;
+        return s.getAsLong();
+    }
+
+    static double half(double v) {
+        return v / 2;
+    }
+
+    public static double halved(double d) {
+        Function<Double, Double> f = Indy::half;
+        return f.apply(d);
+    }
+
+    public static int made(int id) {
+        IntFunction<Counter> f = Counter::new;
+        return f.apply(id).id;
+    }
+
+    static String label(int b) {
+        return null;
+    }
+
+    public static String concat(int b) {
+        return "a" + b;
+    }
+
+    public static String describe(String name, long n, char c, boolean b, float f, double d, Object o) {
+        return name + "=" + n + c + b + f + "/" + d + " " + o;
+    }
+}
+
+class Tag {
+    public String toString() {
+        return "tag";
+    }
+}
@@ -0,0 +1,23 @@
+package fox;
+
+public class Linking {
+    static int readX(Point p) {
+        return p.x;
+    }
+
+    static long count() {
+        return Point.created;
+    }
+
+    static int compare(Point a, Point b) {
+        return a.compareTo(b);
+    }
+
+    static String greet(Greeter g) {
+        return g.greet("fox");
+    }
+
+    static int hash(Greeter g) {
+        return g.hashCode();
+    }
+}
@@ -0,0 +1,22 @@
+package fox;
+
+public class Literals {
+    static final String NAME = "fox";
+    static final String SIGN = "\u20ac";
+
+    static String greeting() {
+        return "hello";
+    }
+
+    static String price() {
+        return "5 \u20ac";
+    }
+
+    static Class<?> self() {
+        return Literals.class;
+    }
+
+    static Class<?> ints() {
+        return int[].class;
+    }
+}
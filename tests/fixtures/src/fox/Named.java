@@ -0,0 +1,9 @@
+package fox;
+
+public interface Named {
+    Object TAG = new Object();
+
+    default String name() {
+        return "fox";
+    }
+}
@@ -0,0 +1,20 @@
+package fox;
+
+public class Point implements Comparable<Point> {
+    public static final int DIMENSIONS = 2;
+    static long created;
+
+    int x;
+    long y;
+    Object label;
+
+    public Point(int x, long y) {
+        this.x = x;
+        this.y = y;
+        created++;
+    }
+
+    public int compareTo(Point other) {
+        return Long.compare(y, other.y);
+    }
+}
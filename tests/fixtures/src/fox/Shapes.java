@@ -0,0 +1,53 @@
+package fox;
+
+public class Shapes {
+    static Counter make(int id) {
+        return new Counter(id);
+    }
+
+    static boolean isPoint(Object value) {
+        return value instanceof Point;
+    }
+
+    static boolean isComparable(Object value) {
+        return value instanceof Comparable;
+    }
+
+    static boolean isPoints(Object value) {
+        return value instanceof Point[];
+    }
+
+    static boolean isCloneable(Object value) {
+        return value instanceof Cloneable;
+    }
+
+    static Point asPoint(Object value) {
+        return (Point) value;
+    }
+
+    static Object[][] asGrid(Object value) {
+        return (Object[][]) value;
+    }
+
+    static int locked(Object lock, int n) {
+        synchronized (lock) {
+            synchronized (lock) {
+                return n + 1;
+            }
+        }
+    }
+
+    static int first(Object lock, int[] values) {
+        synchronized (lock) {
+            return values[0];
+        }
+    }
+
+    static synchronized int firstOf(int[] values) {
+        return values[0];
+    }
+
+    synchronized int size(int[] values) {
+        return values.length;
+    }
+}
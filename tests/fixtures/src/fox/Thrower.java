@@ -0,0 +1,76 @@
+package fox;
+
+public class Thrower {
+    static int divide(int a, int b) {
+        return a / b;
+    }
+
+    static int safeDivide(int a, int b) {
+        try {
+            return divide(a, b);
+        } catch (ArithmeticException e) {
+            return -1;
+        }
+    }
+
+    static int caughtAsSuper(int a, int b) {
+        try {
+            return a / b;
+        } catch (IllegalStateException e) {
+            return -3;
+        } catch (RuntimeException e) {
+            return -2;
+        }
+    }
+
+    static int finallyRethrows(int a, int b) {
+        int tries = 0;
+        try {
+            try {
+                tries++;
+                return a / b;
+            } finally {
+                tries += 10;
+            }
+        } catch (ArithmeticException e) {
+            return tries;
+        }
+    }
+
+    static void rethrow(Throwable t) throws Throwable {
+        throw t;
+    }
+
+    static int throwNull() {
+        try {
+            rethrow(null);
+            return 0;
+        } catch (NullPointerException e) {
+            return 1;
+        } catch (Throwable t) {
+            return 2;
+        }
+    }
+
+    static int uncaught(int a) {
+        return divide(a, 0);
+    }
+
+    static native int answer(int question);
+
+    static int ask(int question) {
+        return answer(question) + 1;
+    }
+
+    static int down(int depth) {
+        return down(depth + 1) + 1;
+    }
+
+    static int overflow() {
+        try {
+            return down(0);
+        } catch (StackOverflowError e) {
+            return -1;
+        }
+    }
+}
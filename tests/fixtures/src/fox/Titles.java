@@ -0,0 +1,13 @@
+package fox;
+
+// implements fox/Named only through Titled, which has no default methods of its own
+public class Titles implements Titled {
+    static int seen;
+
+    static {
+        seen = 1;
+    }
+}
+
+interface Titled extends Named {
+}